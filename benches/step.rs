@@ -0,0 +1,45 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+use mankalla_rl::mankalla::MankallaGame;
+use mankalla_rl::q_learning::Environment;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+
+const GAMES: usize = 20_000;
+
+// Run with and without `--features simd` to compare the two board implementations
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut steps = 0usize;
+    let mut conversions = 0usize;
+
+    let started = Instant::now();
+    for _ in 0..GAMES {
+        let mut state = MankallaGame::new();
+        loop {
+            let relevant: [u8; 12] = black_box(state).into();
+            conversions += 1;
+            let action = *MankallaGame::actions(&relevant)
+                .choose(&mut rng)
+                .expect("Unfinished games always have a legal move");
+            let (next_state, _, outcome) = MankallaGame::step(black_box(&state), &action);
+            steps += 1;
+            if outcome.is_some() {
+                break;
+            }
+            state = next_state;
+        }
+    }
+    let elapsed = started.elapsed();
+
+    println!(
+        "{} games, {} steps, {} conversions in {:.3}s ({:.1} ns per step incl. conversion)",
+        GAMES,
+        steps,
+        conversions,
+        elapsed.as_secs_f64(),
+        elapsed.as_nanos() as f64 / steps as f64
+    );
+}
@@ -0,0 +1,315 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::double_q::DoubleQLearningPolicy;
+use crate::evaluation::{self, EvaluationReport};
+use crate::hyperparameters::Hyperparameters;
+use crate::mankalla::MankallaGame;
+use crate::q_learning::{
+    DeserializeError, EpsilonGreedyPolicy, GreedyPolicy, Policy, QLearning, SeededRandomPolicy,
+};
+use crate::rlearning::RLearningPolicy;
+use crate::sarsa::{ExpectedSarsaPolicy, Sarsa, SarsaPolicy};
+
+pub const ABLATION_REPORT_FILE: &str = "ablation.md";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Learner {
+    QLearning,
+    Sarsa,
+    ExpectedSarsa,
+    DoubleQ,
+    RLearning,
+}
+
+impl FromStr for Learner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "qlearning" => Ok(Learner::QLearning),
+            "sarsa" => Ok(Learner::Sarsa),
+            "expected-sarsa" => Ok(Learner::ExpectedSarsa),
+            "double-q" => Ok(Learner::DoubleQ),
+            "rlearning" => Ok(Learner::RLearning),
+            _ => Err(format!(
+                "unknown learner \"{s}\" (supported: qlearning, sarsa, expected-sarsa, double-q, \
+                 rlearning)"
+            )),
+        }
+    }
+}
+
+impl Learner {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Learner::QLearning => "qlearning",
+            Learner::Sarsa => "sarsa",
+            Learner::ExpectedSarsa => "expected-sarsa",
+            Learner::DoubleQ => "double-q",
+            Learner::RLearning => "rlearning",
+        }
+    }
+}
+
+// One learner with its hyperparameters, the ones a variant does not set come from the base
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub learner: Learner,
+    pub hyperparameters: Hyperparameters,
+    // R-learning's step size for the average reward, unused by the others
+    pub average_reward_rate: f32,
+}
+
+// One variant per line, "<name> <learner> [<hyperparameter>=<value>...]", e.g.
+// "fast-sarsa sarsa learning_rate=0.4". The hyperparameters are those of `Hyperparameters` plus
+// `average_reward_rate`, blank lines and lines starting with '#' are skipped.
+pub fn parse_variants(
+    text: &str,
+    base: Hyperparameters,
+    average_reward_rate: f32,
+) -> Result<Vec<Variant>, DeserializeError> {
+    let variants = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            let reason =
+                |reason: String| DeserializeError::because(format!("{reason} in line {number}"));
+            let mut words = line.split_whitespace();
+            let (Some(name), Some(learner)) = (words.next(), words.next()) else {
+                return Err(reason("a variant has a name and a learner".to_owned()));
+            };
+            let mut variant = Variant {
+                name: name.to_owned(),
+                learner: learner.parse().map_err(reason)?,
+                hyperparameters: base,
+                average_reward_rate,
+            };
+            for setting in words {
+                let parsed = setting
+                    .split_once('=')
+                    .and_then(|(key, value)| Some((key, value.parse::<f32>().ok()?)));
+                let h = &mut variant.hyperparameters;
+                let target = match parsed {
+                    Some(("learning_rate", _)) => &mut h.learning_rate,
+                    Some(("gamma", _)) => &mut h.gamma,
+                    Some(("max_epsilon", _)) => &mut h.max_epsilon,
+                    Some(("min_epsilon", _)) => &mut h.min_epsilon,
+                    Some(("decay_rate", _)) => &mut h.decay_rate,
+                    Some(("average_reward_rate", _)) => &mut variant.average_reward_rate,
+                    _ => return Err(reason(format!("bad setting \"{setting}\""))),
+                };
+                *target = parsed.map_or(0f32, |(_, value)| value);
+            }
+            Ok(variant)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if variants.is_empty() {
+        return Err(DeserializeError::because("no variants to compare"));
+    }
+    for (i, variant) in variants.iter().enumerate() {
+        if variants[..i].iter().any(|other| other.name == variant.name) {
+            return Err(DeserializeError::because(format!(
+                "the name \"{}\" is taken twice",
+                variant.name
+            )));
+        }
+    }
+    Ok(variants)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AblationOptions {
+    pub episodes: usize,
+    // Every variant is measured after this many episodes and at the end
+    pub eval_every: usize,
+    pub eval_games: usize,
+}
+
+impl Default for AblationOptions {
+    fn default() -> Self {
+        AblationOptions {
+            episodes: 5000,
+            eval_every: 1000,
+            eval_games: 200,
+        }
+    }
+}
+
+pub struct VariantResult {
+    pub variant: Variant,
+    // How the greedy table did against random play after the episodes so far
+    pub curve: Vec<(usize, EvaluationReport)>,
+    pub qtable_size: usize,
+    // Evaluations included
+    pub elapsed: Duration,
+}
+
+impl VariantResult {
+    // After all episodes
+    pub fn last(&self) -> &EvaluationReport {
+        &self
+            .curve
+            .last()
+            .expect("Every variant trains for at least one episode")
+            .1
+    }
+}
+
+// Every variant explores with the same `policy_seed` and is measured against random moves drawn
+// from the same `evaluation_seed`, so only the learner and its settings differ between them
+pub fn run_variant(
+    variant: &Variant,
+    options: &AblationOptions,
+    policy_seed: u64,
+    evaluation_seed: u64,
+) -> Result<VariantResult, Box<dyn std::error::Error>> {
+    assert!(
+        options.episodes > 0,
+        "A variant trains for at least one episode"
+    );
+    let mut policy = EpsilonGreedyPolicy::from_hyperparameters(variant.hyperparameters)?;
+    policy.reseed(policy_seed);
+    let measure = Measure {
+        options,
+        evaluation_seed,
+    };
+    let started = Instant::now();
+    let (curve, qtable_size) = match variant.learner {
+        Learner::QLearning => measure.run(policy, QLearning::train, |p| {
+            Cow::Borrowed(p.greedy_policy())
+        }),
+        Learner::ExpectedSarsa => {
+            measure.run(ExpectedSarsaPolicy::from(policy), QLearning::train, |p| {
+                Cow::Borrowed(p.epsilon_greedy_policy().greedy_policy())
+            })
+        }
+        Learner::Sarsa => measure.run(SarsaPolicy::from(policy), Sarsa::train, |p| {
+            Cow::Borrowed(p.epsilon_greedy_policy().greedy_policy())
+        }),
+        Learner::DoubleQ => {
+            measure.run(DoubleQLearningPolicy::new(policy), QLearning::train, |p| {
+                Cow::Owned(p.merged_table())
+            })
+        }
+        Learner::RLearning => measure.run(
+            RLearningPolicy::new(policy, variant.average_reward_rate),
+            QLearning::train,
+            |p| Cow::Borrowed(p.epsilon_greedy_policy().greedy_policy()),
+        ),
+    };
+    Ok(VariantResult {
+        variant: variant.clone(),
+        curve,
+        qtable_size,
+        elapsed: started.elapsed(),
+    })
+}
+
+struct Measure<'a> {
+    options: &'a AblationOptions,
+    evaluation_seed: u64,
+}
+
+impl Measure<'_> {
+    // Trains in blocks of `eval_every` episodes, returns the curve and the size of the final table.
+    // Learners with more than one table hand out the one they play.
+    fn run<P: Policy<MankallaGame>>(
+        &self,
+        mut policy: P,
+        train: fn(&mut P, usize, Option<usize>),
+        table: impl Fn(&P) -> Cow<'_, GreedyPolicy<MankallaGame>>,
+    ) -> (Vec<(usize, EvaluationReport)>, usize) {
+        let mut curve = Vec::new();
+        let mut episodes = 0;
+        while episodes < self.options.episodes {
+            let block = self
+                .options
+                .eval_every
+                .max(1)
+                .min(self.options.episodes - episodes);
+            train(&mut policy, block, None);
+            episodes += block;
+            let report = evaluation::evaluate(
+                table(&policy).as_ref(),
+                &SeededRandomPolicy::new(self.evaluation_seed),
+                self.options.eval_games,
+            );
+            curve.push((episodes, report));
+        }
+        (curve, table(&policy).qtable_size())
+    }
+}
+
+pub struct AblationReport {
+    pub options: AblationOptions,
+    pub seed: u64,
+    pub results: Vec<VariantResult>,
+}
+
+impl AblationReport {
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!(
+            "# Ablation\n\n{} episodes per variant from seed {}, {} games against random moves \
+             every {} episodes.\n\n",
+            self.options.episodes, self.seed, self.options.eval_games, self.options.eval_every
+        );
+        md += "| Variant | Learner | Learning rate | Gamma | Won | Drawn | Score | Q-values | Time |\n";
+        md += "|---|---|---|---|---|---|---|---|---|\n";
+        for result in &self.results {
+            let (h, last) = (result.variant.hyperparameters, result.last());
+            md += &format!(
+                "| {} | {} | {} | {} | {:.1}% | {:.1}% | {:.1}% | {} | {:.1}s |\n",
+                result.variant.name,
+                result.variant.learner.name(),
+                h.learning_rate,
+                h.gamma,
+                last.win_rate() * 100f32,
+                last.draw_rate() * 100f32,
+                last.score() * 100f32,
+                result.qtable_size,
+                result.elapsed.as_secs_f32()
+            );
+        }
+
+        md += "\n## Win rate against random moves\n\n| Episodes |";
+        for result in &self.results {
+            md += &format!(" {} |", result.variant.name);
+        }
+        md += &format!("\n|---|{}\n", "---|".repeat(self.results.len()));
+        let checkpoints = self.results.first().map_or(0, |result| result.curve.len());
+        for checkpoint in 0..checkpoints {
+            md += &format!("| {} |", self.results[0].curve[checkpoint].0);
+            for result in &self.results {
+                md += &format!(" {:.1}% |", result.curve[checkpoint].1.win_rate() * 100f32);
+            }
+            md += "\n";
+        }
+        md
+    }
+}
+
+// A line per variant, best score first
+impl Display for AblationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut results: Vec<&VariantResult> = self.results.iter().collect();
+        results.sort_by(|a, b| b.last().score().total_cmp(&a.last().score()));
+        for result in results {
+            writeln!(
+                f,
+                "{:<16} {:<15} {} ({} Q-values, {:.1}s)",
+                result.variant.name,
+                result.variant.learner.name(),
+                result.last(),
+                result.qtable_size,
+                result.elapsed.as_secs_f32()
+            )?;
+        }
+        Ok(())
+    }
+}
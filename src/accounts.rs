@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::mankalla::{MankallaGame, MankallaGameState, move_info};
+use crate::profile::{Achievement, GameHighlights, PlayerProfile, Profiles, valid_name};
+use crate::q_learning::{Deserialize, DeserializeError, Outcome, Serialize};
+use crate::two_player::TwoPlayerGame;
+
+// Who a token belongs to, read from a file with one "<token> <name>" per line. Tokens are compared
+// as they are, they should be long and random since they are all that tells the users apart.
+// Empty lines and lines starting with '#' are left out.
+pub struct Tokens {
+    users: HashMap<String, String>,
+}
+
+impl Tokens {
+    pub fn user(&self, token: &str) -> Option<&str> {
+        self.users.get(token).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+}
+
+impl Deserialize for Tokens {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let mut users = HashMap::new();
+        for (number, line) in input.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((token, name)) = line.split_once(char::is_whitespace) else {
+                return Err(DeserializeError::because(format!(
+                    "line {number} has a token but no name"
+                )));
+            };
+            let name = name.trim();
+            if !valid_name(name) {
+                return Err(DeserializeError::because(format!(
+                    "line {number}: a name must not be empty or contain ';'"
+                )));
+            }
+            if users.insert(token.to_owned(), name.to_owned()).is_some() {
+                return Err(DeserializeError::because(format!(
+                    "line {number} repeats a token"
+                )));
+            }
+        }
+        Ok(Tokens { users })
+    }
+}
+
+// What traind knows about its users, shared by the connections
+pub struct Users {
+    pub tokens: Tokens,
+    pub accounts: Mutex<Accounts>,
+}
+
+// A game of one user against the served policy, the user moves first as Player1
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct OngoingGame {
+    pub position: MankallaGameState,
+    pub opening: Option<u8>,
+    pub highlights: GameHighlights,
+    // Extra turns in a row up to the last move of the user
+    pub chain: usize,
+}
+
+impl OngoingGame {
+    // The user's move, kept for the profile once the game is over
+    pub fn user_move(&mut self, pit: u8) {
+        let info = move_info(&self.position.into(), &pit);
+        self.highlights.biggest_capture = self.highlights.biggest_capture.max(info.captured);
+        self.chain = match info.extra_turn {
+            true => self.chain + 1,
+            false => 0,
+        };
+        self.highlights.longest_extra_turn_chain =
+            self.highlights.longest_extra_turn_chain.max(self.chain);
+        self.opening.get_or_insert(pit);
+        self.position = MankallaGame::apply_move(&self.position, &pit);
+    }
+
+    pub fn bot_move(&mut self, pit: u8) {
+        self.position = MankallaGame::apply_move(&self.position, &pit);
+    }
+}
+
+// The games everybody has going, by name. Every change is written through to the games file and
+// every finished game to the profiles, so a restart picks up where everyone was and `stats` shows
+// the server games next to the ones played at the terminal.
+pub struct Accounts {
+    games: BTreeMap<String, OngoingGame>,
+    games_path: PathBuf,
+    profiles_path: PathBuf,
+}
+
+impl Accounts {
+    // A games file that does not exist yet holds no games
+    pub fn open(
+        games_path: impl Into<PathBuf>,
+        profiles_path: impl Into<PathBuf>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let games_path = games_path.into();
+        let games = match fs::read_to_string(&games_path) {
+            Ok(s) => deserialize_games(&s).map_err(|e| {
+                format!("Could not parse the games in {}: {e}", games_path.display())
+            })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("Could not read {}: {e}", games_path.display()).into()),
+        };
+        Ok(Accounts {
+            games,
+            games_path,
+            profiles_path: profiles_path.into(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    pub fn game(&self, name: &str) -> Option<&OngoingGame> {
+        self.games.get(name)
+    }
+
+    // A game still going is given up and counted as lost
+    pub fn start(&mut self, name: &str) -> io::Result<MankallaGameState> {
+        if let Some(given_up) = self.games.get(name).copied()
+            && given_up.opening.is_some()
+        {
+            self.finish(name, given_up, Outcome::Loss)?;
+        }
+        let game = OngoingGame::default();
+        self.update(name, game)?;
+        Ok(game.position)
+    }
+
+    pub fn update(&mut self, name: &str, game: OngoingGame) -> io::Result<()> {
+        self.games.insert(name.to_owned(), game);
+        self.write_games()
+    }
+
+    // Returns the achievements the game unlocked. The served policy is the greedy one.
+    pub fn finish(
+        &mut self,
+        name: &str,
+        game: OngoingGame,
+        outcome: Outcome,
+    ) -> io::Result<Vec<Achievement>> {
+        self.games.remove(name);
+        self.write_games()?;
+        let Some(opening) = game.opening else {
+            return Ok(Vec::new());
+        };
+        // Read again every time, a game at the terminal may have been recorded meanwhile
+        let mut profiles = self.profiles()?;
+        let highlights = GameHighlights {
+            against_strongest_bot: true,
+            ..game.highlights
+        };
+        let unlocked = profiles.record(name, outcome, opening, &highlights);
+        write_through(&self.profiles_path, &profiles.serialize())?;
+        Ok(unlocked)
+    }
+
+    pub fn profile(&self, name: &str) -> io::Result<Option<PlayerProfile>> {
+        Ok(self.profiles()?.get(name).cloned())
+    }
+
+    fn profiles(&self) -> io::Result<Profiles> {
+        match fs::read_to_string(&self.profiles_path) {
+            Ok(s) => Profiles::deserialize(&s).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Could not parse the profiles in {}",
+                        self.profiles_path.display()
+                    ),
+                )
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Profiles::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_games(&self) -> io::Result<()> {
+        write_through(&self.games_path, &serialize_games(&self.games))
+    }
+}
+
+// Through a temporary file, a crash halfway leaves the file as it was
+fn write_through(path: &Path, contents: &str) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+}
+
+// name;position;opening;biggest capture;longest extra turn chain;chain, the position in `{:#}`
+// format and "-" for no opening before the first move
+fn serialize_games(games: &BTreeMap<String, OngoingGame>) -> String {
+    games
+        .iter()
+        .map(|(name, game)| {
+            format!(
+                "{name};{:#};{};{};{};{}\n",
+                game.position,
+                game.opening
+                    .map_or_else(|| "-".to_owned(), |pit| pit.to_string()),
+                game.highlights.biggest_capture,
+                game.highlights.longest_extra_turn_chain,
+                game.chain
+            )
+        })
+        .collect()
+}
+
+fn deserialize_games(input: &str) -> Result<BTreeMap<String, OngoingGame>, DeserializeError> {
+    let number = |part: Option<&str>| {
+        part.and_then(|p| p.parse::<usize>().ok())
+            .ok_or_else(|| DeserializeError::because("a game has six ';' separated fields"))
+    };
+    let mut games = BTreeMap::new();
+    for line in input.lines().filter(|l| !l.is_empty()) {
+        let mut parts = line.split(';');
+        let name = match parts.next() {
+            Some(name) if valid_name(name) => name.to_owned(),
+            _ => return Err(DeserializeError::because("a game starts with a name")),
+        };
+        let position = parts.next().unwrap_or_default().parse()?;
+        let opening = match parts.next() {
+            Some("-") => None,
+            part => Some(number(part)? as u8),
+        };
+        let game = OngoingGame {
+            position,
+            opening,
+            highlights: GameHighlights {
+                biggest_capture: number(parts.next())? as u8,
+                longest_extra_turn_chain: number(parts.next())?,
+                against_strongest_bot: false,
+            },
+            chain: number(parts.next())?,
+        };
+        if parts.next().is_some() {
+            return Err(DeserializeError::because(
+                "a game has six ';' separated fields",
+            ));
+        }
+        games.insert(name, game);
+    }
+    Ok(games)
+}
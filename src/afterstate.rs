@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use crate::hyperparameters::{HyperparameterError, Hyperparameters};
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, EpsilonGreedyPolicy, Policy, Serialize,
+    checked_choice, masked_actions,
+};
+
+const HEADER: &str = "afterstate";
+
+// Learns the value of where a move leads, `Environment::afterstate`, instead of the value of the
+// move in the position it was made from. A move is worth the reward it pays up to its afterstate
+// plus the afterstate's value, and all the moves from all the positions that end up on the same
+// board share that value, so what one of them learned counts for the others straight away. The
+// updates are Q-learning's on that sum, the rest of the step's reward, an opponent's reply for
+// example, and the greedy value of the next position go into the afterstate's value.
+//
+// Exploration comes from `EpsilonGreedyPolicy`, whose table is filled with the sums for every
+// position learned in, as they were when it was last learned in, so the policy plays and gets
+// evaluated like any other table. Loading fills it in again with the latest values. The file puts
+// the afterstate values in front of its format:
+//
+//   afterstate;<number of values>
+//   <afterstate>;<value>
+//   ...
+//   <EpsilonGreedyPolicy>
+pub struct AfterstatePolicy<E: Environment> {
+    policy: EpsilonGreedyPolicy<E>,
+    values: HashMap<E::Afterstate, f32>,
+    // NaN or infinite values the updates refused like `GreedyPolicy::set_value`, not saved
+    rejected_updates: usize,
+}
+
+impl<E: Environment> AfterstatePolicy<E> {
+    // Goes on from the exploration and episode of the policy, its table is replaced as positions
+    // are learned in again
+    pub fn new(policy: EpsilonGreedyPolicy<E>) -> Self {
+        AfterstatePolicy {
+            policy,
+            values: HashMap::new(),
+            rejected_updates: 0,
+        }
+    }
+
+    pub fn from_hyperparameters(
+        hyperparameters: Hyperparameters,
+    ) -> Result<Self, HyperparameterError> {
+        Ok(AfterstatePolicy::new(
+            EpsilonGreedyPolicy::from_hyperparameters(hyperparameters)?,
+        ))
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.policy.reseed(seed);
+    }
+
+    // Afterstates with a value, the counterpart of the Q-table size
+    pub fn afterstates(&self) -> usize {
+        self.values.len()
+    }
+
+    // Refused afterstate values and refused sums for the table
+    pub fn rejected_updates(&self) -> usize {
+        self.rejected_updates + self.policy.greedy_policy().rejected_updates()
+    }
+
+    pub fn epsilon_greedy_policy(&self) -> &EpsilonGreedyPolicy<E> {
+        &self.policy
+    }
+
+    pub fn epsilon_greedy_policy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        &mut self.policy
+    }
+
+    // With the table filled in from the latest values
+    pub fn into_epsilon_greedy_policy(mut self) -> EpsilonGreedyPolicy<E> {
+        self.fill_table();
+        self.policy
+    }
+
+    // The reward up to the afterstate and its value, unknown afterstates are worth nothing yet
+    pub fn value(&self, state: E::ActionRelevantState, action: E::Action) -> f32 {
+        let (afterstate, reward) = E::afterstate(&state, &action);
+        reward + self.values.get(&afterstate).copied().unwrap_or(0f32)
+    }
+
+    // Ties go to the later action like in `GreedyPolicy`
+    fn greedy_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        let action = masked_actions::<E>(&state, mask)
+            .into_iter()
+            .map(|action| (action, self.value(state, action)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(action, _)| action)
+            .expect(
+                "The way it is implemented now, there should always be possible actions (might be bad)",
+            );
+        checked_choice::<E>(&state, mask, action)
+    }
+
+    fn fill_row(&mut self, state: E::ActionRelevantState) {
+        for action in E::actions(&state) {
+            let value = self.value(state, action);
+            self.policy
+                .greedy_policy_mut()
+                .set_value(state, action, value);
+        }
+    }
+
+    fn fill_table(&mut self) {
+        let states: Vec<_> = self
+            .policy
+            .greedy_policy()
+            .qtable()
+            .rows()
+            .map(|(state, _)| state)
+            .collect();
+        for state in states {
+            self.fill_row(state);
+        }
+    }
+}
+
+impl<E: Environment> Policy<E> for AfterstatePolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.policy
+            .choose_action_around(state, mask, || self.greedy_action(state, mask))
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.policy.record_visit(state);
+        let (afterstate, reward_so_far) = E::afterstate(&state, &action);
+        let following = match finished {
+            false => {
+                let next_state = next_state.into();
+                self.value(next_state, self.greedy_action(next_state, None))
+            }
+            true => 0f32,
+        };
+        let target = reward - reward_so_far + self.policy.gamma() * following;
+        let learning_rate = self.policy.hyperparameters().learning_rate;
+        let value = self.values.entry(afterstate).or_default();
+        let updated = *value + learning_rate * (target - *value);
+        // A NaN would win every comparison and spread to every move leading to the afterstate
+        if !updated.is_finite() {
+            self.rejected_updates += 1;
+            return;
+        }
+        *value = updated;
+        self.fill_row(state);
+    }
+
+    fn on_episode_increment(&mut self) {
+        self.policy.on_episode_increment();
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.policy
+            .action_distribution_around(state, self.greedy_action(state, None))
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        Some(
+            E::actions(&state)
+                .into_iter()
+                .map(|action| (action, self.value(state, action)))
+                .collect(),
+        )
+    }
+
+    // Known once one of its afterstates has a value
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        Some(
+            E::actions(&state)
+                .iter()
+                .any(|action| self.values.contains_key(&E::afterstate(&state, action).0)),
+        )
+    }
+}
+
+impl<E: Environment> Serialize for AfterstatePolicy<E> {
+    fn serialize(&self) -> String {
+        let values: String = self
+            .values
+            .iter()
+            .map(|(afterstate, value)| format!("{};{value}\n", afterstate.serialize()))
+            .collect();
+        format!("{HEADER};{}\n{values}", self.values.len()) + &self.policy.serialize()
+    }
+}
+
+impl<E: Environment> Deserialize for AfterstatePolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let (first, mut rest) = input
+            .split_once('\n')
+            .ok_or_else(|| DeserializeError::because("an afterstate policy has a first line"))?;
+        let count = first
+            .strip_prefix(HEADER)
+            .and_then(|count| count.strip_prefix(';'))
+            .and_then(|count| count.parse::<usize>().ok());
+        let Some(count) = count else {
+            return Err(DeserializeError::because(format!(
+                "an afterstate policy starts with \"{HEADER};<number of values>\""
+            )));
+        };
+        let mut values = HashMap::with_capacity(count);
+        for _ in 0..count {
+            let (line, after) = rest.split_once('\n').ok_or_else(|| {
+                DeserializeError::because("fewer afterstate values than announced")
+            })?;
+            let (afterstate, value) = line.rsplit_once(';').ok_or_else(|| {
+                DeserializeError::because("an afterstate value is \"<afterstate>;<value>\"")
+            })?;
+            let value = value
+                .parse::<f32>()
+                .map_err(|_| DeserializeError::because(format!("\"{value}\" is not a value")))?;
+            values.insert(E::Afterstate::deserialize(afterstate)?, value);
+            rest = after;
+        }
+        let mut policy = AfterstatePolicy {
+            policy: EpsilonGreedyPolicy::deserialize(rest)?,
+            values,
+            rejected_updates: 0,
+        };
+        policy.fill_table();
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{RIGHT, TwoStateGame};
+
+    #[test]
+    fn non_finite_targets_are_rejected_and_counted() {
+        let mut policy =
+            AfterstatePolicy::<TwoStateGame>::from_hyperparameters(Hyperparameters::default())
+                .expect("The default hyperparameters are valid");
+        policy.improve(1, RIGHT, 1f32, 1, true);
+        let before = policy.value(1, RIGHT);
+        policy.improve(1, RIGHT, f32::INFINITY, 1, true);
+        policy.improve(1, RIGHT, f32::NAN, 1, true);
+        assert_eq!(policy.value(1, RIGHT), before);
+        assert_eq!(policy.rejected_updates(), 2);
+    }
+
+    #[test]
+    fn action_distribution_mixes_epsilon_around_the_greedy_action() {
+        let mut policy =
+            AfterstatePolicy::<TwoStateGame>::from_hyperparameters(Hyperparameters::default())
+                .expect("The default hyperparameters are valid");
+        policy.improve(1, RIGHT, 1f32, 1, true);
+        let distribution = policy.action_distribution(1);
+        let total: f32 = distribution.iter().map(|(_, p)| p).sum();
+        assert!((total - 1f32).abs() < 1e-6);
+        let (most_likely, _) = distribution
+            .iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("There are actions");
+        assert_eq!(*most_likely, RIGHT);
+        assert!(distribution.iter().all(|(_, p)| *p > 0f32));
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::Display;
+
+use crate::q_learning::{Environment, QTable, Serialize};
+
+// Upper bounds of the absolute change buckets, the last bucket is open ended
+const CHANGE_BUCKETS: [f32; 5] = [0f32, 0.01, 0.1, 1., 10.];
+
+pub struct Swing<E: Environment> {
+    pub state: E::ActionRelevantState,
+    pub action: E::Action,
+    pub before: f32,
+    pub after: f32,
+}
+
+pub struct QTableDiff<E: Environment> {
+    pub added: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    pub changed: usize,
+    pub mean_abs_change: f32,
+    pub change_histogram: [usize; CHANGE_BUCKETS.len() + 1],
+    pub largest_swings: Vec<Swing<E>>,
+}
+
+impl<E: Environment> QTableDiff<E> {
+    pub fn new(before: &QTable<E>, after: &QTable<E>, top: usize) -> Self {
+        let added = after
+            .iter()
+            .filter(|(state, action, _)| before.get(state, action).is_none())
+            .count();
+        let removed = before
+            .iter()
+            .filter(|(state, action, _)| after.get(state, action).is_none())
+            .count();
+
+        let mut change_histogram = [0; CHANGE_BUCKETS.len() + 1];
+        let mut swings: Vec<Swing<E>> = before
+            .iter()
+            .filter_map(|(state, action, b)| {
+                after.get(&state, &action).map(|a| Swing {
+                    state,
+                    action,
+                    before: b,
+                    after: a,
+                })
+            })
+            .collect();
+
+        let mut total_abs_change = 0f32;
+        for swing in swings.iter() {
+            let change = swing.change().abs();
+            total_abs_change += change;
+            let bucket = CHANGE_BUCKETS
+                .iter()
+                .position(|&upper| change <= upper)
+                .unwrap_or(CHANGE_BUCKETS.len());
+            change_histogram[bucket] += 1;
+        }
+        let unchanged = change_histogram[0];
+        let changed = swings.len() - unchanged;
+        let mean_abs_change = match swings.len() {
+            0 => 0f32,
+            n => total_abs_change / n as f32,
+        };
+
+        swings.sort_by(|a, b| b.change().abs().total_cmp(&a.change().abs()));
+        swings.truncate(top);
+
+        QTableDiff {
+            added,
+            removed,
+            unchanged,
+            changed,
+            mean_abs_change,
+            change_histogram,
+            largest_swings: swings,
+        }
+    }
+}
+
+impl<E: Environment> Swing<E> {
+    pub fn change(&self) -> f32 {
+        self.after - self.before
+    }
+}
+
+impl<E: Environment> Display for QTableDiff<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Added entries:     {}", self.added)?;
+        writeln!(f, "Removed entries:   {}", self.removed)?;
+        writeln!(f, "Changed entries:   {}", self.changed)?;
+        writeln!(f, "Unchanged entries: {}", self.unchanged)?;
+        writeln!(f, "Mean |change|:     {:.4}", self.mean_abs_change)?;
+        writeln!(f)?;
+        writeln!(f, "|change| distribution:")?;
+        let mut lower = None;
+        for (i, count) in self.change_histogram.iter().enumerate() {
+            let label = match (lower, CHANGE_BUCKETS.get(i)) {
+                (None, Some(upper)) => format!("= {upper}"),
+                (Some(lower), Some(upper)) => format!("({lower}, {upper}]"),
+                (Some(lower), None) => format!("> {lower}"),
+                (None, None) => unreachable!("There is always at least one bucket boundary"),
+            };
+            writeln!(f, "  {label:<14} {count}")?;
+            lower = CHANGE_BUCKETS.get(i);
+        }
+        writeln!(f)?;
+        writeln!(f, "Largest swings:")?;
+        for swing in self.largest_swings.iter() {
+            writeln!(
+                f,
+                "  [{}] action {}: {:.4} -> {:.4} ({:+.4})",
+                swing.state.serialize(),
+                swing.action.serialize(),
+                swing.before,
+                swing.after,
+                swing.change()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use crate::mankalla::{MankallaGame, MankallaGameState, Player};
+use crate::q_learning::{
+    Environment, EpisodeStats, Outcome, Policy, TrainingObserver, TrainingOptions, Transition,
+};
+use crate::two_player;
+
+// Both learners update online, but each one only from its own decisions: a transition runs
+// from one of its moves to its next move, the opponent's rewards in between count against it.
+// The next move is chosen before the transition is learned from, on-policy learners bootstrap
+// from it.
+struct Seat<'a> {
+    policy: &'a mut dyn Policy<MankallaGame>,
+    pending: Option<Transition<MankallaGame>>,
+    transitions: Vec<Transition<MankallaGame>>,
+    stats: EpisodeStats,
+}
+
+impl<'a> Seat<'a> {
+    fn new(policy: &'a mut dyn Policy<MankallaGame>, episode: usize) -> Self {
+        Seat {
+            policy,
+            pending: None,
+            transitions: Vec::new(),
+            stats: EpisodeStats {
+                episode,
+                steps: 0,
+                total_reward: 0f32,
+                outcome: None,
+                clipped_steps: 0,
+            },
+        }
+    }
+
+    fn add_reward(&mut self, reward: f32, clipped: bool) {
+        if let Some(pending) = &mut self.pending {
+            pending.reward += reward;
+            pending.clipped |= clipped;
+            self.stats.total_reward += reward;
+            self.stats.clipped_steps += clipped as usize;
+        }
+    }
+
+    // The seat is about to play `next_action` or the game is over, so its last decision can be
+    // learned from
+    fn flush(
+        &mut self,
+        next_state: MankallaGameState,
+        outcome: Option<Outcome>,
+        truncated: bool,
+        next_action: Option<u8>,
+    ) {
+        if let Some(mut transition) = self.pending.take() {
+            transition.next_state = next_state;
+            transition.outcome = outcome;
+            transition.truncated = truncated;
+            self.policy.improve_on_policy(
+                transition.state.into(),
+                transition.action,
+                transition.reward,
+                next_state,
+                next_action,
+            );
+            self.transitions.push(transition);
+        }
+        self.stats.outcome = outcome;
+    }
+}
+
+fn seat_index(player: Player) -> usize {
+    match player {
+        Player::Player1 => 0,
+        Player::Player2 => 1,
+    }
+}
+
+// What an observer gets to see of one seat after the game
+struct SeatResult {
+    transitions: Vec<Transition<MankallaGame>>,
+    stats: EpisodeStats,
+}
+
+fn play_episode(
+    players: [&mut dyn Policy<MankallaGame>; 2],
+    episode: usize,
+    options: &TrainingOptions,
+) -> [SeatResult; 2] {
+    let mut seats = players.map(|policy| Seat::new(policy, episode));
+    let mut rng = options.opening_rng(episode);
+    let mut state = MankallaGame::new();
+    let mut steps = 0;
+    loop {
+        let mover = seat_index(state.get_player_to_move());
+        let other = 1 - mover;
+        let action = options.choose_action(&*seats[mover].policy, state.into(), steps, &mut rng);
+        seats[mover].flush(state, None, false, Some(action));
+
+        let (next_state, reward, outcome) = two_player::play::<MankallaGame>(&state, &action);
+        steps += 1;
+
+        seats[mover].pending = Some(Transition {
+            state,
+            action,
+            reward: 0f32,
+            next_state,
+            outcome,
+            truncated: false,
+            clipped: false,
+        });
+        seats[mover].stats.steps += 1;
+        let (own_reward, own_clipped) = options.rewards.shape(reward.value(), outcome);
+        seats[mover].add_reward(own_reward, own_clipped);
+        let (other_reward, other_clipped) = options.rewards.shape(
+            reward.for_player(reward.mover().opponent()),
+            outcome.map(Outcome::opposite),
+        );
+        seats[other].add_reward(other_reward, other_clipped);
+
+        let truncated = outcome.is_none() && options.max_steps.is_some_and(|m| steps >= m);
+        if outcome.is_some() || truncated {
+            // A cut off game goes on from the move each seat would play next
+            for (seat, outcome) in [(mover, outcome), (other, outcome.map(Outcome::opposite))] {
+                let next_action = outcome
+                    .is_none()
+                    .then(|| seats[seat].policy.choose_action(next_state.into(), None));
+                seats[seat].flush(next_state, outcome, truncated, next_action);
+            }
+            return seats.map(|seat| SeatResult {
+                transitions: seat.transitions,
+                stats: seat.stats,
+            });
+        }
+        state = next_state;
+    }
+}
+
+fn report<P: Policy<MankallaGame>>(
+    policy: &mut P,
+    result: SeatResult,
+    observer: &mut impl TrainingObserver<MankallaGame, P>,
+) {
+    for transition in &result.transitions {
+        observer.on_step(0, transition);
+    }
+    policy.on_episode_increment();
+    observer.on_episode_end(policy, &result.stats);
+    observer.adjust_policy(policy);
+}
+
+// Two possibly different learners play each other, they swap seats every episode so neither
+// keeps the first move advantage. Each observer sees the episodes from its own learner's side.
+pub fn train_pair<A: Policy<MankallaGame>, B: Policy<MankallaGame>>(
+    first: &mut A,
+    second: &mut B,
+    num_training_episodes: usize,
+    options: &TrainingOptions,
+    first_observer: &mut impl TrainingObserver<MankallaGame, A>,
+    second_observer: &mut impl TrainingObserver<MankallaGame, B>,
+) {
+    for episode in 0..num_training_episodes {
+        let [first_result, second_result] = match episode % 2 {
+            0 => play_episode([first, second], episode, options),
+            _ => {
+                let [second_result, first_result] = play_episode([second, first], episode, options);
+                [first_result, second_result]
+            }
+        };
+        report(first, first_result, first_observer);
+        report(second, second_result, second_observer);
+        if first_observer.should_stop() || second_observer.should_stop() {
+            break;
+        }
+    }
+}
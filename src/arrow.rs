@@ -0,0 +1,228 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Float32Array, RecordBatch, StringArray, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::errors::ParquetError;
+
+use crate::dataset::TransitionRecord;
+use crate::q_learning::{Deserialize, DeserializeError, Environment, QTable, Serialize};
+
+// States and actions are stored with their text serialization, the same one the CSV files use,
+// so a column can be read back without knowing the environment's layout
+#[derive(Debug)]
+pub enum ArrowDatasetError {
+    Io(io::Error),
+    Arrow(ArrowError),
+    Parquet(ParquetError),
+    MissingColumn(&'static str),
+    Deserialize(DeserializeError),
+}
+
+impl Error for ArrowDatasetError {}
+
+impl Display for ArrowDatasetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArrowDatasetError::Io(e) => write!(f, "{e}"),
+            ArrowDatasetError::Arrow(e) => write!(f, "{e}"),
+            ArrowDatasetError::Parquet(e) => write!(f, "{e}"),
+            ArrowDatasetError::MissingColumn(name) => {
+                write!(f, "Column \"{name}\" is missing or has the wrong type")
+            }
+            ArrowDatasetError::Deserialize(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<io::Error> for ArrowDatasetError {
+    fn from(e: io::Error) -> Self {
+        ArrowDatasetError::Io(e)
+    }
+}
+
+impl From<ArrowError> for ArrowDatasetError {
+    fn from(e: ArrowError) -> Self {
+        ArrowDatasetError::Arrow(e)
+    }
+}
+
+impl From<ParquetError> for ArrowDatasetError {
+    fn from(e: ParquetError) -> Self {
+        ArrowDatasetError::Parquet(e)
+    }
+}
+
+impl From<DeserializeError> for ArrowDatasetError {
+    fn from(e: DeserializeError) -> Self {
+        ArrowDatasetError::Deserialize(e)
+    }
+}
+
+pub fn qtable_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("state", DataType::Utf8, false),
+        Field::new("action", DataType::Utf8, false),
+        Field::new("value", DataType::Float32, false),
+    ])
+}
+
+pub fn transitions_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("episode", DataType::UInt64, false),
+        Field::new("step", DataType::UInt64, false),
+        Field::new("state", DataType::Utf8, false),
+        Field::new("action", DataType::Utf8, false),
+        Field::new("reward", DataType::Float32, false),
+        Field::new("next_state", DataType::Utf8, false),
+        Field::new("done", DataType::Boolean, false),
+    ])
+}
+
+fn column<'a, T: Array + 'static>(
+    batch: &'a RecordBatch,
+    name: &'static str,
+) -> Result<&'a T, ArrowDatasetError> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<T>())
+        .ok_or(ArrowDatasetError::MissingColumn(name))
+}
+
+pub fn qtable_to_batch<E: Environment>(qtable: &QTable<E>) -> Result<RecordBatch, ArrowError> {
+    let states = StringArray::from_iter_values(qtable.iter().map(|(s, _, _)| s.serialize()));
+    let actions = StringArray::from_iter_values(qtable.iter().map(|(_, a, _)| a.serialize()));
+    let values = Float32Array::from_iter_values(qtable.iter().map(|(_, _, v)| v));
+    let columns: Vec<ArrayRef> = vec![Arc::new(states), Arc::new(actions), Arc::new(values)];
+    RecordBatch::try_new(Arc::new(qtable_schema()), columns)
+}
+
+pub fn qtable_from_batches<E: Environment>(
+    batches: &[RecordBatch],
+) -> Result<QTable<E>, ArrowDatasetError> {
+    let mut qtable = QTable::<E>::new();
+    for batch in batches {
+        let states = column::<StringArray>(batch, "state")?;
+        let actions = column::<StringArray>(batch, "action")?;
+        let values = column::<Float32Array>(batch, "value")?;
+        for i in 0..batch.num_rows() {
+            let state = states.value(i);
+            let action = actions.value(i);
+            let value = values.value(i);
+            qtable
+                .try_insert(
+                    E::ActionRelevantState::deserialize(state)?,
+                    E::Action::deserialize(action)?,
+                    value,
+                )
+                .map_err(|e| match e.reason() {
+                    Some(reason) => DeserializeError::because(format!(
+                        "{reason} in row \"{state};{action};{value}\""
+                    )),
+                    None => e,
+                })?;
+        }
+    }
+    Ok(qtable)
+}
+
+pub fn transitions_to_batch<E: Environment>(
+    records: &[TransitionRecord<E>],
+) -> Result<RecordBatch, ArrowError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(
+            records.iter().map(|r| r.episode as u64),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            records.iter().map(|r| r.step as u64),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|r| r.state.serialize()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|r| r.action.serialize()),
+        )),
+        Arc::new(Float32Array::from_iter_values(
+            records.iter().map(|r| r.reward),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|r| r.next_state.serialize()),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            records.iter().map(|r| Some(r.done)),
+        )),
+    ];
+    RecordBatch::try_new(Arc::new(transitions_schema()), columns)
+}
+
+pub fn transitions_from_batches<E: Environment>(
+    batches: &[RecordBatch],
+) -> Result<Vec<TransitionRecord<E>>, ArrowDatasetError> {
+    let mut records = Vec::new();
+    for batch in batches {
+        let episodes = column::<UInt64Array>(batch, "episode")?;
+        let steps = column::<UInt64Array>(batch, "step")?;
+        let states = column::<StringArray>(batch, "state")?;
+        let actions = column::<StringArray>(batch, "action")?;
+        let rewards = column::<Float32Array>(batch, "reward")?;
+        let next_states = column::<StringArray>(batch, "next_state")?;
+        let dones = column::<BooleanArray>(batch, "done")?;
+        for i in 0..batch.num_rows() {
+            records.push(TransitionRecord {
+                episode: episodes.value(i) as usize,
+                step: steps.value(i) as usize,
+                state: E::ActionRelevantState::deserialize(states.value(i))?,
+                action: E::Action::deserialize(actions.value(i))?,
+                reward: rewards.value(i),
+                next_state: E::ActionRelevantState::deserialize(next_states.value(i))?,
+                done: dones.value(i),
+            });
+        }
+    }
+    Ok(records)
+}
+
+pub fn write_parquet(path: &str, batch: &RecordBatch) -> Result<(), ArrowDatasetError> {
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), None)?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+// A transitions file written a batch at a time, so a dataset does not have to fit in memory
+pub struct TransitionsWriter {
+    writer: ArrowWriter<File>,
+}
+
+impl TransitionsWriter {
+    pub fn create(path: &str) -> Result<Self, ArrowDatasetError> {
+        let writer =
+            ArrowWriter::try_new(File::create(path)?, Arc::new(transitions_schema()), None)?;
+        Ok(TransitionsWriter { writer })
+    }
+
+    pub fn write<E: Environment>(
+        &mut self,
+        records: &[TransitionRecord<E>],
+    ) -> Result<(), ArrowDatasetError> {
+        self.writer.write(&transitions_to_batch(records)?)?;
+        Ok(())
+    }
+
+    pub fn close(self) -> Result<(), ArrowDatasetError> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+pub fn read_parquet(path: &str) -> Result<Vec<RecordBatch>, ArrowDatasetError> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    Ok(reader.collect::<Result<Vec<_>, _>>()?)
+}
@@ -0,0 +1,237 @@
+use std::fmt::Display;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::hyperparameters::Hyperparameters;
+use crate::q_learning::{
+    ActionList, ActionMask, Environment, EpsilonGreedyPolicy, Outcome, Policy,
+};
+use crate::schedule::ExponentialDecay;
+use crate::seeding::split_mix;
+use crate::softmax::SoftmaxPolicy;
+use crate::thompson::{NormalGamma, ThompsonPolicy};
+use crate::ucb::UcbPolicy;
+
+pub const MAX_ARMS: usize = ActionMask::CAPACITY;
+const TESTBED_ARMS: usize = 10;
+// Constant step sizes and exploration, the usual settings for the testbed
+const LEARNING_RATE: f32 = 0.1;
+const EPSILON: f32 = 0.1;
+const UCB_EXPLORATION: f32 = 2f32;
+const TEMPERATURE: f32 = 0.2;
+
+// Exploration strategies on their own, without states getting in the way: the same policies
+// training uses, on an environment with a single state where every pull ends the episode. Every
+// arm pays its mean plus standard normal noise, regret is what the best arm would have paid on
+// average.
+pub struct MultiArmedBandit;
+
+// The arms and the stream of the noise, the state of `MultiArmedBandit`
+#[derive(Clone, Copy)]
+pub struct Bandit {
+    means: [f32; MAX_ARMS],
+    arms: u8,
+    rng: u64,
+}
+
+// Box-Muller, one of the pair is thrown away
+pub fn standard_normal(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = rng.random_range(f32::EPSILON..1f32);
+    let u2: f32 = rng.random();
+    (-2f32 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
+
+impl Bandit {
+    pub fn new(means: &[f32], seed: u64) -> Self {
+        assert!(
+            (1..=MAX_ARMS).contains(&means.len()),
+            "A bandit has between 1 and {MAX_ARMS} arms"
+        );
+        let mut bandit = Bandit {
+            means: [0f32; MAX_ARMS],
+            arms: means.len() as u8,
+            rng: seed,
+        };
+        bandit.means[..means.len()].copy_from_slice(means);
+        bandit
+    }
+
+    // Means drawn from a standard normal, the usual 10-armed testbed for k = 10
+    pub fn random(arms: usize, rng: &mut impl Rng) -> Self {
+        let means: Vec<f32> = (0..arms).map(|_| standard_normal(rng)).collect();
+        Bandit::new(&means, rng.random())
+    }
+
+    pub fn arms(&self) -> usize {
+        self.arms as usize
+    }
+
+    pub fn mean(&self, arm: usize) -> f32 {
+        self.means[..self.arms()][arm]
+    }
+
+    fn best_mean(&self) -> f32 {
+        self.means[..self.arms()]
+            .iter()
+            .copied()
+            .fold(f32::MIN, f32::max)
+    }
+}
+
+// All a policy gets to see is how many arms there are
+impl From<Bandit> for u8 {
+    fn from(bandit: Bandit) -> u8 {
+        bandit.arms
+    }
+}
+
+impl Environment for MultiArmedBandit {
+    type State = Bandit;
+    type ActionRelevantState = u8;
+    type Action = u8;
+
+    fn new() -> Bandit {
+        MultiArmedBandit::new_seeded(rand::random())
+    }
+
+    fn new_seeded(seed: u64) -> Bandit {
+        Bandit::random(TESTBED_ARMS, &mut StdRng::seed_from_u64(seed))
+    }
+
+    const MAX_ACTIONS: usize = MAX_ARMS;
+
+    fn actions(arms: &u8) -> ActionList<u8> {
+        (0..*arms).collect()
+    }
+
+    fn action_index(action: &u8) -> usize {
+        *action as usize
+    }
+
+    // Every arm on its own, there is nothing to share
+    type Afterstate = [u8; 2];
+
+    fn afterstate(arms: &u8, action: &u8) -> ([u8; 2], f32) {
+        ([*arms, *action], 0f32)
+    }
+
+    // The outcome only ends the episode, a pull is neither won nor lost
+    fn step(bandit: &Bandit, action: &u8) -> (Bandit, f32, Option<Outcome>) {
+        let mut next = *bandit;
+        let mut rng = StdRng::seed_from_u64(split_mix(&mut next.rng));
+        let reward = bandit.mean(*action as usize) + standard_normal(&mut rng);
+        (next, reward, Some(Outcome::Draw))
+    }
+}
+
+// Expected cumulative regret after every step
+pub fn regret_curve(
+    policy: &mut dyn Policy<MultiArmedBandit>,
+    bandit: Bandit,
+    steps: usize,
+) -> Vec<f32> {
+    let best = bandit.best_mean();
+    let mut state = bandit;
+    let mut regret = 0f32;
+    (0..steps)
+        .map(|_| {
+            let arm = policy.choose_action(state.into(), None);
+            let (next_state, reward, _) = MultiArmedBandit::step(&state, &arm);
+            policy.improve(state.into(), arm, reward, next_state, true);
+            policy.on_episode_increment();
+            state = next_state;
+            regret += best - bandit.mean(arm as usize);
+            regret
+        })
+        .collect()
+}
+
+// The standard line-up with fixed exploration, each seeded from the run
+fn strategies(seed: u64) -> Vec<(String, Box<dyn Policy<MultiArmedBandit>>)> {
+    let mut epsilon_greedy = EpsilonGreedyPolicy::from_hyperparameters(Hyperparameters {
+        learning_rate: LEARNING_RATE,
+        gamma: 0f32,
+        max_epsilon: EPSILON,
+        min_epsilon: EPSILON,
+        decay_rate: 0f32,
+    })
+    .expect("The bandit hyperparameters are valid");
+    epsilon_greedy.reseed(seed);
+    let temperature = ExponentialDecay {
+        start: TEMPERATURE,
+        end: TEMPERATURE,
+        rate: 0f32,
+    };
+    let mut softmax = SoftmaxPolicy::new(LEARNING_RATE, 0f32, temperature);
+    softmax.reseed(seed);
+    let mut thompson = ThompsonPolicy::new(0f32, NormalGamma::default(), f32::INFINITY);
+    thompson.reseed(seed);
+    vec![
+        (
+            format!("epsilon-greedy {EPSILON}"),
+            Box::new(epsilon_greedy),
+        ),
+        (
+            format!("UCB c={UCB_EXPLORATION}"),
+            Box::new(UcbPolicy::new(LEARNING_RATE, 0f32, UCB_EXPLORATION)),
+        ),
+        (format!("softmax t={TEMPERATURE}"), Box::new(softmax)),
+        ("Thompson sampling".to_owned(), Box::new(thompson)),
+    ]
+}
+
+pub struct BanditReport {
+    pub arms: usize,
+    pub runs: usize,
+    // Strategy name and its cumulative regret averaged over all runs, one entry per step
+    pub curves: Vec<(String, Vec<f32>)>,
+}
+
+// Every run draws a fresh bandit that all strategies then face
+pub fn compare(arms: usize, steps: usize, runs: usize, seed: u64) -> BanditReport {
+    assert!(runs > 0, "At least one run is needed");
+    let mut curves: Vec<(String, Vec<f32>)> = strategies(seed)
+        .into_iter()
+        .map(|(name, _)| (name, vec![0f32; steps]))
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..runs {
+        let bandit = Bandit::random(arms, &mut rng);
+        for ((_, mut policy), (_, curve)) in strategies(rng.random()).into_iter().zip(&mut curves) {
+            let run = regret_curve(policy.as_mut(), bandit, steps);
+            for (total, regret) in curve.iter_mut().zip(run) {
+                *total += regret / runs as f32;
+            }
+        }
+    }
+    BanditReport { arms, runs, curves }
+}
+
+// Cumulative regret at ten evenly spaced checkpoints
+impl Display for BanditReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let steps = self.curves.first().map_or(0, |(_, curve)| curve.len());
+        let checkpoints: Vec<usize> = (1..=10)
+            .map(|i| i * steps / 10)
+            .filter(|&s| s > 0)
+            .collect();
+        writeln!(
+            f,
+            "Cumulative regret on {}-armed bandits, averaged over {} runs",
+            self.arms, self.runs
+        )?;
+        write!(f, "{:<20}", "step")?;
+        for step in &checkpoints {
+            write!(f, "{step:>9}")?;
+        }
+        for (name, curve) in &self.curves {
+            write!(f, "\n{name:<20}")?;
+            for step in &checkpoints {
+                write!(f, "{:>9.1}", curve[step - 1])?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use std::env;
+use std::error::Error;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use mankalla_rl::tournament::{EngineProcess, GoLimits, run_tournament};
+use rand::{SeedableRng, rngs::StdRng};
+
+// Matches between two programs that speak the protocol of `mankalla-rl engine`, refereed by this
+// crate's rules. Named apart from the `arena` subcommand, which pits learners against each other.
+const USAGE: &str = "Usage: engine-arena [--pairs N] [--opening-plies N] [--depth N] \
+    [--movetime MS] [--timeout MS] [--seed S] \"<engine command>\" \"<engine command>\"";
+
+struct Args {
+    pairs: usize,
+    opening_plies: usize,
+    limits: GoLimits,
+    seed: Option<u64>,
+    engines: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut args = Args {
+        pairs: 10,
+        opening_plies: 4,
+        limits: GoLimits {
+            depth: None,
+            movetime: None,
+            timeout: Duration::from_secs(30),
+        },
+        seed: None,
+        engines: Vec::new(),
+    };
+    let mut words = env::args().skip(1);
+    while let Some(arg) = words.next() {
+        let mut value = || {
+            words
+                .next()
+                .ok_or_else(|| format!("Missing value for \"{arg}\""))
+        };
+        match arg.as_str() {
+            "--pairs" => args.pairs = value()?.parse()?,
+            "--opening-plies" => args.opening_plies = value()?.parse()?,
+            "--depth" => match value()?.parse()? {
+                0 => return Err("--depth has to be at least 1".into()),
+                depth => args.limits.depth = Some(depth),
+            },
+            "--movetime" => args.limits.movetime = Some(Duration::from_millis(value()?.parse()?)),
+            "--timeout" => args.limits.timeout = Duration::from_millis(value()?.parse()?),
+            "--seed" => args.seed = Some(value()?.parse()?),
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown argument \"{arg}\"").into());
+            }
+            _ => args.engines.push(arg),
+        }
+    }
+    match args.engines.len() {
+        2 => Ok(args),
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+    let seed = args.seed.unwrap_or_else(rand::random);
+    println!("Openings with seed {seed}");
+    let mut first = EngineProcess::spawn(&args.engines[0])
+        .map_err(|e| format!("Could not start \"{}\": {e}", args.engines[0]))?;
+    let mut second = EngineProcess::spawn(&args.engines[1])
+        .map_err(|e| format!("Could not start \"{}\": {e}", args.engines[1]))?;
+    let report = run_tournament(
+        &mut first,
+        &mut second,
+        args.pairs,
+        args.opening_plies,
+        &args.limits,
+        &mut StdRng::seed_from_u64(seed),
+    );
+    println!("{report}");
+    Ok(())
+}
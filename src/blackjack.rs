@@ -0,0 +1,142 @@
+use crate::q_learning::{ActionList, Environment, Outcome, Policy};
+use crate::seeding::split_mix;
+
+pub const STICK: u8 = 0;
+pub const HIT: u8 = 1;
+
+// Blackjack against a dealer who stands on 17, drawn from an infinite deck, the way Sutton and
+// Barto set it up. Sums below 12 are dealt through since hitting them can never bust. The cards
+// come from a stream that travels inside the state, so `step` stays a plain function of its
+// inputs and a game started from the same seed is dealt the same cards.
+pub struct Blackjack;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlackjackState {
+    player_sum: u8,
+    usable_ace: bool,
+    dealer_card: u8,
+    rng: u64,
+}
+
+// Aces are 1, face cards 10
+fn draw(rng: &mut u64) -> u8 {
+    ((split_mix(rng) % 13) as u8 + 1).min(10)
+}
+
+// (sum, usable ace) after adding a card, an ace counts 11 while that does not bust the hand
+fn add_card((sum, usable_ace): (u8, bool), card: u8) -> (u8, bool) {
+    let (sum, usable_ace) = match card == 1 && sum + 11 <= 21 {
+        true => (sum + 11, true),
+        false => (sum + card, usable_ace),
+    };
+    match usable_ace && sum > 21 {
+        true => (sum - 10, false),
+        false => (sum, usable_ace),
+    }
+}
+
+impl Environment for Blackjack {
+    type State = BlackjackState;
+    // (player sum, dealer card, usable ace)
+    type ActionRelevantState = [u8; 3];
+    type Action = u8;
+
+    fn new() -> BlackjackState {
+        Blackjack::new_seeded(rand::random())
+    }
+
+    fn new_seeded(seed: u64) -> BlackjackState {
+        let mut rng = seed;
+        let mut hand = (0, false);
+        while hand.0 < 12 {
+            hand = add_card(hand, draw(&mut rng));
+        }
+        BlackjackState {
+            player_sum: hand.0,
+            usable_ace: hand.1,
+            dealer_card: draw(&mut rng),
+            rng,
+        }
+    }
+
+    const MAX_ACTIONS: usize = 2;
+
+    fn actions(_state: &[u8; 3]) -> ActionList<u8> {
+        [STICK, HIT].into_iter().collect()
+    }
+
+    fn action_index(action: &u8) -> usize {
+        *action as usize
+    }
+
+    // Nothing is settled before the next card, so the decision itself is all there is
+    type Afterstate = [u8; 4];
+
+    fn afterstate(state: &[u8; 3], action: &u8) -> ([u8; 4], f32) {
+        ([state[0], state[1], state[2], *action], 0f32)
+    }
+
+    fn step(state: &BlackjackState, action: &u8) -> (BlackjackState, f32, Option<Outcome>) {
+        let mut state = *state;
+        match *action {
+            HIT => {
+                (state.player_sum, state.usable_ace) =
+                    add_card((state.player_sum, state.usable_ace), draw(&mut state.rng));
+                match state.player_sum > 21 {
+                    true => (state, -1f32, Some(Outcome::Loss)),
+                    false => (state, 0f32, None),
+                }
+            }
+            STICK => {
+                let mut dealer = add_card((0, false), state.dealer_card);
+                while dealer.0 < 17 {
+                    dealer = add_card(dealer, draw(&mut state.rng));
+                }
+                match (dealer.0 > 21, state.player_sum.cmp(&dealer.0)) {
+                    (true, _) | (false, std::cmp::Ordering::Greater) => {
+                        (state, 1f32, Some(Outcome::Win))
+                    }
+                    (false, std::cmp::Ordering::Equal) => (state, 0f32, Some(Outcome::Draw)),
+                    (false, std::cmp::Ordering::Less) => (state, -1f32, Some(Outcome::Loss)),
+                }
+            }
+            _ => panic!("Blackjack only knows stick and hit"),
+        }
+    }
+}
+
+impl From<BlackjackState> for [u8; 3] {
+    fn from(value: BlackjackState) -> Self {
+        [value.player_sum, value.dealer_card, value.usable_ace as u8]
+    }
+}
+
+// The optimal policy for this variant, figure 5.2 in Sutton and Barto
+pub fn optimal_action(state: &[u8; 3]) -> u8 {
+    let [sum, dealer, usable_ace] = *state;
+    let stick = match usable_ace {
+        1 => sum >= 19 || sum == 18 && (2..=8).contains(&dealer),
+        _ => {
+            sum >= 17
+                || (13..=16).contains(&sum) && (2..=6).contains(&dealer)
+                || sum == 12 && (4..=6).contains(&dealer)
+        }
+    };
+    match stick {
+        true => STICK,
+        false => HIT,
+    }
+}
+
+// Every state a game can be in, 10 sums times 10 dealer cards, with and without a usable ace
+pub fn states() -> impl Iterator<Item = [u8; 3]> {
+    (12..=21).flat_map(|sum| (1..=10).flat_map(move |dealer| [[sum, dealer, 0], [sum, dealer, 1]]))
+}
+
+// (states where the policy acts optimally, all states)
+pub fn optimal_agreement(policy: &(impl Policy<Blackjack> + ?Sized)) -> (usize, usize) {
+    states().fold((0, 0), |(agreeing, all), state| {
+        let agrees = policy.choose_action(state, None) == optimal_action(&state);
+        (agreeing + agrees as usize, all + 1)
+    })
+}
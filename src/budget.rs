@@ -0,0 +1,123 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::q_learning::{Environment, EpisodeStats, Policy, TrainingObserver, Transition};
+
+// Limits for a whole training run, checked between episodes so a run that hits one still ends
+// with a finished episode, a saved policy and the usual report
+pub struct TrainingBudget {
+    max_wall_time: Option<Duration>,
+    max_steps: Option<usize>,
+    started: Instant,
+    steps: usize,
+    episodes: usize,
+    exhausted: Option<Exhausted>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exhausted {
+    WallTime { limit: Duration, episodes: usize },
+    Steps { limit: usize, episodes: usize },
+}
+
+impl TrainingBudget {
+    // The clock starts right away
+    pub fn new(max_wall_time: Option<Duration>, max_steps: Option<usize>) -> Self {
+        TrainingBudget {
+            max_wall_time,
+            max_steps,
+            started: Instant::now(),
+            steps: 0,
+            episodes: 0,
+            exhausted: None,
+        }
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn episodes(&self) -> usize {
+        self.episodes
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    // Which limit stopped the run, `None` if it got through all its episodes
+    pub fn exhausted(&self) -> Option<Exhausted> {
+        self.exhausted
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized> TrainingObserver<E, P> for TrainingBudget {
+    fn on_step(&mut self, _env: usize, _transition: &Transition<E>) {
+        self.steps += 1;
+    }
+
+    fn on_episode_end(&mut self, _policy: &P, _stats: &EpisodeStats) {
+        self.episodes += 1;
+        if self.exhausted.is_some() {
+            return;
+        }
+        let episodes = self.episodes;
+        self.exhausted = match (self.max_wall_time, self.max_steps) {
+            (Some(limit), _) if self.started.elapsed() >= limit => {
+                Some(Exhausted::WallTime { limit, episodes })
+            }
+            (_, Some(limit)) if self.steps >= limit => Some(Exhausted::Steps { limit, episodes }),
+            _ => None,
+        };
+    }
+
+    fn should_stop(&self) -> bool {
+        self.exhausted.is_some()
+    }
+}
+
+impl Display for Exhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exhausted::WallTime { limit, episodes } => write!(
+                f,
+                "Stopped after {episodes} episodes, the wall time budget of {} is used up",
+                format_duration(*limit)
+            ),
+            Exhausted::Steps { limit, episodes } => write!(
+                f,
+                "Stopped after {episodes} episodes, the budget of {limit} steps is used up"
+            ),
+        }
+    }
+}
+
+// "90", "90s", "15m", "2h", "1.5d"; a number without unit is seconds
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1f64,
+        "m" => 60f64,
+        "h" => 3600f64,
+        "d" => 86400f64,
+        _ => return Err(format!("Unknown unit in \"{s}\" (supported: s, m, h, d)")),
+    };
+    match number.parse::<f64>() {
+        Ok(n) if n >= 0f64 && n.is_finite() => Ok(Duration::from_secs_f64(n * seconds)),
+        _ => Err(format!(
+            "Invalid duration \"{s}\", expected e.g. 90s, 15m or 2h"
+        )),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s:02}s"),
+        (h, m, _) => format!("{h}h{m:02}m"),
+    }
+}
@@ -0,0 +1,105 @@
+use crate::q_learning::{Deserialize, DeserializeError, Serialize};
+
+const HEADER: &str = "mankalla-rl bundle 1";
+
+// Several serialized policies in one file, one per difficulty, with free-form metadata for
+// whoever hands them out. Policies stay in their own text format, each behind a line with its
+// difficulty and its length in bytes:
+//
+//   mankalla-rl bundle 1
+//   meta name Shipped bots
+//   policy easy 5120
+//   <5120 bytes of policy>
+//   policy hard 81920
+//   <81920 bytes of policy>
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PolicyBundle {
+    pub metadata: Vec<(String, String)>,
+    policies: Vec<(String, String)>,
+}
+
+impl PolicyBundle {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Difficulties are single words and unique, a second policy for one replaces the first
+    pub fn add_policy(&mut self, difficulty: &str, policy: String) {
+        assert!(
+            !difficulty.is_empty() && !difficulty.contains(char::is_whitespace),
+            "A difficulty is one word"
+        );
+        match self.policies.iter_mut().find(|(d, _)| d == difficulty) {
+            Some((_, existing)) => *existing = policy,
+            None => self.policies.push((difficulty.to_owned(), policy)),
+        }
+    }
+
+    // In the order they were added, which is meant to be from easy to hard
+    pub fn difficulties(&self) -> impl Iterator<Item = &str> {
+        self.policies.iter().map(|(d, _)| d.as_str())
+    }
+
+    pub fn policy(&self, difficulty: &str) -> Option<&str> {
+        self.policies
+            .iter()
+            .find(|(d, _)| d == difficulty)
+            .map(|(_, p)| p.as_str())
+    }
+}
+
+impl Serialize for PolicyBundle {
+    fn serialize(&self) -> String {
+        let mut s = format!("{HEADER}\n");
+        for (key, value) in &self.metadata {
+            s += &format!("meta {key} {value}\n");
+        }
+        for (difficulty, policy) in &self.policies {
+            s += &format!("policy {difficulty} {}\n{policy}", policy.len());
+        }
+        s
+    }
+}
+
+impl Deserialize for PolicyBundle {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let mut rest = input
+            .strip_prefix(HEADER)
+            .and_then(|rest| rest.strip_prefix('\n'))
+            .ok_or_else(|| {
+                DeserializeError::because(format!("a bundle starts with \"{HEADER}\""))
+            })?;
+        let mut bundle = PolicyBundle::new();
+        while !rest.is_empty() {
+            let (line, after) = rest.split_once('\n').unwrap_or((rest, ""));
+            rest = after;
+            match line.split_once(' ') {
+                Some(("meta", entry)) => {
+                    let (key, value) = entry.split_once(' ').unwrap_or((entry, ""));
+                    bundle.metadata.push((key.to_owned(), value.to_owned()));
+                }
+                Some(("policy", entry)) => {
+                    let (difficulty, length) = entry
+                        .split_once(' ')
+                        .and_then(|(d, l)| Some((d, l.parse::<usize>().ok()?)))
+                        .ok_or_else(|| {
+                            DeserializeError::because(format!("bad policy line \"{line}\""))
+                        })?;
+                    let policy = rest.get(..length).ok_or_else(|| {
+                        DeserializeError::because(format!(
+                            "the {difficulty} policy is cut off, expected {length} bytes"
+                        ))
+                    })?;
+                    bundle.add_policy(difficulty, policy.to_owned());
+                    rest = &rest[length..];
+                }
+                _ => {
+                    return Err(DeserializeError::because(format!(
+                        "unexpected line \"{line}\""
+                    )));
+                }
+            }
+        }
+        Ok(bundle)
+    }
+}
@@ -0,0 +1,124 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::budget::parse_duration;
+use crate::two_player::Player;
+
+// How much of the remaining time one move may take, as if this many moves were still to come
+const MOVES_TO_GO: u32 = 20;
+
+// Fischer time control: both players start with `base` and get `increment` for every move they
+// finish in time. Written "5m+2s", or "5m" without increment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (base, increment) = s.split_once('+').unwrap_or((s, "0"));
+        let control = TimeControl {
+            base: parse_duration(base)?,
+            increment: parse_duration(increment)?,
+        };
+        match control.base.is_zero() {
+            true => Err(format!("The clock in \"{s}\" has no time to start with")),
+            false => Ok(control),
+        }
+    }
+}
+
+// One clock per player, only the one of the player to move runs. A player whose time runs out
+// loses, which the game finds out when the move ends or by asking `flag_fallen` while it waits.
+pub struct GameClock {
+    control: TimeControl,
+    player1: Duration,
+    player2: Duration,
+    running: Option<(Player, Instant)>,
+}
+
+impl GameClock {
+    pub fn new(control: TimeControl) -> Self {
+        GameClock {
+            control,
+            player1: control.base,
+            player2: control.base,
+            running: None,
+        }
+    }
+
+    fn time(&mut self, player: Player) -> &mut Duration {
+        match player {
+            Player::Player1 => &mut self.player1,
+            Player::Player2 => &mut self.player2,
+        }
+    }
+
+    // Stops whichever clock is running without an increment, the move was not finished
+    pub fn start(&mut self, player: Player) {
+        self.pause();
+        self.running = Some((player, Instant::now()));
+    }
+
+    fn pause(&mut self) {
+        if let Some((player, started)) = self.running.take() {
+            let time = self.time(player);
+            *time = time.saturating_sub(started.elapsed());
+        }
+    }
+
+    // Ends the move of the player whose clock runs. Returns whether it was in time, only then
+    // the increment is added. An extra turn is a move of its own and earns one as well.
+    pub fn stop(&mut self) -> bool {
+        let Some((player, _)) = self.running else {
+            return true;
+        };
+        self.pause();
+        let increment = self.control.increment;
+        let time = self.time(player);
+        match time.is_zero() {
+            true => false,
+            false => {
+                *time += increment;
+                true
+            }
+        }
+    }
+
+    // With the running move taken off
+    pub fn remaining(&self, player: Player) -> Duration {
+        let time = match player {
+            Player::Player1 => self.player1,
+            Player::Player2 => self.player2,
+        };
+        match self.running {
+            Some((running, started)) if running == player => time.saturating_sub(started.elapsed()),
+            _ => time,
+        }
+    }
+
+    pub fn flag_fallen(&self) -> Option<Player> {
+        [Player::Player1, Player::Player2]
+            .into_iter()
+            .find(|&player| self.remaining(player).is_zero())
+    }
+
+    // What a player with a time budget may spend on the next move: a share of what is left plus
+    // the increment it earns back, never all that is left
+    pub fn move_budget(&self, player: Player) -> Duration {
+        let remaining = self.remaining(player);
+        (remaining / MOVES_TO_GO + self.control.increment).min(remaining / 2)
+    }
+}
+
+// "4:05" for what a clock shows, tenths once less than ten seconds are left
+pub fn format_clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..10 => format!("0:0{}.{}", seconds, duration.subsec_millis() / 100),
+        _ => format!("{}:{:02}", seconds / 60, seconds % 60),
+    }
+}
@@ -0,0 +1,55 @@
+use crate::mankalla::{MankallaGame, MoveInfo, move_info, next_relevant_state};
+use crate::q_learning::{Environment, GreedyPolicy};
+
+// Everything worth saying about one move, seen from the player who made it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoveAnalysis {
+    pub info: MoveInfo,
+    // The stones the opponent could steal right away afterwards, 0 after an extra turn
+    pub steal_left_open: u8,
+    // Compared against the best other move by the mover's own values, `None` without values or
+    // without an alternative
+    pub runner_up: Option<RunnerUp>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunnerUp {
+    pub action: u8,
+    // Value of the chosen move minus the value of this one, negative if the mover passed on it
+    pub gap: f32,
+}
+
+pub fn analyze(
+    state: &[u8; 12],
+    action: u8,
+    values: Option<&GreedyPolicy<MankallaGame>>,
+) -> MoveAnalysis {
+    let info = move_info(state, &action);
+    let steal_left_open = match info.extra_turn {
+        true => 0,
+        false => {
+            let next_state = next_relevant_state(state, &action);
+            MankallaGame::actions(&next_state)
+                .iter()
+                .map(|a| move_info(&next_state, a).captured)
+                .max()
+                .unwrap_or(0)
+        }
+    };
+    let runner_up = values.and_then(|values| {
+        MankallaGame::actions(state)
+            .into_iter()
+            .filter(|&a| a != action)
+            .map(|a| (a, values.value(*state, a)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(a, value)| RunnerUp {
+                action: a,
+                gap: values.value(*state, action) - value,
+            })
+    });
+    MoveAnalysis {
+        info,
+        steal_left_open,
+        runner_up,
+    }
+}
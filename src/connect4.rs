@@ -0,0 +1,166 @@
+use std::fmt::Display;
+
+use crate::q_learning::{ActionList, Outcome};
+use crate::two_player::{Player, TwoPlayerGame};
+
+const WIDTH: u32 = 7;
+const HEIGHT: u32 = 6;
+// Every column gets one extra bit on top so a column overflowing never spills into the next one
+const COLUMN_BITS: u32 = HEIGHT + 1;
+
+pub struct ConnectFour;
+
+// Bit `column * 7 + row` is set for every stone, row 0 is the bottom
+//  5 12 19 26 33 40 47
+//  4 11 18 25 32 39 46
+//  3 10 17 24 31 38 45
+//  2  9 16 23 30 37 44
+//  1  8 15 22 29 36 43
+//  0  7 14 21 28 35 42
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ConnectFourState {
+    // Stones of the player to move
+    current: u64,
+    // Stones of both players
+    mask: u64,
+    moves: u8,
+}
+
+fn bottom(column: u8) -> u64 {
+    1 << (column as u32 * COLUMN_BITS)
+}
+
+fn top(column: u8) -> u64 {
+    1 << (column as u32 * COLUMN_BITS + HEIGHT - 1)
+}
+
+fn has_four(stones: u64) -> bool {
+    // Vertical, horizontal and both diagonals
+    [1, COLUMN_BITS, COLUMN_BITS - 1, COLUMN_BITS + 1]
+        .iter()
+        .any(|&shift| {
+            let pairs = stones & (stones >> shift);
+            pairs & (pairs >> (2 * shift)) != 0
+        })
+}
+
+impl TwoPlayerGame for ConnectFour {
+    type Position = ConnectFourState;
+    // (stones of the player to move, all stones), the same position looks the same to both players
+    type View = [u64; 2];
+    type Move = u8;
+    // The stone the move placed
+    type Undo = u64;
+
+    fn start() -> ConnectFourState {
+        Default::default()
+    }
+
+    fn from_view(view: &[u64; 2]) -> ConnectFourState {
+        ConnectFourState {
+            current: view[0],
+            mask: view[1],
+            moves: view[1].count_ones() as u8,
+        }
+    }
+
+    fn current_player(position: &ConnectFourState) -> Player {
+        match position.first_player_to_move() {
+            true => Player::Player1,
+            false => Player::Player2,
+        }
+    }
+
+    const MAX_MOVES: usize = WIDTH as usize;
+
+    fn legal_moves(view: &[u64; 2]) -> ActionList<u8> {
+        (0..WIDTH as u8)
+            .filter(|&column| view[1] & top(column) == 0)
+            .collect()
+    }
+
+    fn move_index(column: &u8) -> usize {
+        *column as usize
+    }
+
+    fn make_move(position: &mut ConnectFourState, column: &u8) -> u64 {
+        assert!(*column < WIDTH as u8);
+        assert!(position.mask & top(*column) == 0, "Column {column} is full");
+
+        let mask = position.mask | (position.mask + bottom(*column));
+        let stone = mask ^ position.mask;
+        let mover = position.current | stone;
+        position.current = mover ^ mask;
+        position.mask = mask;
+        position.moves += 1;
+        stone
+    }
+
+    fn unmake_move(position: &mut ConnectFourState, stone: u64) {
+        let mover = position.current ^ position.mask;
+        position.current = mover ^ stone;
+        position.mask ^= stone;
+        position.moves -= 1;
+    }
+
+    // Only the winning move is rewarded, there are no points along the way
+    fn outcome(position: &ConnectFourState) -> Option<Outcome> {
+        // The stones of whoever moved last
+        let last_mover = position.current ^ position.mask;
+        let first_moved_last = !position.first_player_to_move();
+        match (
+            has_four(last_mover),
+            position.moves as u32 == WIDTH * HEIGHT,
+        ) {
+            (true, _) => match first_moved_last {
+                true => Some(Outcome::Win),
+                false => Some(Outcome::Loss),
+            },
+            (false, true) => Some(Outcome::Draw),
+            (false, false) => None,
+        }
+    }
+}
+
+impl From<ConnectFourState> for [u64; 2] {
+    fn from(value: ConnectFourState) -> Self {
+        [value.current, value.mask]
+    }
+}
+
+impl ConnectFourState {
+    pub fn moves(&self) -> u8 {
+        self.moves
+    }
+
+    // The first player has the X stones
+    pub fn first_player_to_move(&self) -> bool {
+        self.moves.is_multiple_of(2)
+    }
+
+    fn first_player_stones(&self) -> u64 {
+        match self.first_player_to_move() {
+            true => self.current,
+            false => self.current ^ self.mask,
+        }
+    }
+}
+
+impl Display for ConnectFourState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let first = self.first_player_stones();
+        for row in (0..HEIGHT).rev() {
+            for column in 0..WIDTH {
+                let bit = 1 << (column * COLUMN_BITS + row);
+                let cell = match (self.mask & bit != 0, first & bit != 0) {
+                    (false, _) => '.',
+                    (true, true) => 'X',
+                    (true, false) => 'O',
+                };
+                write!(f, " {cell}")?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, " 0 1 2 3 4 5 6")
+    }
+}
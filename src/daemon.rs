@@ -0,0 +1,615 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::accounts::{Accounts, OngoingGame, Users};
+use crate::hyperparameters::Hyperparameters;
+use crate::json::{Json, ToJson};
+use crate::mankalla::{MankallaGame, MankallaGameState};
+use crate::prometheus::ServingMetrics;
+use crate::q_learning::{
+    EpisodeStats, EpsilonGreedyPolicy, GreedyPolicy, Outcome, Policy, Serialize, TrainingObserver,
+};
+use crate::signals;
+use crate::snapshot::{SnapshotPublisher, SnapshotReader};
+use crate::two_player::{Player, TwoPlayerGame};
+
+// One request per line on the control socket, one line back for each:
+//   status                  progress as JSON
+//   checkpoint              writes the policy file now
+//   set <name> <value>      a hyperparameter, `epsilon` pins min and max epsilon to the value
+//   swap                    serves a copy of the policy as it is now
+//   move <position>         the serving policy's move in a position in `{:#}` format
+//   stop                    a last checkpoint, then training ends
+//   health                  "ok" for as long as the process answers at all
+//   ready                   "ok" while moves can be served, an error before the first swap and
+//                           while shutting down
+// Health and ready are also answered over HTTP on the metrics port, as /healthz and /readyz.
+// With `--tokens` every connection has to authenticate before anything but health and ready, and
+// then has a game of its own against the serving policy, moving first:
+//   auth <token>            binds the connection to the user the token belongs to
+//   newgame                 starts over, a game still going counts as lost
+//   play <pit>              the user's move, answered with the bot's moves that followed as
+//                           "ok bot <pits, comma separated or -> result <win, loss, draw or ->
+//                           position <position>"
+//   game                    the position of the game going on
+//   stats                   the user's results as JSON
+#[derive(Clone, PartialEq)]
+pub enum ControlRequest {
+    Status,
+    Checkpoint,
+    Set(String, f32),
+    Swap,
+    Move(MankallaGameState),
+    Stop,
+    Health,
+    Ready,
+    Auth(String),
+    NewGame,
+    Play(u8),
+    Game,
+    Stats,
+}
+
+impl FromStr for ControlRequest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (command, rest) = s.split_once(' ').unwrap_or((s, ""));
+        let rest = rest.trim();
+        match (command, rest) {
+            ("status", "") => Ok(ControlRequest::Status),
+            ("checkpoint", "") => Ok(ControlRequest::Checkpoint),
+            ("swap", "") => Ok(ControlRequest::Swap),
+            ("stop", "") => Ok(ControlRequest::Stop),
+            ("health", "") => Ok(ControlRequest::Health),
+            ("ready", "") => Ok(ControlRequest::Ready),
+            ("newgame", "") => Ok(ControlRequest::NewGame),
+            ("game", "") => Ok(ControlRequest::Game),
+            ("stats", "") => Ok(ControlRequest::Stats),
+            ("auth", token) if !token.is_empty() && !token.contains(' ') => {
+                Ok(ControlRequest::Auth(token.to_owned()))
+            }
+            ("auth", _) => Err("Usage: auth <token>".to_owned()),
+            ("play", pit) => match pit.parse() {
+                Ok(pit) if pit < 6 => Ok(ControlRequest::Play(pit)),
+                _ => Err(format!("\"{pit}\" is not a pit, those are 0 to 5")),
+            },
+            ("set", setting) => match setting.split_whitespace().collect::<Vec<_>>()[..] {
+                [name, value] => value
+                    .parse()
+                    .map(|value| ControlRequest::Set(name.to_owned(), value))
+                    .map_err(|_| format!("\"{value}\" is not a number")),
+                _ => Err("Usage: set <name> <value>".to_owned()),
+            },
+            ("move", position) => position
+                .parse()
+                .map(ControlRequest::Move)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!(
+                "Unknown request \"{s}\" (status, checkpoint, set, swap, move, stop, health, \
+                 ready, auth, newgame, play, game, stats)"
+            )),
+        }
+    }
+}
+
+// The hyperparameters with one of them changed, checked like everywhere else
+pub fn with_setting(
+    hyperparameters: Hyperparameters,
+    name: &str,
+    value: f32,
+) -> Result<Hyperparameters, String> {
+    let mut changed = hyperparameters;
+    match name {
+        "learning_rate" => changed.learning_rate = value,
+        "gamma" => changed.gamma = value,
+        "max_epsilon" => changed.max_epsilon = value,
+        "min_epsilon" => changed.min_epsilon = value,
+        "decay_rate" => changed.decay_rate = value,
+        "epsilon" => (changed.max_epsilon, changed.min_epsilon) = (value, value),
+        _ => {
+            return Err(format!(
+                "Unknown setting \"{name}\" (learning_rate, gamma, max_epsilon, min_epsilon, \
+                 decay_rate, epsilon)"
+            ));
+        }
+    }
+    changed.validate().map_err(|e| e.to_string())?;
+    Ok(changed)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DaemonStatus {
+    pub episode: usize,
+    pub epsilon: f32,
+    pub hyperparameters: Hyperparameters,
+    pub qtable_size: usize,
+    pub episodes_per_second: f32,
+    pub serving_episode: Option<usize>,
+    pub last_checkpoint: Option<usize>,
+}
+
+impl ToJson for DaemonStatus {
+    fn to_json(&self) -> Json {
+        let h = &self.hyperparameters;
+        Json::object([
+            ("episode", self.episode.into()),
+            ("epsilon", self.epsilon.into()),
+            (
+                "hyperparameters",
+                Json::object([
+                    ("learning_rate", h.learning_rate.into()),
+                    ("gamma", h.gamma.into()),
+                    ("max_epsilon", h.max_epsilon.into()),
+                    ("min_epsilon", h.min_epsilon.into()),
+                    ("decay_rate", h.decay_rate.into()),
+                ]),
+            ),
+            ("qtable_size", self.qtable_size.into()),
+            ("episodes_per_second", self.episodes_per_second.into()),
+            ("serving_episode", self.serving_episode.into()),
+            ("last_checkpoint", self.last_checkpoint.into()),
+        ])
+    }
+}
+
+// A request from a connection together with where the answer goes
+pub type Control = (ControlRequest, Sender<String>);
+
+// Answers the control requests between two episodes, so the policy is never seen half updated.
+// Swapping publishes a copy for the connections to play from while training goes on.
+pub struct DaemonObserver {
+    requests: Receiver<Control>,
+    checkpoint_path: String,
+    checkpoint_every: Option<usize>,
+    swap_every: Option<usize>,
+    serving: SnapshotPublisher<GreedyPolicy<MankallaGame>>,
+    serving_episode: Option<usize>,
+    last_checkpoint: Option<usize>,
+    episode: usize,
+    started: Instant,
+    episodes_trained: usize,
+    metrics: Arc<ServingMetrics>,
+    stopped: bool,
+    // The first error writing a checkpoint, which ends training
+    pub failure: Option<String>,
+}
+
+impl DaemonObserver {
+    pub fn new(
+        requests: Receiver<Control>,
+        checkpoint_path: String,
+        checkpoint_every: Option<usize>,
+        swap_every: Option<usize>,
+        serving: SnapshotPublisher<GreedyPolicy<MankallaGame>>,
+        metrics: Arc<ServingMetrics>,
+    ) -> Self {
+        DaemonObserver {
+            requests,
+            checkpoint_path,
+            checkpoint_every,
+            swap_every,
+            serving,
+            serving_episode: None,
+            last_checkpoint: None,
+            episode: 0,
+            started: Instant::now(),
+            episodes_trained: 0,
+            metrics,
+            stopped: false,
+            failure: None,
+        }
+    }
+
+    pub fn last_checkpoint(&self) -> Option<usize> {
+        self.last_checkpoint
+    }
+
+    pub fn swap(&mut self, policy: &EpsilonGreedyPolicy<MankallaGame>) {
+        self.serving
+            .publish(policy.episode(), policy.greedy_policy().clone());
+        self.serving_episode = Some(policy.episode());
+        self.metrics.serving(policy.episode());
+    }
+
+    // Through a temporary file, a crash halfway leaves the last checkpoint as it was
+    pub fn checkpoint(&mut self, policy: &EpsilonGreedyPolicy<MankallaGame>) -> io::Result<()> {
+        let temporary = format!("{}.tmp", self.checkpoint_path);
+        fs::write(&temporary, policy.serialize())?;
+        fs::rename(&temporary, &self.checkpoint_path)?;
+        self.last_checkpoint = Some(policy.episode());
+        Ok(())
+    }
+
+    fn status(&self, policy: &EpsilonGreedyPolicy<MankallaGame>) -> DaemonStatus {
+        DaemonStatus {
+            episode: policy.episode(),
+            epsilon: policy.epsilon(),
+            hyperparameters: policy.hyperparameters(),
+            qtable_size: policy.greedy_policy().qtable_size(),
+            episodes_per_second: self.episodes_trained as f32
+                / self.started.elapsed().as_secs_f32(),
+            serving_episode: self.serving_episode,
+            last_checkpoint: self.last_checkpoint,
+        }
+    }
+
+    fn answer(
+        &mut self,
+        request: ControlRequest,
+        policy: &mut EpsilonGreedyPolicy<MankallaGame>,
+    ) -> String {
+        match request {
+            ControlRequest::Status => self.status(policy).to_json().to_string(),
+            ControlRequest::Checkpoint => match self.checkpoint(policy) {
+                Ok(()) => format!("ok checkpoint at episode {}", policy.episode()),
+                Err(e) => format!("error {e}"),
+            },
+            ControlRequest::Set(name, value) => {
+                match with_setting(policy.hyperparameters(), &name, value)
+                    .and_then(|h| policy.set_hyperparameters(h).map_err(|e| e.to_string()))
+                {
+                    Ok(_) => format!("ok {name} = {value}"),
+                    Err(e) => format!("error {e}"),
+                }
+            }
+            ControlRequest::Swap => {
+                self.swap(policy);
+                format!("ok serving episode {}", policy.episode())
+            }
+            ControlRequest::Stop => {
+                self.stopped = true;
+                format!("ok stopping at episode {}", policy.episode())
+            }
+            // The connections answer those themselves
+            ControlRequest::Move(_)
+            | ControlRequest::Health
+            | ControlRequest::Ready
+            | ControlRequest::Auth(_)
+            | ControlRequest::NewGame
+            | ControlRequest::Play(_)
+            | ControlRequest::Game
+            | ControlRequest::Stats => {
+                "error the trainer only answers training requests".to_owned()
+            }
+        }
+    }
+}
+
+impl TrainingObserver<MankallaGame, EpsilonGreedyPolicy<MankallaGame>> for DaemonObserver {
+    fn on_episode_end(
+        &mut self,
+        _policy: &EpsilonGreedyPolicy<MankallaGame>,
+        stats: &EpisodeStats,
+    ) {
+        self.episode = stats.episode + 1;
+        self.episodes_trained += 1;
+        self.metrics.episode_trained();
+    }
+
+    fn adjust_policy(&mut self, policy: &mut EpsilonGreedyPolicy<MankallaGame>) {
+        let episode = self.episode;
+        let due = |every: Option<usize>| every.is_some_and(|n| episode.is_multiple_of(n));
+        if due(self.swap_every) {
+            self.swap(policy);
+        }
+        if due(self.checkpoint_every)
+            && let Err(e) = self.checkpoint(policy)
+        {
+            self.failure = Some(format!("Could not write {}: {e}", self.checkpoint_path));
+        }
+        while let Ok((request, reply)) = self.requests.try_recv() {
+            let answer = self.answer(request, policy);
+            // A client that hung up does not need the answer
+            let _ = reply.send(answer);
+        }
+    }
+
+    // A termination signal stops training the way a `stop` request does
+    fn should_stop(&self) -> bool {
+        self.stopped || self.failure.is_some() || signals::termination_requested()
+    }
+}
+
+// The connection threads of `serve`. Connections hold no game of their own, every move request
+// brings its position along and the users' games are written through as they change, so shutting
+// down only has to let the requests already read finish: no new connections are accepted, the
+// open ones stop reading and end once their last answer is written. The policy itself is the
+// trainer's to checkpoint.
+pub struct Server {
+    address: SocketAddr,
+    draining: Arc<AtomicBool>,
+    acceptor: JoinHandle<()>,
+    connections: Connections,
+    readiness: Readiness,
+}
+
+// What `ready` answers, for probes that come in over HTTP
+#[derive(Clone)]
+pub struct Readiness {
+    serving: SnapshotReader<GreedyPolicy<MankallaGame>>,
+    draining: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    pub fn check(&self) -> String {
+        readiness(&self.serving, &self.draining)
+    }
+}
+
+// Each with a handle on its stream to stop it reading
+type Connections = Arc<Mutex<Vec<(TcpStream, JoinHandle<()>)>>>;
+
+impl Server {
+    pub fn readiness(&self) -> Readiness {
+        self.readiness.clone()
+    }
+
+    // Training requests sent while draining are answered with an error once the trainer is gone,
+    // so the trainer's end of the channel has to be dropped before
+    pub fn shutdown(self) {
+        self.draining.store(true, Ordering::Relaxed);
+        // Wakes the acceptor, which sees the flag and stops
+        let _ = TcpStream::connect(self.address);
+        let _ = self.acceptor.join();
+        let connections = std::mem::take(
+            &mut *self
+                .connections
+                .lock()
+                .expect("The connections lock is never held across a panic"),
+        );
+        for (stream, handle) in connections {
+            let _ = stream.shutdown(Shutdown::Read);
+            let _ = handle.join();
+        }
+    }
+}
+
+// Accepts connections until `Server::shutdown`, each on its own thread. Moves, health and
+// readiness are answered right there from the serving copy, everything else waits for the
+// trainer's next episode end.
+pub fn serve(
+    listener: TcpListener,
+    trainer: Sender<Control>,
+    serving: SnapshotReader<GreedyPolicy<MankallaGame>>,
+    metrics: Arc<ServingMetrics>,
+    users: Option<Arc<Users>>,
+) -> io::Result<Server> {
+    let address = listener.local_addr()?;
+    let draining = Arc::new(AtomicBool::new(false));
+    let readiness = Readiness {
+        serving: serving.clone(),
+        draining: draining.clone(),
+    };
+    let connections: Connections = Arc::default();
+    let acceptor = {
+        let draining = draining.clone();
+        let connections = connections.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if draining.load(Ordering::Relaxed) {
+                    break;
+                }
+                let Ok(shutdown_handle) = stream.try_clone() else {
+                    continue;
+                };
+                let trainer = trainer.clone();
+                let serving = serving.clone();
+                let draining = draining.clone();
+                let metrics = metrics.clone();
+                let users = users.clone();
+                let handle = thread::spawn(move || {
+                    metrics.connection_opened();
+                    // A connection that breaks only ends itself
+                    let _ = handle_connection(
+                        stream,
+                        &trainer,
+                        &serving,
+                        &draining,
+                        &metrics,
+                        users.as_deref(),
+                    );
+                    metrics.connection_closed();
+                });
+                let mut connections = connections
+                    .lock()
+                    .expect("The connections lock is never held across a panic");
+                connections.retain(|(_, handle)| !handle.is_finished());
+                connections.push((shutdown_handle, handle));
+            }
+        })
+    };
+    Ok(Server {
+        address,
+        draining,
+        acceptor,
+        connections,
+        readiness,
+    })
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    trainer: &Sender<Control>,
+    serving: &SnapshotReader<GreedyPolicy<MankallaGame>>,
+    draining: &AtomicBool,
+    metrics: &ServingMetrics,
+    users: Option<&Users>,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = stream.try_clone()?;
+    // Who authenticated on this connection
+    let mut user: Option<String> = None;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let answer = match line.parse() {
+            Ok(ControlRequest::Health) => "ok".to_owned(),
+            Ok(ControlRequest::Ready) => readiness(serving, draining),
+            Ok(ControlRequest::Auth(token)) => match users {
+                None => "error traind runs without --tokens".to_owned(),
+                Some(users) => {
+                    user = users.tokens.user(&token).map(str::to_owned);
+                    match &user {
+                        Some(name) => format!("ok {name}"),
+                        None => "error unknown token".to_owned(),
+                    }
+                }
+            },
+            Ok(_) if users.is_some() && user.is_none() => {
+                "error authenticate first with auth <token>".to_owned()
+            }
+            Ok(ControlRequest::Move(position)) => serve_move(&position, serving, metrics),
+            Ok(
+                request @ (ControlRequest::NewGame
+                | ControlRequest::Play(_)
+                | ControlRequest::Game
+                | ControlRequest::Stats),
+            ) => match (users, &user) {
+                (Some(users), Some(name)) => answer_user(request, name, users, serving, metrics),
+                _ => "error games need traind --tokens".to_owned(),
+            },
+            Ok(request) => {
+                let (reply, answer) = mpsc::channel();
+                // Training has ended when the request can not be sent or the answer never comes
+                match trainer.send((request, reply)) {
+                    Ok(()) => answer
+                        .recv()
+                        .unwrap_or_else(|_| "error training has stopped".to_owned()),
+                    Err(_) => "error training has stopped".to_owned(),
+                }
+            }
+            Err(e) => format!("error {e}"),
+        };
+        metrics.request(&answer);
+        writeln!(writer, "{answer}")?;
+    }
+    Ok(())
+}
+
+fn readiness(
+    serving: &SnapshotReader<GreedyPolicy<MankallaGame>>,
+    draining: &AtomicBool,
+) -> String {
+    match (draining.load(Ordering::Relaxed), serving.latest()) {
+        (true, _) => "error shutting down".to_owned(),
+        (false, None) => "error no policy is served yet".to_owned(),
+        (false, Some(snapshot)) => format!("ok serving episode {}", snapshot.episode),
+    }
+}
+fn serve_move(
+    position: &MankallaGameState,
+    serving: &SnapshotReader<GreedyPolicy<MankallaGame>>,
+    metrics: &ServingMetrics,
+) -> String {
+    let started = Instant::now();
+    let Some(snapshot) = serving.latest() else {
+        return "error no policy is served yet, send swap first".to_owned();
+    };
+    if MankallaGame::outcome(position).is_some() {
+        return "error the game is over".to_owned();
+    }
+    let view = (*position).into();
+    let action = snapshot.value.choose_action(view, None);
+    metrics.move_served(started.elapsed(), snapshot.value.knows_state(view));
+    format!(
+        "move {action} value {} episode {}",
+        snapshot.value.value(view, action),
+        snapshot.episode
+    )
+}
+
+// The answer to one of the requests that need a user
+fn answer_user(
+    request: ControlRequest,
+    name: &str,
+    users: &Users,
+    serving: &SnapshotReader<GreedyPolicy<MankallaGame>>,
+    metrics: &ServingMetrics,
+) -> String {
+    let mut accounts = users
+        .accounts
+        .lock()
+        .expect("The accounts lock is never held across a panic");
+    let answer = match request {
+        ControlRequest::NewGame => accounts
+            .start(name)
+            .map(|position| format!("ok position {position:#}")),
+        ControlRequest::Game => Ok(match accounts.game(name) {
+            Some(game) => format!("ok position {:#}", game.position),
+            None => "error no game going on, send newgame".to_owned(),
+        }),
+        ControlRequest::Stats => accounts.profile(name).map(|profile| match profile {
+            Some(profile) => profile.to_json().to_string(),
+            None => format!("error no finished games for {name}"),
+        }),
+        ControlRequest::Play(pit) => match accounts.game(name).copied() {
+            Some(game) => play_turn(&mut accounts, name, game, pit, serving, metrics),
+            None => Ok("error no game going on, send newgame".to_owned()),
+        },
+        _ => unreachable!("Only requests for a user are answered here"),
+    };
+    answer.unwrap_or_else(|e| format!("error {e}"))
+}
+
+// The user's move, then the serving policy's for as long as it is the bot's turn
+fn play_turn(
+    accounts: &mut Accounts,
+    name: &str,
+    mut game: OngoingGame,
+    pit: u8,
+    serving: &SnapshotReader<GreedyPolicy<MankallaGame>>,
+    metrics: &ServingMetrics,
+) -> io::Result<String> {
+    // Checked before the user's move, a game must not be left waiting for the bot
+    let Some(snapshot) = serving.latest() else {
+        return Ok("error no policy is served yet, send swap first".to_owned());
+    };
+    if !MankallaGame::legal_moves(&game.position.into()).contains(&pit) {
+        return Ok(format!("error pit {pit} is empty"));
+    }
+    game.user_move(pit);
+    let mut bot_moves = Vec::new();
+    while game.position.outcome(&Player::Player1).is_none()
+        && game.position.get_player_to_move() == Player::Player2
+    {
+        let started = Instant::now();
+        let view = game.position.into();
+        let action = snapshot.value.choose_action(view, None);
+        metrics.move_served(started.elapsed(), snapshot.value.knows_state(view));
+        game.bot_move(action);
+        bot_moves.push(action.to_string());
+    }
+    let bot_moves = match bot_moves.is_empty() {
+        true => "-".to_owned(),
+        false => bot_moves.join(","),
+    };
+    let result = match game.position.outcome(&Player::Player1) {
+        Some(outcome) => {
+            accounts.finish(name, game, outcome)?;
+            match outcome {
+                Outcome::Win => "win",
+                Outcome::Loss => "loss",
+                Outcome::Draw => "draw",
+            }
+        }
+        None => {
+            accounts.update(name, game)?;
+            "-"
+        }
+    };
+    Ok(format!(
+        "ok bot {bot_moves} result {result} position {:#}",
+        game.position
+    ))
+}
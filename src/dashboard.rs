@@ -0,0 +1,122 @@
+use std::fmt::Display;
+use std::time::Instant;
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const SPARKLINE_WIDTH: usize = 60;
+
+pub enum DashboardUpdate {
+    Episode {
+        episode: usize,
+        epsilon: f32,
+        qtable_size: usize,
+    },
+    Evaluation {
+        episode: usize,
+        win_rate: f32,
+        draw_rate: f32,
+    },
+    Openings {
+        entropy: f32,
+        distinct_lines: usize,
+    },
+}
+
+pub struct TrainingDashboard {
+    total_episodes: usize,
+    episode: usize,
+    epsilon: f32,
+    qtable_size: usize,
+    win_rates: Vec<(usize, f32)>,
+    draw_rate: f32,
+    opening_entropy: Option<(f32, usize)>,
+    started: Instant,
+}
+
+// Values are expected to lie in [0, 1], only the last `width` values are shown
+pub fn sparkline(values: &[f32], width: usize) -> String {
+    values[values.len().saturating_sub(width)..]
+        .iter()
+        .map(|v| {
+            let index = (v.clamp(0f32, 1f32) * (SPARK_CHARS.len() - 1) as f32).round();
+            SPARK_CHARS[index as usize]
+        })
+        .collect()
+}
+
+impl TrainingDashboard {
+    pub fn new(total_episodes: usize) -> Self {
+        TrainingDashboard {
+            total_episodes,
+            episode: 0,
+            epsilon: 0f32,
+            qtable_size: 0,
+            win_rates: Vec::new(),
+            draw_rate: 0f32,
+            opening_entropy: None,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, update: DashboardUpdate) {
+        match update {
+            DashboardUpdate::Episode {
+                episode,
+                epsilon,
+                qtable_size,
+            } => {
+                self.episode = episode + 1;
+                self.epsilon = epsilon;
+                self.qtable_size = qtable_size;
+            }
+            DashboardUpdate::Evaluation {
+                episode,
+                win_rate,
+                draw_rate,
+            } => {
+                self.win_rates.push((episode + 1, win_rate));
+                self.draw_rate = draw_rate;
+            }
+            DashboardUpdate::Openings {
+                entropy,
+                distinct_lines,
+            } => self.opening_entropy = Some((entropy, distinct_lines)),
+        }
+    }
+
+    pub fn episodes_per_sec(&self) -> f32 {
+        self.episode as f32 / self.started.elapsed().as_secs_f32().max(f32::EPSILON)
+    }
+}
+
+impl Display for TrainingDashboard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let win_rates: Vec<f32> = self.win_rates.iter().map(|&(_, w)| w).collect();
+        writeln!(
+            f,
+            "Episode      {:>10} / {}",
+            self.episode, self.total_episodes
+        )?;
+        writeln!(f, "Episodes/s   {:>10.1}", self.episodes_per_sec())?;
+        writeln!(f, "Epsilon      {:>10.4}", self.epsilon)?;
+        writeln!(f, "Q-table size {:>10}", self.qtable_size)?;
+        match self.win_rates.last() {
+            Some((episode, win_rate)) => writeln!(
+                f,
+                "Win rate     {:>9.1}% (vs random, episode {}, {:.1}% drawn)",
+                win_rate * 100f32,
+                episode,
+                self.draw_rate * 100f32
+            )?,
+            None => writeln!(f, "Win rate     {:>10}", "-")?,
+        }
+        match self.opening_entropy {
+            Some((entropy, distinct_lines)) => writeln!(
+                f,
+                "Openings     {:>6.2} bits ({} distinct lines)",
+                entropy, distinct_lines
+            )?,
+            None => writeln!(f, "Openings     {:>10}", "-")?,
+        }
+        write!(f, "             {}", sparkline(&win_rates, SPARKLINE_WIDTH))
+    }
+}
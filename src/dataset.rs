@@ -0,0 +1,62 @@
+use std::io::{self, Write};
+
+use crate::q_learning::{Environment, Serialize, Transition};
+
+pub const CSV_HEADER: &str = "episode,step,state,action,reward,next_state,done";
+
+// One row of a dataset, states are stored in their action relevant form,
+// i.e. from the view of the player to move, and the reward is that player's
+pub struct TransitionRecord<E: Environment> {
+    pub episode: usize,
+    pub step: usize,
+    pub state: E::ActionRelevantState,
+    pub action: E::Action,
+    pub reward: f32,
+    pub next_state: E::ActionRelevantState,
+    pub done: bool,
+}
+
+impl<E: Environment> TransitionRecord<E> {
+    pub fn new(episode: usize, step: usize, transition: &Transition<E>) -> Self {
+        TransitionRecord {
+            episode,
+            step,
+            state: transition.state.into(),
+            action: transition.action,
+            reward: transition.reward,
+            next_state: transition.next_state.into(),
+            done: transition.outcome.is_some(),
+        }
+    }
+}
+
+pub fn records<E: Environment>(
+    episode: usize,
+    transitions: &[Transition<E>],
+) -> impl Iterator<Item = TransitionRecord<E>> + '_ {
+    transitions
+        .iter()
+        .enumerate()
+        .map(move |(step, transition)| TransitionRecord::new(episode, step, transition))
+}
+
+pub fn write_csv<E: Environment>(
+    writer: &mut impl Write,
+    episode: usize,
+    transitions: &[Transition<E>],
+) -> io::Result<()> {
+    for record in records(episode, transitions) {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            record.episode,
+            record.step,
+            record.state.serialize(),
+            record.action.serialize(),
+            record.reward,
+            record.next_state.serialize(),
+            record.done
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use crate::q_learning::{Environment, EpsilonGreedyPolicy, Outcome, Policy, TrainingOptions};
+
+// Everything that happened in one step of a recorded episode, Q-values are taken right before
+// the update so `old_value` is among them
+pub struct DebugStep<E: Environment> {
+    pub state: E::State,
+    pub q_values: Vec<(E::Action, f32)>,
+    pub greedy_action: E::Action,
+    pub action: E::Action,
+    pub reward: f32,
+    pub next_state: E::State,
+    pub outcome: Option<Outcome>,
+    pub old_value: f32,
+    pub target: f32,
+    pub new_value: f32,
+}
+
+impl<E: Environment> DebugStep<E> {
+    pub fn explored(&self) -> bool {
+        self.action != self.greedy_action
+    }
+
+    pub fn td_error(&self) -> f32 {
+        self.target - self.old_value
+    }
+}
+
+fn q_value<E: Environment>(
+    policy: &EpsilonGreedyPolicy<E>,
+    state: E::ActionRelevantState,
+    action: E::Action,
+) -> f32 {
+    policy.greedy_policy().value(state, action)
+}
+
+// reward + γ·max Q(next), taken before the update like the update itself does
+fn td_target<E: Environment>(
+    policy: &EpsilonGreedyPolicy<E>,
+    reward: f32,
+    next_state: E::State,
+    finished: bool,
+) -> f32 {
+    match finished {
+        true => reward,
+        false => {
+            let next_state = next_state.into();
+            let greedy = policy.greedy_policy().choose_action(next_state, None);
+            reward + policy.gamma() * q_value(policy, next_state, greedy)
+        }
+    }
+}
+
+// Plays and learns from one episode exactly like training would, keeping a record of every step.
+// The new value is read back from the update, so a learning rate that does not move the value
+// towards the target shows up next to it.
+pub fn record_episode<E: Environment>(
+    policy: &mut EpsilonGreedyPolicy<E>,
+    options: &TrainingOptions,
+) -> Vec<DebugStep<E>> {
+    let mut steps = Vec::new();
+    let mut state = E::new();
+    loop {
+        let relevant_state: E::ActionRelevantState = state.into();
+        let q_values: Vec<(E::Action, f32)> = E::actions(&relevant_state)
+            .into_iter()
+            .map(|a| (a, q_value(policy, relevant_state, a)))
+            .collect();
+        let greedy_action = policy.greedy_policy().choose_action(relevant_state, None);
+        let action = policy.choose_action(relevant_state, None);
+
+        let (next_state, reward, outcome) = E::step(&state, &action);
+        let (reward, _) = options.rewards.shape(reward, outcome);
+        let old_value = q_value(policy, relevant_state, action);
+        let target = td_target(policy, reward, next_state, outcome.is_some());
+        policy.improve(
+            relevant_state,
+            action,
+            reward,
+            next_state,
+            outcome.is_some(),
+        );
+        let new_value = q_value(policy, relevant_state, action);
+
+        steps.push(DebugStep {
+            state,
+            q_values,
+            greedy_action,
+            action,
+            reward,
+            next_state,
+            outcome,
+            old_value,
+            target,
+            new_value,
+        });
+
+        let truncated = options.max_steps.is_some_and(|m| steps.len() >= m);
+        if outcome.is_some() || truncated {
+            policy.on_episode_increment();
+            return steps;
+        }
+        state = next_state;
+    }
+}
@@ -0,0 +1,194 @@
+use crate::hyperparameters::{HyperparameterError, Hyperparameters};
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, EpsilonGreedyPolicy, GreedyPolicy,
+    Policy, Serialize, checked_choice, masked_actions,
+};
+
+const HEADER: &str = "double-q";
+const SECOND_TABLE: &str = "second-table";
+
+// Double Q-learning: the max in Q-learning's target picks whichever value happens to be
+// overestimated, two tables updated in turn keep picking and judging apart. The updated table
+// picks the best next action, the other one tells its value. Moves are chosen on the sum of both,
+// exploring like the `EpsilonGreedyPolicy` that keeps the first table. Which table learns next
+// is not saved. The file puts the second table behind the policy's format:
+//
+//   double-q
+//   <EpsilonGreedyPolicy with the first table>
+//   second-table
+//   <GreedyPolicy with the second table>
+pub struct DoubleQLearningPolicy<E: Environment> {
+    policy: EpsilonGreedyPolicy<E>,
+    second: GreedyPolicy<E>,
+    update_second: bool,
+}
+
+impl<E: Environment> DoubleQLearningPolicy<E> {
+    pub fn new(policy: EpsilonGreedyPolicy<E>) -> Self {
+        let hyperparameters = policy.hyperparameters();
+        DoubleQLearningPolicy {
+            policy,
+            second: GreedyPolicy::new(hyperparameters.learning_rate, hyperparameters.gamma),
+            update_second: false,
+        }
+    }
+
+    pub fn from_hyperparameters(
+        hyperparameters: Hyperparameters,
+    ) -> Result<Self, HyperparameterError> {
+        Ok(DoubleQLearningPolicy::new(
+            EpsilonGreedyPolicy::from_hyperparameters(hyperparameters)?,
+        ))
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.policy.reseed(seed);
+    }
+
+    // With the first table
+    pub fn epsilon_greedy_policy(&self) -> &EpsilonGreedyPolicy<E> {
+        &self.policy
+    }
+
+    pub fn epsilon_greedy_policy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        &mut self.policy
+    }
+
+    pub fn second_table(&self) -> &GreedyPolicy<E> {
+        &self.second
+    }
+
+    // Both tables averaged into the first, so the policy plays wherever a single table is
+    // expected. Pairs only one table knows keep its value.
+    pub fn into_epsilon_greedy_policy(mut self) -> EpsilonGreedyPolicy<E> {
+        *self.policy.greedy_policy_mut() = self.merged_table();
+        self.policy
+    }
+
+    // The table `into_epsilon_greedy_policy` plays, leaving this policy as it is
+    pub fn merged_table(&self) -> GreedyPolicy<E> {
+        let mut merged = self.policy.greedy_policy().clone();
+        for (state, action, second) in self.second.qtable().iter() {
+            let first = merged.qtable().get(&state, &action);
+            let value = first.map_or(second, |first| (first + second) / 2f32);
+            merged.set_value(state, action, value);
+        }
+        merged
+    }
+
+    fn value(&self, state: E::ActionRelevantState, action: E::Action) -> f32 {
+        self.policy.greedy_policy().value(state, action) + self.second.value(state, action)
+    }
+
+    fn greedy_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        let action = *masked_actions::<E>(&state, mask)
+            .iter()
+            .max_by(|&&a, &&b| self.value(state, a).total_cmp(&self.value(state, b)))
+            .expect(
+                "The way it is implemented now, there should always be possible actions (might be bad)",
+            );
+        checked_choice::<E>(&state, mask, action)
+    }
+}
+
+impl<E: Environment> Policy<E> for DoubleQLearningPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.policy
+            .choose_action_around(state, mask, || self.greedy_action(state, mask))
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.policy.record_visit(state);
+        let (gamma, learning_rate) = (
+            self.policy.gamma(),
+            self.policy.hyperparameters().learning_rate,
+        );
+        let (updated, other) = match self.update_second {
+            false => (self.policy.greedy_policy_mut(), &self.second),
+            true => (&mut self.second, self.policy.greedy_policy()),
+        };
+        let next_value = match finished {
+            false => {
+                let next_state = next_state.into();
+                other.value(next_state, updated.choose_action(next_state, None))
+            }
+            true => 0f32,
+        };
+        let former_value = updated.value(state, action);
+        let target = reward + gamma * next_value;
+        updated.set_value(
+            state,
+            action,
+            former_value + learning_rate * (target - former_value),
+        );
+        self.update_second = !self.update_second;
+    }
+
+    fn on_episode_increment(&mut self) {
+        self.policy.on_episode_increment();
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.policy
+            .action_distribution_around(state, self.greedy_action(state, None))
+    }
+
+    // The mean of both tables, the scale a single table would have
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        Some(
+            E::actions(&state)
+                .into_iter()
+                .map(|a| (a, self.value(state, a) / 2f32))
+                .collect(),
+        )
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        Some(
+            self.policy.greedy_policy().qtable().row(&state).is_some()
+                || self.second.qtable().row(&state).is_some(),
+        )
+    }
+}
+
+impl<E: Environment> Serialize for DoubleQLearningPolicy<E> {
+    fn serialize(&self) -> String {
+        format!(
+            "{HEADER}\n{}{SECOND_TABLE}\n{}",
+            self.policy.serialize(),
+            self.second.serialize()
+        )
+    }
+}
+
+impl<E: Environment> Deserialize for DoubleQLearningPolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let rest = input
+            .strip_prefix(HEADER)
+            .and_then(|rest| rest.strip_prefix('\n'))
+            .ok_or_else(|| {
+                DeserializeError::because(format!(
+                    "a Double Q-learning policy starts with {HEADER}"
+                ))
+            })?;
+        let (first, second) = rest
+            .split_once(&format!("\n{SECOND_TABLE}\n"))
+            .ok_or_else(|| {
+                DeserializeError::because(format!(
+                    "a Double Q-learning policy has a line {SECOND_TABLE} before its second table"
+                ))
+            })?;
+        Ok(DoubleQLearningPolicy {
+            policy: EpsilonGreedyPolicy::deserialize(first)?,
+            second: GreedyPolicy::deserialize(second)?,
+            update_second: false,
+        })
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::hyperparameters::{HyperparameterError, Hyperparameters};
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, EpsilonGreedyPolicy, Policy, Serialize,
+};
+
+const HEADER: &str = "dyna-q";
+
+// Dyna-Q: every real step is learned from like in Q-learning and also kept in a table model of
+// what the pair led to, the reward and next state seen last. Then `planning_steps` pairs drawn
+// from the model at random are learned from again as if they had just been played, so values
+// spread back through known positions without playing them over. The model assumes the same pair
+// always leads to the same place, which holds for Mankalla in self-play; against an opponent it
+// holds the reply seen last. Any of `QLearning`'s loops trains it. Exploration and the table are
+// `EpsilonGreedyPolicy`'s, the file puts the planning steps in front of its format, the model is
+// not saved:
+//
+//   dyna-q;<planning steps>
+//   <EpsilonGreedyPolicy>
+pub struct DynaQPolicy<E: Environment> {
+    policy: EpsilonGreedyPolicy<E>,
+    planning_steps: usize,
+    model: Vec<ModelEntry<E>>,
+    // Where each pair sits in the model
+    entries: HashMap<(E::ActionRelevantState, E::Action), usize>,
+    rng: StdRng,
+}
+
+struct ModelEntry<E: Environment> {
+    state: E::ActionRelevantState,
+    action: E::Action,
+    reward: f32,
+    next_state: E::State,
+    finished: bool,
+}
+
+impl<E: Environment> DynaQPolicy<E> {
+    // Goes on from what the policy learned so far. No planning steps learns like Q-learning.
+    pub fn new(policy: EpsilonGreedyPolicy<E>, planning_steps: usize) -> Self {
+        DynaQPolicy {
+            policy,
+            planning_steps,
+            model: Vec::new(),
+            entries: HashMap::new(),
+            rng: StdRng::from_os_rng(),
+        }
+    }
+
+    pub fn from_hyperparameters(
+        hyperparameters: Hyperparameters,
+        planning_steps: usize,
+    ) -> Result<Self, HyperparameterError> {
+        Ok(DynaQPolicy::new(
+            EpsilonGreedyPolicy::from_hyperparameters(hyperparameters)?,
+            planning_steps,
+        ))
+    }
+
+    // Exploration and the planned pairs, each from its own stream
+    pub fn reseed(&mut self, seed: u64) {
+        self.policy.reseed(seed);
+        self.rng = StdRng::seed_from_u64(seed.wrapping_add(1));
+    }
+
+    pub fn planning_steps(&self) -> usize {
+        self.planning_steps
+    }
+
+    // Pairs the model knows where they lead
+    pub fn model_size(&self) -> usize {
+        self.model.len()
+    }
+
+    pub fn epsilon_greedy_policy(&self) -> &EpsilonGreedyPolicy<E> {
+        &self.policy
+    }
+
+    pub fn epsilon_greedy_policy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        &mut self.policy
+    }
+
+    pub fn into_epsilon_greedy_policy(self) -> EpsilonGreedyPolicy<E> {
+        self.policy
+    }
+
+    fn remember(&mut self, entry: ModelEntry<E>) {
+        match self.entries.get(&(entry.state, entry.action)) {
+            Some(&i) => self.model[i] = entry,
+            None => {
+                self.entries
+                    .insert((entry.state, entry.action), self.model.len());
+                self.model.push(entry);
+            }
+        }
+    }
+
+    // Planned updates are not visits, exploration by visits only counts what was played
+    fn plan(&mut self) {
+        if self.model.is_empty() {
+            return;
+        }
+        let gamma = self.policy.gamma();
+        for _ in 0..self.planning_steps {
+            let entry = &self.model[self.rng.random_range(0..self.model.len())];
+            self.policy.greedy_policy_mut().improve_discounted(
+                entry.state,
+                entry.action,
+                entry.reward,
+                entry.next_state,
+                entry.finished,
+                gamma,
+            );
+        }
+    }
+}
+
+impl<E: Environment> Policy<E> for DynaQPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.policy.choose_action(state, mask)
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.policy
+            .improve(state, action, reward, next_state, finished);
+        self.remember(ModelEntry {
+            state,
+            action,
+            reward,
+            next_state,
+            finished,
+        });
+        self.plan();
+    }
+
+    fn on_episode_increment(&mut self) {
+        self.policy.on_episode_increment();
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.policy.action_distribution(state)
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.policy.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.policy.knows_state(state)
+    }
+}
+
+impl<E: Environment> Serialize for DynaQPolicy<E> {
+    fn serialize(&self) -> String {
+        format!("{HEADER};{}\n", self.planning_steps) + &self.policy.serialize()
+    }
+}
+
+impl<E: Environment> Deserialize for DynaQPolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let (first, rest) = input
+            .split_once('\n')
+            .ok_or_else(|| DeserializeError::because("a Dyna-Q policy has a first line"))?;
+        let planning_steps = first
+            .strip_prefix(HEADER)
+            .and_then(|steps| steps.strip_prefix(';'))
+            .and_then(|steps| steps.parse::<usize>().ok());
+        let Some(planning_steps) = planning_steps else {
+            return Err(DeserializeError::because(format!(
+                "a Dyna-Q policy starts with \"{HEADER};<planning steps>\""
+            )));
+        };
+        Ok(DynaQPolicy::new(
+            EpsilonGreedyPolicy::deserialize(rest)?,
+            planning_steps,
+        ))
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::mankalla::{MankallaGame, MankallaGameState};
+use crate::q_learning::{ActionMask, Serialize, masked_actions};
+use crate::search::MinimaxPolicy;
+use crate::two_player::TwoPlayerGame;
+
+// A line protocol after the UCI chess engines speak, for GUIs and scripts that drive the minimax
+// bot. Moves are pits 0 to 5 counted from the mover's side, positions are written like `{:#}` of
+// `MankallaGameState`. Every command is answered before the next one is read, there is no `stop`.
+//
+//   mei                                     -> id name ..., meiok
+//   isready                                 -> readyok
+//   newgame                                 back to the start
+//   position startpos [moves 2 5 ...]
+//   position fields <14 fields> <P1|P2> [moves ...]
+//   go [depth <plies>] [movetime <ms>]      -> info depth .. score .. nodes .. time .. pv ..,
+//      [searchmoves <pit> ...]                 bestmove <pit>
+//   quit
+//
+// `searchmoves` comes last and only searches the pits it lists, "bestmove none" if none of them
+// is legal.
+pub const ENGINE_NAME: &str = concat!("mankalla-rl ", env!("CARGO_PKG_VERSION"));
+// Time-limited searches deepen up to here, enough for every game to end within it
+const TIMED_MAX_DEPTH: usize = 64;
+
+#[derive(Clone, PartialEq)]
+pub enum EngineCommand {
+    Hello,
+    IsReady,
+    NewGame,
+    Position(MankallaGameState),
+    Go {
+        depth: Option<usize>,
+        movetime: Option<Duration>,
+        searchmoves: Option<ActionMask>,
+    },
+    Quit,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProtocolError(String);
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl FromStr for EngineCommand {
+    type Err = ProtocolError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["mei"] => Ok(EngineCommand::Hello),
+            ["isready"] => Ok(EngineCommand::IsReady),
+            ["newgame"] => Ok(EngineCommand::NewGame),
+            ["quit"] => Ok(EngineCommand::Quit),
+            ["position", rest @ ..] => parse_position(rest).map(EngineCommand::Position),
+            ["go", rest @ ..] => parse_go(rest),
+            _ => Err(ProtocolError(format!("Unknown command \"{line}\""))),
+        }
+    }
+}
+
+fn parse_position(tokens: &[&str]) -> Result<MankallaGameState, ProtocolError> {
+    let (position, moves) = match tokens {
+        ["startpos", moves @ ..] => (MankallaGame::start(), moves),
+        ["fields", rest @ ..] => {
+            let end = rest
+                .iter()
+                .position(|&t| t == "moves")
+                .unwrap_or(rest.len());
+            let position = rest[..end]
+                .join(" ")
+                .parse()
+                .map_err(|e| ProtocolError(format!("Bad position: {e}")))?;
+            (position, &rest[end..])
+        }
+        _ => {
+            return Err(ProtocolError(
+                "A position is \"startpos\" or \"fields ...\"".to_owned(),
+            ));
+        }
+    };
+    let moves = match moves {
+        [] => &[][..],
+        ["moves", moves @ ..] => moves,
+        [other, ..] => {
+            return Err(ProtocolError(format!(
+                "Expected \"moves\", got \"{other}\""
+            )));
+        }
+    };
+    moves.iter().try_fold(position, |position, game_move| {
+        let pit = game_move
+            .parse::<u8>()
+            .map_err(|_| ProtocolError(format!("Bad move \"{game_move}\"")))?;
+        match MankallaGame::outcome(&position).is_none()
+            && MankallaGame::legal_moves(&position.into()).contains(&pit)
+        {
+            true => Ok(MankallaGame::apply_move(&position, &pit)),
+            false => Err(ProtocolError(format!(
+                "Move {pit} is not legal in {position:#}"
+            ))),
+        }
+    })
+}
+
+fn parse_go(tokens: &[&str]) -> Result<EngineCommand, ProtocolError> {
+    let (tokens, searchmoves) = match tokens.iter().position(|&t| t == "searchmoves") {
+        Some(i) => (&tokens[..i], Some(parse_searchmoves(&tokens[i + 1..])?)),
+        None => (tokens, None),
+    };
+    let (mut depth, mut movetime) = (None, None);
+    for pair in tokens.chunks(2) {
+        let value = |value: &str| {
+            value
+                .parse::<u64>()
+                .ok()
+                .filter(|&v| v > 0)
+                .ok_or_else(|| ProtocolError(format!("\"{value}\" is not a positive number")))
+        };
+        match pair {
+            ["depth", plies] => depth = Some(value(plies)? as usize),
+            ["movetime", ms] => movetime = Some(Duration::from_millis(value(ms)?)),
+            _ => {
+                return Err(ProtocolError(format!(
+                    "Bad go argument \"{}\"",
+                    pair.join(" ")
+                )));
+            }
+        }
+    }
+    Ok(EngineCommand::Go {
+        depth,
+        movetime,
+        searchmoves,
+    })
+}
+
+fn parse_searchmoves(tokens: &[&str]) -> Result<ActionMask, ProtocolError> {
+    let pits = tokens
+        .iter()
+        .map(|token| {
+            token
+                .parse::<u8>()
+                .ok()
+                .filter(|&pit| (pit as usize) < MankallaGame::MAX_MOVES)
+                .ok_or_else(|| ProtocolError(format!("Bad move \"{token}\"")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match pits.is_empty() {
+        true => Err(ProtocolError(
+            "searchmoves needs at least one move".to_owned(),
+        )),
+        false => Ok(ActionMask::from_actions::<MankallaGame>(&pits)),
+    }
+}
+
+// Only reads positions, nothing it searches is learned
+pub struct Engine {
+    position: MankallaGameState,
+    default_depth: usize,
+}
+
+impl Engine {
+    // `default_depth` for a `go` without limits
+    pub fn new(default_depth: usize) -> Self {
+        Engine {
+            position: MankallaGame::start(),
+            default_depth,
+        }
+    }
+
+    // The lines to send back, `None` once the engine should quit
+    pub fn handle(&mut self, command: EngineCommand) -> Option<Vec<String>> {
+        let response = match command {
+            EngineCommand::Hello => vec![format!("id name {ENGINE_NAME}"), "meiok".to_owned()],
+            EngineCommand::IsReady => vec!["readyok".to_owned()],
+            EngineCommand::NewGame => {
+                self.position = MankallaGame::start();
+                Vec::new()
+            }
+            EngineCommand::Position(position) => {
+                self.position = position;
+                Vec::new()
+            }
+            EngineCommand::Go {
+                depth,
+                movetime,
+                searchmoves,
+            } => self.go(depth, movetime, searchmoves),
+            EngineCommand::Quit => return None,
+        };
+        Some(response)
+    }
+
+    fn go(
+        &self,
+        depth: Option<usize>,
+        movetime: Option<Duration>,
+        searchmoves: Option<ActionMask>,
+    ) -> Vec<String> {
+        if MankallaGame::outcome(&self.position).is_some()
+            || masked_actions::<MankallaGame>(&self.position.into(), searchmoves).is_empty()
+        {
+            return vec!["bestmove none".to_owned()];
+        }
+        let max_depth = match (depth, movetime) {
+            (Some(depth), _) => depth,
+            (None, Some(_)) => TIMED_MAX_DEPTH,
+            (None, None) => self.default_depth,
+        };
+        let (best, stats) = MinimaxPolicy::<MankallaGame>::new(max_depth, movetime)
+            .search(self.position.into(), searchmoves);
+        let line = stats
+            .principal_variation
+            .iter()
+            .map(Serialize::serialize)
+            .collect::<Vec<_>>()
+            .join(" ");
+        vec![
+            format!(
+                "info depth {} score {} nodes {} time {} pv {line}",
+                stats.depth,
+                stats.score,
+                stats.nodes,
+                stats.time.as_millis()
+            ),
+            format!("bestmove {}", best.serialize()),
+        ]
+    }
+}
@@ -0,0 +1,732 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rand::seq::IndexedRandom;
+use rand::{Rng, RngCore};
+
+use crate::json::{Json, ToJson};
+use crate::mankalla::{
+    MankallaGame, MankallaGameState, Player, capture_heuristic, material_lookahead, move_info,
+};
+use crate::q_learning::{
+    ActionMask, DeserializeError, Environment, Outcome, Policy, masked_actions, softmax_action,
+};
+use crate::search::SearchStats;
+use crate::two_player::{SeededOpponent, TwoPlayerGame, play};
+
+// Games in a strength probe, played as mirrored pairs from short random openings
+pub const PROBE_GAMES: usize = 200;
+const PROBE_OPENING_PLIES: usize = 4;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EvaluationReport {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+}
+
+// Every opening is played twice with the seats swapped, the pair score is the mean of both games
+#[derive(Clone, Debug, Default)]
+pub struct PairedEvaluationReport {
+    pub report: EvaluationReport,
+    pub pair_scores: Vec<f32>,
+    // Empty for sides that do not search
+    pub policy_search: SearchSummary,
+    pub opponent_search: SearchSummary,
+    // Empty for sides without a table
+    pub policy_coverage: Coverage,
+    pub opponent_coverage: Coverage,
+    // Games that differ from all others in their opening or in at least one move
+    pub distinct_games: usize,
+}
+
+// The first `plies` moves of every game after the opening are drawn from a softmax over the
+// mover's action values, so deterministic players do not replay the same game over and over.
+// Policies without values pick uniformly among the legal moves.
+#[derive(Clone, Copy, Debug)]
+pub struct Temperature {
+    pub plies: usize,
+    pub temperature: f32,
+}
+
+impl Temperature {
+    pub fn new(plies: usize, temperature: f32) -> Self {
+        assert!(temperature > 0f32, "The temperature has to be positive");
+        Temperature { plies, temperature }
+    }
+
+    fn choose(
+        &self,
+        policy: &(impl Policy<MankallaGame> + ?Sized),
+        state: [u8; 12],
+        rng: &mut dyn RngCore,
+    ) -> u8 {
+        softmax_action::<MankallaGame>(policy, state, self.temperature, rng)
+    }
+}
+
+// What the searches of one side cost over a match
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchSummary {
+    pub moves: usize,
+    pub nodes: usize,
+    pub depth: usize,
+    pub time: Duration,
+}
+
+impl SearchSummary {
+    pub fn record(&mut self, stats: &SearchStats<u8>) {
+        self.moves += 1;
+        self.nodes += stats.nodes;
+        self.depth += stats.depth;
+        self.time += stats.time;
+    }
+
+    pub fn add(&mut self, other: &SearchSummary) {
+        self.moves += other.moves;
+        self.nodes += other.nodes;
+        self.depth += other.depth;
+        self.time += other.time;
+    }
+
+    fn per_move(&self, total: f64) -> f64 {
+        match self.moves {
+            0 => 0f64,
+            moves => total / moves as f64,
+        }
+    }
+}
+
+impl Display for SearchSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} searched moves, per move {:.0} nodes, depth {:.1}, {:.2} ms",
+            self.moves,
+            self.per_move(self.nodes as f64),
+            self.per_move(self.depth as f64),
+            self.per_move(self.time.as_secs_f64() * 1000f64)
+        )
+    }
+}
+
+impl ToJson for SearchSummary {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("moves", self.moves.into()),
+            ("nodes_per_move", self.per_move(self.nodes as f64).into()),
+            ("depth_per_move", self.per_move(self.depth as f64).into()),
+            (
+                "ms_per_move",
+                self.per_move(self.time.as_secs_f64() * 1000f64).into(),
+            ),
+        ])
+    }
+}
+
+// How many of one side's moves were picked in states its table knew, the others came from
+// default values. Low coverage means the games go where training never went.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Coverage {
+    pub moves: usize,
+    pub known: usize,
+}
+
+impl Coverage {
+    pub fn record(&mut self, known: bool) {
+        self.moves += 1;
+        self.known += known as usize;
+    }
+
+    pub fn add(&mut self, other: &Coverage) {
+        self.moves += other.moves;
+        self.known += other.known;
+    }
+
+    pub fn rate(&self) -> f32 {
+        match self.moves {
+            0 => 0f32,
+            moves => self.known as f32 / moves as f32,
+        }
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.0}% ({} of {} moves in known states)",
+            self.rate() * 100f32,
+            self.known,
+            self.moves
+        )
+    }
+}
+
+impl ToJson for Coverage {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("moves", self.moves.into()),
+            ("known", self.known.into()),
+            ("rate", self.rate().into()),
+        ])
+    }
+}
+
+// What one side's moves cost and how many of them its table covered
+#[derive(Clone, Copy, Debug, Default)]
+struct Seat {
+    search: SearchSummary,
+    coverage: Coverage,
+}
+
+impl EvaluationReport {
+    pub fn record(&mut self, outcome: Option<Outcome>) {
+        match outcome {
+            Some(Outcome::Win) => self.wins += 1,
+            Some(Outcome::Loss) => self.losses += 1,
+            Some(Outcome::Draw) => self.draws += 1,
+            None => unreachable!("play_game only returns finished games"),
+        }
+    }
+
+    // Wins count 1, draws 0.5
+    pub fn score(&self) -> f32 {
+        self.rate(self.wins) + 0.5 * self.rate(self.draws)
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn win_rate(&self) -> f32 {
+        self.rate(self.wins)
+    }
+
+    pub fn draw_rate(&self) -> f32 {
+        self.rate(self.draws)
+    }
+
+    fn rate(&self, count: usize) -> f32 {
+        match self.games() {
+            0 => 0f32,
+            games => count as f32 / games as f32,
+        }
+    }
+}
+
+impl Display for EvaluationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} games: {} wins, {} losses, {} draws ({:.1}% won, {:.1}% drawn)",
+            self.games(),
+            self.wins,
+            self.losses,
+            self.draws,
+            self.win_rate() * 100f32,
+            self.draw_rate() * 100f32
+        )
+    }
+}
+
+impl ToJson for EvaluationReport {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("games", self.games().into()),
+            ("wins", self.wins.into()),
+            ("losses", self.losses.into()),
+            ("draws", self.draws.into()),
+            ("win_rate", self.win_rate().into()),
+            ("draw_rate", self.draw_rate().into()),
+            ("score", self.score().into()),
+        ])
+    }
+}
+
+pub fn play_game(
+    player1: &(impl Policy<MankallaGame> + ?Sized),
+    player2: &(impl Policy<MankallaGame> + ?Sized),
+) -> MankallaGameState {
+    play_game_from(player1, player2, MankallaGame::new())
+}
+
+pub fn play_game_from(
+    player1: &(impl Policy<MankallaGame> + ?Sized),
+    player2: &(impl Policy<MankallaGame> + ?Sized),
+    state: MankallaGameState,
+) -> MankallaGameState {
+    play_game_searched(player1, player2, state, None, &mut Default::default()).0
+}
+
+// Adds the cost of every searched move and whether its state was known to the stats of its seat,
+// tempered moves are not searched. Returns the final state with the moves that led there.
+fn play_game_searched(
+    player1: &(impl Policy<MankallaGame> + ?Sized),
+    player2: &(impl Policy<MankallaGame> + ?Sized),
+    mut state: MankallaGameState,
+    mut tempered: Option<(&Temperature, &mut dyn RngCore)>,
+    seats: &mut [Seat; 2],
+) -> (MankallaGameState, Vec<u8>) {
+    let mut moves = Vec::new();
+    loop {
+        let tempered: Option<(&Temperature, &mut dyn RngCore)> = match &mut tempered {
+            Some((temperature, rng)) if moves.len() < temperature.plies => Some((temperature, rng)),
+            _ => None,
+        };
+        let action = match state.get_player_to_move() {
+            Player::Player1 => next_move(player1, state, tempered, &mut seats[0]),
+            Player::Player2 => next_move(player2, state, tempered, &mut seats[1]),
+        };
+        moves.push(action);
+        let (next_state, _, outcome) = play::<MankallaGame>(&state, &action);
+        if outcome.is_some() {
+            return (next_state, moves);
+        }
+        state = next_state;
+    }
+}
+
+fn next_move(
+    policy: &(impl Policy<MankallaGame> + ?Sized),
+    state: MankallaGameState,
+    tempered: Option<(&Temperature, &mut dyn RngCore)>,
+    seat: &mut Seat,
+) -> u8 {
+    if let Some(known) = policy.knows_state(state.into()) {
+        seat.coverage.record(known);
+    }
+    if let Some((temperature, rng)) = tempered {
+        return temperature.choose(policy, state.into(), rng);
+    }
+    let action = policy.choose_action(state.into(), None);
+    if let Some(stats) = policy.last_search() {
+        seat.search.record(&stats);
+    }
+    action
+}
+
+// Plays half of the games in each seat so the first move advantage cancels out
+pub fn evaluate(
+    policy: &(impl Policy<MankallaGame> + ?Sized),
+    opponent: &(impl Policy<MankallaGame> + ?Sized),
+    num_games: usize,
+) -> EvaluationReport {
+    let mut report = EvaluationReport::default();
+    for game in 0..num_games {
+        let (seat, final_state) = match game % 2 {
+            0 => (Player::Player1, play_game(policy, opponent)),
+            _ => (Player::Player2, play_game(opponent, policy)),
+        };
+        report.record(final_state.outcome(&seat));
+    }
+    report
+}
+
+// `evaluate` for any two-player game, e.g. Mankalla learned on another view than `[u8; 12]`
+pub fn evaluate_game<G: TwoPlayerGame>(
+    policy: &(impl Policy<G> + ?Sized),
+    opponent: &(impl Policy<G> + ?Sized),
+    num_games: usize,
+) -> EvaluationReport {
+    let mut report = EvaluationReport::default();
+    for game in 0..num_games {
+        let seat = match game % 2 {
+            0 => Player::Player1,
+            _ => Player::Player2,
+        };
+        let mut position = G::start();
+        let outcome = loop {
+            let mover = G::current_player(&position);
+            let action = match mover == seat {
+                true => policy.choose_action(position.into(), None),
+                false => opponent.choose_action(position.into(), None),
+            };
+            G::make_move(&mut position, &action);
+            if let Some(outcome) = G::outcome(&position) {
+                break outcome;
+            }
+        };
+        report.record(Some(match seat {
+            Player::Player1 => outcome,
+            Player::Player2 => outcome.opposite(),
+        }));
+    }
+    report
+}
+
+// Uniformly random moves for `plies` turns, openings that already end the game are drawn again
+pub fn random_opening(plies: usize, rng: &mut impl Rng) -> MankallaGameState {
+    'retry: loop {
+        let mut state = MankallaGame::new();
+        for _ in 0..plies {
+            let action = *MankallaGame::actions(&state.into())
+                .choose(rng)
+                .expect("A running game always has a legal move");
+            let outcome;
+            (state, _, outcome) = play::<MankallaGame>(&state, &action);
+            if outcome.is_some() {
+                continue 'retry;
+            }
+        }
+        return state;
+    }
+}
+
+// Every position `plies` moves into the game, each once, in the order they are first reached.
+// Lines that end the game before are left out.
+pub fn all_openings(plies: usize) -> Vec<MankallaGameState> {
+    let mut positions = vec![MankallaGame::new()];
+    for _ in 0..plies {
+        let mut seen = HashSet::new();
+        positions = positions
+            .iter()
+            .flat_map(|state| {
+                MankallaGame::actions(&(*state).into())
+                    .into_iter()
+                    .map(|action| play::<MankallaGame>(state, &action))
+            })
+            .filter(|(next, _, outcome)| outcome.is_none() && seen.insert(*next))
+            .map(|(next, _, _)| next)
+            .collect();
+    }
+    positions
+}
+
+// One position per line in the `{:#}` format of `MankallaGameState`, blank lines and lines
+// starting with '#' are skipped
+pub fn parse_opening_book(text: &str) -> Result<Vec<MankallaGameState>, DeserializeError> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            let reason = |e: DeserializeError| {
+                let reason = e.reason().unwrap_or("bad position").to_owned();
+                DeserializeError::because(format!("{reason} in line {number}"))
+            };
+            let opening: MankallaGameState = line.parse().map_err(reason)?;
+            match MankallaGame::outcome(&opening) {
+                None => Ok(opening),
+                Some(_) => Err(reason(DeserializeError::because(
+                    "the game is already over",
+                ))),
+            }
+        })
+        .collect()
+}
+
+// Mirror matches: the seat swap cancels the advantage of both the first move and the opening,
+// so the pair scores vary much less than single games do. A tempered start is drawn anew for
+// every game, which gives up some of that for games that are not all the same.
+pub fn evaluate_paired(
+    policy: &(impl Policy<MankallaGame> + ?Sized),
+    opponent: &(impl Policy<MankallaGame> + ?Sized),
+    num_pairs: usize,
+    opening_plies: usize,
+    temperature: Option<Temperature>,
+    rng: &mut impl Rng,
+) -> PairedEvaluationReport {
+    let openings: Vec<_> = (0..num_pairs)
+        .map(|_| random_opening(opening_plies, rng))
+        .collect();
+    evaluate_openings(policy, opponent, &openings, temperature, rng)
+}
+
+// A pair of games from each of the given openings, for books that cover the positions a
+// deterministic player would otherwise never leave
+pub fn evaluate_openings(
+    policy: &(impl Policy<MankallaGame> + ?Sized),
+    opponent: &(impl Policy<MankallaGame> + ?Sized),
+    openings: &[MankallaGameState],
+    temperature: Option<Temperature>,
+    rng: &mut impl Rng,
+) -> PairedEvaluationReport {
+    let mut paired = PairedEvaluationReport::default();
+    let mut games = HashSet::new();
+    for &opening in openings {
+        let mut pair = EvaluationReport::default();
+        let mut seats = Default::default();
+        let tempered = temperature.as_ref().map(|t| (t, rng as &mut dyn RngCore));
+        let (final_state, moves) =
+            play_game_searched(policy, opponent, opening, tempered, &mut seats);
+        pair.record(final_state.outcome(&Player::Player1));
+        games.insert((opening, moves));
+        paired.add_seats(&seats[0], &seats[1]);
+        let mut seats = Default::default();
+        let tempered = temperature.as_ref().map(|t| (t, rng as &mut dyn RngCore));
+        let (final_state, moves) =
+            play_game_searched(opponent, policy, opening, tempered, &mut seats);
+        pair.record(final_state.outcome(&Player::Player2));
+        games.insert((opening, moves));
+        paired.add_seats(&seats[1], &seats[0]);
+        paired.report.wins += pair.wins;
+        paired.report.losses += pair.losses;
+        paired.report.draws += pair.draws;
+        paired.pair_scores.push(pair.score());
+    }
+    paired.distinct_games = games.len();
+    paired
+}
+
+impl PairedEvaluationReport {
+    fn add_seats(&mut self, policy: &Seat, opponent: &Seat) {
+        self.policy_search.add(&policy.search);
+        self.opponent_search.add(&opponent.search);
+        self.policy_coverage.add(&policy.coverage);
+        self.opponent_coverage.add(&opponent.coverage);
+    }
+
+    pub fn mean_score(&self) -> f32 {
+        match self.pair_scores.len() {
+            0 => 0f32,
+            n => self.pair_scores.iter().sum::<f32>() / n as f32,
+        }
+    }
+
+    pub fn standard_error(&self) -> f32 {
+        let n = self.pair_scores.len();
+        if n < 2 {
+            return 0f32;
+        }
+        let mean = self.mean_score();
+        let variance = self
+            .pair_scores
+            .iter()
+            .map(|s| (s - mean) * (s - mean))
+            .sum::<f32>()
+            / (n - 1) as f32;
+        (variance / n as f32).sqrt()
+    }
+
+    // Deterministic players from the same opening play the same game, one per seat
+    pub fn all_games_repeated(&self) -> bool {
+        self.pair_scores.len() > 1 && self.distinct_games <= 2
+    }
+}
+
+impl Display for PairedEvaluationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.report)?;
+        write!(
+            f,
+            "{} opening pairs: score {:.3} ± {:.3}",
+            self.pair_scores.len(),
+            self.mean_score(),
+            self.standard_error()
+        )?;
+        let games = self.report.games();
+        match self.all_games_repeated() {
+            true => write!(
+                f,
+                "\nWarning: every pair replayed the same games, randomize the openings or the \
+                 first moves to get a score that means something"
+            )?,
+            false if self.distinct_games < games => write!(
+                f,
+                "\n{} of {games} games were distinct",
+                self.distinct_games
+            )?,
+            false => {}
+        }
+        for (side, search) in [
+            ("Policy", &self.policy_search),
+            ("Opponent", &self.opponent_search),
+        ] {
+            if search.moves > 0 {
+                write!(f, "\n{side} search: {search}")?;
+            }
+        }
+        for (side, coverage) in [
+            ("Policy", &self.policy_coverage),
+            ("Opponent", &self.opponent_coverage),
+        ] {
+            if coverage.moves > 0 {
+                write!(f, "\n{side} coverage: {coverage}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The searches only for sides that searched, the coverage only for sides with a table
+impl ToJson for PairedEvaluationReport {
+    fn to_json(&self) -> Json {
+        let search = |search: &SearchSummary| match search.moves {
+            0 => Json::Null,
+            _ => search.to_json(),
+        };
+        let coverage = |coverage: &Coverage| match coverage.moves {
+            0 => Json::Null,
+            _ => coverage.to_json(),
+        };
+        Json::object([
+            ("report", self.report.to_json()),
+            ("pairs", self.pair_scores.len().into()),
+            ("mean_score", self.mean_score().into()),
+            ("standard_error", self.standard_error().into()),
+            ("distinct_games", self.distinct_games.into()),
+            ("all_games_repeated", self.all_games_repeated().into()),
+            ("policy_search", search(&self.policy_search)),
+            ("opponent_search", search(&self.opponent_search)),
+            ("policy_coverage", coverage(&self.policy_coverage)),
+            ("opponent_coverage", coverage(&self.opponent_coverage)),
+        ])
+    }
+}
+
+// Takes whatever scores most right now and prefers extra turns on a tie. Deterministic, beats a
+// random player nearly always and a decently trained table rarely, which makes it a useful yardstick.
+#[derive(Default)]
+pub struct HeuristicPolicy;
+
+impl Policy<MankallaGame> for HeuristicPolicy {
+    fn choose_action(&self, state: [u8; 12], mask: Option<ActionMask>) -> u8 {
+        let score = |action: &u8| {
+            (
+                capture_heuristic(&state, action),
+                move_info(&state, action).extra_turn,
+            )
+        };
+        masked_actions::<MankallaGame>(&state, mask)
+            .into_iter()
+            .map(|a| (a, score(&a)))
+            .max_by(|(_, (a, a_extra)), (_, (b, b_extra))| {
+                a.total_cmp(b).then(a_extra.cmp(b_extra))
+            })
+            .map(|(a, _)| a)
+            .expect("A running game always has a legal move")
+    }
+
+    fn action_values(&self, state: [u8; 12]) -> Option<Vec<(u8, f32)>> {
+        Some(
+            MankallaGame::actions(&state)
+                .into_iter()
+                .map(|a| (a, capture_heuristic(&state, &a)))
+                .collect(),
+        )
+    }
+
+    fn improve(
+        &mut self,
+        _state: [u8; 12],
+        _action: u8,
+        _reward: f32,
+        _next_state: MankallaGameState,
+        _finished: bool,
+    ) {
+    }
+}
+
+impl SeededOpponent<MankallaGame> for HeuristicPolicy {
+    fn seeded(_seed: u64) -> Self {
+        HeuristicPolicy
+    }
+}
+
+// What a bot goes by in positions its table never saw, where every move is worth the same default
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fallback {
+    // `HeuristicPolicy`'s move
+    Heuristic,
+    // The move with the best `material_lookahead`
+    Lookahead,
+}
+
+impl FromStr for Fallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "heuristic" => Ok(Fallback::Heuristic),
+            "lookahead" => Ok(Fallback::Lookahead),
+            _ => Err(format!(
+                "Unknown fallback \"{s}\" (supported: heuristic, lookahead)"
+            )),
+        }
+    }
+}
+
+impl Fallback {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fallback::Heuristic => "heuristic",
+            Fallback::Lookahead => "lookahead",
+        }
+    }
+
+    pub fn choose_action(&self, state: [u8; 12], mask: Option<ActionMask>) -> u8 {
+        match self {
+            Fallback::Heuristic => HeuristicPolicy.choose_action(state, mask),
+            Fallback::Lookahead => masked_actions::<MankallaGame>(&state, mask)
+                .into_iter()
+                .max_by(|a, b| {
+                    material_lookahead(&state, a).total_cmp(&material_lookahead(&state, b))
+                })
+                .expect("A running game always has a legal move"),
+        }
+    }
+}
+
+// Elo difference that predicts the given expected score. Scores of 0 and 1 are pulled in by half
+// a game so the estimate stays finite.
+pub fn elo_difference(score: f32, games: usize) -> f32 {
+    let margin = 0.5 / games.max(1) as f32;
+    let score = score.clamp(margin, 1f32 - margin);
+    400f32 * (score / (1f32 - score)).log10()
+}
+
+pub struct StrengthProbe {
+    pub report: PairedEvaluationReport,
+}
+
+impl StrengthProbe {
+    pub fn elo(&self) -> f32 {
+        elo_difference(self.report.mean_score(), self.report.report.games())
+    }
+
+    // Roughly 95%, from the standard error of the pair scores
+    pub fn elo_interval(&self) -> (f32, f32) {
+        let games = self.report.report.games();
+        let error = 1.96 * self.report.standard_error();
+        (
+            elo_difference(self.report.mean_score() - error, games),
+            elo_difference(self.report.mean_score() + error, games),
+        )
+    }
+}
+
+// A quick blitz against `HeuristicPolicy`, enough to tell a trained table from a stale one. The
+// openings are fixed by `rng` so two files probed with the same seed face the same games.
+pub fn strength_probe(
+    policy: &(impl Policy<MankallaGame> + ?Sized),
+    rng: &mut impl Rng,
+) -> StrengthProbe {
+    StrengthProbe {
+        report: evaluate_paired(
+            policy,
+            &HeuristicPolicy,
+            PROBE_GAMES / 2,
+            PROBE_OPENING_PLIES,
+            None,
+            rng,
+        ),
+    }
+}
+
+impl Display for StrengthProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (low, high) = self.elo_interval();
+        write!(
+            f,
+            "{} games against the heuristic bot, score {:.1}%: about {:+.0} Elo ({:+.0} to {:+.0})",
+            self.report.report.games(),
+            self.report.mean_score() * 100f32,
+            self.elo(),
+            low,
+            high
+        )
+    }
+}
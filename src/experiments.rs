@@ -0,0 +1,228 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::q_learning::{Deserialize, DeserializeError, Serialize};
+
+// Every training run gets its own directory below the experiments root:
+//   run-0001/manifest.csv        what ran, when, how it ended and which files belong to it
+//   run-0001/config.csv          the settings of the run
+//   run-0001/metrics.csv         the training curve
+//   run-0001/seeds.csv           every seed stream, enough to repeat the run
+//   run-0001/eval.txt            evaluation reports, if the game has one
+//   run-0001/checkpoints/        policies saved along the way
+pub const MANIFEST_FILE: &str = "manifest.csv";
+pub const CONFIG_FILE: &str = "config.csv";
+pub const METRICS_FILE: &str = "metrics.csv";
+pub const SEEDS_FILE: &str = "seeds.csv";
+pub const EVAL_FILE: &str = "eval.txt";
+pub const CHECKPOINTS_DIR: &str = "checkpoints";
+
+const ID_PREFIX: &str = "run-";
+
+#[derive(Clone)]
+pub struct Experiment {
+    id: String,
+    dir: PathBuf,
+}
+
+// Ordered `key;value` lines, the same layout as the run metadata. The config uses it as well.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+    entries: Vec<(String, String)>,
+}
+
+impl Manifest {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // Replaces the value if the key exists, appends it otherwise. Line breaks in values are
+    // flattened since every entry is one line.
+    pub fn set(&mut self, key: &str, value: impl ToString) {
+        let value = value.to_string().replace(['\n', '\r'], " ");
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.entries.push((key.to_owned(), value)),
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+}
+
+impl Serialize for Manifest {
+    fn serialize(&self) -> String {
+        self.entries
+            .iter()
+            .map(|(k, v)| format!("{k};{v}\n"))
+            .collect()
+    }
+}
+
+impl Deserialize for Manifest {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let entries = input
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|line| match line.split_once(';') {
+                Some((k, v)) => Ok((k.to_owned(), v.to_owned())),
+                None => Err(DeserializeError::new()),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Manifest { entries })
+    }
+}
+
+impl Experiment {
+    // Takes the next free number, creating the directory claims it so parallel runs never share one
+    pub fn create(root: &Path) -> io::Result<Experiment> {
+        fs::create_dir_all(root)?;
+        let mut number = list_ids(root)?
+            .iter()
+            .filter_map(|id| id.strip_prefix(ID_PREFIX)?.parse::<usize>().ok())
+            .max()
+            .unwrap_or(0);
+        loop {
+            number += 1;
+            let id = format!("{ID_PREFIX}{number:04}");
+            let dir = root.join(&id);
+            match fs::create_dir(&dir) {
+                Ok(()) => {
+                    fs::create_dir(dir.join(CHECKPOINTS_DIR))?;
+                    return Ok(Experiment { id, dir });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Accepts the full id or just its number, "run-0007" and "7" are the same run
+    pub fn open(root: &Path, id: &str) -> io::Result<Experiment> {
+        let id = match id.parse::<usize>() {
+            Ok(number) => format!("{ID_PREFIX}{number:04}"),
+            Err(_) => id.to_owned(),
+        };
+        let dir = root.join(&id);
+        match dir.join(MANIFEST_FILE).is_file() {
+            true => Ok(Experiment { id, dir }),
+            false => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("There is no run {id} in {}", root.display()),
+            )),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    pub fn write(&self, file: &str, contents: &str) -> io::Result<()> {
+        fs::write(self.path(file), contents)
+    }
+
+    pub fn manifest(&self) -> io::Result<Manifest> {
+        Manifest::deserialize(&fs::read_to_string(self.path(MANIFEST_FILE))?).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Could not parse the manifest of {}", self.id),
+            )
+        })
+    }
+
+    pub fn write_manifest(&self, manifest: &Manifest) -> io::Result<()> {
+        self.write(MANIFEST_FILE, &manifest.serialize())
+    }
+
+    // Returns the path relative to the run directory
+    pub fn checkpoint(&self, episode: usize, policy: &str) -> io::Result<String> {
+        let file = format!("{CHECKPOINTS_DIR}/policy-{episode}.csv");
+        self.write(&file, policy)?;
+        Ok(file)
+    }
+
+    // Files of the run relative to its directory, checkpoints included, sorted by name
+    pub fn files(&self) -> io::Result<Vec<String>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            match entry.file_type()?.is_dir() {
+                true => {
+                    for inner in fs::read_dir(entry.path())? {
+                        files.push(format!("{name}/{}", inner?.file_name().to_string_lossy()));
+                    }
+                }
+                false => files.push(name),
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+fn list_ids(root: &Path) -> io::Result<Vec<String>> {
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.starts_with(ID_PREFIX) {
+            ids.push(name);
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+// Every run with a readable manifest, oldest first. A missing root just means nothing ran yet.
+pub fn list(root: &Path) -> io::Result<Vec<(String, Manifest)>> {
+    if !root.is_dir() {
+        return Ok(Vec::new());
+    }
+    Ok(list_ids(root)?
+        .into_iter()
+        .filter_map(|id| {
+            let manifest = Experiment::open(root, &id).ok()?.manifest().ok()?;
+            Some((id, manifest))
+        })
+        .collect())
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// "2024-03-01 17:05 UTC", civil from days after Howard Hinnant
+pub fn format_unix_time(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let (hour, minute) = (seconds % 86400 / 3600, seconds % 3600 / 60);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = match mp < 10 {
+        true => mp + 3,
+        false => mp - 9,
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02} UTC")
+}
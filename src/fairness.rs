@@ -0,0 +1,149 @@
+use std::fmt::Display;
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+
+use crate::evaluation::elo_difference;
+use crate::json::{Json, ToJson};
+use crate::q_learning::{Outcome, Policy};
+use crate::two_player::{Player, TwoPlayerGame};
+
+// What moving first is worth under one rule set, measured with the same player in both seats so
+// any difference comes from the seat alone. The margin is the first player's final score minus
+// the second's, its mean is the komi that would even the game out for this player.
+#[derive(Clone, Debug, Default)]
+pub struct FairnessReport {
+    pub first_wins: usize,
+    pub second_wins: usize,
+    pub draws: usize,
+    margin_sum: f64,
+    margin_squares: f64,
+}
+
+impl FairnessReport {
+    fn record(&mut self, outcome: Outcome, margin: f32) {
+        match outcome {
+            Outcome::Win => self.first_wins += 1,
+            Outcome::Loss => self.second_wins += 1,
+            Outcome::Draw => self.draws += 1,
+        }
+        self.margin_sum += margin as f64;
+        self.margin_squares += (margin as f64).powi(2);
+    }
+
+    pub fn games(&self) -> usize {
+        self.first_wins + self.second_wins + self.draws
+    }
+
+    // Draws count half, 0.5 is a fair game
+    pub fn first_player_score(&self) -> f32 {
+        (self.first_wins as f32 + self.draws as f32 / 2f32) / self.games().max(1) as f32
+    }
+
+    // Of the score, from the spread of the single game results
+    pub fn score_standard_error(&self) -> f32 {
+        let games = self.games();
+        if games < 2 {
+            return 0f32;
+        }
+        let mean = self.first_player_score();
+        let squares = self.first_wins as f32 + self.draws as f32 / 4f32;
+        let variance = (squares / games as f32 - mean * mean).max(0f32);
+        (variance / (games - 1) as f32).sqrt()
+    }
+
+    // The score turned into how much stronger the first seat plays, in Elo
+    pub fn elo(&self) -> f32 {
+        elo_difference(self.first_player_score(), self.games())
+    }
+
+    pub fn mean_margin(&self) -> f32 {
+        (self.margin_sum / self.games().max(1) as f64) as f32
+    }
+
+    pub fn margin_standard_error(&self) -> f32 {
+        let games = self.games();
+        if games < 2 {
+            return 0f32;
+        }
+        let mean = self.margin_sum / games as f64;
+        let variance = (self.margin_squares / games as f64 - mean * mean).max(0f64);
+        (variance / (games - 1) as f64).sqrt() as f32
+    }
+}
+
+impl Display for FairnessReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} games: first player {} wins, second player {} wins, {} draws, first player \
+             scores {:.3} ± {:.3} ({:+.0} Elo), leads by {:.2} ± {:.2} stones",
+            self.games(),
+            self.first_wins,
+            self.second_wins,
+            self.draws,
+            self.first_player_score(),
+            self.score_standard_error(),
+            self.elo(),
+            self.mean_margin(),
+            self.margin_standard_error()
+        )
+    }
+}
+
+impl ToJson for FairnessReport {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("games", self.games().into()),
+            ("first_wins", self.first_wins.into()),
+            ("second_wins", self.second_wins.into()),
+            ("draws", self.draws.into()),
+            ("first_player_score", self.first_player_score().into()),
+            ("score_standard_error", self.score_standard_error().into()),
+            ("elo", self.elo().into()),
+            ("mean_margin", self.mean_margin().into()),
+            ("margin_standard_error", self.margin_standard_error().into()),
+        ])
+    }
+}
+
+// `games` games of `policy` against itself, each from its own random opening of `opening_plies`
+// moves so a deterministic player does not replay one game. Openings that end the game are
+// drawn again.
+pub fn audit<G: TwoPlayerGame>(
+    policy: &(impl Policy<G> + ?Sized),
+    games: usize,
+    opening_plies: usize,
+    rng: &mut impl Rng,
+) -> FairnessReport {
+    let mut report = FairnessReport::default();
+    for _ in 0..games {
+        let mut position = random_opening::<G>(opening_plies, rng);
+        let outcome = loop {
+            let action = policy.choose_action(position.into(), None);
+            G::make_move(&mut position, &action);
+            if let Some(outcome) = G::outcome(&position) {
+                break outcome;
+            }
+        };
+        let margin = G::score(&position, Player::Player1) - G::score(&position, Player::Player2);
+        report.record(outcome, margin);
+    }
+    report
+}
+
+fn random_opening<G: TwoPlayerGame>(plies: usize, rng: &mut impl Rng) -> G::Position {
+    'retry: loop {
+        let mut position = G::start();
+        for _ in 0..plies {
+            let action = *G::legal_moves(&position.into())
+                .choose(rng)
+                .expect("A running game always has a legal move");
+            G::make_move(&mut position, &action);
+            if G::outcome(&position).is_some() {
+                continue 'retry;
+            }
+        }
+        return position;
+    }
+}
@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hyperparameters {
+    pub learning_rate: f32,
+    pub gamma: f32,
+    pub max_epsilon: f32,
+    pub min_epsilon: f32,
+    pub decay_rate: f32,
+}
+
+#[derive(Debug)]
+pub struct HyperparameterError {
+    pub name: &'static str,
+    pub value: f32,
+    pub expected: &'static str,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum HyperparameterWarning {
+    NegativeDecayRate(f32),
+    MinEpsilonAboveMax { min_epsilon: f32, max_epsilon: f32 },
+    DecayWithoutEffect,
+    NoExploration,
+}
+
+impl Default for Hyperparameters {
+    fn default() -> Self {
+        Hyperparameters {
+            learning_rate: 0.2,
+            gamma: 1.,
+            max_epsilon: 1.,
+            min_epsilon: 0.1,
+            decay_rate: 0.01,
+        }
+    }
+}
+
+impl Error for HyperparameterError {}
+
+impl Display for HyperparameterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Hyperparameter {} = {} is out of range, expected {}",
+            self.name, self.value, self.expected
+        )
+    }
+}
+
+impl Display for HyperparameterWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HyperparameterWarning::NegativeDecayRate(decay_rate) => write!(
+                f,
+                "decay_rate = {decay_rate} is negative, epsilon will grow every episode instead of decaying"
+            ),
+            HyperparameterWarning::MinEpsilonAboveMax {
+                min_epsilon,
+                max_epsilon,
+            } => write!(
+                f,
+                "min_epsilon = {min_epsilon} is above max_epsilon = {max_epsilon}, epsilon will rise over time"
+            ),
+            HyperparameterWarning::DecayWithoutEffect => write!(
+                f,
+                "min_epsilon equals max_epsilon, decay_rate has no effect"
+            ),
+            HyperparameterWarning::NoExploration => {
+                write!(f, "max_epsilon is 0, the policy will never explore")
+            }
+        }
+    }
+}
+
+fn check_range(
+    name: &'static str,
+    value: f32,
+    valid: bool,
+    expected: &'static str,
+) -> Result<(), HyperparameterError> {
+    match value.is_finite() && valid {
+        true => Ok(()),
+        false => Err(HyperparameterError {
+            name,
+            value,
+            expected,
+        }),
+    }
+}
+
+impl Hyperparameters {
+    // Errors for values that break learning outright, warnings for combinations that are most likely a mistake
+    pub fn validate(&self) -> Result<Vec<HyperparameterWarning>, HyperparameterError> {
+        check_range(
+            "learning_rate",
+            self.learning_rate,
+            0f32 < self.learning_rate && self.learning_rate <= 1f32,
+            "a value in (0, 1]",
+        )?;
+        check_range(
+            "gamma",
+            self.gamma,
+            (0f32..=1f32).contains(&self.gamma),
+            "a value in [0, 1]",
+        )?;
+        check_range(
+            "max_epsilon",
+            self.max_epsilon,
+            (0f32..=1f32).contains(&self.max_epsilon),
+            "a value in [0, 1]",
+        )?;
+        check_range(
+            "min_epsilon",
+            self.min_epsilon,
+            (0f32..=1f32).contains(&self.min_epsilon),
+            "a value in [0, 1]",
+        )?;
+        check_range("decay_rate", self.decay_rate, true, "a finite value")?;
+
+        let mut warnings = Vec::new();
+        if self.decay_rate < 0f32 {
+            warnings.push(HyperparameterWarning::NegativeDecayRate(self.decay_rate));
+        }
+        if self.min_epsilon > self.max_epsilon {
+            warnings.push(HyperparameterWarning::MinEpsilonAboveMax {
+                min_epsilon: self.min_epsilon,
+                max_epsilon: self.max_epsilon,
+            });
+        }
+        if self.min_epsilon == self.max_epsilon && self.decay_rate != 0f32 {
+            warnings.push(HyperparameterWarning::DecayWithoutEffect);
+        }
+        if self.max_epsilon == 0f32 {
+            warnings.push(HyperparameterWarning::NoExploration);
+        }
+        Ok(warnings)
+    }
+}
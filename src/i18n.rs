@@ -0,0 +1,309 @@
+use std::env;
+use std::error::Error;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::mankalla::Handicap;
+use crate::profile::Achievement;
+use crate::q_learning::Outcome;
+use crate::two_player::Player;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+pub enum Message {
+    ChooseAction {
+        options: String,
+    },
+    Goodbye,
+    ChooseDifficulty {
+        options: String,
+    },
+    UnknownDifficulty {
+        input: String,
+    },
+    PolicyReloaded {
+        path: String,
+        episode: usize,
+    },
+    PolicyNotReloaded {
+        path: String,
+        error: String,
+    },
+    UnknownMove {
+        input: String,
+    },
+    EmptyPit {
+        action: String,
+    },
+    HumanMove {
+        turn: usize,
+        action: String,
+    },
+    BotMove {
+        turn: usize,
+        action: String,
+    },
+    BotConsiders {
+        distribution: String,
+    },
+    BotSearched {
+        stats: String,
+        line: String,
+    },
+    // The bot's table does not know the position, the fallback evaluator picks the move
+    BotFallback {
+        evaluator: String,
+    },
+    // What is left on both clocks, already formatted
+    Clocks {
+        own: String,
+        bot: String,
+    },
+    OutOfTime {
+        player: Player,
+    },
+    // Before the first move of an uneven game, the human is the first player
+    Handicap {
+        handicap: Handicap,
+    },
+    // The komi is part of the points
+    GameOver {
+        outcome: Outcome,
+        own_points: u16,
+        bot_points: u16,
+    },
+    // Of the bot's moves in a game, how many it made in states it had learned about
+    BotCoverage {
+        known: usize,
+        moves: usize,
+    },
+    AchievementUnlocked {
+        achievement: Achievement,
+    },
+}
+
+pub struct Catalog {
+    locale: Locale,
+}
+
+#[derive(Debug)]
+pub struct UnknownLocaleError(String);
+
+impl Error for UnknownLocaleError {}
+
+impl Display for UnknownLocaleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown locale \"{}\" (supported: en, de)", self.0)
+    }
+}
+
+impl FromStr for Locale {
+    type Err = UnknownLocaleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Accepts plain codes as well as POSIX style values like "de_DE.UTF-8"
+        let language = s.split(['_', '-', '.']).next().unwrap_or("").to_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Ok(Locale::En),
+            "de" => Ok(Locale::De),
+            _ => Err(UnknownLocaleError(s.to_owned())),
+        }
+    }
+}
+
+impl Locale {
+    pub fn from_env() -> Self {
+        ["MANKALLA_LANG", "LC_ALL", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find_map(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Catalog {
+    pub fn new(locale: Locale) -> Self {
+        Catalog { locale }
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    pub fn get(&self, message: Message) -> String {
+        match self.locale {
+            Locale::En => match message {
+                Message::ChooseAction { options } => format!("Choose your action: ({options},q,r)"),
+                Message::Goodbye => "Ok, goodbye".to_owned(),
+                Message::ChooseDifficulty { options } => {
+                    format!("Choose the difficulty: ({options},q)")
+                }
+                Message::UnknownDifficulty { input } => format!("\"{input}\" is no difficulty"),
+                Message::PolicyReloaded { path, episode } => {
+                    format!("The bot now plays {path} from episode {episode}")
+                }
+                Message::PolicyNotReloaded { path, error } => {
+                    format!("Could not reload {path}, the bot keeps its policy: {error}")
+                }
+                Message::UnknownMove { input } => format!("\"{input}\" is not a move"),
+                Message::EmptyPit { action } => format!("Pit {action} is empty"),
+                Message::HumanMove { turn, action } => format!("Turn {turn}, you chose {action}"),
+                Message::BotMove { turn, action } => format!("Turn {turn}, bot chose {action}"),
+                Message::BotConsiders { distribution } => {
+                    format!("The bot picks from {distribution}")
+                }
+                Message::BotSearched { stats, line } => {
+                    format!("The bot searched {stats}, expecting {line}")
+                }
+                Message::BotFallback { evaluator } => {
+                    format!("The bot does not know this position, the {evaluator} fallback decides")
+                }
+                Message::Clocks { own, bot } => format!("Clock: you {own}, bot {bot}"),
+                Message::OutOfTime { player } => match player {
+                    Player::Player1 => "Your time is up".to_owned(),
+                    Player::Player2 => "The bot's time is up".to_owned(),
+                },
+                Message::Handicap { handicap } => {
+                    let mut terms = Vec::new();
+                    match handicap.komi {
+                        0 => {}
+                        komi if komi > 0 => terms.push(format!("you get {komi} stones of komi")),
+                        komi => {
+                            terms.push(format!("the bot gets {} stones of komi", -(komi as i16)))
+                        }
+                    }
+                    match handicap.pit_stones {
+                        0 => {}
+                        stones if stones > 0 => {
+                            terms.push(format!("the bot starts with {stones} fewer stones per pit"))
+                        }
+                        stones => {
+                            terms.push(format!("you start with {} fewer stones per pit", -stones))
+                        }
+                    }
+                    format!("Handicap: {}", terms.join(", "))
+                }
+                Message::GameOver {
+                    outcome,
+                    own_points,
+                    bot_points,
+                } => match outcome {
+                    Outcome::Win => format!("You win {own_points}:{bot_points}!"),
+                    Outcome::Loss => format!("The bot wins {bot_points}:{own_points}"),
+                    Outcome::Draw => format!("Draw, {own_points}:{bot_points}"),
+                },
+                Message::BotCoverage { known, moves } => format!(
+                    "Policy coverage of this game: {:.0}% ({known} of {moves} bot moves in known \
+                     states)",
+                    percent(known, moves)
+                ),
+                Message::AchievementUnlocked { achievement } => {
+                    let description = match achievement {
+                        Achievement::FirstWin => "First win against the bot",
+                        Achievement::BigCapture => "Captured 8 or more stones in one move",
+                        Achievement::ExtraTurnChain => "Earned 3 extra turns in a row",
+                        Achievement::BeatStrongestBot => "Beat the greedy or the minimax bot",
+                        Achievement::WinStreak => "Won 5 games in a row",
+                    };
+                    format!("Achievement unlocked: {description}")
+                }
+            },
+            Locale::De => match message {
+                Message::ChooseAction { options } => format!("Wähle deinen Zug: ({options},q,r)"),
+                Message::Goodbye => "Ok, tschüss".to_owned(),
+                Message::ChooseDifficulty { options } => {
+                    format!("Wähle den Schwierigkeitsgrad: ({options},q)")
+                }
+                Message::UnknownDifficulty { input } => {
+                    format!("\"{input}\" ist kein Schwierigkeitsgrad")
+                }
+                Message::PolicyReloaded { path, episode } => {
+                    format!("Der Bot spielt jetzt {path} aus Episode {episode}")
+                }
+                Message::PolicyNotReloaded { path, error } => format!(
+                    "{path} konnte nicht neu geladen werden, der Bot behält seine Strategie: \
+                     {error}"
+                ),
+                Message::UnknownMove { input } => format!("\"{input}\" ist kein Zug"),
+                Message::EmptyPit { action } => format!("Mulde {action} ist leer"),
+                Message::HumanMove { turn, action } => format!("Zug {turn}, du wählst {action}"),
+                Message::BotMove { turn, action } => format!("Zug {turn}, der Bot wählt {action}"),
+                Message::BotConsiders { distribution } => {
+                    format!("Der Bot wählt aus {distribution}")
+                }
+                Message::BotSearched { stats, line } => {
+                    format!("Der Bot hat gesucht ({stats}) und erwartet {line}")
+                }
+                Message::BotFallback { evaluator } => {
+                    format!(
+                        "Der Bot kennt diese Stellung nicht, es entscheidet die Ausweichregel {evaluator}"
+                    )
+                }
+                Message::Clocks { own, bot } => format!("Uhr: du {own}, Bot {bot}"),
+                Message::OutOfTime { player } => match player {
+                    Player::Player1 => "Deine Zeit ist abgelaufen".to_owned(),
+                    Player::Player2 => "Die Zeit des Bots ist abgelaufen".to_owned(),
+                },
+                Message::Handicap { handicap } => {
+                    let mut terms = Vec::new();
+                    match handicap.komi {
+                        0 => {}
+                        komi if komi > 0 => terms.push(format!("du bekommst {komi} Steine Komi")),
+                        komi => {
+                            terms.push(format!("der Bot bekommt {} Steine Komi", -(komi as i16)))
+                        }
+                    }
+                    match handicap.pit_stones {
+                        0 => {}
+                        stones if stones > 0 => terms.push(format!(
+                            "der Bot beginnt mit {stones} Steinen weniger pro Mulde"
+                        )),
+                        stones => terms.push(format!(
+                            "du beginnst mit {} Steinen weniger pro Mulde",
+                            -stones
+                        )),
+                    }
+                    format!("Vorgabe: {}", terms.join(", "))
+                }
+                Message::GameOver {
+                    outcome,
+                    own_points,
+                    bot_points,
+                } => match outcome {
+                    Outcome::Win => format!("Du gewinnst {own_points}:{bot_points}!"),
+                    Outcome::Loss => format!("Der Bot gewinnt {bot_points}:{own_points}"),
+                    Outcome::Draw => format!("Unentschieden, {own_points}:{bot_points}"),
+                },
+                Message::BotCoverage { known, moves } => format!(
+                    "Abdeckung der Strategie in diesem Spiel: {:.0}% ({known} von {moves} \
+                     Bot-Zügen in bekannten Stellungen)",
+                    percent(known, moves)
+                ),
+                Message::AchievementUnlocked { achievement } => {
+                    let description = match achievement {
+                        Achievement::FirstWin => "Erster Sieg gegen den Bot",
+                        Achievement::BigCapture => "8 oder mehr Steine mit einem Zug erbeutet",
+                        Achievement::ExtraTurnChain => "3 Extrazüge hintereinander",
+                        Achievement::BeatStrongestBot => {
+                            "Den gierigen oder den Minimax-Bot geschlagen"
+                        }
+                        Achievement::WinStreak => "5 Spiele in Folge gewonnen",
+                    };
+                    format!("Erfolg freigeschaltet: {description}")
+                }
+            },
+        }
+    }
+}
+
+fn percent(part: usize, whole: usize) -> f32 {
+    match whole {
+        0 => 0f32,
+        whole => part as f32 / whole as f32 * 100f32,
+    }
+}
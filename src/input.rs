@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+const NUM_PITS: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum InputScheme {
+    #[default]
+    ZeroBased,
+    OneBased,
+    Letters,
+    // Column letter plus row number, the human player always owns row 1
+    Coordinates,
+}
+
+pub enum PlayerRequest {
+    Action(u8),
+    // Read the policy file again now instead of waiting for it to change
+    Reload,
+    Quit,
+}
+
+// The words of a line based input one at a time, so a whole game can come in one line like
+// "2 5 1 q" as well as a move per line. Lines are only read once the words before are used up,
+// anything after a '#' is a comment.
+pub struct WordReader<R> {
+    input: R,
+    pending: VecDeque<String>,
+    line: usize,
+}
+
+impl<R: BufRead> WordReader<R> {
+    pub fn new(input: R) -> Self {
+        WordReader {
+            input,
+            pending: VecDeque::new(),
+            line: 0,
+        }
+    }
+
+    // Where the last word came from, counting from 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // `None` once the input has ended, a last line without a newline still counts
+    pub fn next_word(&mut self) -> io::Result<Option<String>> {
+        loop {
+            if let Some(word) = self.pending.pop_front() {
+                return Ok(Some(word));
+            }
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            let words = line.split('#').next().unwrap_or_default();
+            self.pending
+                .extend(words.split_whitespace().map(str::to_owned));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownInputSchemeError(String);
+
+impl Error for UnknownInputSchemeError {}
+
+impl Display for UnknownInputSchemeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown input scheme \"{}\" (supported: 0-5, 1-6, a-f, coords)",
+            self.0
+        )
+    }
+}
+
+impl FromStr for InputScheme {
+    type Err = UnknownInputSchemeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0-5" => Ok(InputScheme::ZeroBased),
+            "1-6" => Ok(InputScheme::OneBased),
+            "a-f" => Ok(InputScheme::Letters),
+            "coords" => Ok(InputScheme::Coordinates),
+            _ => Err(UnknownInputSchemeError(s.to_owned())),
+        }
+    }
+}
+
+impl InputScheme {
+    pub fn label(&self, action: u8) -> String {
+        match self {
+            InputScheme::ZeroBased => action.to_string(),
+            InputScheme::OneBased => (action + 1).to_string(),
+            InputScheme::Letters => ((b'a' + action) as char).to_string(),
+            InputScheme::Coordinates => format!("{}1", (b'a' + action) as char),
+        }
+    }
+
+    pub fn opponent_label(&self, action: u8) -> String {
+        match self {
+            // The opponent's row is printed mirrored above the human's row
+            InputScheme::Coordinates => {
+                format!("{}2", (b'a' + NUM_PITS - 1 - action) as char)
+            }
+            _ => self.label(action),
+        }
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        (0..NUM_PITS).map(|action| self.label(action)).collect()
+    }
+
+    pub fn parse_action(&self, input: &str) -> Option<u8> {
+        let input = input.trim().to_lowercase();
+        (0..NUM_PITS).find(|&action| self.label(action) == input)
+    }
+
+    pub fn parse_request(&self, input: &str) -> Option<PlayerRequest> {
+        match input.trim() {
+            "q" => Some(PlayerRequest::Quit),
+            "r" => Some(PlayerRequest::Reload),
+            other => self.parse_action(other).map(PlayerRequest::Action),
+        }
+    }
+}
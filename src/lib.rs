@@ -1,2 +1,82 @@
+pub mod ablation;
+pub mod accounts;
+pub mod afterstate;
+pub mod analysis;
+pub mod arena;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod bandit;
+pub mod blackjack;
+pub mod budget;
+pub mod bundle;
+pub mod clock;
+pub mod commentary;
+pub mod connect4;
+pub mod daemon;
+pub mod dashboard;
+pub mod dataset;
+pub mod debugger;
+pub mod double_q;
+pub mod dyna_q;
+pub mod engine;
+pub mod evaluation;
+pub mod experiments;
+pub mod fairness;
+pub mod hyperparameters;
+pub mod i18n;
+pub mod input;
+pub mod json;
 pub mod mankalla;
+pub mod metrics;
+pub mod monte_carlo;
+pub mod nim;
+pub mod ope;
+pub mod pbt;
+pub mod perft;
+pub mod profile;
+pub mod prometheus;
+pub mod q_lambda;
 pub mod q_learning;
+pub mod reload;
+pub mod replay;
+pub mod report;
+pub mod rlearning;
+pub mod sarsa;
+pub mod schedule;
+pub mod search;
+pub mod seeding;
+pub mod self_check;
+pub mod signals;
+#[cfg(feature = "simd")]
+pub mod simd;
+pub mod similarity;
+pub mod snapshot;
+pub mod softmax;
+pub mod state_index;
+pub mod testing;
+pub mod thompson;
+pub mod tournament;
+pub mod tracking;
+pub mod two_player;
+pub mod ucb;
+pub mod vec_env;
+
+// For everyone who spells it the usual way
+pub use mankalla as mancala;
+
+pub use mankalla::{MankallaGame, MankallaGameState, Player};
+pub use q_learning::{
+    Deserialize, DeserializeError, Environment, EpisodeStats, EpsilonGreedyPolicy, GreedyPolicy,
+    Outcome, PersistentPolicy, Policy, QLearning, RandomPolicy, RewardOptions, SeededRandomPolicy,
+    Serialize, TrainingObserver, TrainingOptions, Transition,
+};
+pub use two_player::TwoPlayerGame;
+
+pub mod prelude {
+    pub use crate::mankalla::{MankallaGame, MankallaGameState, Player};
+    pub use crate::q_learning::{
+        Deserialize, Environment, EpsilonGreedyPolicy, GreedyPolicy, Outcome, PersistentPolicy,
+        Policy, QLearning, Serialize,
+    };
+    pub use crate::two_player::TwoPlayerGame;
+}
@@ -1,93 +1,4588 @@
 use std::{
+    env,
     error::Error,
-    fs,
-    io::{self, Stdin},
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::{IpAddr, Ipv4Addr, TcpListener},
+    path::Path,
+    process::ExitCode,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, RecvTimeoutError, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+#[cfg(feature = "arrow")]
+use mankalla_rl::arrow;
+use mankalla_rl::prelude::*;
 use mankalla_rl::{
-    mankalla::{MankallaGame, MankallaGameState, Player},
-    q_learning::{Deserialize, Environment, EpsilonGreedyPolicy, Policy, QLearning, Serialize},
+    EpisodeStats, Outcome, RandomPolicy, SeededRandomPolicy, TrainingObserver, TrainingOptions,
+    Transition,
+    ablation::{self, ABLATION_REPORT_FILE, AblationOptions, AblationReport},
+    accounts::{Accounts, Tokens, Users},
+    afterstate::AfterstatePolicy,
+    analysis::QTableDiff,
+    arena::train_pair,
+    bandit,
+    blackjack::Blackjack,
+    budget::{self, TrainingBudget},
+    bundle::PolicyBundle,
+    clock::{self, GameClock, TimeControl},
+    commentary::{self, MoveAnalysis},
+    connect4::ConnectFour,
+    daemon::{self, DaemonObserver},
+    dashboard::{DashboardUpdate, TrainingDashboard},
+    dataset,
+    debugger::{self, DebugStep},
+    double_q::DoubleQLearningPolicy,
+    dyna_q::DynaQPolicy,
+    engine::{Engine, EngineCommand},
+    evaluation::{self, Coverage, EvaluationReport, Fallback, HeuristicPolicy, Temperature},
+    experiments::{self, Experiment, Manifest},
+    fairness::{self, FairnessReport},
+    hyperparameters::Hyperparameters,
+    i18n::{Catalog, Locale, Message},
+    input::{InputScheme, PlayerRequest, WordReader},
+    json::{Json, ToJson},
+    mankalla::{
+        Handicap, LeadAwareMankalla, LeadView, MankallaWithStones, STONES_PER_PIT,
+        capture_heuristic, move_info, scale_view,
+    },
+    metrics::{EpisodeLengths, OpeningDiversity, ProbedCurve, TrainingCurve},
+    monte_carlo::{MonteCarloControl, MonteCarloPolicy},
+    nim::Nim,
+    ope::{self, LoggedStep, Transcript},
+    pbt::{PbtOptions, PopulationTrainer},
+    perft,
+    profile::{self, Achievement, GameHighlights, Profiles},
+    prometheus::{self, ServingMetrics},
+    q_lambda::QLambdaPolicy,
+    q_learning::{ActionMask, InitialValue, OpeningTemperature, RootNoise, constant_initial_value},
+    reload::FileWatcher,
+    replay::ReplayBuffer,
+    report::{self, QTableStats, TrainingReport},
+    rlearning::RLearningPolicy,
+    sarsa::{ExpectedSarsaPolicy, Sarsa, SarsaPolicy},
+    schedule::{GammaAnnealing, PlateauDetector, ReheatOptions},
+    search::{self, MinimaxPolicy},
+    seeding::SeedStreams,
+    self_check, signals,
+    similarity::{self, SimilarStatesFallback},
+    snapshot::SnapshotPublisher,
+    state_index::StateIndex,
+    tracking,
+    two_player::{self, ChainedTurns, VsOpponentEnv},
 };
+use rand::{Rng, SeedableRng, rngs::StdRng};
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut policy = match fs::read_to_string("policy.csv") {
-        Ok(s) => EpsilonGreedyPolicy::<MankallaGame>::deserialize(s.as_str())?,
-        Err(_) => EpsilonGreedyPolicy::<MankallaGame>::new(0.2, 1., 1., 0.1, -0.01),
+const POLICY_FILE: &str = "policy.csv";
+const CONNECT4_POLICY_FILE: &str = "connect4-policy.csv";
+const NIM_POLICY_FILE: &str = "nim-policy.csv";
+const LONG_EPISODES_FILE: &str = "long-episodes.csv";
+const LONG_EPISODE_MIN_HISTORY: usize = 100;
+const LONG_EPISODES_KEPT: usize = 20;
+const BLACKJACK_POLICY_FILE: &str = "blackjack-policy.csv";
+const REPLAY_FILE: &str = "replay.csv";
+const DATASET_FILE: &str = "transitions.csv";
+// Episodes collect keeps in memory before writing them out
+const DATASET_BATCH_EPISODES: usize = 1000;
+const ARENA_Q_LEARNING_FILE: &str = "arena-q-learning.csv";
+const ARENA_SARSA_FILE: &str = "arena-sarsa.csv";
+const RUN_METADATA_FILE: &str = "run-metadata.csv";
+const EXPERIMENTS_DIR: &str = "experiments";
+const CURVE_EVERY: usize = 100;
+// Greedy games against random play at the end of every curve block, coarse but cheap next to the
+// block's episodes
+const CURVE_PROBE_GAMES: usize = 20;
+const PROBE_SEED: u64 = 0;
+
+// `println!` unless the command answers in JSON, which keeps stdout for that answer alone and says
+// the rest on stderr
+macro_rules! say {
+    ($json:expr, $($arg:tt)*) => {
+        match $json {
+            true => eprintln!($($arg)*),
+            false => println!($($arg)*),
+        }
+    };
+}
+const PROFILES_FILE: &str = "profiles.csv";
+const FAVORITE_OPENINGS: usize = 3;
+const EVAL_GAMES: usize = 100;
+const ENCODING_COMPARISON_SEED: u64 = 0;
+const OPENING_DEPTH: usize = 4;
+const OPENING_WINDOW: usize = 500;
+// What the report at the end of training plays and shows
+const REPORT_PAIRS: usize = 50;
+const REPORT_OPENING_PLIES: usize = 4;
+const REPORT_MINIMAX_DEPTH: usize = 4;
+const REPORT_GREEDY_PLIES: usize = 12;
+const REPORT_OPENINGS: usize = 10;
+// Plies the minimax bot looks ahead, and how long it may think in interactive play
+const MINIMAX_DEPTH: usize = 8;
+const MINIMAX_THINK_TIME: Duration = Duration::from_secs(2);
+// Deep enough to play sensibly, shallow enough for a few hundred games per rule set
+const FAIRNESS_MINIMAX_DEPTH: usize = 4;
+const TRAIND_PORT: u16 = 7411;
+// The games traind's users have going, their finished games go to PROFILES_FILE
+const SERVER_GAMES_FILE: &str = "server-games.csv";
+
+enum Command {
+    Play(PlayArgs),
+    Train(Box<TrainArgs>),
+    PoliciesDiff(DiffArgs),
+    PoliciesExport(ExportArgs),
+    PoliciesBundle(BundleArgs),
+    PoliciesIndex(IndexArgs),
+    PoliciesQuery(QueryArgs),
+    PoliciesSimilar(SimilarArgs),
+    Ope(OpeArgs),
+    Collect(CollectArgs),
+    Arena(ArenaArgs),
+    Pbt(PbtArgs),
+    Ablate(AblateArgs),
+    Evaluate(EvaluateArgs),
+    Fairness(FairnessArgs),
+    DebugEpisode(DebugArgs),
+    SelfCheck,
+    Perft(PerftArgs),
+    Engine,
+    Bandit(BanditArgs),
+    Stats(StatsArgs),
+    Inspect(InspectArgs),
+    Watch(WatchArgs),
+    Traind(TraindArgs),
+    RunsList,
+    RunsShow(RunsShowArgs),
+    RunsExport(RunsExportArgs),
+}
+
+// With a script the human's moves come from a file of words, like piped input, and anything that
+// is no legal move stops the game with an error instead of being skipped. With a bundle the bot
+// plays one of its policies, the one for --difficulty or else the one the human picks first. With
+// a clock both sides play on Fischer time, a fallen flag loses.
+struct PlayArgs {
+    record: Option<String>,
+    script: Option<String>,
+    bundle: Option<String>,
+    difficulty: Option<String>,
+    clock: Option<TimeControl>,
+    bot: BotKind,
+    name: String,
+    depth: usize,
+    // Picks the bot's moves in positions its table does not know
+    fallback: Option<Fallback>,
+    // The human moves first, positive values are in their favor
+    handicap: Handicap,
+}
+
+// One position given on the command line, or a file of them with --batch. Positions are in the
+// `{:#}` format of `MankallaGameState`, one per line.
+struct InspectArgs {
+    policy: String,
+    bot: BotKind,
+    depth: usize,
+    // Positions the greedy table does not know are judged by this many similar ones, 0 for none
+    neighbors: usize,
+    batch: Option<String>,
+    position: Option<String>,
+    json: bool,
+}
+
+// Every profile when no name is given
+struct StatsArgs {
+    name: Option<String>,
+    json: bool,
+}
+
+#[derive(Clone, Copy)]
+enum BotKind {
+    EpsilonGreedy,
+    Greedy,
+    Random,
+    Minimax,
+}
+
+impl FromStr for BotKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "epsilon-greedy" => Ok(BotKind::EpsilonGreedy),
+            "greedy" => Ok(BotKind::Greedy),
+            "random" => Ok(BotKind::Random),
+            "minimax" => Ok(BotKind::Minimax),
+            _ => Err(format!(
+                "Unknown bot \"{s}\" (supported: epsilon-greedy, greedy, random, minimax)"
+            )),
+        }
+    }
+}
+
+enum Game {
+    Mankalla,
+    ConnectFour,
+    Nim,
+    Blackjack,
+}
+
+impl FromStr for Game {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mankalla" | "mancala" => Ok(Game::Mankalla),
+            "connect4" => Ok(Game::ConnectFour),
+            "nim" => Ok(Game::Nim),
+            "blackjack" => Ok(Game::Blackjack),
+            _ => Err(format!(
+                "Unknown game \"{s}\" (supported: mankalla, connect4, nim, blackjack)"
+            )),
+        }
+    }
+}
+
+impl Game {
+    fn name(&self) -> &'static str {
+        match self {
+            Game::Mankalla => "mankalla",
+            Game::ConnectFour => "connect4",
+            Game::Nim => "nim",
+            Game::Blackjack => "blackjack",
+        }
+    }
+}
+
+// Q-learning discounts, R-learning learns values relative to the average reward per step
+#[derive(Clone, Copy)]
+enum Algorithm {
+    QLearning,
+    // On-policy, learns from the action it plays next
+    Sarsa,
+    // SARSA's target averaged over the epsilon greedy choice, steadier updates
+    ExpectedSarsa,
+    // Two tables to keep Q-learning's max from picking overestimated values
+    DoubleQ,
+    // Q-learning with eligibility traces, credit reaches back over several moves at once
+    QLambda,
+    // Learns from the whole return of each game once it is over, nothing bootstrapped
+    MonteCarlo,
+    // Q-learning that also replays remembered moves from a model of where they led
+    DynaQ,
+    // Values of the positions moves lead to, shared by every move that gets there
+    Afterstate,
+    RLearning,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "qlearning" => Ok(Algorithm::QLearning),
+            "sarsa" => Ok(Algorithm::Sarsa),
+            "expected-sarsa" => Ok(Algorithm::ExpectedSarsa),
+            "double-q" => Ok(Algorithm::DoubleQ),
+            "q-lambda" => Ok(Algorithm::QLambda),
+            "monte-carlo" => Ok(Algorithm::MonteCarlo),
+            "dyna-q" => Ok(Algorithm::DynaQ),
+            "afterstate" => Ok(Algorithm::Afterstate),
+            "rlearning" => Ok(Algorithm::RLearning),
+            _ => Err(format!(
+                "Unknown algorithm \"{s}\" (supported: qlearning, sarsa, expected-sarsa, \
+                 double-q, q-lambda, monte-carlo, dyna-q, afterstate, rlearning)"
+            )),
+        }
+    }
+}
+
+impl Algorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            Algorithm::QLearning => "qlearning",
+            Algorithm::Sarsa => "sarsa",
+            Algorithm::ExpectedSarsa => "expected-sarsa",
+            Algorithm::DoubleQ => "double-q",
+            Algorithm::QLambda => "q-lambda",
+            Algorithm::MonteCarlo => "monte-carlo",
+            Algorithm::DynaQ => "dyna-q",
+            Algorithm::Afterstate => "afterstate",
+            Algorithm::RLearning => "rlearning",
+        }
+    }
+}
+
+// Who plays both seats in `fairness`. A policy is read from each rule set's own file, the one
+// `train --stones` writes.
+#[derive(Clone, Copy)]
+enum FairnessPlayer {
+    Minimax,
+    Random,
+    Policy,
+}
+
+impl FromStr for FairnessPlayer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimax" => Ok(FairnessPlayer::Minimax),
+            "random" => Ok(FairnessPlayer::Random),
+            "policy" => Ok(FairnessPlayer::Policy),
+            _ => Err(format!(
+                "Unknown player \"{s}\" (supported: minimax, random, policy)"
+            )),
+        }
+    }
+}
+
+impl FairnessPlayer {
+    fn name(&self) -> &'static str {
+        match self {
+            FairnessPlayer::Minimax => "minimax",
+            FairnessPlayer::Random => "random",
+            FairnessPlayer::Policy => "policy",
+        }
+    }
+}
+
+// Played by the environment during training instead of the learner playing both sides
+#[derive(Clone, Copy)]
+enum Opponent {
+    Random,
+    Heuristic,
+}
+
+impl FromStr for Opponent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Opponent::Random),
+            "heuristic" => Ok(Opponent::Heuristic),
+            _ => Err(format!(
+                "Unknown opponent \"{s}\" (supported: random, heuristic)"
+            )),
+        }
+    }
+}
+
+impl Opponent {
+    fn name(&self) -> &'static str {
+        match self {
+            Opponent::Random => "random",
+            Opponent::Heuristic => "heuristic",
+        }
+    }
+}
+
+// What the Mankalla table keys on: the pits alone, or with the lead in stones of the player to
+// move, in buckets of a few stones if the table should not grow as much
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StateEncoding {
+    Pits,
+    Lead { bucket: u8 },
+}
+
+impl FromStr for StateEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pits" => Ok(StateEncoding::Pits),
+            "lead" => Ok(StateEncoding::Lead { bucket: 1 }),
+            _ => match s.strip_prefix("lead:").map(str::parse) {
+                Some(Ok(bucket @ (1 | 2 | 4 | 8))) => Ok(StateEncoding::Lead { bucket }),
+                _ => Err(format!(
+                    "Unknown state encoding \"{s}\" (supported: pits, lead, lead:<2, 4 or 8 \
+                     stones per bucket>)"
+                )),
+            },
+        }
+    }
+}
+
+impl StateEncoding {
+    fn name(&self) -> String {
+        match self {
+            StateEncoding::Pits => "pits".to_owned(),
+            StateEncoding::Lead { bucket: 1 } => "lead".to_owned(),
+            StateEncoding::Lead { bucket } => format!("lead:{bucket}"),
+        }
+    }
+}
+
+// Starting values for unseen (state, action) pairs, zero unless asked otherwise
+enum InitialValues {
+    Constant(f32),
+    Capture,
+    // The values a table of Mankalla with fewer stones per pit has for the view scaled down to it
+    Transfer { stones: u8, path: String },
+}
+
+impl FromStr for InitialValues {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "capture" => Ok(InitialValues::Capture),
+            _ => match s.split_once('=') {
+                Some((stones, path)) => match stones.parse() {
+                    Ok(stones @ 3..STONES_PER_PIT) => Ok(InitialValues::Transfer {
+                        stones,
+                        path: path.to_owned(),
+                    }),
+                    _ => Err(format!(
+                        "\"{stones}\" in \"{s}\" is no smaller variant (supported: 3 to {} \
+                         stones per pit)",
+                        STONES_PER_PIT - 1
+                    )),
+                },
+                None => s.parse().map(InitialValues::Constant).map_err(|_| {
+                    format!(
+                        "Unknown initial values \"{s}\" (supported: capture, a number, or \
+                         <stones per pit>=<policy> of a smaller variant)"
+                    )
+                }),
+            },
+        }
+    }
+}
+
+struct TrainArgs {
+    game: Game,
+    algorithm: Algorithm,
+    // How fast R-learning's estimate of the average reward follows
+    average_reward_rate: f32,
+    // How far back Q(λ)'s traces reach, per step on top of gamma
+    lambda: f32,
+    // Updates Dyna-Q plans from its model after every real step
+    planning_steps: usize,
+    // Self-play if there is none
+    opponent: Option<Opponent>,
+    // Extra turns are played out by the heuristic inside the step that earned them
+    chain_extra_turns: bool,
+    state_encoding: StateEncoding,
+    // Stones per pit at the start, variants besides `STONES_PER_PIT` keep their own policy file
+    stones: u8,
+    episodes: usize,
+    watch: bool,
+    eval_every: usize,
+    seed: Option<u64>,
+    replay_seed: Option<u64>,
+    until_episode: Option<usize>,
+    // The policy a replay starts from, the policy file `train` resumes from without it
+    from: Option<String>,
+    num_envs: usize,
+    options: TrainingOptions,
+    hyperparameters: Hyperparameters,
+    reheat: Option<ReheatOptions>,
+    gamma_annealing: Option<GammaAnnealing>,
+    root_noise: Option<RootNoise>,
+    // Moves at the start of every game drawn from a softmax over the learner's values, none by
+    // default
+    temperature_plies: usize,
+    temperature: f32,
+    initial_values: Option<InitialValues>,
+    long_episode_percentile: f32,
+    visit_scale: Option<f32>,
+    // Transitions kept for replay after each episode, 0 learns online only
+    replay_capacity: usize,
+    replay_batch: usize,
+    max_wall_time: Option<Duration>,
+    // For the whole run, `options.max_steps` caps single episodes
+    max_total_steps: Option<usize>,
+    // Episodes between the checkpoints kept in the run, besides the one at the end
+    checkpoint_every: Option<usize>,
+    // Exported to when the run ends, into the tracker's default directory
+    tracker: Option<Tracker>,
+    // Everything a script could want from the run as one JSON object on stdout
+    json: bool,
+}
+
+struct DiffArgs {
+    before: String,
+    after: String,
+    top: usize,
+}
+
+struct ExportArgs {
+    policy: String,
+    out: String,
+}
+
+struct IndexArgs {
+    policy: String,
+    out: String,
+}
+
+// The leading fields of the states to list, e.g. "0" for positions with the first pit empty
+struct QueryArgs {
+    index: String,
+    prefix: Vec<String>,
+    top: usize,
+}
+
+// The known states nearest to a position, as `inspect` reads it
+struct SimilarArgs {
+    policy: String,
+    position: String,
+    k: usize,
+}
+
+// Policies as "<difficulty>=<file>" from easy to hard, metadata as "<key>=<value>"
+struct BundleArgs {
+    out: String,
+    policies: Vec<String>,
+    metadata: Vec<String>,
+}
+
+struct OpeArgs {
+    transcripts: String,
+    policy: String,
+    gamma: f32,
+}
+
+struct CollectArgs {
+    episodes: usize,
+    bot: BotKind,
+    policy: String,
+    out: String,
+    seed: Option<u64>,
+    max_steps: Option<usize>,
+}
+
+struct ArenaArgs {
+    episodes: usize,
+    report_every: usize,
+    seed: Option<u64>,
+}
+
+struct PbtArgs {
+    rounds: usize,
+    seed: Option<u64>,
+    options: PbtOptions,
+    hyperparameters: Hyperparameters,
+}
+
+// The variants come from a file, see `ablation::parse_variants`
+struct AblateArgs {
+    config: String,
+    seed: Option<u64>,
+    options: AblationOptions,
+}
+
+// Stones per pit of every rule set to audit, all supported ones by default
+struct FairnessArgs {
+    player: FairnessPlayer,
+    depth: usize,
+    games: usize,
+    opening_plies: usize,
+    stones: Vec<u8>,
+    seed: Option<u64>,
+    json: bool,
+}
+
+struct EvaluateArgs {
+    policy: String,
+    opponent: String,
+    pairs: usize,
+    opening_plies: usize,
+    // Moves after the opening drawn from a softmax over the players' values, none by default
+    temperature_plies: usize,
+    temperature: f32,
+    // Either replaces the random openings, then --pairs and --opening-plies do not count
+    book: Option<String>,
+    book_plies: Option<usize>,
+    seed: Option<u64>,
+    json: bool,
+}
+
+// Counts from the start unless a position in the `{:#}` format of `MankallaGameState` is given
+struct PerftArgs {
+    game: Game,
+    depth: usize,
+    position: Option<String>,
+}
+
+struct RunsShowArgs {
+    id: String,
+}
+
+struct RunsExportArgs {
+    id: String,
+    tracker: Tracker,
+    out: Option<String>,
+}
+
+// External dashboards a run can be exported for
+#[derive(Clone, Copy)]
+enum Tracker {
+    Mlflow,
+    Wandb,
+}
+
+impl FromStr for Tracker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mlflow" => Ok(Tracker::Mlflow),
+            "wandb" => Ok(Tracker::Wandb),
+            _ => Err(format!(
+                "Unknown tracker \"{s}\" (supported: mlflow, wandb)"
+            )),
+        }
+    }
+}
+
+impl Tracker {
+    fn default_dir(&self) -> &'static str {
+        match self {
+            Tracker::Mlflow => "mlruns",
+            Tracker::Wandb => "wandb-export",
+        }
+    }
+
+    fn export(
+        &self,
+        experiment: &Experiment,
+        out: Option<&str>,
+        json: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let root = Path::new(out.unwrap_or(self.default_dir()));
+        let dir = match self {
+            Tracker::Mlflow => tracking::export_mlflow(experiment, root)?,
+            Tracker::Wandb => tracking::export_wandb(experiment, root)?,
+        };
+        say!(json, "Exported {} to {}", experiment.id(), dir.display());
+        Ok(())
+    }
+}
+
+// Trains until told to stop over the control port, which listens on localhost unless bound
+// elsewhere. A period of None turns the automatic checkpoints or swaps off, they can still be
+// asked for.
+struct TraindArgs {
+    // For the control and the metrics port, anything but loopback lets the network in, in plaintext
+    bind: IpAddr,
+    port: u16,
+    // Prometheus scrapes GET /metrics here and probes check /healthz and /readyz, no endpoint
+    // without it
+    metrics_port: Option<u16>,
+    // "<token> <name>" per line, users can only play with one and everybody has to authenticate
+    tokens: Option<String>,
+    checkpoint_every: Option<usize>,
+    swap_every: Option<usize>,
+    seed: Option<u64>,
+}
+
+// Each side is a policy file or "random"
+struct WatchArgs {
+    first: String,
+    second: String,
+    delay: Duration,
+    seed: Option<u64>,
+    // Positive values are in favor of the first policy
+    handicap: Handicap,
+}
+
+struct BanditArgs {
+    arms: usize,
+    steps: usize,
+    runs: usize,
+    seed: Option<u64>,
+}
+
+struct DebugArgs {
+    policy: String,
+    seed: Option<u64>,
+}
+
+struct Args {
+    command: Command,
+    locale: Locale,
+    input_scheme: InputScheme,
+    verbose: bool,
+    probe: bool,
+}
+
+struct Ui {
+    catalog: Catalog,
+    input_scheme: InputScheme,
+    verbose: bool,
+    // `--probe`, every policy file loaded gets a strength probe
+    probe: bool,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut args = env::args().skip(1).peekable();
+
+    let command = match args.peek().map(String::as_str) {
+        Some("train") => Command::Train(Box::new(TrainArgs {
+            game: Game::Mankalla,
+            algorithm: Algorithm::QLearning,
+            average_reward_rate: 0.01,
+            lambda: 0.8,
+            planning_steps: 10,
+            opponent: None,
+            chain_extra_turns: false,
+            state_encoding: StateEncoding::Pits,
+            stones: STONES_PER_PIT,
+            episodes: 1000,
+            watch: false,
+            eval_every: 500,
+            seed: None,
+            replay_seed: None,
+            until_episode: None,
+            from: None,
+            num_envs: 1,
+            options: TrainingOptions::default(),
+            hyperparameters: Hyperparameters::default(),
+            reheat: None,
+            gamma_annealing: None,
+            initial_values: None,
+            long_episode_percentile: 0.99,
+            visit_scale: None,
+            replay_capacity: 0,
+            replay_batch: 32,
+            root_noise: None,
+            temperature_plies: 0,
+            temperature: 1.0,
+            max_wall_time: None,
+            max_total_steps: None,
+            checkpoint_every: Some(10_000),
+            tracker: None,
+            json: false,
+        })),
+        Some("policies") => {
+            args.next();
+            match args.peek().map(String::as_str) {
+                Some("diff") => Command::PoliciesDiff(DiffArgs {
+                    before: String::new(),
+                    after: String::new(),
+                    top: 10,
+                }),
+                Some("export") => Command::PoliciesExport(ExportArgs {
+                    policy: String::new(),
+                    out: String::new(),
+                }),
+                Some("bundle") => Command::PoliciesBundle(BundleArgs {
+                    out: String::new(),
+                    policies: Vec::new(),
+                    metadata: Vec::new(),
+                }),
+                Some("index") => Command::PoliciesIndex(IndexArgs {
+                    policy: String::new(),
+                    out: String::new(),
+                }),
+                Some("query") => Command::PoliciesQuery(QueryArgs {
+                    index: String::new(),
+                    prefix: Vec::new(),
+                    top: 10,
+                }),
+                Some("similar") => Command::PoliciesSimilar(SimilarArgs {
+                    policy: String::new(),
+                    position: String::new(),
+                    k: 5,
+                }),
+                _ => {
+                    return Err(
+                        "Usage: policies diff <before> <after> | policies export <policy> <out.parquet> \
+                         | policies bundle <out> <difficulty>=<policy>... \
+                         | policies index <policy> <out> | policies query <index> [<field>...] \
+                         | policies similar <policy> <position>"
+                            .into(),
+                    );
+                }
+            }
+        }
+        Some("runs") => {
+            args.next();
+            match args.peek().map(String::as_str) {
+                Some("list") => Command::RunsList,
+                Some("show") => Command::RunsShow(RunsShowArgs { id: String::new() }),
+                Some("export") => Command::RunsExport(RunsExportArgs {
+                    id: String::new(),
+                    tracker: Tracker::Mlflow,
+                    out: None,
+                }),
+                _ => {
+                    return Err(
+                        "Usage: runs list | runs show <id> | runs export <id> [--format mlflow|wandb] [--out <dir>]"
+                            .into(),
+                    );
+                }
+            }
+        }
+        Some("ope") => Command::Ope(OpeArgs {
+            transcripts: String::new(),
+            policy: POLICY_FILE.to_owned(),
+            gamma: 1.,
+        }),
+        Some("collect") => Command::Collect(CollectArgs {
+            episodes: 1000,
+            bot: BotKind::EpsilonGreedy,
+            policy: POLICY_FILE.to_owned(),
+            out: DATASET_FILE.to_owned(),
+            seed: None,
+            max_steps: None,
+        }),
+        Some("arena") => Command::Arena(ArenaArgs {
+            episodes: 1000,
+            report_every: 500,
+            seed: None,
+        }),
+        Some("pbt") => Command::Pbt(PbtArgs {
+            rounds: 10,
+            seed: None,
+            options: PbtOptions::default(),
+            hyperparameters: Hyperparameters::default(),
+        }),
+        Some("ablate") => Command::Ablate(AblateArgs {
+            config: String::new(),
+            seed: None,
+            options: AblationOptions::default(),
+        }),
+        Some("evaluate") => Command::Evaluate(EvaluateArgs {
+            policy: POLICY_FILE.to_owned(),
+            opponent: "random".to_owned(),
+            pairs: 100,
+            opening_plies: 4,
+            temperature_plies: 0,
+            temperature: 1.0,
+            book: None,
+            book_plies: None,
+            seed: None,
+            json: false,
+        }),
+        Some("fairness") => Command::Fairness(FairnessArgs {
+            player: FairnessPlayer::Minimax,
+            depth: FAIRNESS_MINIMAX_DEPTH,
+            games: 400,
+            opening_plies: 4,
+            stones: vec![3, 4, 5, STONES_PER_PIT],
+            seed: None,
+            json: false,
+        }),
+        Some("debug-episode") => Command::DebugEpisode(DebugArgs {
+            policy: POLICY_FILE.to_owned(),
+            seed: None,
+        }),
+        Some("self-check") => Command::SelfCheck,
+        Some("engine") => Command::Engine,
+        Some("perft") => Command::Perft(PerftArgs {
+            game: Game::Mankalla,
+            depth: 6,
+            position: None,
+        }),
+        Some("bandit") => Command::Bandit(BanditArgs {
+            arms: 10,
+            steps: 1000,
+            runs: 200,
+            seed: None,
+        }),
+        Some("stats") => Command::Stats(StatsArgs {
+            name: None,
+            json: false,
+        }),
+        Some("inspect") => Command::Inspect(InspectArgs {
+            policy: POLICY_FILE.to_owned(),
+            bot: BotKind::Greedy,
+            depth: MINIMAX_DEPTH,
+            neighbors: 0,
+            batch: None,
+            position: None,
+            json: false,
+        }),
+        Some("watch") => Command::Watch(WatchArgs {
+            first: POLICY_FILE.to_owned(),
+            second: "random".to_owned(),
+            delay: Duration::from_millis(1000),
+            seed: None,
+            handicap: Handicap::default(),
+        }),
+        Some("traind") => Command::Traind(TraindArgs {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: TRAIND_PORT,
+            metrics_port: None,
+            tokens: None,
+            checkpoint_every: Some(10_000),
+            swap_every: Some(1000),
+            seed: None,
+        }),
+        _ => Command::Play(PlayArgs {
+            record: None,
+            script: None,
+            bundle: None,
+            difficulty: None,
+            clock: None,
+            bot: BotKind::EpsilonGreedy,
+            name: default_player_name(),
+            depth: MINIMAX_DEPTH,
+            fallback: None,
+            handicap: Handicap::default(),
+        }),
+    };
+    if let Some(
+        "train" | "play" | "diff" | "export" | "ope" | "collect" | "arena" | "pbt" | "ablate"
+        | "evaluate" | "fairness" | "debug-episode" | "self-check" | "perft" | "engine" | "bandit"
+        | "stats" | "watch" | "list" | "show" | "inspect" | "traind" | "bundle" | "index" | "query"
+        | "similar",
+    ) = args.peek().map(String::as_str)
+    {
+        args.next();
+    }
+
+    parse_flags(command, args)
+}
+
+fn parse_flags(
+    mut command: Command,
+    mut args: impl Iterator<Item = String>,
+) -> Result<Args, Box<dyn Error>> {
+    let mut locale = None;
+    let mut input_scheme = InputScheme::default();
+    let mut verbose = false;
+    let mut probe = false;
+    let mut positionals = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positionals.push(arg);
+            continue;
+        }
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_owned(), Some(value.to_owned())),
+            None => (arg.clone(), None),
+        };
+        let mut value = || match inline_value.clone().or_else(|| args.next()) {
+            Some(value) => Ok(value),
+            None => Err(format!("{flag} expects a value")),
+        };
+        match (&mut command, flag.as_str()) {
+            (_, "--lang") => locale = Some(value()?.parse()?),
+            (_, "--input") => input_scheme = value()?.parse()?,
+            (_, "--verbose") => verbose = true,
+            (_, "--probe") => probe = true,
+            (Command::Train(train), "--json") => train.json = true,
+            (Command::Evaluate(evaluate), "--json") => evaluate.json = true,
+            (Command::Fairness(fairness), "--json") => fairness.json = true,
+            (Command::Stats(stats), "--json") => stats.json = true,
+            (Command::Inspect(inspect), "--json") => inspect.json = true,
+            (Command::Train(train), "--game") => train.game = value()?.parse()?,
+            (Command::Train(train), "--algo") => train.algorithm = value()?.parse()?,
+            (Command::Train(train), "--opponent") => train.opponent = Some(value()?.parse()?),
+            (Command::Train(train), "--chain-extra-turns") => train.chain_extra_turns = true,
+            (Command::Train(train), "--stones") => train.stones = value()?.parse()?,
+            (Command::Train(train), "--state-encoding") => {
+                train.state_encoding = value()?.parse()?
+            }
+            (Command::Train(train), "--average-reward-rate") => {
+                train.average_reward_rate = value()?.parse()?
+            }
+            (Command::Train(train), "--lambda") => train.lambda = value()?.parse()?,
+            (Command::Train(train), "--planning-steps") => {
+                train.planning_steps = value()?.parse()?
+            }
+            (Command::Train(train), "--initial-values") => {
+                train.initial_values = Some(value()?.parse()?)
+            }
+            (Command::Train(train), "--max-episode-steps") => {
+                train.options.max_steps = Some(value()?.parse()?)
+            }
+            (Command::Train(train), "--max-total-steps") => {
+                train.max_total_steps = Some(value()?.parse()?)
+            }
+            (Command::Train(train), "--checkpoint-every") => {
+                train.checkpoint_every = every(value()?.parse()?)
+            }
+            (Command::Train(train), "--max-wall-time") => {
+                train.max_wall_time = Some(budget::parse_duration(&value()?)?)
+            }
+            (Command::Train(train), "--long-episode-percentile") => {
+                train.long_episode_percentile = value()?.parse()?
+            }
+            (Command::Train(train), "--explore-by-visits") => {
+                train.visit_scale = Some(value()?.parse()?)
+            }
+            (Command::Train(train), "--episodes") => train.episodes = value()?.parse()?,
+            (Command::Train(train), "--watch") => train.watch = true,
+            (Command::Train(train), "--eval-every") => train.eval_every = value()?.parse()?,
+            (Command::Train(train), "--seed") => train.seed = Some(value()?.parse()?),
+            (Command::Train(train), "--replay-seed") => train.replay_seed = Some(value()?.parse()?),
+            (Command::Train(train), "--until-episode") => {
+                train.until_episode = Some(value()?.parse()?)
+            }
+            (Command::Train(train), "--from") => train.from = Some(value()?),
+            (Command::Train(train), "--num-envs") => train.num_envs = value()?.parse()?,
+            (Command::Train(train), "--learning-rate") => {
+                train.hyperparameters.learning_rate = value()?.parse()?
+            }
+            (Command::Train(train), "--gamma") => train.hyperparameters.gamma = value()?.parse()?,
+            (Command::Train(train), "--max-epsilon") => {
+                train.hyperparameters.max_epsilon = value()?.parse()?
+            }
+            (Command::Train(train), "--min-epsilon") => {
+                train.hyperparameters.min_epsilon = value()?.parse()?
+            }
+            (Command::Train(train), "--decay-rate") => {
+                train.hyperparameters.decay_rate = value()?.parse()?
+            }
+            (Command::Train(train), "--win-reward") => {
+                train.options.rewards.win = value()?.parse()?
+            }
+            (Command::Train(train), "--loss-reward") => {
+                train.options.rewards.loss = value()?.parse()?
+            }
+            (Command::Train(train), "--draw-reward") => {
+                train.options.rewards.draw = value()?.parse()?
+            }
+            (Command::Train(train), "--reward-clip") => {
+                train.options.rewards.clip = Some(value()?.parse()?)
+            }
+            (Command::Train(train), "--reheat-patience") => {
+                train.reheat.get_or_insert_default().patience = value()?.parse()?
+            }
+            (Command::Train(train), "--reheat-min-improvement") => {
+                train.reheat.get_or_insert_default().min_improvement = value()?.parse()?
+            }
+            (Command::Train(train), "--reheat-boost") => {
+                train.reheat.get_or_insert_default().boost = value()?.parse()?
+            }
+            (Command::Train(train), "--reheat-duration") => {
+                train.reheat.get_or_insert_default().duration = value()?.parse()?
+            }
+            (Command::Train(train), "--replay-capacity") => {
+                train.replay_capacity = value()?.parse()?
+            }
+            (Command::Train(train), "--replay-batch") => match value()?.parse()? {
+                0 => return Err("--replay-batch has to be at least 1".into()),
+                batch => train.replay_batch = batch,
+            },
+            (Command::Train(train), "--temperature-plies") => {
+                train.temperature_plies = value()?.parse()?
+            }
+            (Command::Train(train), "--temperature") => match value()?.parse()? {
+                t if t > 0f32 => train.temperature = t,
+                _ => return Err("--temperature has to be positive".into()),
+            },
+            (Command::Train(train), "--root-noise-alpha") => {
+                train.root_noise.get_or_insert_default().alpha = value()?.parse()?
+            }
+            (Command::Train(train), "--root-noise-fraction") => {
+                train.root_noise.get_or_insert_default().fraction = value()?.parse()?
+            }
+            (Command::Train(train), "--anneal-gamma-from") => {
+                train.gamma_annealing.get_or_insert_default().start = value()?.parse()?
+            }
+            (Command::Train(train), "--anneal-gamma-episodes") => {
+                train.gamma_annealing.get_or_insert_default().episodes = value()?.parse()?
+            }
+            (Command::PoliciesDiff(diff), "--top") => diff.top = value()?.parse()?,
+            (Command::PoliciesQuery(query), "--top") => query.top = value()?.parse()?,
+            (Command::PoliciesSimilar(similar), "--k") => similar.k = value()?.parse()?,
+            (Command::Play(play), "--record") => play.record = Some(value()?),
+            (Command::Play(play), "--script") => play.script = Some(value()?),
+            (Command::Play(play), "--bundle") => play.bundle = Some(value()?),
+            (Command::Play(play), "--difficulty") => play.difficulty = Some(value()?),
+            (Command::Play(play), "--clock") => play.clock = Some(value()?.parse()?),
+            (Command::PoliciesBundle(bundle), "--meta") => bundle.metadata.push(value()?),
+            (Command::Play(play), "--bot") => play.bot = value()?.parse()?,
+            (Command::Play(play), "--fallback") => play.fallback = Some(value()?.parse()?),
+            (Command::Play(play), "--name") => play.name = player_name(value()?)?,
+            (Command::Play(play), "--depth") => match value()?.parse()? {
+                0 => return Err("--depth has to be at least 1".into()),
+                depth => play.depth = depth,
+            },
+            (Command::Watch(watch), "--first") => watch.first = value()?,
+            (Command::Watch(watch), "--second") => watch.second = value()?,
+            (Command::Watch(watch), "--delay") => {
+                watch.delay = Duration::from_millis(value()?.parse()?)
+            }
+            (Command::Watch(watch), "--seed") => watch.seed = Some(value()?.parse()?),
+            (Command::Play(play), "--komi") => play.handicap.komi = value()?.parse()?,
+            (Command::Watch(watch), "--komi") => watch.handicap.komi = value()?.parse()?,
+            (Command::Play(play), "--pit-handicap") => {
+                play.handicap.pit_stones = pit_handicap(&value()?)?
+            }
+            (Command::Watch(watch), "--pit-handicap") => {
+                watch.handicap.pit_stones = pit_handicap(&value()?)?
+            }
+            (Command::Traind(traind), "--bind") => traind.bind = value()?.parse()?,
+            (Command::Traind(traind), "--port") => traind.port = value()?.parse()?,
+            (Command::Traind(traind), "--metrics-port") => {
+                traind.metrics_port = Some(value()?.parse()?)
+            }
+            (Command::Traind(traind), "--tokens") => traind.tokens = Some(value()?),
+            (Command::Traind(traind), "--checkpoint-every") => {
+                traind.checkpoint_every = every(value()?.parse()?)
+            }
+            (Command::Traind(traind), "--swap-every") => {
+                traind.swap_every = every(value()?.parse()?)
+            }
+            (Command::Traind(traind), "--seed") => traind.seed = Some(value()?.parse()?),
+            (Command::RunsExport(export), "--format") => export.tracker = value()?.parse()?,
+            (Command::RunsExport(export), "--out") => export.out = Some(value()?),
+            (Command::Train(train), "--track") => train.tracker = Some(value()?.parse()?),
+            (Command::Stats(stats), "--name") => stats.name = Some(player_name(value()?)?),
+            (Command::Inspect(inspect), "--policy") => inspect.policy = value()?,
+            (Command::Inspect(inspect), "--bot") => inspect.bot = value()?.parse()?,
+            (Command::Inspect(inspect), "--depth") => match value()?.parse()? {
+                0 => return Err("--depth has to be at least 1".into()),
+                depth => inspect.depth = depth,
+            },
+            (Command::Inspect(inspect), "--batch") => inspect.batch = Some(value()?),
+            (Command::Inspect(inspect), "--neighbors") => inspect.neighbors = value()?.parse()?,
+            (Command::Ope(ope), "--policy") => ope.policy = value()?,
+            (Command::Ope(ope), "--gamma") => ope.gamma = value()?.parse()?,
+            (Command::Collect(collect), "--episodes") => collect.episodes = value()?.parse()?,
+            (Command::Collect(collect), "--bot") => collect.bot = value()?.parse()?,
+            (Command::Collect(collect), "--policy") => collect.policy = value()?,
+            (Command::Collect(collect), "--out") => collect.out = value()?,
+            (Command::Collect(collect), "--seed") => collect.seed = Some(value()?.parse()?),
+            (Command::Arena(arena), "--episodes") => arena.episodes = value()?.parse()?,
+            (Command::Arena(arena), "--report-every") => arena.report_every = value()?.parse()?,
+            (Command::Arena(arena), "--seed") => arena.seed = Some(value()?.parse()?),
+            (Command::Pbt(pbt), "--rounds") => pbt.rounds = value()?.parse()?,
+            (Command::Pbt(pbt), "--population") => pbt.options.population = value()?.parse()?,
+            (Command::Pbt(pbt), "--episodes-per-round") => {
+                pbt.options.episodes_per_round = value()?.parse()?
+            }
+            (Command::Pbt(pbt), "--truncation") => pbt.options.truncation = value()?.parse()?,
+            (Command::Pbt(pbt), "--seed") => pbt.seed = Some(value()?.parse()?),
+            (Command::Ablate(ablate), "--episodes") => {
+                ablate.options.episodes = value()?.parse()?
+            }
+            (Command::Ablate(ablate), "--eval-every") => {
+                ablate.options.eval_every = value()?.parse()?
+            }
+            (Command::Ablate(ablate), "--eval-games") => {
+                ablate.options.eval_games = value()?.parse()?
+            }
+            (Command::Ablate(ablate), "--seed") => ablate.seed = Some(value()?.parse()?),
+            (Command::Evaluate(evaluate), "--policy") => evaluate.policy = value()?,
+            (Command::Evaluate(evaluate), "--opponent") => evaluate.opponent = value()?,
+            (Command::Evaluate(evaluate), "--pairs") => evaluate.pairs = value()?.parse()?,
+            (Command::Evaluate(evaluate), "--opening-plies") => {
+                evaluate.opening_plies = value()?.parse()?
+            }
+            (Command::Evaluate(evaluate), "--temperature-plies") => {
+                evaluate.temperature_plies = value()?.parse()?
+            }
+            (Command::Evaluate(evaluate), "--book") => evaluate.book = Some(value()?),
+            (Command::Evaluate(evaluate), "--book-plies") => {
+                evaluate.book_plies = Some(value()?.parse()?)
+            }
+            (Command::Evaluate(evaluate), "--temperature") => match value()?.parse()? {
+                t if t > 0f32 => evaluate.temperature = t,
+                _ => return Err("--temperature has to be positive".into()),
+            },
+            (Command::Evaluate(evaluate), "--seed") => evaluate.seed = Some(value()?.parse()?),
+            (Command::Fairness(fairness), "--player") => fairness.player = value()?.parse()?,
+            (Command::Fairness(fairness), "--depth") => match value()?.parse()? {
+                0 => return Err("--depth has to be at least 1".into()),
+                depth => fairness.depth = depth,
+            },
+            (Command::Fairness(fairness), "--games") => match value()?.parse()? {
+                0 => return Err("--games has to be at least 1".into()),
+                games => fairness.games = games,
+            },
+            (Command::Fairness(fairness), "--opening-plies") => {
+                fairness.opening_plies = value()?.parse()?
+            }
+            (Command::Fairness(fairness), "--stones") => {
+                fairness.stones = value()?
+                    .split(',')
+                    .map(|stones| match stones.parse::<u8>() {
+                        Ok(stones @ 3..=STONES_PER_PIT) => Ok(stones),
+                        _ => Err(format!(
+                            "--stones expects a list of 3 to {STONES_PER_PIT} stones per pit, \
+                             e.g. 4,{STONES_PER_PIT}"
+                        )),
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            (Command::Fairness(fairness), "--seed") => fairness.seed = Some(value()?.parse()?),
+            (Command::Perft(perft), "--game") => perft.game = value()?.parse()?,
+            (Command::Perft(perft), "--depth") => match value()?.parse()? {
+                0 => return Err("--depth has to be at least 1".into()),
+                depth => perft.depth = depth,
+            },
+            (Command::Perft(perft), "--position") => perft.position = Some(value()?),
+            (Command::Bandit(bandit), "--arms") => bandit.arms = value()?.parse()?,
+            (Command::Bandit(bandit), "--steps") => bandit.steps = value()?.parse()?,
+            (Command::Bandit(bandit), "--runs") => bandit.runs = value()?.parse()?,
+            (Command::Bandit(bandit), "--seed") => bandit.seed = Some(value()?.parse()?),
+            (Command::DebugEpisode(debug), "--policy") => debug.policy = value()?,
+            (Command::DebugEpisode(debug), "--seed") => debug.seed = Some(value()?.parse()?),
+            (Command::Collect(collect), "--max-episode-steps") => {
+                collect.max_steps = Some(value()?.parse()?)
+            }
+            _ => return Err(format!("Unknown argument \"{arg}\"").into()),
+        }
+    }
+
+    match (&mut command, positionals.as_mut_slice()) {
+        (Command::PoliciesDiff(diff), [before, after]) => {
+            diff.before = std::mem::take(before);
+            diff.after = std::mem::take(after);
+        }
+        (Command::PoliciesDiff(_), _) => {
+            return Err("Usage: policies diff <before> <after>".into());
+        }
+        (Command::PoliciesExport(export), [policy, out]) => {
+            export.policy = std::mem::take(policy);
+            export.out = std::mem::take(out);
+        }
+        (Command::PoliciesExport(_), _) => {
+            return Err("Usage: policies export <policy> <out.parquet>".into());
+        }
+        (Command::PoliciesBundle(bundle), [out, policies @ ..]) if !policies.is_empty() => {
+            bundle.out = std::mem::take(out);
+            bundle.policies = policies.to_vec();
+        }
+        (Command::PoliciesIndex(index), [policy, out]) => {
+            index.policy = std::mem::take(policy);
+            index.out = std::mem::take(out);
+        }
+        (Command::PoliciesIndex(_), _) => {
+            return Err("Usage: policies index <policy> <out>".into());
+        }
+        (Command::PoliciesQuery(query), [index, prefix @ ..]) => {
+            query.index = std::mem::take(index);
+            query.prefix = prefix.to_vec();
+        }
+        (Command::PoliciesQuery(_), _) => {
+            return Err("Usage: policies query <index> [<field>...] [--top <n>]".into());
+        }
+        (Command::PoliciesSimilar(similar), [policy, position]) => {
+            similar.policy = std::mem::take(policy);
+            similar.position = std::mem::take(position);
+        }
+        (Command::PoliciesSimilar(_), _) => {
+            return Err("Usage: policies similar <policy> <position> [--k <n>]".into());
+        }
+        (Command::PoliciesBundle(_), _) => {
+            return Err(
+                "Usage: policies bundle <out> <difficulty>=<policy>... [--meta <key>=<value>]"
+                    .into(),
+            );
+        }
+        (Command::Play(play), []) if play.difficulty.is_some() && play.bundle.is_none() => {
+            return Err("--difficulty picks from a --bundle".into());
+        }
+        (Command::Inspect(inspect), [position]) if inspect.batch.is_none() => {
+            inspect.position = Some(std::mem::take(position))
+        }
+        (Command::Inspect(inspect), []) if inspect.batch.is_some() => {}
+        (Command::Inspect(_), _) => {
+            return Err(
+                "Usage: inspect \"<position>\" | --batch <file> [--policy <file>] \
+                 [--bot greedy|minimax] [--depth <plies>]"
+                    .into(),
+            );
+        }
+        (Command::RunsShow(show), [id]) => show.id = std::mem::take(id),
+        (Command::RunsShow(_), _) => return Err("Usage: runs show <id>".into()),
+        (Command::RunsExport(export), [id]) => export.id = std::mem::take(id),
+        (Command::RunsExport(_), _) => {
+            return Err("Usage: runs export <id> [--format mlflow|wandb] [--out <dir>]".into());
+        }
+        (Command::Ablate(ablate), [config]) => ablate.config = std::mem::take(config),
+        (Command::Ablate(_), _) => {
+            return Err(
+                "Usage: ablate <variants> [--episodes <n>] [--eval-every <n>] \
+                        [--eval-games <n>] [--seed <seed>]"
+                    .into(),
+            );
+        }
+        (Command::Ope(ope), [transcripts]) => ope.transcripts = std::mem::take(transcripts),
+        (Command::Ope(_), _) => {
+            return Err("Usage: ope <transcripts> [--policy <file>] [--gamma <gamma>]".into());
+        }
+        (_, []) => {}
+        (_, [arg, ..]) => return Err(format!("Unknown argument \"{arg}\"").into()),
+    }
+
+    Ok(Args {
+        command,
+        locale: locale.unwrap_or_else(Locale::from_env),
+        input_scheme,
+        verbose,
+        probe,
+    })
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+    let ui = Ui {
+        catalog: Catalog::new(args.locale),
+        input_scheme: args.input_scheme,
+        verbose: args.verbose,
+        probe: args.probe,
+    };
+
+    match args.command {
+        Command::Play(play_args) => {
+            let (bot, game) = match &play_args.script {
+                Some(path) => {
+                    let file =
+                        File::open(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+                    let mut input = WordReader::new(BufReader::new(file));
+                    play(&play_args, &mut input, Some(path), &ui)?
+                }
+                None => play(
+                    &play_args,
+                    &mut WordReader::new(io::stdin().lock()),
+                    None,
+                    &ui,
+                )?,
+            };
+            let Some(bot) = bot else {
+                return Ok(());
+            };
+            // What the bot learned from a bundle's policy stays out of policy.csv and the bundle
+            if let PolicySource::File(path) = &bot.source {
+                fs::write(path, bot.policy.serialize())?;
+            }
+            if let Some((transcript, outcome)) = &game {
+                let highlights = GameHighlights {
+                    against_strongest_bot: matches!(bot.kind, BotKind::Greedy | BotKind::Minimax),
+                    ..highlights(transcript)
+                };
+                for achievement in
+                    update_profile(&play_args.name, transcript, *outcome, &highlights)?
+                {
+                    println!(
+                        "{}",
+                        ui.catalog.get(Message::AchievementUnlocked { achievement })
+                    );
+                }
+            }
+            if let (Some(path), Some((transcript, _))) = (play_args.record, game) {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", transcript.serialize())?;
+            }
+        }
+        Command::Train(train_args) => train(&train_args, &ui)?,
+        Command::PoliciesDiff(diff_args) => diff_policies(&diff_args, &ui)?,
+        Command::PoliciesExport(export_args) => export_policy(&export_args, &ui)?,
+        Command::PoliciesBundle(bundle_args) => bundle_policies(&bundle_args, &ui)?,
+        Command::PoliciesIndex(index_args) => index_policy(&index_args, &ui)?,
+        Command::PoliciesQuery(query_args) => query_index(&query_args)?,
+        Command::PoliciesSimilar(similar_args) => similar_positions(&similar_args, &ui)?,
+        Command::Collect(collect_args) => collect(&collect_args, &ui)?,
+        Command::Arena(arena_args) => arena(&arena_args)?,
+        Command::Pbt(pbt_args) => pbt(&pbt_args)?,
+        Command::Ablate(ablate_args) => ablate(&ablate_args)?,
+        Command::Evaluate(evaluate_args) => evaluate(&evaluate_args, &ui)?,
+        Command::Fairness(fairness_args) => audit_fairness(&fairness_args)?,
+        Command::DebugEpisode(debug_args) => debug_episode(&debug_args, &ui)?,
+        Command::Perft(perft_args) => perft(&perft_args)?,
+        Command::Engine => engine()?,
+        Command::Bandit(bandit_args) => compare_bandit_strategies(&bandit_args)?,
+        Command::Stats(stats_args) => show_stats(&stats_args, &ui)?,
+        Command::Inspect(inspect_args) => inspect(&inspect_args, &ui)?,
+        Command::Watch(watch_args) => watch(&watch_args, &ui)?,
+        Command::Traind(traind_args) => traind(&traind_args, &ui)?,
+        Command::RunsList => list_runs()?,
+        Command::RunsShow(show_args) => show_run(&show_args)?,
+        Command::RunsExport(export_args) => export_args.tracker.export(
+            &Experiment::open(EXPERIMENTS_DIR.as_ref(), &export_args.id)?,
+            export_args.out.as_deref(),
+            false,
+        )?,
+        Command::SelfCheck => {
+            let report = self_check::run();
+            println!("{report}");
+            if !report.passed() {
+                return Err("The self-check failed, see the report above".into());
+            }
+        }
+        Command::Ope(ope_args) => {
+            let policy = load_policy(&ope_args.policy, ui.probe)?;
+            let transcripts = ope::parse_transcripts::<MankallaGame>(&fs::read_to_string(
+                &ope_args.transcripts,
+            )?)?;
+            println!(
+                "{}",
+                ope::estimate(policy.greedy_policy(), &transcripts, ope_args.gamma)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn new_policy<E: Environment>(
+    hyperparameters: Hyperparameters,
+) -> Result<EpsilonGreedyPolicy<E>, Box<dyn Error>> {
+    warn_about(hyperparameters)?;
+    Ok(EpsilonGreedyPolicy::from_hyperparameters(hyperparameters)?)
+}
+
+// Hyperparameters only apply to new policies, a policy file brings its own
+fn load_or_new_policy<E: Environment>(
+    path: &str,
+    hyperparameters: Hyperparameters,
+) -> Result<EpsilonGreedyPolicy<E>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(s) => {
+            let policy = EpsilonGreedyPolicy::deserialize(s.as_str())?;
+            warn_about(policy.hyperparameters())?;
+            Ok(policy)
+        }
+        Err(_) => new_policy(hyperparameters),
+    }
+}
+
+fn load_bundle(path: &str) -> Result<PolicyBundle, Box<dyn Error>> {
+    let input = fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+    let bundle = PolicyBundle::deserialize(input.as_str())
+        .map_err(|e| format!("{path} is no policy bundle: {e}"))?;
+    if bundle.difficulties().next().is_none() {
+        return Err(format!("{path} has no policies").into());
+    }
+    Ok(bundle)
+}
+
+fn bundled_policy(
+    bundle: &PolicyBundle,
+    difficulty: &str,
+) -> Result<EpsilonGreedyPolicy<MankallaGame>, Box<dyn Error>> {
+    let policy = bundle.policy(difficulty).ok_or_else(|| {
+        format!(
+            "The bundle has no difficulty \"{difficulty}\" (it has: {})",
+            bundle.difficulties().collect::<Vec<_>>().join(", ")
+        )
+    })?;
+    EpsilonGreedyPolicy::deserialize(policy)
+        .map_err(|e| format!("The {difficulty} policy of the bundle: {e}").into())
+}
+
+fn warn_about(hyperparameters: Hyperparameters) -> Result<(), Box<dyn Error>> {
+    for warning in hyperparameters.validate()? {
+        eprintln!("Warning: {warning}");
+    }
+    Ok(())
+}
+
+// R-learning, Q(λ) and Dyna-Q files carry their parameters in front, afterstate files their
+// values and Double Q-learning files a second table behind, their tables play like any other
+fn load_policy(
+    path: &str,
+    probe: bool,
+) -> Result<EpsilonGreedyPolicy<MankallaGame>, Box<dyn Error>> {
+    let input = fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+    let policy = match EpsilonGreedyPolicy::deserialize(input.as_str()) {
+        Ok(policy) => policy,
+        Err(e) => RLearningPolicy::deserialize(input.as_str())
+            .map(RLearningPolicy::into_epsilon_greedy_policy)
+            .or_else(|_| {
+                DoubleQLearningPolicy::deserialize(input.as_str())
+                    .map(DoubleQLearningPolicy::into_epsilon_greedy_policy)
+            })
+            .or_else(|_| {
+                QLambdaPolicy::deserialize(input.as_str())
+                    .map(QLambdaPolicy::into_epsilon_greedy_policy)
+            })
+            .or_else(|_| {
+                DynaQPolicy::deserialize(input.as_str())
+                    .map(DynaQPolicy::into_epsilon_greedy_policy)
+            })
+            .or_else(|_| {
+                AfterstatePolicy::deserialize(input.as_str())
+                    .map(AfterstatePolicy::into_epsilon_greedy_policy)
+            })
+            .map_err(|_| e)?,
+    };
+    probe_if_loaded(path, &policy, probe);
+    Ok(policy)
+}
+
+// Fresh policies are not worth a probe, `load_or_new_policy` hands those out for missing files.
+// It goes to stderr like the warnings, so it never gets into a command's JSON answer.
+fn probe_if_loaded(path: &str, policy: &EpsilonGreedyPolicy<MankallaGame>, probe: bool) {
+    if !probe || !Path::new(path).exists() {
+        return;
+    }
+    let probe = evaluation::strength_probe(
+        policy.greedy_policy(),
+        &mut StdRng::seed_from_u64(PROBE_SEED),
+    );
+    eprintln!(
+        "{path} (episode {}, {} Q-values): {probe}",
+        policy.episode(),
+        policy.greedy_policy().qtable_size()
+    );
+}
+
+fn train(train_args: &TrainArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    if train_args.stones != STONES_PER_PIT {
+        let policy_file = format!("stones{}-{POLICY_FILE}", train_args.stones);
+        return match (
+            &train_args.game,
+            train_args.opponent,
+            train_args.chain_extra_turns,
+            train_args.state_encoding,
+        ) {
+            (Game::Mankalla, None, false, StateEncoding::Pits) => match train_args.stones {
+                3 => train_plain::<MankallaWithStones<3>>(train_args, &policy_file),
+                4 => train_plain::<MankallaWithStones<4>>(train_args, &policy_file),
+                5 => train_plain::<MankallaWithStones<5>>(train_args, &policy_file),
+                stones => Err(format!(
+                    "Mankalla with {stones} stones per pit is not supported (supported: 3, 4, 5, \
+                     {STONES_PER_PIT})"
+                )
+                .into()),
+            },
+            (Game::Mankalla, _, _, _) => Err(
+                "--stones does not go with --opponent, --chain-extra-turns or --state-encoding"
+                    .into(),
+            ),
+            _ => Err("--stones is only supported for mankalla".into()),
+        };
+    }
+    if let StateEncoding::Lead { bucket } = train_args.state_encoding {
+        return match (
+            &train_args.game,
+            train_args.opponent,
+            train_args.chain_extra_turns,
+        ) {
+            (Game::Mankalla, None, false) => match bucket {
+                1 => train_lead::<1>(train_args),
+                2 => train_lead::<2>(train_args),
+                4 => train_lead::<4>(train_args),
+                _ => train_lead::<8>(train_args),
+            },
+            (Game::Mankalla, _, _) => Err(
+                "--state-encoding lead does not go with --opponent or --chain-extra-turns".into(),
+            ),
+            _ => Err("--state-encoding is only supported for mankalla".into()),
+        };
+    }
+    if train_args.chain_extra_turns {
+        return match (&train_args.game, train_args.opponent) {
+            (Game::Mankalla, None) => {
+                train_plain::<ChainedTurns<MankallaGame, HeuristicPolicy>>(train_args, POLICY_FILE)
+            }
+            (Game::Mankalla, Some(_)) => {
+                Err("--chain-extra-turns does not go with --opponent".into())
+            }
+            _ => Err("--chain-extra-turns is only supported for mankalla".into()),
+        };
+    }
+    match (&train_args.game, train_args.opponent, train_args.algorithm) {
+        (Game::Mankalla, None, Algorithm::QLearning) => {}
+        (Game::Mankalla, None, _) => return train_plain::<MankallaGame>(train_args, POLICY_FILE),
+        (Game::Mankalla, Some(Opponent::Random), _) => {
+            return train_plain::<VsOpponentEnv<MankallaGame, SeededRandomPolicy>>(
+                train_args,
+                POLICY_FILE,
+            );
+        }
+        (Game::Mankalla, Some(Opponent::Heuristic), _) => {
+            return train_plain::<VsOpponentEnv<MankallaGame, HeuristicPolicy>>(
+                train_args,
+                POLICY_FILE,
+            );
+        }
+        (_, Some(_), _) => return Err("--opponent is only supported for mankalla".into()),
+        (Game::ConnectFour, None, _) => {
+            return train_plain::<ConnectFour>(train_args, CONNECT4_POLICY_FILE);
+        }
+        (Game::Nim, None, _) => return train_plain::<Nim>(train_args, NIM_POLICY_FILE),
+        (Game::Blackjack, None, _) => {
+            return train_plain::<Blackjack>(train_args, BLACKJACK_POLICY_FILE);
+        }
+    }
+    if (train_args.watch || train_args.replay_seed.is_some()) && train_args.json {
+        return Err("--json does not go with --watch or --replay-seed".into());
+    }
+    if let Some(seed) = train_args.replay_seed {
+        return replay(train_args, seed, ui);
+    }
+
+    let mut policy = load_or_new_policy(POLICY_FILE, train_args.hyperparameters)?;
+    probe_if_loaded(POLICY_FILE, &policy, ui.probe);
+    let mut seeds = seed_streams(train_args.seed, "Training", train_args.json);
+    let experiment = start_experiment(train_args, &seeds)?;
+    let (options, mut replay) = prepare_run(&mut policy, train_args, &mut seeds)?;
+
+    let probe_opponent = SeededRandomPolicy::new(seeds.seed("curve-evaluator"));
+    let mut run_observer = (
+        Checkpoints::new(&experiment, train_args.checkpoint_every),
+        (
+            ClipCounter::default(),
+            (
+                ReheatObserver::new(
+                    train_args.reheat,
+                    train_args.eval_every,
+                    seeds.seed("reheat-evaluator"),
+                ),
+                (
+                    episode_lengths(train_args)?,
+                    (
+                        TrainingBudget::new(train_args.max_wall_time, train_args.max_total_steps),
+                        (
+                            ProbedCurve::new(
+                                TrainingCurve::new(CURVE_EVERY),
+                                move |policy: &EpsilonGreedyPolicy<MankallaGame>| {
+                                    evaluation::evaluate(
+                                        policy.greedy_policy(),
+                                        &probe_opponent,
+                                        CURVE_PROBE_GAMES,
+                                    )
+                                    .win_rate()
+                                },
+                            ),
+                            OpeningDiversity::new(OPENING_DEPTH, OPENING_WINDOW),
+                        ),
+                    ),
+                ),
+            ),
+        ),
+    );
+    match train_args.watch {
+        true => {
+            let evaluator_seed = seeds.seed("dashboard-evaluator");
+            (policy, run_observer) = train_watched(
+                policy,
+                train_args,
+                options,
+                replay,
+                evaluator_seed,
+                run_observer,
+            )?
+        }
+        false => {
+            run_training(
+                &mut policy,
+                train_args.episodes,
+                train_args.num_envs,
+                &options,
+                replay.as_mut(),
+                &mut run_observer,
+            );
+        }
+    }
+    let (checkpoints, (clip_counter, (reheat_observer, (lengths, (budget, (curve, openings)))))) =
+        run_observer;
+    checkpoints.finish()?;
+    let curve = curve.into_curve();
+    say!(train_args.json, "{openings}");
+    report_budget(&budget, train_args.json);
+    report_rejected_updates(policy.greedy_policy().rejected_updates());
+    report_gamma(&policy, train_args);
+    if let Some(clip) = train_args.options.rewards.clip {
+        say!(
+            train_args.json,
+            "Clipped {} of {} rewards to ±{clip} ({:.2}%)",
+            clip_counter.clipped,
+            clip_counter.steps,
+            clip_counter.rate() * 100f32
+        );
+    }
+    if train_args.reheat.is_some() {
+        say!(
+            train_args.json,
+            "Raised epsilon {} times after the win rate stalled",
+            reheat_observer.reheats
+        );
+    }
+    report_episode_lengths(&lengths, train_args.json)?;
+
+    let evaluator = SeededRandomPolicy::new(seeds.seed("experiment-evaluator"));
+    let report = evaluation::evaluate(policy.greedy_policy(), &evaluator, EVAL_GAMES);
+    say!(train_args.json, "Against a random opponent: {report}");
+    let mut manifest = experiment.manifest()?;
+    manifest.set("eval_win_rate", report.win_rate());
+    experiment.write(
+        experiments::EVAL_FILE,
+        &format!("Against a random opponent: {report}\n"),
+    )?;
+    experiment.write_manifest(&manifest)?;
+
+    let mut rng = seeds.rng("report-openings");
+    let heuristic = evaluation::evaluate_paired(
+        policy.greedy_policy(),
+        &HeuristicPolicy,
+        REPORT_PAIRS,
+        REPORT_OPENING_PLIES,
+        None,
+        &mut rng,
+    );
+    let minimax = evaluation::evaluate_paired(
+        policy.greedy_policy(),
+        &MinimaxPolicy::new(REPORT_MINIMAX_DEPTH, None),
+        REPORT_PAIRS,
+        REPORT_OPENING_PLIES,
+        None,
+        &mut rng,
+    );
+    let evaluations = [
+        ("random".to_owned(), report),
+        ("heuristic".to_owned(), heuristic.report),
+        (
+            format!("minimax depth {REPORT_MINIMAX_DEPTH}"),
+            minimax.report,
+        ),
+    ];
+    write_report(
+        &experiment,
+        train_args,
+        &curve,
+        policy.greedy_policy(),
+        &evaluations,
+        &openings,
+    )?;
+
+    let serialized = policy.serialize();
+    finish_experiment(
+        &experiment,
+        &serialized,
+        policy.episode(),
+        &curve,
+        &budget,
+        &seeds,
+        train_args,
+    )?;
+    fs::write(POLICY_FILE, serialized)?;
+    write_run_metadata(&seeds, train_args.json)?;
+    if train_args.json {
+        print_training_json(
+            &experiment,
+            &seeds,
+            &budget,
+            &curve,
+            policy.greedy_policy(),
+            &evaluations,
+        );
+    }
+    Ok(())
+}
+
+fn train_plain<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    match train_args.algorithm {
+        Algorithm::QLearning => {
+            let policy = load_or_new_policy::<E>(policy_file, train_args.hyperparameters)?;
+            train_game(train_args, policy_file, policy)
+        }
+        Algorithm::Sarsa => train_sarsa::<E>(train_args, policy_file),
+        Algorithm::ExpectedSarsa => train_expected_sarsa::<E>(train_args, policy_file),
+        Algorithm::DoubleQ => train_double_q::<E>(train_args, policy_file),
+        Algorithm::QLambda => train_q_lambda::<E>(train_args, policy_file),
+        Algorithm::MonteCarlo => train_monte_carlo::<E>(train_args, policy_file),
+        Algorithm::DynaQ => train_dyna_q::<E>(train_args, policy_file),
+        Algorithm::Afterstate => train_afterstate::<E>(train_args, policy_file),
+        Algorithm::RLearning => train_rlearning::<E>(train_args, policy_file),
+    }
+}
+
+// Trains every algorithm's policy for the games besides Mankalla and against fixed opponents. The
+// dashboard, reheating and replays only measure Q-learning against Mankalla opponents.
+fn train_game<E: Environment, P: TrainedPolicy<E>>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+    mut policy: P,
+) -> Result<(), Box<dyn Error>> {
+    if train_args.watch
+        || train_args.reheat.is_some()
+        || train_args.replay_seed.is_some()
+        || train_args.initial_values.is_some()
+    {
+        return Err(
+            "--watch, --reheat-*, --replay-seed and --initial-values are only supported for \
+             Q-learning self-play on mankalla"
+                .into(),
+        );
+    }
+    let mut seeds = seed_streams(train_args.seed, "Training", train_args.json);
+    let experiment = start_experiment(train_args, &seeds)?;
+    policy.epsilon_greedy_mut().reseed(seeds.seed("trainer"));
+    let options = training_options(train_args, &mut seeds);
+    let mut replay = replay_buffer(train_args, &mut seeds)?;
+    explore_by_visits(policy.epsilon_greedy_mut(), train_args)?;
+    anneal_gamma(policy.epsilon_greedy_mut(), train_args)?;
+    add_root_noise(policy.epsilon_greedy_mut(), train_args)?;
+
+    let mut checkpoints = Checkpoints::new(&experiment, train_args.checkpoint_every);
+    let mut clip_counter = ClipCounter::default();
+    let mut lengths = episode_lengths(train_args)?;
+    let mut budget = TrainingBudget::new(train_args.max_wall_time, train_args.max_total_steps);
+    let mut curve = TrainingCurve::new(CURVE_EVERY);
+    let mut openings = OpeningDiversity::new(OPENING_DEPTH, OPENING_WINDOW);
+    policy.train(
+        train_args.episodes,
+        train_args.num_envs,
+        &options,
+        replay.as_mut(),
+        &mut (
+            &mut checkpoints,
+            (
+                &mut clip_counter,
+                (&mut lengths, (&mut budget, (&mut curve, &mut openings))),
+            ),
+        ),
+    );
+    checkpoints.finish()?;
+    report_budget(&budget, train_args.json);
+    report_rejected_updates(policy.rejected_updates());
+    report_gamma(policy.epsilon_greedy(), train_args);
+    if let Some(clip) = train_args.options.rewards.clip {
+        say!(
+            train_args.json,
+            "Clipped {} of {} rewards to ±{clip} ({:.2}%)",
+            clip_counter.clipped,
+            clip_counter.steps,
+            clip_counter.rate() * 100f32
+        );
+    }
+    report_episode_lengths(&lengths, train_args.json)?;
+    say!(
+        train_args.json,
+        "Trained until episode {}",
+        policy.epsilon_greedy().episode()
+    );
+    say!(
+        train_args.json,
+        "Q-table size: {}",
+        policy.epsilon_greedy().greedy_policy().qtable_size()
+    );
+    policy.report(train_args.json);
+    write_report(
+        &experiment,
+        train_args,
+        &curve,
+        policy.epsilon_greedy().greedy_policy(),
+        &[],
+        &openings,
+    )?;
+
+    let serialized = policy.serialize();
+    finish_experiment(
+        &experiment,
+        &serialized,
+        policy.epsilon_greedy().episode(),
+        &curve,
+        &budget,
+        &seeds,
+        train_args,
+    )?;
+    fs::write(policy_file, serialized)?;
+    write_run_metadata(&seeds, train_args.json)?;
+    if train_args.json {
+        print_training_json(
+            &experiment,
+            &seeds,
+            &budget,
+            &curve,
+            policy.epsilon_greedy().greedy_policy(),
+            &[],
+        );
+    }
+    Ok(())
+}
+
+// What `train_game` trains: policies that explore like `EpsilonGreedyPolicy` and keep their
+// table in one
+trait TrainedPolicy<E: Environment>: Policy<E> + Serialize {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E>;
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E>;
+
+    // Said at the end of training, besides what every policy reports
+    fn report(&self, _json: bool) {}
+
+    // Non-finite values the updates refused
+    fn rejected_updates(&self) -> usize {
+        self.epsilon_greedy().greedy_policy().rejected_updates()
+    }
+
+    // Off-policy learners go through `QLearning`'s loop
+    fn train(
+        &mut self,
+        episodes: usize,
+        num_envs: usize,
+        options: &TrainingOptions,
+        replay: Option<&mut ReplayBuffer<E>>,
+        observer: &mut impl TrainingObserver<E, Self>,
+    ) where
+        Self: Sized,
+    {
+        run_training(self, episodes, num_envs, options, replay, observer);
+    }
+}
+
+impl<E: Environment> TrainedPolicy<E> for EpsilonGreedyPolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self
+    }
+}
+
+// `train_sarsa` makes sure there is only one environment
+impl<E: Environment> TrainedPolicy<E> for SarsaPolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy()
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy_mut()
+    }
+
+    fn train(
+        &mut self,
+        episodes: usize,
+        _num_envs: usize,
+        options: &TrainingOptions,
+        _replay: Option<&mut ReplayBuffer<E>>,
+        observer: &mut impl TrainingObserver<E, Self>,
+    ) {
+        Sarsa::train_observed(self, episodes, options, observer);
+    }
+}
+
+impl<E: Environment> TrainedPolicy<E> for ExpectedSarsaPolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy()
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy_mut()
+    }
+}
+
+impl<E: Environment> TrainedPolicy<E> for RLearningPolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy()
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy_mut()
+    }
+
+    fn report(&self, json: bool) {
+        say!(
+            json,
+            "Average reward per step: {:.4}",
+            self.average_reward()
+        );
+    }
+}
+
+// The reports above are about the first table
+impl<E: Environment> TrainedPolicy<E> for DoubleQLearningPolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy()
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy_mut()
+    }
+
+    fn report(&self, json: bool) {
+        say!(
+            json,
+            "Second Q-table size: {}",
+            self.second_table().qtable_size()
+        );
+    }
+}
+
+// `train_q_lambda` makes sure there is only one environment
+impl<E: Environment> TrainedPolicy<E> for QLambdaPolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy()
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy_mut()
+    }
+}
+
+// `train_monte_carlo` makes sure there is only one environment
+impl<E: Environment> TrainedPolicy<E> for MonteCarloPolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy()
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy_mut()
+    }
+
+    fn train(
+        &mut self,
+        episodes: usize,
+        _num_envs: usize,
+        options: &TrainingOptions,
+        _replay: Option<&mut ReplayBuffer<E>>,
+        observer: &mut impl TrainingObserver<E, Self>,
+    ) {
+        MonteCarloControl::train_observed(self, episodes, options, observer);
+    }
+}
+
+impl<E: Environment> TrainedPolicy<E> for DynaQPolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy()
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy_mut()
+    }
+
+    fn report(&self, json: bool) {
+        say!(json, "Model size: {} pairs", self.model_size());
+    }
+}
+
+// The table holds the values of every position learned in, worked out from the afterstates
+impl<E: Environment> TrainedPolicy<E> for AfterstatePolicy<E> {
+    fn epsilon_greedy(&self) -> &EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy()
+    }
+
+    fn epsilon_greedy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        self.epsilon_greedy_policy_mut()
+    }
+
+    fn report(&self, json: bool) {
+        say!(json, "Afterstate values: {}", self.afterstates());
+    }
+
+    fn rejected_updates(&self) -> usize {
+        AfterstatePolicy::rejected_updates(self)
+    }
+}
+
+// R-learning keeps its own policy file next to the game's, e.g. rlearning-policy.csv, so the two
+// formulations can be trained side by side and compared. Like the hyperparameters, the rate only
+// applies to new policies.
+fn train_rlearning<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    if train_args.gamma_annealing.is_some() {
+        return Err("R-learning does not discount, --anneal-gamma-* does not apply".into());
+    }
+    if !(train_args.average_reward_rate > 0f32 && train_args.average_reward_rate <= 1f32) {
+        return Err("--average-reward-rate expects a value in (0, 1]".into());
+    }
+    let policy_file = format!("rlearning-{policy_file}");
+    let policy = match fs::read_to_string(&policy_file) {
+        Ok(s) => {
+            let policy = RLearningPolicy::<E>::deserialize(s.as_str())?;
+            warn_about(policy.epsilon_greedy_policy().hyperparameters())?;
+            policy
+        }
+        Err(_) => RLearningPolicy::new(
+            new_policy(train_args.hyperparameters)?,
+            train_args.average_reward_rate,
+        ),
+    };
+    train_game(train_args, &policy_file, policy)
+}
+
+// Starting values for `MankallaGame` from the table of a variant with `stones` per pit. Its views
+// are `[u8; 12]` like `MankallaGame`'s, so it loads as one of its tables.
+fn transferred_values(
+    stones: u8,
+    path: &str,
+    json: bool,
+) -> Result<InitialValue<MankallaGame>, Box<dyn Error>> {
+    let input = fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+    let table = EpsilonGreedyPolicy::<MankallaGame>::deserialize(input.as_str())?
+        .greedy_policy()
+        .clone();
+    say!(
+        json,
+        "Starting from the {} Q-values of {path}, pits scaled from {STONES_PER_PIT} to {stones} \
+         stones",
+        table.qtable_size()
+    );
+    Ok(Arc::new(move |view: &[u8; 12], pit: &u8| {
+        table.value(scale_view(view, STONES_PER_PIT, stones), *pit)
+    }))
+}
+
+// Mankalla keyed on `LeadView` keeps its own file next to policy.csv, lead-policy.csv or e.g.
+// lead4-policy.csv for buckets of 4 stones. A new Q-learning table starts from what policy.csv
+// learned for the same pits, whatever the lead, for the run that creates it: the starting values
+// are not saved, only what was learned from them. Both tables play the same random opponent in
+// the end.
+fn train_lead<const BUCKET: u8>(train_args: &TrainArgs) -> Result<(), Box<dyn Error>> {
+    let policy_file = match BUCKET {
+        1 => format!("lead-{POLICY_FILE}"),
+        _ => format!("lead{BUCKET}-{POLICY_FILE}"),
+    };
+    let lead_table: EpsilonGreedyPolicy<LeadAwareMankalla<BUCKET>> = match train_args.algorithm {
+        Algorithm::QLearning => {
+            let new_table = !Path::new(&policy_file).exists();
+            let mut policy = load_or_new_policy::<LeadAwareMankalla<BUCKET>>(
+                &policy_file,
+                train_args.hyperparameters,
+            )?;
+            if let (true, Ok(pits)) = (new_table, fs::read_to_string(POLICY_FILE)) {
+                let pits = EpsilonGreedyPolicy::<MankallaGame>::deserialize(pits.as_str())?
+                    .greedy_policy()
+                    .clone();
+                say!(
+                    train_args.json,
+                    "Starting {policy_file} from the values in {POLICY_FILE}"
+                );
+                policy.greedy_policy_mut().set_initial_value(Arc::new(
+                    move |view: &LeadView<BUCKET>, pit: &u8| pits.value(view.pits, *pit),
+                ));
+            }
+            train_game(train_args, &policy_file, policy)?;
+            EpsilonGreedyPolicy::deserialize(fs::read_to_string(&policy_file)?.as_str())?
+        }
+        Algorithm::Sarsa => {
+            train_sarsa::<LeadAwareMankalla<BUCKET>>(train_args, &policy_file)?;
+            let input = fs::read_to_string(format!("sarsa-{policy_file}"))?;
+            EpsilonGreedyPolicy::deserialize(input.as_str())?
+        }
+        Algorithm::ExpectedSarsa => {
+            train_expected_sarsa::<LeadAwareMankalla<BUCKET>>(train_args, &policy_file)?;
+            let input = fs::read_to_string(format!("expected-sarsa-{policy_file}"))?;
+            EpsilonGreedyPolicy::deserialize(input.as_str())?
+        }
+        Algorithm::RLearning => {
+            train_rlearning::<LeadAwareMankalla<BUCKET>>(train_args, &policy_file)?;
+            let input = fs::read_to_string(format!("rlearning-{policy_file}"))?;
+            RLearningPolicy::deserialize(input.as_str())?.into_epsilon_greedy_policy()
+        }
+        Algorithm::DoubleQ => {
+            train_double_q::<LeadAwareMankalla<BUCKET>>(train_args, &policy_file)?;
+            let input = fs::read_to_string(format!("double-q-{policy_file}"))?;
+            DoubleQLearningPolicy::deserialize(input.as_str())?.into_epsilon_greedy_policy()
+        }
+        Algorithm::QLambda => {
+            train_q_lambda::<LeadAwareMankalla<BUCKET>>(train_args, &policy_file)?;
+            let input = fs::read_to_string(format!("q-lambda-{policy_file}"))?;
+            QLambdaPolicy::deserialize(input.as_str())?.into_epsilon_greedy_policy()
+        }
+        Algorithm::MonteCarlo => {
+            train_monte_carlo::<LeadAwareMankalla<BUCKET>>(train_args, &policy_file)?;
+            let input = fs::read_to_string(format!("monte-carlo-{policy_file}"))?;
+            EpsilonGreedyPolicy::deserialize(input.as_str())?
+        }
+        Algorithm::DynaQ => {
+            train_dyna_q::<LeadAwareMankalla<BUCKET>>(train_args, &policy_file)?;
+            let input = fs::read_to_string(format!("dyna-q-{policy_file}"))?;
+            DynaQPolicy::deserialize(input.as_str())?.into_epsilon_greedy_policy()
+        }
+        Algorithm::Afterstate => {
+            train_afterstate::<LeadAwareMankalla<BUCKET>>(train_args, &policy_file)?;
+            let input = fs::read_to_string(format!("afterstate-{policy_file}"))?;
+            AfterstatePolicy::deserialize(input.as_str())?.into_epsilon_greedy_policy()
+        }
+    };
+    compare_encodings(lead_table.greedy_policy(), train_args.json)
+}
+
+// Both encodings against the same random moves, with the table sizes to show what the lead costs
+fn compare_encodings<const BUCKET: u8>(
+    lead_table: &GreedyPolicy<LeadAwareMankalla<BUCKET>>,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let lead = evaluation::evaluate_game(
+        lead_table,
+        &SeededRandomPolicy::new(ENCODING_COMPARISON_SEED),
+        EVAL_GAMES,
+    );
+    say!(
+        json,
+        "With the lead ({} Q-values) against a random opponent: {lead}",
+        lead_table.qtable_size()
+    );
+    let Ok(pits_table) = fs::read_to_string(POLICY_FILE) else {
+        say!(json, "No {POLICY_FILE} to compare with");
+        return Ok(());
+    };
+    let pits_table = EpsilonGreedyPolicy::<MankallaGame>::deserialize(pits_table.as_str())?;
+    let pits = evaluation::evaluate(
+        pits_table.greedy_policy(),
+        &SeededRandomPolicy::new(ENCODING_COMPARISON_SEED),
+        EVAL_GAMES,
+    );
+    say!(
+        json,
+        "With the pits alone ({} Q-values, {POLICY_FILE}) against a random opponent: {pits}",
+        pits_table.greedy_policy().qtable_size()
+    );
+    Ok(())
+}
+
+// SARSA keeps its own policy file like R-learning, e.g. sarsa-policy.csv, in the same format as
+// Q-learning's so either can be played and evaluated as the other
+fn train_sarsa<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    if train_args.num_envs != 1 {
+        return Err("SARSA plays one game at a time, --num-envs does not apply".into());
+    }
+    let policy_file = format!("sarsa-{policy_file}");
+    let policy = match fs::read_to_string(&policy_file) {
+        Ok(s) => {
+            let policy = SarsaPolicy::<E>::deserialize(s.as_str())?;
+            warn_about(policy.epsilon_greedy_policy().hyperparameters())?;
+            policy
+        }
+        Err(_) => SarsaPolicy::from_hyperparameters(train_args.hyperparameters)?,
+    };
+    train_game(train_args, &policy_file, policy)
+}
+
+// Expected SARSA gets its own file next to SARSA's, e.g. expected-sarsa-policy.csv, in the same
+// format. It never looks at the next action, so unlike SARSA it trains on several games at once.
+fn train_expected_sarsa<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    let policy_file = format!("expected-sarsa-{policy_file}");
+    let policy = match fs::read_to_string(&policy_file) {
+        Ok(s) => {
+            let policy = ExpectedSarsaPolicy::<E>::deserialize(s.as_str())?;
+            warn_about(policy.epsilon_greedy_policy().hyperparameters())?;
+            policy
+        }
+        Err(_) => ExpectedSarsaPolicy::from_hyperparameters(train_args.hyperparameters)?,
+    };
+    train_game(train_args, &policy_file, policy)
+}
+
+// Double Q-learning keeps its own policy file too, e.g. double-q-policy.csv. Played or evaluated
+// from there, the two tables are averaged into one.
+fn train_double_q<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    let policy_file = format!("double-q-{policy_file}");
+    let policy = match fs::read_to_string(&policy_file) {
+        Ok(s) => {
+            let policy = DoubleQLearningPolicy::<E>::deserialize(s.as_str())?;
+            warn_about(policy.epsilon_greedy_policy().hyperparameters())?;
+            policy
+        }
+        Err(_) => DoubleQLearningPolicy::from_hyperparameters(train_args.hyperparameters)?,
+    };
+    train_game(train_args, &policy_file, policy)
+}
+
+// Q(λ) keeps its own policy file as well, e.g. q-lambda-policy.csv. Like the rate of R-learning,
+// --lambda only applies to new policies.
+fn train_q_lambda<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    if train_args.num_envs != 1 {
+        return Err("Q(λ) plays one game at a time, --num-envs does not apply".into());
+    }
+    if !(0f32..=1f32).contains(&train_args.lambda) {
+        return Err("--lambda expects a value in [0, 1]".into());
+    }
+    let policy_file = format!("q-lambda-{policy_file}");
+    let policy = match fs::read_to_string(&policy_file) {
+        Ok(s) => {
+            let policy = QLambdaPolicy::<E>::deserialize(s.as_str())?;
+            warn_about(policy.epsilon_greedy_policy().hyperparameters())?;
+            policy
+        }
+        Err(_) => {
+            QLambdaPolicy::from_hyperparameters(train_args.hyperparameters, train_args.lambda)?
+        }
+    };
+    train_game(train_args, &policy_file, policy)
+}
+
+// Monte Carlo control keeps its own file in Q-learning's format too, e.g. monte-carlo-policy.csv
+fn train_monte_carlo<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    if train_args.num_envs != 1 {
+        return Err(
+            "Monte Carlo control plays one game at a time, --num-envs does not apply".into(),
+        );
+    }
+    let policy_file = format!("monte-carlo-{policy_file}");
+    let policy = match fs::read_to_string(&policy_file) {
+        Ok(s) => {
+            let policy = MonteCarloPolicy::<E>::deserialize(s.as_str())?;
+            warn_about(policy.epsilon_greedy_policy().hyperparameters())?;
+            policy
+        }
+        Err(_) => MonteCarloPolicy::from_hyperparameters(train_args.hyperparameters)?,
+    };
+    train_game(train_args, &policy_file, policy)
+}
+
+// Dyna-Q keeps its own file as well, e.g. dyna-q-policy.csv. The model is built anew every run,
+// --planning-steps only applies to new policies like --lambda.
+fn train_dyna_q<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    let policy_file = format!("dyna-q-{policy_file}");
+    let policy = match fs::read_to_string(&policy_file) {
+        Ok(s) => {
+            let policy = DynaQPolicy::<E>::deserialize(s.as_str())?;
+            warn_about(policy.epsilon_greedy_policy().hyperparameters())?;
+            policy
+        }
+        Err(_) => DynaQPolicy::from_hyperparameters(
+            train_args.hyperparameters,
+            train_args.planning_steps,
+        )?,
+    };
+    train_game(train_args, &policy_file, policy)
+}
+
+// Afterstate values keep their own file too, e.g. afterstate-policy.csv
+fn train_afterstate<E: Environment>(
+    train_args: &TrainArgs,
+    policy_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    let policy_file = format!("afterstate-{policy_file}");
+    let policy = match fs::read_to_string(&policy_file) {
+        Ok(s) => {
+            let policy = AfterstatePolicy::<E>::deserialize(s.as_str())?;
+            warn_about(policy.epsilon_greedy_policy().hyperparameters())?;
+            policy
+        }
+        Err(_) => AfterstatePolicy::from_hyperparameters(train_args.hyperparameters)?,
+    };
+    train_game(train_args, &policy_file, policy)
+}
+
+fn explore_by_visits<E: Environment>(
+    policy: &mut EpsilonGreedyPolicy<E>,
+    train_args: &TrainArgs,
+) -> Result<(), Box<dyn Error>> {
+    match train_args.visit_scale {
+        Some(scale) if scale <= 0f32 => Err("--explore-by-visits expects a positive scale".into()),
+        Some(scale) => {
+            policy.explore_by_visits(scale);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+fn add_root_noise<E: Environment>(
+    policy: &mut EpsilonGreedyPolicy<E>,
+    train_args: &TrainArgs,
+) -> Result<(), Box<dyn Error>> {
+    match train_args.root_noise {
+        Some(noise) if noise.alpha <= 0f32 => {
+            Err("--root-noise-alpha expects a positive concentration".into())
+        }
+        Some(noise) if !(0f32..=1f32).contains(&noise.fraction) => {
+            Err("--root-noise-fraction expects a value in [0, 1]".into())
+        }
+        Some(noise) => {
+            policy.add_root_noise(noise);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+// The options from the command line, with the streams a tempered opening and the environments
+// draw from
+fn training_options(train_args: &TrainArgs, seeds: &mut SeedStreams) -> TrainingOptions {
+    TrainingOptions {
+        environment_seed: Some(seeds.seed("environment")),
+        opening_temperature: match train_args.temperature_plies {
+            0 => None,
+            plies => Some(OpeningTemperature::new(
+                plies,
+                train_args.temperature,
+                seeds.seed("opening-temperature"),
+            )),
+        },
+        ..train_args.options
+    }
+}
+
+fn anneal_gamma<E: Environment>(
+    policy: &mut EpsilonGreedyPolicy<E>,
+    train_args: &TrainArgs,
+) -> Result<(), Box<dyn Error>> {
+    match train_args.gamma_annealing {
+        Some(annealing) if !(0f32..=1f32).contains(&annealing.start) => {
+            Err("--anneal-gamma-from expects a value in [0, 1]".into())
+        }
+        Some(annealing) => {
+            policy.anneal_gamma(annealing.start, annealing.episodes);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+fn train_config(train_args: &TrainArgs) -> Manifest {
+    let mut config = Manifest::default();
+    let h = train_args.hyperparameters;
+    let rewards = train_args.options.rewards;
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "none".to_owned());
+    config.set("game", train_args.game.name());
+    config.set("algorithm", train_args.algorithm.name());
+    config.set("chain_extra_turns", train_args.chain_extra_turns);
+    config.set("state_encoding", train_args.state_encoding.name());
+    config.set("stones", train_args.stones);
+    config.set(
+        "opponent",
+        train_args.opponent.map_or("self-play", |o| o.name()),
+    );
+    config.set("episodes", train_args.episodes);
+    config.set("num_envs", train_args.num_envs);
+    config.set("learning_rate", h.learning_rate);
+    config.set("gamma", h.gamma);
+    if let Algorithm::RLearning = train_args.algorithm {
+        config.set("average_reward_rate", train_args.average_reward_rate);
+    }
+    if let Algorithm::QLambda = train_args.algorithm {
+        config.set("lambda", train_args.lambda);
+    }
+    if let Algorithm::DynaQ = train_args.algorithm {
+        config.set("planning_steps", train_args.planning_steps);
+    }
+    config.set("max_epsilon", h.max_epsilon);
+    config.set("min_epsilon", h.min_epsilon);
+    config.set("decay_rate", h.decay_rate);
+    config.set("win_reward", rewards.win);
+    config.set("loss_reward", rewards.loss);
+    config.set("draw_reward", rewards.draw);
+    config.set("reward_clip", optional(rewards.clip.map(|c| c.to_string())));
+    config.set(
+        "max_episode_steps",
+        optional(train_args.options.max_steps.map(|m| m.to_string())),
+    );
+    config.set(
+        "max_total_steps",
+        optional(train_args.max_total_steps.map(|m| m.to_string())),
+    );
+    config.set(
+        "checkpoint_every",
+        optional(train_args.checkpoint_every.map(|e| e.to_string())),
+    );
+    config.set(
+        "max_wall_time_seconds",
+        optional(
+            train_args
+                .max_wall_time
+                .map(|d| d.as_secs_f64().to_string()),
+        ),
+    );
+    config.set(
+        "visit_scale",
+        optional(train_args.visit_scale.map(|v| v.to_string())),
+    );
+    config.set(
+        "root_noise_alpha",
+        optional(train_args.root_noise.map(|n| n.alpha.to_string())),
+    );
+    config.set(
+        "root_noise_fraction",
+        optional(train_args.root_noise.map(|n| n.fraction.to_string())),
+    );
+    config.set("temperature_plies", train_args.temperature_plies);
+    config.set("temperature", train_args.temperature);
+    config.set("replay_capacity", train_args.replay_capacity);
+    config.set("replay_batch", train_args.replay_batch);
+    config.set(
+        "initial_values",
+        match &train_args.initial_values {
+            Some(InitialValues::Constant(value)) => value.to_string(),
+            Some(InitialValues::Capture) => "capture".to_owned(),
+            Some(InitialValues::Transfer { stones, path }) => format!("{stones}={path}"),
+            None => "none".to_owned(),
+        },
+    );
+    config.set(
+        "reheat",
+        optional(train_args.reheat.map(|r| format!("{r:?}"))),
+    );
+    config.set(
+        "gamma_annealing",
+        optional(train_args.gamma_annealing.map(|a| format!("{a:?}"))),
+    );
+    config.set("watch", train_args.watch);
+    config
+}
+
+// Policy files loaded at the start are not copied, the config and the seeds say how the run went
+fn start_experiment(
+    train_args: &TrainArgs,
+    seeds: &SeedStreams,
+) -> Result<Experiment, Box<dyn Error>> {
+    let experiment = Experiment::create(EXPERIMENTS_DIR.as_ref())?;
+    experiment.write(
+        experiments::CONFIG_FILE,
+        &train_config(train_args).serialize(),
+    )?;
+    let mut manifest = Manifest::default();
+    manifest.set("id", experiment.id());
+    manifest.set("game", train_args.game.name());
+    manifest.set("command", env::args().skip(1).collect::<Vec<_>>().join(" "));
+    manifest.set("started", experiments::unix_time());
+    manifest.set("status", "running");
+    manifest.set("seed", seeds.master());
+    experiment.write_manifest(&manifest)?;
+    say!(
+        train_args.json,
+        "Recording the run in {}",
+        experiment.dir().display()
+    );
+    Ok(experiment)
+}
+
+// The report and its charts go next to the rest of the run
+fn write_report<E: Environment>(
+    experiment: &Experiment,
+    train_args: &TrainArgs,
+    curve: &TrainingCurve,
+    policy: &GreedyPolicy<E>,
+    evaluations: &[(String, EvaluationReport)],
+    openings: &OpeningDiversity<E>,
+) -> Result<(), Box<dyn Error>> {
+    let config = train_config(train_args);
+    let report = TrainingReport {
+        run: experiment.id(),
+        config: &config,
+        curve,
+        evaluations,
+        table: QTableStats::new(policy.qtable()),
+        greedy_line: report::greedy_line(policy, REPORT_GREEDY_PLIES),
+        openings: openings
+            .most_common(REPORT_OPENINGS)
+            .into_iter()
+            .map(|(line, count)| (line.iter().map(Serialize::serialize).collect(), count))
+            .collect(),
+        opening_window: openings.episodes(),
     };
+    if let Some(chart) = report.win_rate_chart() {
+        experiment.write(report::WIN_RATE_CHART, &chart)?;
+    }
+    if let Some(chart) = report.reward_chart() {
+        experiment.write(report::REWARD_CHART, &chart)?;
+    }
+    experiment.write(report::REPORT_FILE, &report.to_markdown())?;
+    say!(
+        train_args.json,
+        "Report written to {}",
+        experiment.path(report::REPORT_FILE).display()
+    );
+    Ok(())
+}
+
+// Everything a script could want from the run in one object, for --json
+fn print_training_json<E: Environment>(
+    experiment: &Experiment,
+    seeds: &SeedStreams,
+    budget: &TrainingBudget,
+    curve: &TrainingCurve,
+    policy: &GreedyPolicy<E>,
+    evaluations: &[(String, EvaluationReport)],
+) {
+    let status = match budget.exhausted() {
+        Some(exhausted) => exhausted.to_string(),
+        None => "finished".to_owned(),
+    };
+    let json = Json::object([
+        ("run", experiment.id().into()),
+        ("seed", seeds.master().into()),
+        ("status", status.into()),
+        ("episodes", budget.episodes().into()),
+        ("steps", budget.steps().into()),
+        ("qtable", QTableStats::new(policy.qtable()).to_json()),
+        (
+            "evaluations",
+            Json::Array(
+                evaluations
+                    .iter()
+                    .map(|(opponent, report)| {
+                        Json::object([
+                            ("opponent", opponent.as_str().into()),
+                            ("report", report.to_json()),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+        (
+            "curve",
+            Json::Array(curve.points().iter().map(ToJson::to_json).collect()),
+        ),
+    ]);
+    println!("{json}");
+}
+
+fn finish_experiment(
+    experiment: &Experiment,
+    policy: &str,
+    episode: usize,
+    curve: &TrainingCurve,
+    budget: &TrainingBudget,
+    seeds: &SeedStreams,
+    train_args: &TrainArgs,
+) -> Result<(), Box<dyn Error>> {
+    let checkpoint = experiment.checkpoint(episode, policy)?;
+    experiment.write(experiments::METRICS_FILE, &curve.to_csv())?;
+    experiment.write(experiments::SEEDS_FILE, &format!("{seeds}\n"))?;
+
+    let mut manifest = experiment.manifest()?;
+    manifest.set("finished", experiments::unix_time());
+    manifest.set(
+        "status",
+        match budget.exhausted() {
+            Some(exhausted) => exhausted.to_string(),
+            None => "finished".to_owned(),
+        },
+    );
+    manifest.set("episodes", budget.episodes());
+    manifest.set("steps", budget.steps());
+    manifest.set("policy_episode", episode);
+    manifest.set("checkpoint", checkpoint);
+    experiment.write_manifest(&manifest)?;
+    match train_args.tracker {
+        Some(tracker) => tracker.export(experiment, None, train_args.json),
+        None => Ok(()),
+    }
+}
+
+fn list_runs() -> Result<(), Box<dyn Error>> {
+    let runs = experiments::list(EXPERIMENTS_DIR.as_ref())?;
+    if runs.is_empty() {
+        println!("No runs recorded in {EXPERIMENTS_DIR} yet");
+        return Ok(());
+    }
+    println!(
+        "{:<10} {:<21} {:<10} {:>10} {:>9}  status",
+        "id", "started", "game", "episodes", "win rate"
+    );
+    for (id, manifest) in runs {
+        let started = manifest
+            .get("started")
+            .and_then(|s| s.parse().ok())
+            .map_or_else(|| "?".to_owned(), experiments::format_unix_time);
+        let win_rate = manifest
+            .get("eval_win_rate")
+            .and_then(|w| w.parse::<f32>().ok())
+            .map_or_else(|| "-".to_owned(), |w| format!("{:.1}%", w * 100f32));
+        println!(
+            "{:<10} {:<21} {:<10} {:>10} {:>9}  {}",
+            id,
+            started,
+            manifest.get("game").unwrap_or("?"),
+            manifest.get("episodes").unwrap_or("-"),
+            win_rate,
+            manifest.get("status").unwrap_or("?")
+        );
+    }
+    Ok(())
+}
+
+fn show_run(show_args: &RunsShowArgs) -> Result<(), Box<dyn Error>> {
+    let experiment = Experiment::open(EXPERIMENTS_DIR.as_ref(), &show_args.id)?;
+    println!("{}", experiment.dir().display());
+    for (key, value) in experiment.manifest()?.entries() {
+        let value = match key.as_str() {
+            "started" | "finished" => value
+                .parse()
+                .map_or_else(|_| value.clone(), experiments::format_unix_time),
+            _ => value.clone(),
+        };
+        println!("  {key:<14} {value}");
+    }
+    if let Ok(config) = fs::read_to_string(experiment.path(experiments::CONFIG_FILE)) {
+        println!("config");
+        for (key, value) in Manifest::deserialize(&config)
+            .map_err(|_| "Could not parse the config of the run")?
+            .entries()
+        {
+            println!("  {key:<22} {value}");
+        }
+    }
+    if let Ok(eval) = fs::read_to_string(experiment.path(experiments::EVAL_FILE)) {
+        print!("eval\n  {eval}");
+    }
+    println!("files");
+    for file in experiment.files()? {
+        println!("  {file}");
+    }
+    Ok(())
+}
+
+fn report_budget(budget: &TrainingBudget, json: bool) {
+    if let Some(exhausted) = budget.exhausted() {
+        say!(json, "{exhausted}");
+    }
+}
+
+fn report_gamma<E: Environment>(policy: &EpsilonGreedyPolicy<E>, train_args: &TrainArgs) {
+    if train_args.gamma_annealing.is_some() {
+        say!(
+            train_args.json,
+            "Gamma annealed to {:.3} of {}",
+            policy.gamma(),
+            policy.greedy_policy().gamma()
+        );
+    }
+}
+
+fn report_rejected_updates(rejected: usize) {
+    if rejected > 0 {
+        eprintln!(
+            "Warning: skipped {rejected} updates with a NaN or infinite Q-value, check the rewards and hyperparameters"
+        );
+    }
+}
+
+fn episode_lengths<E: Environment>(
+    train_args: &TrainArgs,
+) -> Result<EpisodeLengths<E>, Box<dyn Error>> {
+    if !(0f32..=1f32).contains(&train_args.long_episode_percentile) {
+        return Err("--long-episode-percentile has to lie in [0, 1]".into());
+    }
+    Ok(EpisodeLengths::new(
+        train_args.long_episode_percentile,
+        LONG_EPISODE_MIN_HISTORY,
+        LONG_EPISODES_KEPT,
+    ))
+}
+
+fn report_episode_lengths<E: Environment>(
+    lengths: &EpisodeLengths<E>,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    say!(json, "{lengths}");
+    if lengths.anomalies().is_empty() {
+        return Ok(());
+    }
+    let mut file = BufWriter::new(fs::File::create(LONG_EPISODES_FILE)?);
+    writeln!(file, "{}", dataset::CSV_HEADER)?;
+    for (episode, transitions) in lengths.anomalies() {
+        dataset::write_csv(&mut file, *episode, transitions)?;
+    }
+    say!(
+        json,
+        "Transcripts of the first {} written to {LONG_EPISODES_FILE}",
+        lengths.anomalies().len()
+    );
+    Ok(())
+}
+
+// The master seed is printed so the run can be repeated, every stream derived from it is
+// recorded with `write_run_metadata`
+fn seed_streams(seed: Option<u64>, activity: &str, json: bool) -> SeedStreams {
+    let seeds = SeedStreams::new(seed.unwrap_or_else(rand::random));
+    say!(json, "{activity} with seed {}", seeds.master());
+    seeds
+}
+
+fn write_run_metadata(seeds: &SeedStreams, json: bool) -> Result<(), Box<dyn Error>> {
+    fs::write(RUN_METADATA_FILE, format!("{seeds}\n"))?;
+    say!(json, "Seeds written to {RUN_METADATA_FILE}");
+    Ok(())
+}
+
+// `replay_buffer` makes sure a buffer only comes with one environment
+fn run_training<E: Environment, P: Policy<E> + ?Sized>(
+    policy: &mut P,
+    episodes: usize,
+    num_envs: usize,
+    options: &TrainingOptions,
+    replay: Option<&mut ReplayBuffer<E>>,
+    observer: &mut impl TrainingObserver<E, P>,
+) {
+    match (replay, num_envs) {
+        (Some(buffer), _) => {
+            QLearning::train_with_replay(policy, episodes, options, buffer, observer)
+        }
+        (None, 1) => QLearning::train_observed(policy, episodes, options, observer),
+        (None, _) => QLearning::train_vectorized(policy, num_envs, episodes, options, observer),
+    }
+}
+
+// The buffer for --replay-capacity, with its own stream for the batches
+fn replay_buffer<E: Environment>(
+    train_args: &TrainArgs,
+    seeds: &mut SeedStreams,
+) -> Result<Option<ReplayBuffer<E>>, Box<dyn Error>> {
+    if train_args.replay_capacity == 0 {
+        return Ok(None);
+    }
+    if let Algorithm::Sarsa | Algorithm::QLambda | Algorithm::MonteCarlo = train_args.algorithm {
+        return Err(format!(
+            "{} learns from the moves in the order they were played, --replay-capacity does not \
+             apply",
+            train_args.algorithm.name()
+        )
+        .into());
+    }
+    if train_args.num_envs != 1 {
+        return Err("Replay plays one game at a time, --num-envs does not apply".into());
+    }
+    let mut buffer = ReplayBuffer::new(train_args.replay_capacity, train_args.replay_batch);
+    buffer.reseed(seeds.seed("replay"));
+    Ok(Some(buffer))
+}
+
+// The options and the replay buffer of a self-play run
+type RunSetup = (TrainingOptions, Option<ReplayBuffer<MankallaGame>>);
+
+// What a self-play run sets up on the policy before the first episode. `replay` goes through the
+// same so it starts out exactly like the run it repeats.
+fn prepare_run(
+    policy: &mut EpsilonGreedyPolicy<MankallaGame>,
+    train_args: &TrainArgs,
+    seeds: &mut SeedStreams,
+) -> Result<RunSetup, Box<dyn Error>> {
+    policy.reseed(seeds.seed("trainer"));
+    let options = training_options(train_args, seeds);
+    let replay = replay_buffer(train_args, seeds)?;
+    explore_by_visits(policy, train_args)?;
+    anneal_gamma(policy, train_args)?;
+    add_root_noise(policy, train_args)?;
+    match &train_args.initial_values {
+        Some(InitialValues::Constant(value)) => policy
+            .greedy_policy_mut()
+            .set_initial_value(constant_initial_value::<MankallaGame>(*value)),
+        Some(InitialValues::Capture) => policy
+            .greedy_policy_mut()
+            .set_initial_value(Arc::new(capture_heuristic)),
+        Some(InitialValues::Transfer { stones, path }) => policy
+            .greedy_policy_mut()
+            .set_initial_value(transferred_values(*stones, path, train_args.json)?),
+        None => {}
+    }
+    Ok((options, replay))
+}
+
+// Re-executes a seeded run from the same starting policy and stops at the requested episode.
+// Without --from that is the one `train` would start from, the policy file as the run found it or
+// a new policy if there was none. The run overwrote the file, so it has to be put back first.
+fn replay(train_args: &TrainArgs, seed: u64, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let until_episode = train_args
+        .until_episode
+        .ok_or("--replay-seed requires --until-episode")?;
+    let mut policy = match &train_args.from {
+        Some(path) => load_policy(path, ui.probe)?,
+        None => load_or_new_policy(POLICY_FILE, train_args.hyperparameters)?,
+    };
+    let mut seeds = SeedStreams::new(seed);
+    let (options, mut replay) = prepare_run(&mut policy, train_args, &mut seeds)?;
+
+    let mut last_episode = None;
+    run_training(
+        &mut policy,
+        until_episode,
+        train_args.num_envs,
+        &options,
+        replay.as_mut(),
+        &mut last_episode,
+    );
+
+    say!(
+        train_args.json,
+        "Replayed seed {seed} until episode {}",
+        policy.episode()
+    );
+    say!(train_args.json, "Epsilon:      {}", policy.epsilon());
+    say!(
+        train_args.json,
+        "Q-table size: {}",
+        policy.greedy_policy().qtable_size()
+    );
+    if let Some(stats) = last_episode {
+        say!(
+            train_args.json,
+            "Last episode: {} steps, total reward {}",
+            stats.steps,
+            stats.total_reward
+        );
+    }
+    fs::write(REPLAY_FILE, policy.serialize())?;
+    say!(
+        train_args.json,
+        "Full trainer state written to {REPLAY_FILE}"
+    );
+
+    Ok(())
+}
+
+fn diff_policies(diff_args: &DiffArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let before = load_policy(&diff_args.before, ui.probe)?;
+    let after = load_policy(&diff_args.after, ui.probe)?;
+    print!(
+        "{}",
+        QTableDiff::<MankallaGame>::new(
+            before.greedy_policy().qtable(),
+            after.greedy_policy().qtable(),
+            diff_args.top
+        )
+    );
+    Ok(())
+}
+
+fn index_policy(index_args: &IndexArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let policy = load_policy(&index_args.policy, ui.probe)?;
+    let index = StateIndex::<MankallaGame>::new(policy.greedy_policy().qtable());
+    fs::write(&index_args.out, index.serialize())?;
+    println!("Wrote {} states to {}", index.states(), index_args.out);
+    Ok(())
+}
+
+// Fields are the pits of the player to move first, then the opponent's, as in the policy file
+fn query_index(query_args: &QueryArgs) -> Result<(), Box<dyn Error>> {
+    let input = fs::read_to_string(&query_args.index)
+        .map_err(|e| format!("Could not read {}: {e}", query_args.index))?;
+    let index = StateIndex::<MankallaGame>::deserialize(input.as_str())?;
+    let prefix: Vec<&str> = query_args.prefix.iter().map(String::as_str).collect();
+    let rows = index.with_prefix(&prefix);
+    let best: Vec<f32> = rows
+        .iter()
+        .filter_map(|row| row.best())
+        .map(|(_, v)| v)
+        .collect();
+    println!(
+        "States starting with \"{}\": {} of {}",
+        prefix.join(" "),
+        rows.len(),
+        index.states()
+    );
+    if !best.is_empty() {
+        println!(
+            "Mean best value:   {:.4}",
+            best.iter().sum::<f32>() / best.len() as f32
+        );
+    }
+    for row in rows.iter().take(query_args.top) {
+        match row.best() {
+            Some((pit, value)) => {
+                println!("  [{}] best pit {pit}: {value:.4}", row.state.serialize())
+            }
+            None => println!("  [{}] no values", row.state.serialize()),
+        }
+    }
+    Ok(())
+}
+
+fn similar_positions(similar_args: &SimilarArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let policy = load_policy(&similar_args.policy, ui.probe)?;
+    let position: MankallaGameState = similar_args.position.parse()?;
+    let greedy = policy.greedy_policy();
+    for (view, distance) in
+        similarity::similar_states(greedy.qtable(), &position.into(), similar_args.k)
+    {
+        let best = greedy.choose_action(view, None);
+        println!(
+            "[{}] distance {distance}, best pit {best}: {:.4}",
+            view.serialize(),
+            greedy.value(view, best)
+        );
+    }
+    Ok(())
+}
+
+// Every policy is loaded once before it goes in, a bundle should not ship one that `play` can not
+// read
+fn bundle_policies(bundle_args: &BundleArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let mut bundle = PolicyBundle::new();
+    for entry in &bundle_args.metadata {
+        let (key, value) = entry
+            .split_once('=')
+            .filter(|(key, _)| !key.is_empty() && !key.contains(char::is_whitespace))
+            .ok_or_else(|| format!("--meta expects <key>=<value>, not \"{entry}\""))?;
+        bundle.metadata.push((key.to_owned(), value.to_owned()));
+    }
+    for entry in &bundle_args.policies {
+        let (difficulty, path) = entry
+            .split_once('=')
+            .filter(|(difficulty, _)| {
+                !difficulty.is_empty() && !difficulty.contains(char::is_whitespace)
+            })
+            .ok_or_else(|| format!("Expected <difficulty>=<policy>, not \"{entry}\""))?;
+        if bundle.policy(difficulty).is_some() {
+            return Err(format!("The difficulty \"{difficulty}\" is given twice").into());
+        }
+        let policy = load_policy(path, ui.probe)?;
+        println!(
+            "{difficulty}: {path} (episode {}, {} Q-values)",
+            policy.episode(),
+            policy.greedy_policy().qtable_size()
+        );
+        bundle.add_policy(difficulty, fs::read_to_string(path)?);
+    }
+    fs::write(&bundle_args.out, bundle.serialize())?;
+    println!("Wrote {}", bundle_args.out);
+    Ok(())
+}
+
+// The policy only acts during collection, it is neither improved nor written back. Minimax is
+// the only bot that does not draw from the seed.
+fn collect(collect_args: &CollectArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let collector_seed = seed_streams(collect_args.seed, "Collecting", false).seed("collector");
+    let minimax = MinimaxPolicy::new(MINIMAX_DEPTH, None);
+    let random = SeededRandomPolicy::new(collector_seed);
+    let policy = match collect_args.bot {
+        BotKind::Random | BotKind::Minimax => None,
+        _ => {
+            let mut policy = load_policy(&collect_args.policy, ui.probe)?;
+            policy.reseed(collector_seed);
+            Some(policy)
+        }
+    };
+    let bot: &dyn Policy<MankallaGame> = match (&collect_args.bot, &policy) {
+        (BotKind::EpsilonGreedy, Some(policy)) => policy,
+        (BotKind::Greedy, Some(policy)) => policy.greedy_policy(),
+        (BotKind::Minimax, _) => &minimax,
+        _ => &random,
+    };
+
+    let options = TrainingOptions {
+        max_steps: collect_args.max_steps,
+        ..Default::default()
+    };
+    let mut writer = DatasetWriter::create(&collect_args.out)?;
+    let mut transitions = 0;
+    for first in (0..collect_args.episodes).step_by(DATASET_BATCH_EPISODES) {
+        let episodes: Vec<_> = (first..collect_args.episodes.min(first + DATASET_BATCH_EPISODES))
+            .map(|episode| QLearning::collect_episode(bot, episode, &options).transitions)
+            .collect();
+        transitions += episodes.iter().map(Vec::len).sum::<usize>();
+        writer.write(first, &episodes)?;
+    }
+    writer.finish()?;
+
+    println!(
+        "Wrote {transitions} transitions from {} episodes to {}",
+        collect_args.episodes, collect_args.out
+    );
+    Ok(())
+}
+
+// Where collect writes, CSV unless the file name ends in .parquet
+enum DatasetWriter {
+    Csv(BufWriter<fs::File>),
+    #[cfg(feature = "arrow")]
+    Parquet(Box<arrow::TransitionsWriter>),
+}
+
+impl DatasetWriter {
+    #[cfg(feature = "arrow")]
+    fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        match path.ends_with(".parquet") {
+            true => Ok(DatasetWriter::Parquet(Box::new(
+                arrow::TransitionsWriter::create(path)?,
+            ))),
+            false => DatasetWriter::create_csv(path),
+        }
+    }
+
+    #[cfg(not(feature = "arrow"))]
+    fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        match path.ends_with(".parquet") {
+            true => Err("Parquet output needs a build with the arrow feature".into()),
+            false => DatasetWriter::create_csv(path),
+        }
+    }
+
+    fn create_csv(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "{}", dataset::CSV_HEADER)?;
+        Ok(DatasetWriter::Csv(writer))
+    }
+
+    // The episodes numbered on from `first_episode`
+    fn write(
+        &mut self,
+        first_episode: usize,
+        episodes: &[Vec<Transition<MankallaGame>>],
+    ) -> Result<(), Box<dyn Error>> {
+        let numbered = episodes
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (first_episode + i, e));
+        match self {
+            DatasetWriter::Csv(writer) => {
+                for (episode, transitions) in numbered {
+                    dataset::write_csv(writer, episode, transitions)?;
+                }
+            }
+            #[cfg(feature = "arrow")]
+            DatasetWriter::Parquet(writer) => {
+                let records: Vec<_> = numbered
+                    .flat_map(|(episode, transitions)| dataset::records(episode, transitions))
+                    .collect();
+                writer.write(&records)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self {
+            DatasetWriter::Csv(mut writer) => writer.flush()?,
+            #[cfg(feature = "arrow")]
+            DatasetWriter::Parquet(writer) => writer.close()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn export_policy(export_args: &ExportArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let policy = load_policy(&export_args.policy, ui.probe)?;
+    let batch = arrow::qtable_to_batch::<MankallaGame>(policy.greedy_policy().qtable())?;
+    arrow::write_parquet(&export_args.out, &batch)?;
+    println!("Wrote {} Q-values to {}", batch.num_rows(), export_args.out);
+    Ok(())
+}
+
+#[cfg(not(feature = "arrow"))]
+fn export_policy(_export_args: &ExportArgs, _ui: &Ui) -> Result<(), Box<dyn Error>> {
+    Err("policies export needs a build with the arrow feature".into())
+}
+
+#[derive(Default)]
+struct ClipCounter {
+    steps: usize,
+    clipped: usize,
+}
+
+impl ClipCounter {
+    fn rate(&self) -> f32 {
+        match self.steps {
+            0 => 0f32,
+            steps => self.clipped as f32 / steps as f32,
+        }
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized> TrainingObserver<E, P> for ClipCounter {
+    fn on_episode_end(&mut self, _policy: &P, stats: &EpisodeStats) {
+        self.steps += stats.steps;
+        self.clipped += stats.clipped_steps;
+    }
+}
+
+// Both learners start from the default hyperparameters, so only the update rule differs
+fn arena(arena_args: &ArenaArgs) -> Result<(), Box<dyn Error>> {
+    let mut q_learning = new_policy(Hyperparameters::default())?;
+    let mut sarsa = SarsaPolicy::from_hyperparameters(Hyperparameters::default())?;
+    let mut seeds = seed_streams(arena_args.seed, "Training Q-learning against SARSA", false);
+    q_learning.reseed(seeds.seed("arena/q-learning"));
+    sarsa.reseed(seeds.seed("arena/sarsa"));
 
-    // QLearning::train(&mut policy, 1000, None);
+    train_pair(
+        &mut q_learning,
+        &mut sarsa,
+        arena_args.episodes,
+        &TrainingOptions::default(),
+        &mut ArenaReporter::new("q-learning", arena_args.report_every),
+        &mut ArenaReporter::new("sarsa", arena_args.report_every),
+    );
 
-    game_loop(&mut policy);
+    fs::write(ARENA_Q_LEARNING_FILE, q_learning.serialize())?;
+    fs::write(ARENA_SARSA_FILE, sarsa.serialize())?;
+    println!("Policies written to {ARENA_Q_LEARNING_FILE} and {ARENA_SARSA_FILE}");
+    write_run_metadata(&seeds, false)
+}
 
-    fs::write("policy.csv", policy.serialize())?;
+// Regret of the exploration strategies on fresh bandits, the same policies training uses
+fn compare_bandit_strategies(bandit_args: &BanditArgs) -> Result<(), Box<dyn Error>> {
+    if bandit_args.arms == 0 || bandit_args.runs == 0 {
+        return Err("--arms and --runs have to be at least 1".into());
+    }
+    if bandit_args.arms > bandit::MAX_ARMS {
+        return Err(format!("--arms can be at most {}", bandit::MAX_ARMS).into());
+    }
+    let mut seeds = seed_streams(bandit_args.seed, "Comparing exploration strategies", false);
+    println!(
+        "{}",
+        bandit::compare(
+            bandit_args.arms,
+            bandit_args.steps,
+            bandit_args.runs,
+            seeds.seed("bandit"),
+        )
+    );
+    write_run_metadata(&seeds, false)
+}
 
+// Speaks the engine protocol until "quit" or the end of the input, mistakes are reported as info
+// lines and do not end the session
+fn engine() -> Result<(), Box<dyn Error>> {
+    let mut engine = Engine::new(MINIMAX_DEPTH);
+    for line in io::stdin().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match line.parse::<EngineCommand>() {
+            Ok(command) => match engine.handle(command) {
+                Some(response) => response,
+                None => break,
+            },
+            Err(e) => vec![format!("info string {e}")],
+        };
+        for line in response {
+            println!("{line}");
+        }
+    }
     Ok(())
 }
 
-fn game_loop(policy: &mut impl Policy<MankallaGame>) {
-    let mut turn: usize = 1;
-    let mut state = MankallaGame::new();
-    let mut finished;
+fn perft(perft_args: &PerftArgs) -> Result<(), Box<dyn Error>> {
+    match (&perft_args.game, &perft_args.position) {
+        (Game::Mankalla, position) => {
+            let position = match position {
+                Some(position) => position.parse()?,
+                None => MankallaGame::start(),
+            };
+            print_divide::<MankallaGame>(&position, perft_args.depth);
+        }
+        (Game::ConnectFour, None) => {
+            print_divide::<ConnectFour>(&ConnectFour::start(), perft_args.depth)
+        }
+        (Game::ConnectFour, Some(_)) => {
+            return Err("--position is only read for mankalla".into());
+        }
+        (game, _) => {
+            return Err(
+                format!("perft needs a two-player game, {} is not one", game.name()).into(),
+            );
+        }
+    }
+    Ok(())
+}
 
-    println!("{}", state);
+fn print_divide<G: TwoPlayerGame>(position: &G::Position, depth: usize)
+where
+    G::Move: Display,
+{
+    let started = Instant::now();
+    let divided = perft::divide::<G>(position, depth);
+    let elapsed = started.elapsed();
+    for (game_move, count) in &divided {
+        println!("{game_move}: {count}");
+    }
+    let total = divided.iter().map(|(_, count)| count).sum::<u64>();
+    println!(
+        "{total} continuations of {depth} plies in {:.2?} ({:.0} per second)",
+        elapsed,
+        total as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn default_player_name() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .ok()
+        .filter(|name| profile::valid_name(name))
+        .unwrap_or_else(|| "player".to_owned())
+}
+
+fn player_name(name: String) -> Result<String, String> {
+    match profile::valid_name(&name) {
+        true => Ok(name),
+        false => Err(format!(
+            "Invalid player name \"{name}\", it must not be empty or contain ';'"
+        )),
+    }
+}
+
+// Stones fewer per pit for the second player, negative for the first
+fn pit_handicap(stones: &str) -> Result<i8, Box<dyn Error>> {
+    match stones.parse::<i8>()? {
+        stones if stones.abs() <= Handicap::MAX_PIT_STONES => Ok(stones),
+        _ => Err(format!(
+            "--pit-handicap expects a value from -{max} to {max}, every pit keeps a stone",
+            max = Handicap::MAX_PIT_STONES
+        )
+        .into()),
+    }
+}
+
+fn load_profiles() -> Result<Profiles, Box<dyn Error>> {
+    match fs::read_to_string(PROFILES_FILE) {
+        Ok(s) => Ok(Profiles::deserialize(&s)
+            .map_err(|_| format!("Could not parse the profiles in {PROFILES_FILE}"))?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Profiles::default()),
+        Err(e) => Err(format!("Could not read {PROFILES_FILE}: {e}").into()),
+    }
+}
+
+// The transcript holds the human moves only, so consecutive steps with an extra turn are a chain
+fn highlights(transcript: &Transcript<MankallaGame>) -> GameHighlights {
+    let mut highlights = GameHighlights::default();
+    let mut chain = 0;
+    for step in &transcript.steps {
+        let info = move_info(&step.state, &step.action);
+        highlights.biggest_capture = highlights.biggest_capture.max(info.captured);
+        chain = match info.extra_turn {
+            true => chain + 1,
+            false => 0,
+        };
+        highlights.longest_extra_turn_chain = highlights.longest_extra_turn_chain.max(chain);
+    }
+    highlights
+}
+
+// Returns the achievements the game unlocked
+fn update_profile(
+    name: &str,
+    transcript: &Transcript<MankallaGame>,
+    outcome: Outcome,
+    highlights: &GameHighlights,
+) -> Result<Vec<Achievement>, Box<dyn Error>> {
+    // The human always moves first
+    let Some(opening) = transcript.steps.first() else {
+        return Ok(Vec::new());
+    };
+    let mut profiles = load_profiles()?;
+    let unlocked = profiles.record(name, outcome, opening.action, highlights);
+    fs::write(PROFILES_FILE, profiles.serialize())?;
+    Ok(unlocked)
+}
+
+fn show_stats(stats_args: &StatsArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let profiles = load_profiles()?;
+    let shown: Vec<_> = match &stats_args.name {
+        Some(name) => match profiles.get(name) {
+            Some(p) => vec![(name, p)],
+            None => return Err(format!("No games recorded for \"{name}\"").into()),
+        },
+        None => profiles.iter().collect(),
+    };
+    if stats_args.json {
+        let profiles = shown
+            .iter()
+            .map(|(name, p)| {
+                Json::object([("name", name.as_str().into()), ("profile", p.to_json())])
+            })
+            .collect();
+        println!("{}", Json::object([("profiles", Json::Array(profiles))]));
+        return Ok(());
+    }
+    if shown.is_empty() {
+        println!("No games recorded yet, play one first");
+    }
+    for (name, p) in shown {
+        let streak = match p.streak {
+            0 => "none".to_owned(),
+            n if n > 0 => format!("{n} won"),
+            n => format!("{} lost", -n),
+        };
+        let openings = p
+            .favorite_openings(FAVORITE_OPENINGS)
+            .iter()
+            .map(|(action, n)| format!("{} ({n}x)", ui.input_scheme.label(*action)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("{name}");
+        println!(
+            "  games {}, wins {}, losses {}, draws {}, win rate {:.1}%",
+            p.games,
+            p.wins,
+            p.losses,
+            p.draws,
+            p.win_rate() * 100f32
+        );
+        println!(
+            "  current streak {streak}, longest winning streak {}",
+            p.longest_win_streak
+        );
+        println!("  favorite openings {openings}");
+        if !p.achievements.is_empty() {
+            println!(
+                "  achievements {}",
+                p.achievements
+                    .iter()
+                    .map(Achievement::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+// Best move and value for every position, from the Q-table or a minimax search. Finished games
+// have neither. Values are from the view of the player to move.
+fn inspect(inspect_args: &InspectArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let positions: Vec<MankallaGameState> = match (&inspect_args.batch, &inspect_args.position) {
+        (Some(path), _) => {
+            let text =
+                fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+            text.lines()
+                .enumerate()
+                .map(|(index, line)| (index + 1, line.trim()))
+                .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+                .map(|(number, line)| {
+                    line.parse()
+                        .map_err(|e| format!("{path}, line {number}: {e}"))
+                })
+                .collect::<Result<_, _>>()?
+        }
+        (None, Some(position)) => vec![position.parse()?],
+        (None, None) => unreachable!("parse_args asks for a position or a batch"),
+    };
+    let policy = match inspect_args.bot {
+        BotKind::Greedy => Some(load_policy(&inspect_args.policy, ui.probe)?),
+        BotKind::Minimax => None,
+        _ => return Err("inspect knows the greedy and the minimax bot".into()),
+    };
+    let minimax = MinimaxPolicy::<MankallaGame>::new(inspect_args.depth, None);
+
+    let json = inspect_args.json;
+    let mut inspected = Vec::new();
+    for position in positions {
+        let view = position.into();
+        let best = match (MankallaGame::outcome(&position), &policy) {
+            (Some(_), _) => None,
+            (None, Some(policy)) => {
+                let greedy =
+                    SimilarStatesFallback::new(policy.greedy_policy(), inspect_args.neighbors);
+                let best = greedy.choose_action(view, None);
+                let values = greedy
+                    .action_values(view)
+                    .expect("A table values every move");
+                let (_, value) = *values
+                    .iter()
+                    .find(|(pit, _)| *pit == best)
+                    .expect("The best move is a legal one");
+                Some((best, value, Some(values)))
+            }
+            (None, None) => {
+                let (best, stats) = minimax.search(view, None);
+                Some((best, stats.score, None))
+            }
+        };
+        match (json, &best) {
+            (true, _) => {
+                let (best_move, value, values) = match best {
+                    Some((best, value, values)) => (Some(best), Some(value), values),
+                    None => (None, None, None),
+                };
+                let values = values.map(|values| {
+                    Json::Array(
+                        values
+                            .into_iter()
+                            .map(|(pit, value)| {
+                                Json::object([("pit", pit.into()), ("value", value.into())])
+                            })
+                            .collect(),
+                    )
+                });
+                inspected.push(Json::object([
+                    ("position", position.to_json()),
+                    ("best_move", best_move.into()),
+                    ("value", value.into()),
+                    ("action_values", values.unwrap_or(Json::Null)),
+                ]));
+            }
+            (false, Some((best, value, _))) => println!("{position:#}\t{best}\t{value}"),
+            (false, None) => println!("{position:#}\t-\t-"),
+        }
+    }
+    if json {
+        println!("{}", Json::object([("positions", Json::Array(inspected))]));
+    }
+    Ok(())
+}
+
+// 0 for never
+fn every(episodes: usize) -> Option<usize> {
+    match episodes {
+        0 => None,
+        n => Some(n),
+    }
+}
+
+fn traind(traind_args: &TraindArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let mut policy = load_or_new_policy(POLICY_FILE, Hyperparameters::default())?;
+    probe_if_loaded(POLICY_FILE, &policy, ui.probe);
+    let mut seeds = seed_streams(traind_args.seed, "Training", false);
+    policy.reseed(seeds.seed("trainer"));
+
+    let port = traind_args.port;
+    let listener = TcpListener::bind((traind_args.bind, port))
+        .map_err(|e| format!("Could not listen on {}:{port}: {e}", traind_args.bind))?;
+    let address = listener.local_addr()?;
+    if !address.ip().is_loopback() {
+        eprintln!(
+            "Warning: the control port is open to the network on {address}, requests and auth \
+             tokens travel in plaintext"
+        );
+    }
+    let metrics = Arc::new(ServingMetrics::default());
+    let users = match &traind_args.tokens {
+        Some(path) => {
+            let input =
+                fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+            let tokens = Tokens::deserialize(&input)
+                .map_err(|e| format!("Could not parse the tokens in {path}: {e}"))?;
+            let accounts = Accounts::open(SERVER_GAMES_FILE, PROFILES_FILE)?;
+            println!(
+                "{} users, {} games going on in {SERVER_GAMES_FILE}",
+                tokens.len(),
+                accounts.len()
+            );
+            Some(Arc::new(Users {
+                tokens,
+                accounts: Mutex::new(accounts),
+            }))
+        }
+        None => None,
+    };
+    let (sender, requests) = mpsc::channel();
+    let serving = SnapshotPublisher::new();
+    let server = daemon::serve(listener, sender, serving.reader(), metrics.clone(), users)?;
+    if let Some(metrics_port) = traind_args.metrics_port {
+        let metrics_listener =
+            TcpListener::bind((traind_args.bind, metrics_port)).map_err(|e| {
+                format!(
+                    "Could not listen on {}:{metrics_port}: {e}",
+                    traind_args.bind
+                )
+            })?;
+        let metrics_address = metrics_listener.local_addr()?;
+        let readiness = server.readiness();
+        prometheus::serve_metrics(metrics_listener, metrics.clone(), move || readiness.check());
+        println!("Metrics on http://{metrics_address}/metrics, probes on /healthz and /readyz");
+    }
+    // A service manager's stop goes through the same checkpoint and drain as a stop request
+    signals::catch_termination()
+        .map_err(|e| format!("Could not catch termination signals: {e}"))?;
+    let mut observer = DaemonObserver::new(
+        requests,
+        POLICY_FILE.to_owned(),
+        traind_args.checkpoint_every,
+        traind_args.swap_every,
+        serving,
+        metrics,
+    );
+    observer.swap(&policy);
+    println!(
+        "Training from episode {} until stopped, control on {address} one request per \
+         line: status, checkpoint, set <name> <value>, swap, move <position>, stop, health, ready, \
+         auth <token>, newgame, play <pit>, game, stats",
+        policy.episode()
+    );
+
+    run_training(
+        &mut policy,
+        usize::MAX,
+        1,
+        &TrainingOptions::default(),
+        None,
+        &mut observer,
+    );
+    if let Some(failure) = observer.failure.take() {
+        return Err(failure.into());
+    }
+    if signals::termination_requested() {
+        println!("Terminated, stopping like on a stop request");
+    }
+    observer
+        .checkpoint(&policy)
+        .map_err(|e| format!("Could not write {POLICY_FILE}: {e}"))?;
+    // Requests still waiting for the trainer get their error once it is gone
+    drop(observer);
+    server.shutdown();
+    println!(
+        "Stopped at episode {}, the policy is in {POLICY_FILE}",
+        policy.episode()
+    );
+    write_run_metadata(&seeds, false)
+}
+
+// Value gaps below this are a close call, above the other one an obvious choice
+const CLOSE_CALL: f32 = 0.25;
+const OBVIOUS_CHOICE: f32 = 2.;
+
+// Bot against bot at reading speed, with a comment on every move
+fn watch(watch_args: &WatchArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let mut seeds = seed_streams(watch_args.seed, "Watching", false);
+    let load = |spec: &str| match spec {
+        "random" => Ok(None),
+        path => load_policy(path, ui.probe).map(Some),
+    };
+    let policies = [load(&watch_args.first)?, load(&watch_args.second)?];
+    let randoms = [
+        SeededRandomPolicy::new(seeds.seed("first")),
+        SeededRandomPolicy::new(seeds.seed("second")),
+    ];
+    let names = ["Bot A", "Bot B"];
+    let handicap = watch_args.handicap;
+
+    let mut state = handicap.start();
+    let mut turn = 1;
+    println!("{state}");
+    let outcome = loop {
+        let side = match state.get_player_to_move() {
+            Player::Player1 => 0,
+            Player::Player2 => 1,
+        };
+        let label = |action: u8| match side {
+            0 => ui.input_scheme.label(action),
+            _ => ui.input_scheme.opponent_label(action),
+        };
+        let values = policies[side].as_ref().map(|p| p.greedy_policy());
+        let bot: &dyn Policy<MankallaGame> = match values {
+            Some(values) => values,
+            None => &randoms[side],
+        };
+        let relevant_state = state.into();
+        let action = bot.choose_action(relevant_state, None);
+        let analysis = commentary::analyze(&relevant_state, action, values);
+
+        thread::sleep(watch_args.delay);
+        println!("\nTurn {turn}, {} plays {}", names[side], label(action));
+        for line in comment(names[side], &analysis, label) {
+            println!("  {line}");
+        }
+        let outcome;
+        (state, _, outcome) = two_player::play::<MankallaGame>(&state, &action);
+        println!("{state}");
+        turn += 1;
+        if outcome.is_some() {
+            break handicap.outcome(&state, Player::Player1);
+        }
+    };
+
+    let (a, b) = (
+        handicap.score(&state, Player::Player1),
+        handicap.score(&state, Player::Player2),
+    );
+    match outcome {
+        Some(Outcome::Win) => println!("\n{} wins {a}:{b}", names[0]),
+        Some(Outcome::Loss) => println!("\n{} wins {b}:{a}", names[1]),
+        _ => println!("\nDraw, {a}:{b}"),
+    }
+    write_run_metadata(&seeds, false)
+}
+
+fn comment(name: &str, analysis: &MoveAnalysis, label: impl Fn(u8) -> String) -> Vec<String> {
+    let mut lines = Vec::new();
+    match analysis.runner_up {
+        Some(r) if r.gap < CLOSE_CALL => lines.push(format!(
+            "A close call, {} looked just as good",
+            label(r.action)
+        )),
+        Some(r) if r.gap > OBVIOUS_CHOICE => lines.push(format!(
+            "The obvious choice, {:.1} ahead of {}",
+            r.gap,
+            label(r.action)
+        )),
+        _ => {}
+    }
+    if analysis.info.captured > 0 {
+        lines.push(format!("{name} steals {} stones!", analysis.info.captured));
+    }
+    if analysis.info.extra_turn {
+        lines.push(format!(
+            "The last stone lands in the store, {name} moves again"
+        ));
+    }
+    if analysis.steal_left_open > 0 && analysis.info.captured == 0 {
+        lines.push(format!(
+            "That leaves {} stones open to a steal",
+            analysis.steal_left_open
+        ));
+    }
+    lines
+}
+
+fn evaluate(evaluate_args: &EvaluateArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let policy = load_policy(&evaluate_args.policy, ui.probe)?;
+    let mut seeds = seed_streams(evaluate_args.seed, "Evaluating", evaluate_args.json);
+    let opponent_policy = match evaluate_args.opponent.as_str() {
+        "random" | "minimax" => None,
+        path => Some(load_policy(path, ui.probe)?),
+    };
+    let random = SeededRandomPolicy::new(seeds.seed("random-opponent"));
+    let minimax = MinimaxPolicy::new(MINIMAX_DEPTH, None);
+    let opponent: &dyn Policy<MankallaGame> =
+        match (&opponent_policy, evaluate_args.opponent.as_str()) {
+            (Some(opponent), _) => opponent.greedy_policy(),
+            (None, "minimax") => &minimax,
+            (None, _) => &random,
+        };
+
+    let temperature = match evaluate_args.temperature_plies {
+        0 => None,
+        plies => Some(Temperature::new(plies, evaluate_args.temperature)),
+    };
+    let book = match (&evaluate_args.book, evaluate_args.book_plies) {
+        (Some(_), Some(_)) => return Err("--book and --book-plies do not go together".into()),
+        (Some(path), None) => {
+            let text =
+                fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+            Some(evaluation::parse_opening_book(&text)?)
+        }
+        (None, Some(plies)) => Some(evaluation::all_openings(plies)),
+        (None, None) => None,
+    };
+    let mut rng = seeds.rng("openings");
+    let report = match &book {
+        Some(openings) => {
+            say!(
+                evaluate_args.json,
+                "{} book openings, each played from both seats",
+                openings.len()
+            );
+            evaluation::evaluate_openings(
+                policy.greedy_policy(),
+                opponent,
+                openings,
+                temperature,
+                &mut rng,
+            )
+        }
+        None => evaluation::evaluate_paired(
+            policy.greedy_policy(),
+            opponent,
+            evaluate_args.pairs,
+            evaluate_args.opening_plies,
+            temperature,
+            &mut rng,
+        ),
+    };
+    match evaluate_args.json {
+        true => println!(
+            "{}",
+            Json::object([
+                ("seed", seeds.master().into()),
+                ("policy", evaluate_args.policy.as_str().into()),
+                ("opponent", evaluate_args.opponent.as_str().into()),
+                ("book_openings", book.as_ref().map(Vec::len).into()),
+                ("evaluation", report.to_json()),
+            ])
+        ),
+        false => println!("{report}"),
+    }
+    write_run_metadata(&seeds, evaluate_args.json)
+}
+
+// The same player in both seats under every rule set asked for, to see how much the first move
+// is worth before choosing how to evaluate or whether to hand the second player stones
+fn audit_fairness(fairness_args: &FairnessArgs) -> Result<(), Box<dyn Error>> {
+    let mut seeds = seed_streams(fairness_args.seed, "Auditing", fairness_args.json);
+    let mut variants = Vec::new();
+    for &stones in &fairness_args.stones {
+        let mut rng = seeds.rng(&format!("stones{stones}"));
+        let report = match stones {
+            3 => audit_variant::<MankallaWithStones<3>>(fairness_args, stones, &mut rng)?,
+            4 => audit_variant::<MankallaWithStones<4>>(fairness_args, stones, &mut rng)?,
+            5 => audit_variant::<MankallaWithStones<5>>(fairness_args, stones, &mut rng)?,
+            _ => audit_variant::<MankallaGame>(fairness_args, stones, &mut rng)?,
+        };
+        let Some(report) = report else {
+            continue;
+        };
+        match fairness_args.json {
+            true => variants.push(Json::object([
+                ("stones", (stones as usize).into()),
+                ("report", report.to_json()),
+            ])),
+            false => println!("Kalah(6,{stones}): {report}"),
+        }
+    }
+    if fairness_args.json {
+        println!(
+            "{}",
+            Json::object([
+                ("seed", seeds.master().into()),
+                ("player", fairness_args.player.name().into()),
+                ("opening_plies", fairness_args.opening_plies.into()),
+                ("variants", Json::Array(variants)),
+            ])
+        );
+    }
+    write_run_metadata(&seeds, fairness_args.json)
+}
+
+// `None` when the player is a policy and this rule set has none trained
+fn audit_variant<G>(
+    fairness_args: &FairnessArgs,
+    stones: u8,
+    rng: &mut StdRng,
+) -> Result<Option<FairnessReport>, Box<dyn Error>>
+where
+    G: TwoPlayerGame + Environment<ActionRelevantState = G::View, Action = G::Move> + 'static,
+    G::View: Send,
+    G::Move: Send,
+{
+    let (games, opening_plies) = (fairness_args.games, fairness_args.opening_plies);
+    let report = match fairness_args.player {
+        FairnessPlayer::Minimax => {
+            let minimax = MinimaxPolicy::<G>::new(fairness_args.depth, None);
+            fairness::audit::<G>(&minimax, games, opening_plies, rng)
+        }
+        FairnessPlayer::Random => {
+            let random = SeededRandomPolicy::new(rng.random());
+            fairness::audit::<G>(&random, games, opening_plies, rng)
+        }
+        FairnessPlayer::Policy => {
+            let (path, train) = match stones {
+                STONES_PER_PIT => (POLICY_FILE.to_owned(), "train".to_owned()),
+                stones => (
+                    format!("stones{stones}-{POLICY_FILE}"),
+                    format!("train --stones {stones}"),
+                ),
+            };
+            let Ok(input) = fs::read_to_string(&path) else {
+                say!(
+                    fairness_args.json,
+                    "Kalah(6,{stones}): no {path}, {train} writes one"
+                );
+                return Ok(None);
+            };
+            let policy = EpsilonGreedyPolicy::<G>::deserialize(input.as_str())?;
+            fairness::audit::<G>(policy.greedy_policy(), games, opening_plies, rng)
+        }
+    };
+    Ok(Some(report))
+}
+
+// Records one training episode and lets the user step through it, the policy file is left untouched
+fn debug_episode(debug_args: &DebugArgs, ui: &Ui) -> Result<(), Box<dyn Error>> {
+    let mut policy = match fs::exists(&debug_args.policy)? {
+        true => load_policy(&debug_args.policy, ui.probe)?,
+        false => new_policy(Hyperparameters::default())?,
+    };
+    let mut seeds = seed_streams(debug_args.seed, "Debugging an episode", false);
+    policy.reseed(seeds.seed("trainer"));
+    let steps = debugger::record_episode(&mut policy, &TrainingOptions::default());
 
     let stdin = io::stdin();
+    let mut current = 0;
+    loop {
+        print_debug_step(&steps, current, ui);
+        println!("[n]ext, [b]ack, [f]irst, [l]ast, <step number>, [q]uit");
+        let mut input = String::new();
+        if stdin.read_line(&mut input)? == 0 {
+            return Ok(());
+        }
+        current = match input.trim() {
+            "" | "n" => (current + 1).min(steps.len() - 1),
+            "b" => current.saturating_sub(1),
+            "f" => 0,
+            "l" => steps.len() - 1,
+            "q" => return Ok(()),
+            number => match number.parse::<usize>() {
+                Ok(step) if (1..=steps.len()).contains(&step) => step - 1,
+                _ => current,
+            },
+        };
+    }
+}
+
+fn print_debug_step(steps: &[DebugStep<MankallaGame>], current: usize, ui: &Ui) {
+    let step = &steps[current];
+    let label = |action: u8| match step.state.get_player_to_move() {
+        Player::Player1 => ui.input_scheme.label(action),
+        Player::Player2 => ui.input_scheme.opponent_label(action),
+    };
+    println!(
+        "\nStep {}/{}, {:?} to move",
+        current + 1,
+        steps.len(),
+        step.state.get_player_to_move()
+    );
+    println!("{}", step.state);
+    let q_values: Vec<String> = step
+        .q_values
+        .iter()
+        .map(|&(a, q)| {
+            let marker = match (a == step.action, a == step.greedy_action) {
+                (true, _) => "*",
+                (false, true) => "g",
+                (false, false) => " ",
+            };
+            format!("{}{marker} {q:+.3}", label(a))
+        })
+        .collect();
+    println!("Q-values     {}", q_values.join("  "));
+    println!(
+        "Action       {} ({}), reward {:+.2}{}",
+        label(step.action),
+        match step.explored() {
+            true => "explored",
+            false => "greedy",
+        },
+        step.reward,
+        step.outcome
+            .map(|o| format!(", game over: {o:?}"))
+            .unwrap_or_default()
+    );
+    println!(
+        "TD update    Q {:+.3} -> {:+.3}, target {:+.3}, error {:+.3}",
+        step.old_value,
+        step.new_value,
+        step.target,
+        step.td_error()
+    );
+    println!("{}", step.next_state);
+}
+
+// The best member of the last round ends up in the policy file
+fn pbt(pbt_args: &PbtArgs) -> Result<(), Box<dyn Error>> {
+    if pbt_args.options.population < 2 {
+        return Err("--population needs at least two members".into());
+    }
+    warn_about(pbt_args.hyperparameters)?;
+    let mut seeds = seed_streams(pbt_args.seed, "Population based training", false);
+
+    let mut trainer =
+        PopulationTrainer::new(pbt_args.hyperparameters, pbt_args.options, &mut seeds);
+    for _ in 0..pbt_args.rounds {
+        println!("{}", trainer.round());
+    }
+
+    let best = trainer.best();
+    fs::write(POLICY_FILE, best.policy.serialize())?;
+    println!(
+        "Member #{} wrote its policy to {POLICY_FILE} (score {:.1}%)",
+        best.id,
+        best.score * 100f32
+    );
+    write_run_metadata(&seeds, false)
+}
+
+fn ablate(ablate_args: &AblateArgs) -> Result<(), Box<dyn Error>> {
+    let path = &ablate_args.config;
+    let input = fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+    let variants = ablation::parse_variants(&input, Hyperparameters::default(), 0.01)
+        .map_err(|e| format!("{path}: {e}"))?;
+    if ablate_args.options.episodes == 0 {
+        return Err("--episodes expects at least one episode".into());
+    }
+    for variant in &variants {
+        warn_about(variant.hyperparameters)?;
+    }
+    let mut seeds = seed_streams(ablate_args.seed, "Ablation", false);
+    let (policy_seed, evaluation_seed) = (
+        seeds.seed("ablation/policy"),
+        seeds.seed("ablation/evaluator"),
+    );
+
+    let mut results = Vec::new();
+    for variant in &variants {
+        let result =
+            ablation::run_variant(variant, &ablate_args.options, policy_seed, evaluation_seed)?;
+        println!(
+            "Trained {} in {:.1}s",
+            variant.name,
+            result.elapsed.as_secs_f32()
+        );
+        results.push(result);
+    }
+    let report = AblationReport {
+        options: ablate_args.options,
+        seed: seeds.master(),
+        results,
+    };
+    print!("{report}");
+    fs::write(ABLATION_REPORT_FILE, report.to_markdown())?;
+    println!("Report written to {ABLATION_REPORT_FILE}");
+    write_run_metadata(&seeds, false)
+}
+
+// One metric stream per learner, summarizing the last `report_every` episodes from its side
+struct ArenaReporter {
+    name: &'static str,
+    report_every: usize,
+    wins: usize,
+    draws: usize,
+    episodes: usize,
+    total_reward: f32,
+}
+
+impl ArenaReporter {
+    fn new(name: &'static str, report_every: usize) -> Self {
+        ArenaReporter {
+            name,
+            report_every,
+            wins: 0,
+            draws: 0,
+            episodes: 0,
+            total_reward: 0f32,
+        }
+    }
+}
+
+impl<P: Policy<MankallaGame>> TrainingObserver<MankallaGame, P> for ArenaReporter {
+    fn on_episode_end(&mut self, _policy: &P, stats: &EpisodeStats) {
+        self.episodes += 1;
+        self.total_reward += stats.total_reward;
+        match stats.outcome {
+            Some(Outcome::Win) => self.wins += 1,
+            Some(Outcome::Draw) => self.draws += 1,
+            _ => {}
+        }
+        if self.report_every > 0 && (stats.episode + 1).is_multiple_of(self.report_every) {
+            println!(
+                "[{:<10}] episodes {:>6}-{:<6} {:>5.1}% won, {:>5.1}% drawn, mean reward {:.2}",
+                self.name,
+                stats.episode + 2 - self.episodes,
+                stats.episode + 1,
+                self.wins as f32 / self.episodes as f32 * 100f32,
+                self.draws as f32 / self.episodes as f32 * 100f32,
+                self.total_reward / self.episodes as f32
+            );
+            *self = ArenaReporter::new(self.name, self.report_every);
+        }
+    }
+}
+
+// Evaluates against random play every `eval_every` episodes and reheats exploration on a plateau
+struct ReheatObserver {
+    controller: Option<(PlateauDetector, ReheatOptions)>,
+    eval_every: usize,
+    opponent: SeededRandomPolicy,
+    pending: bool,
+    reheats: usize,
+}
+
+impl ReheatObserver {
+    fn new(options: Option<ReheatOptions>, eval_every: usize, evaluator_seed: u64) -> Self {
+        ReheatObserver {
+            controller: options.map(|o| (PlateauDetector::new(o.patience, o.min_improvement), o)),
+            eval_every,
+            opponent: SeededRandomPolicy::new(evaluator_seed),
+            pending: false,
+            reheats: 0,
+        }
+    }
+}
+
+impl TrainingObserver<MankallaGame, EpsilonGreedyPolicy<MankallaGame>> for ReheatObserver {
+    fn on_episode_end(&mut self, policy: &EpsilonGreedyPolicy<MankallaGame>, stats: &EpisodeStats) {
+        if let Some((detector, _)) = &mut self.controller
+            && self.eval_every > 0
+            && (stats.episode + 1).is_multiple_of(self.eval_every)
+        {
+            let report = evaluation::evaluate(policy.greedy_policy(), &self.opponent, EVAL_GAMES);
+            self.pending = detector.observe(report.win_rate());
+        }
+    }
+
+    fn adjust_policy(&mut self, policy: &mut EpsilonGreedyPolicy<MankallaGame>) {
+        if let Some((_, options)) = &self.controller
+            && std::mem::take(&mut self.pending)
+        {
+            policy.reheat(options.boost, options.duration);
+            self.reheats += 1;
+        }
+    }
+}
+
+// Keeps the policy in the run every `every` episodes, so a crash or a killed run loses at most that
+// many. Training goes on after a failed write, `finish` reports the first one.
+struct Checkpoints {
+    experiment: Experiment,
+    every: Option<usize>,
+    error: Option<io::Error>,
+}
+
+impl Checkpoints {
+    fn new(experiment: &Experiment, every: Option<usize>) -> Self {
+        Checkpoints {
+            experiment: experiment.clone(),
+            every,
+            error: None,
+        }
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error>> {
+        match self.error {
+            Some(e) => Err(format!("Could not write a checkpoint: {e}").into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E: Environment, P: Policy<E> + Serialize> TrainingObserver<E, P> for Checkpoints {
+    fn on_episode_end(&mut self, policy: &P, stats: &EpisodeStats) {
+        if let Some(every) = self.every
+            && self.error.is_none()
+            && (stats.episode + 1).is_multiple_of(every)
+        {
+            self.error = self
+                .experiment
+                .checkpoint(stats.episode + 1, &policy.serialize())
+                .err();
+        }
+    }
+}
+
+// Publishes a snapshot of the greedy policy every `eval_every` episodes, the dashboard thread
+// evaluates those so training does not wait for it
+struct WatchObserver {
+    sender: Sender<DashboardUpdate>,
+    eval_every: usize,
+    snapshots: SnapshotPublisher<GreedyPolicy<MankallaGame>>,
+    openings: OpeningDiversity<MankallaGame>,
+}
+
+impl TrainingObserver<MankallaGame, EpsilonGreedyPolicy<MankallaGame>> for WatchObserver {
+    fn on_step(&mut self, env: usize, transition: &Transition<MankallaGame>) {
+        TrainingObserver::<_, EpsilonGreedyPolicy<_>>::on_step(&mut self.openings, env, transition);
+    }
+
+    fn on_episode_end(&mut self, policy: &EpsilonGreedyPolicy<MankallaGame>, stats: &EpisodeStats) {
+        // The receiving end only disappears when the dashboard is gone, training goes on regardless
+        let _ = self.sender.send(DashboardUpdate::Episode {
+            episode: stats.episode,
+            epsilon: policy.epsilon(),
+            qtable_size: policy.greedy_policy().qtable_size(),
+        });
+        if self.eval_every > 0 && (stats.episode + 1).is_multiple_of(self.eval_every) {
+            self.snapshots
+                .publish(stats.episode, policy.greedy_policy().clone());
+            let _ = self.sender.send(DashboardUpdate::Openings {
+                entropy: self.openings.entropy(),
+                distinct_lines: self.openings.distinct_lines(),
+            });
+        }
+    }
+}
+
+// The extra observer runs on the training thread and is handed back once training is done
+fn train_watched<O>(
+    mut policy: EpsilonGreedyPolicy<MankallaGame>,
+    train_args: &TrainArgs,
+    options: TrainingOptions,
+    mut replay: Option<ReplayBuffer<MankallaGame>>,
+    evaluator_seed: u64,
+    observer: O,
+) -> Result<(EpsilonGreedyPolicy<MankallaGame>, O), Box<dyn Error>>
+where
+    O: TrainingObserver<MankallaGame, EpsilonGreedyPolicy<MankallaGame>> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let snapshots = SnapshotPublisher::new();
+    let reader = snapshots.reader();
+    let mut observer = (
+        WatchObserver {
+            sender,
+            eval_every: train_args.eval_every,
+            snapshots,
+            openings: OpeningDiversity::new(OPENING_DEPTH, OPENING_WINDOW),
+        },
+        observer,
+    );
+    let episodes = train_args.episodes;
+    let num_envs = train_args.num_envs;
+    let trainer = thread::spawn(move || {
+        run_training(
+            &mut policy,
+            episodes,
+            num_envs,
+            &options,
+            replay.as_mut(),
+            &mut observer,
+        );
+        (policy, observer.1)
+    });
+
+    let opponent = SeededRandomPolicy::new(evaluator_seed);
+    let mut evaluated = None;
+    let mut dashboard = TrainingDashboard::new(episodes);
+    loop {
+        let finished = match receiver.recv_timeout(Duration::from_millis(200)) {
+            Ok(update) => {
+                dashboard.update(update);
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+        for update in receiver.try_iter() {
+            dashboard.update(update);
+        }
+        // Snapshots published while the last one was evaluated are skipped, only the newest counts
+        if let Some(snapshot) = reader.latest()
+            && evaluated != Some(snapshot.episode)
+        {
+            let report = evaluation::evaluate(&snapshot.value, &opponent, EVAL_GAMES);
+            dashboard.update(DashboardUpdate::Evaluation {
+                episode: snapshot.episode,
+                win_rate: report.win_rate(),
+                draw_rate: report.draw_rate(),
+            });
+            evaluated = Some(snapshot.episode);
+        }
+        println!("\x1b[2J\x1b[H{}", dashboard);
+        if finished {
+            break;
+        }
+    }
+
+    trainer
+        .join()
+        .map_err(|_| "The training thread panicked".into())
+}
+
+// Where the bot of `play` has its policy from
+enum PolicySource {
+    File(String),
+    Bundle { path: String, difficulty: String },
+}
+
+impl PolicySource {
+    fn path(&self) -> &str {
+        match self {
+            PolicySource::File(path) | PolicySource::Bundle { path, .. } => path,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            PolicySource::File(path) => path.clone(),
+            PolicySource::Bundle { path, difficulty } => format!("{path} ({difficulty})"),
+        }
+    }
+
+    fn load(&self, probe: bool) -> Result<EpsilonGreedyPolicy<MankallaGame>, Box<dyn Error>> {
+        match self {
+            PolicySource::File(path) => load_policy(path, probe),
+            PolicySource::Bundle { path, difficulty } => {
+                bundled_policy(&load_bundle(path)?, difficulty)
+            }
+        }
+    }
+}
+
+// The bot of `play` with all it can be. The policy file is read again when it was written during
+// the game, e.g. by a training run next to it, or when asked to. Either happens between moves only.
+struct Bot {
+    kind: BotKind,
+    policy: EpsilonGreedyPolicy<MankallaGame>,
+    source: PolicySource,
+    random: RandomPolicy,
+    minimax: MinimaxPolicy<MankallaGame>,
+    watcher: FileWatcher,
+    fallback: Option<Fallback>,
+}
+
+impl Bot {
+    fn policy(&mut self) -> &mut dyn Policy<MankallaGame> {
+        match self.kind {
+            BotKind::EpsilonGreedy => &mut self.policy,
+            BotKind::Greedy => self.policy.greedy_policy_mut(),
+            BotKind::Random => &mut self.random,
+            BotKind::Minimax => &mut self.minimax,
+        }
+    }
+
+    // The new policy replaces the old one only once it has been read completely. A file that does
+    // not load keeps the old one until the file is written again.
+    fn reload(&mut self, ui: &Ui) {
+        self.watcher.mark_read();
+        let message = match self.source.load(ui.probe) {
+            Ok(policy) => {
+                self.policy = policy;
+                Message::PolicyReloaded {
+                    path: self.source.name(),
+                    episode: self.policy.episode(),
+                }
+            }
+            Err(e) => Message::PolicyNotReloaded {
+                path: self.source.name(),
+                error: e.to_string(),
+            },
+        };
+        println!("{}", ui.catalog.get(message));
+    }
+
+    fn reload_if_changed(&mut self, ui: &Ui) {
+        if self.watcher.changed() {
+            self.reload(ui);
+        }
+    }
+}
 
-    let action;
-    match get_player_input(&stdin) {
-        PlayerRequest::Action(a) => {
-            action = a;
+// The human's decisions for off-policy evaluation and how the game ended for them
+type FinishedGame = (Transcript<MankallaGame>, Outcome);
+
+// No bot if the human quit before a game, while picking the difficulty
+fn play(
+    play_args: &PlayArgs,
+    input: &mut WordReader<impl BufRead>,
+    script: Option<&str>,
+    ui: &Ui,
+) -> Result<(Option<Bot>, Option<FinishedGame>), Box<dyn Error>> {
+    let (source, policy) = match &play_args.bundle {
+        Some(path) => {
+            let bundle = load_bundle(path)?;
+            let difficulty = match &play_args.difficulty {
+                Some(difficulty) => difficulty.clone(),
+                None => match pick_difficulty(&bundle, input, script, ui)? {
+                    Some(difficulty) => difficulty,
+                    None => {
+                        println!("{}", ui.catalog.get(Message::Goodbye));
+                        return Ok((None, None));
+                    }
+                },
+            };
+            let policy = bundled_policy(&bundle, &difficulty)?;
+            let source = PolicySource::Bundle {
+                path: path.clone(),
+                difficulty,
+            };
+            (source, policy)
         }
-        PlayerRequest::Quit => {
-            println!("Ok, goodbye");
-            return;
+        None => {
+            let policy = load_or_new_policy(POLICY_FILE, Hyperparameters::default())?;
+            probe_if_loaded(POLICY_FILE, &policy, ui.probe);
+            (PolicySource::File(POLICY_FILE.to_owned()), policy)
         }
     };
+    let mut bot = Bot {
+        kind: play_args.bot,
+        policy,
+        watcher: FileWatcher::new(source.path()),
+        source,
+        random: RandomPolicy,
+        minimax: MinimaxPolicy::new(play_args.depth, Some(MINIMAX_THINK_TIME)),
+        fallback: play_args.fallback,
+    };
+    let game = game_loop(
+        &mut bot,
+        input,
+        script,
+        play_args.clock,
+        play_args.handicap,
+        ui,
+    )?;
+    Ok((Some(bot), game))
+}
+
+// Words are read until one names a difficulty of the bundle, from a script the first one has to.
+// Returns nothing if the human quit instead.
+fn pick_difficulty(
+    bundle: &PolicyBundle,
+    input: &mut WordReader<impl BufRead>,
+    script: Option<&str>,
+    ui: &Ui,
+) -> Result<Option<String>, Box<dyn Error>> {
+    if script.is_none() {
+        for (key, value) in &bundle.metadata {
+            println!("{key}: {value}");
+        }
+        println!(
+            "{}",
+            ui.catalog.get(Message::ChooseDifficulty {
+                options: bundle.difficulties().collect::<Vec<_>>().join(",")
+            })
+        );
+    }
+    loop {
+        let word = match input.next_word() {
+            Ok(Some(word)) => word,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(format!("Could not read the input: {e}").into()),
+        };
+        if word == "q" {
+            return Ok(None);
+        }
+        if bundle.policy(&word).is_some() {
+            return Ok(Some(word));
+        }
+        let complaint = ui.catalog.get(Message::UnknownDifficulty { input: word });
+        match script {
+            Some(path) => return Err(format!("{path}, line {}: {complaint}", input.line()).into()),
+            None => println!("{complaint}"),
+        }
+    }
+}
+
+// Returns nothing if the game was quit
+fn game_loop(
+    bot: &mut Bot,
+    input: &mut WordReader<impl BufRead>,
+    script: Option<&str>,
+    time_control: Option<TimeControl>,
+    handicap: Handicap,
+    ui: &Ui,
+) -> Result<Option<FinishedGame>, Box<dyn Error>> {
+    let mut turn: usize = 1;
+    let mut state = handicap.start();
+    let mut finished = false;
+    let mut transcript = Transcript::default();
+    let mut coverage = Coverage::default();
+    let mut clock = time_control.map(GameClock::new);
+    let mut out_of_time = None;
+
+    if !handicap.is_even() {
+        println!("{}", ui.catalog.get(Message::Handicap { handicap }));
+    }
+    println!("{}", state);
 
-    (state, finished) = player_turn(state, action, policy, &mut turn);
     while !finished {
-        match state.get_player_to_move() {
+        bot.reload_if_changed(ui);
+        let mover = state.get_player_to_move();
+        if let Some(clock) = &mut clock {
+            start_clock(clock, mover, bot, ui);
+        }
+        match mover {
             Player::Player2 => {
-                (state, finished) = bot_turn(state, policy, &mut turn);
+                let fallback = bot.fallback;
+                (state, finished) = bot_turn(
+                    state,
+                    bot.policy(),
+                    fallback,
+                    &mut turn,
+                    &mut transcript,
+                    &mut coverage,
+                    ui,
+                );
             }
             Player::Player1 => {
-                let action;
-                match get_player_input(&stdin) {
-                    PlayerRequest::Action(a) => {
-                        action = a;
+                let action = match get_player_input(input, state, script, ui)? {
+                    PlayerRequest::Action(a) => a,
+                    PlayerRequest::Reload => {
+                        bot.reload(ui);
+                        continue;
                     }
                     PlayerRequest::Quit => {
-                        println!("Ok, goodbye");
-                        return;
+                        println!("{}", ui.catalog.get(Message::Goodbye));
+                        return Ok(None);
                     }
                 };
 
-                (state, finished) = player_turn(state, action, policy, &mut turn);
+                (state, finished) =
+                    player_turn(state, action, bot.policy(), &mut turn, &mut transcript, ui);
             }
         }
+        if let Some(clock) = &mut clock
+            && !clock.stop()
+        {
+            println!("{}", ui.catalog.get(Message::OutOfTime { player: mover }));
+            out_of_time = Some(mover);
+            break;
+        }
     }
+
+    let outcome = match out_of_time {
+        Some(Player::Player1) => Outcome::Loss,
+        Some(Player::Player2) => Outcome::Win,
+        None => handicap
+            .outcome(&state, Player::Player1)
+            .expect("The game loop only ends early by quitting or on time"),
+    };
+    println!(
+        "{}",
+        ui.catalog.get(Message::GameOver {
+            outcome,
+            own_points: handicap.score(&state, Player::Player1),
+            bot_points: handicap.score(&state, Player::Player2),
+        })
+    );
+    if coverage.moves > 0 {
+        println!(
+            "{}",
+            ui.catalog.get(Message::BotCoverage {
+                known: coverage.known,
+                moves: coverage.moves,
+            })
+        );
+    }
+
+    Ok(Some((transcript, outcome)))
 }
 
-enum PlayerRequest {
-    Action(u8),
-    Quit,
+// The human sees both clocks before every move of theirs, the minimax bot thinks for its share of
+// what it has left. Restarting the running clock, after a reload, keeps the time it used.
+fn start_clock(clock: &mut GameClock, mover: Player, bot: &mut Bot, ui: &Ui) {
+    match mover {
+        Player::Player1 => println!(
+            "{}",
+            ui.catalog.get(Message::Clocks {
+                own: clock::format_clock(clock.remaining(Player::Player1)),
+                bot: clock::format_clock(clock.remaining(Player::Player2)),
+            })
+        ),
+        Player::Player2 => bot
+            .minimax
+            .set_time_limit(Some(clock.move_budget(Player::Player2))),
+    }
+    clock.start(mover);
 }
 
-fn get_player_input(stdin: &Stdin) -> PlayerRequest {
-    println!("Choose your action: (0,1,2,3,4,5,q)");
+// Skips words that are no move or pick an empty pit, a script has to be right instead. The end of
+// the input quits, so piped games end instead of waiting forever.
+fn get_player_input(
+    input: &mut WordReader<impl BufRead>,
+    state: MankallaGameState,
+    script: Option<&str>,
+    ui: &Ui,
+) -> Result<PlayerRequest, Box<dyn Error>> {
+    if script.is_none() {
+        println!(
+            "{}",
+            ui.catalog.get(Message::ChooseAction {
+                options: ui.input_scheme.labels().join(",")
+            })
+        );
+    }
 
-    let mut input = String::new();
+    let legal = ActionMask::legal::<MankallaGame>(&state.into());
     loop {
-        stdin
-            .read_line(&mut input)
-            .expect("Something with stdin went wrong");
-
-        match input.as_str().strip_suffix("\n").unwrap_or("") {
-            digit @ ("0" | "1" | "2" | "3" | "4" | "5") => {
-                return PlayerRequest::Action(digit.parse().expect("Guaranteed to work"));
+        let word = match input.next_word() {
+            Ok(Some(word)) => word,
+            Ok(None) => return Ok(PlayerRequest::Quit),
+            Err(e) => return Err(format!("Could not read the input: {e}").into()),
+        };
+        let complaint = match ui.input_scheme.parse_request(&word) {
+            Some(PlayerRequest::Action(action)) if !legal.allows::<MankallaGame>(&action) => {
+                Message::EmptyPit {
+                    action: ui.input_scheme.label(action),
+                }
             }
-            "q" => return PlayerRequest::Quit,
-            _ => continue,
+            Some(request) => return Ok(request),
+            None => Message::UnknownMove { input: word },
+        };
+        match script {
+            Some(path) => {
+                return Err(format!(
+                    "{path}, line {}: {} ({state:#})",
+                    input.line(),
+                    ui.catalog.get(complaint)
+                )
+                .into());
+            }
+            None => println!("{}", ui.catalog.get(complaint)),
         }
     }
 }
@@ -95,12 +4590,29 @@ fn get_player_input(stdin: &Stdin) -> PlayerRequest {
 fn player_turn(
     state: MankallaGameState,
     action: u8,
-    policy: &mut impl Policy<MankallaGame>,
+    policy: &mut (impl Policy<MankallaGame> + ?Sized),
     turn: &mut usize,
+    transcript: &mut Transcript<MankallaGame>,
+    ui: &Ui,
 ) -> (MankallaGameState, bool) {
-    println!("Turn {turn}, you chose {action}");
+    println!(
+        "{}",
+        ui.catalog.get(Message::HumanMove {
+            turn: *turn,
+            action: ui.input_scheme.label(action)
+        })
+    );
 
-    let (next_state, reward, finished) = MankallaGame::step(&state, &action);
+    let (next_state, reward, outcome) = two_player::play::<MankallaGame>(&state, &action);
+    let finished = outcome.is_some();
+    // The policy learns the human's move as its own, from the human's side of the board
+    let reward = reward.value();
+    transcript.push(LoggedStep {
+        state: state.into(),
+        action,
+        reward,
+        behavior_probability: None,
+    });
     println!("{}", next_state);
     policy.improve(state.into(), action, reward, next_state, finished);
 
@@ -109,18 +4621,88 @@ fn player_turn(
     (next_state, finished)
 }
 
+// Moves labelled from the side of whoever makes them, the bot is always the second player
+fn principal_variation(mut state: MankallaGameState, line: &[u8], ui: &Ui) -> String {
+    let mut labels = Vec::new();
+    for action in line {
+        labels.push(match state.get_player_to_move() {
+            Player::Player1 => ui.input_scheme.label(*action),
+            Player::Player2 => ui.input_scheme.opponent_label(*action),
+        });
+        state = MankallaGame::apply_move(&state, action);
+    }
+    labels.join(" ")
+}
+
+// Policies without a table always know where they are, the fallback is only for tables
 fn bot_turn(
     state: MankallaGameState,
-    policy: &mut impl Policy<MankallaGame>,
+    policy: &mut (impl Policy<MankallaGame> + ?Sized),
+    fallback: Option<Fallback>,
     turn: &mut usize,
+    transcript: &mut Transcript<MankallaGame>,
+    coverage: &mut Coverage,
+    ui: &Ui,
 ) -> (MankallaGameState, bool) {
-    let action = policy.choose_action(state.into());
+    let known = policy.knows_state(state.into());
+    if let Some(known) = known {
+        coverage.record(known);
+    }
+    let fallback = fallback.filter(|_| known == Some(false));
+    if let Some(fallback) = fallback {
+        println!(
+            "{}",
+            ui.catalog.get(Message::BotFallback {
+                evaluator: fallback.name().to_owned()
+            })
+        );
+    } else if ui.verbose {
+        let distribution = policy
+            .action_distribution(state.into())
+            .iter()
+            .map(|(a, p)| format!("{} {:.1}%", ui.input_scheme.opponent_label(*a), p * 100f32))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("{}", ui.catalog.get(Message::BotConsiders { distribution }));
+    }
+    let action = match fallback {
+        Some(fallback) => fallback.choose_action(state.into(), None),
+        None => policy.choose_action(state.into(), None),
+    };
+
+    println!(
+        "{}",
+        ui.catalog.get(Message::BotMove {
+            turn: *turn,
+            action: ui.input_scheme.opponent_label(action)
+        })
+    );
 
-    println!("Turn {turn}, bot chose {action}");
+    if let (true, Some(search)) = (ui.verbose, policy.last_search()) {
+        println!(
+            "{}",
+            ui.catalog.get(Message::BotSearched {
+                stats: search.to_string(),
+                line: principal_variation(state, &search.principal_variation, ui),
+            })
+        );
+    }
 
-    let (next_state, reward, finished) = MankallaGame::step(&state, &action);
+    let (next_state, reward, outcome) = two_player::play::<MankallaGame>(&state, &action);
+    let finished = outcome.is_some();
+    // Think about the expected reply while the human does
+    if !finished && next_state.get_player_to_move() == Player::Player1 {
+        let predicted = policy.last_search().and_then(|search| {
+            search::predicted_return::<MankallaGame>(&state, &search.principal_variation)
+        });
+        if let Some(view) = predicted {
+            policy.ponder(view);
+        }
+    }
+    // The transcript is the human's
+    transcript.add_reward(reward.for_player(reward.mover().opponent()));
     println!("{}", next_state);
-    policy.improve(state.into(), action, reward, next_state, finished);
+    policy.improve(state.into(), action, reward.value(), next_state, finished);
 
     *turn += 1;
 
@@ -1,8 +1,61 @@
-use crate::q_learning::{Deserialize, DeserializeError, Environment, Serialize};
+use crate::json::{Json, ToJson};
+use crate::q_learning::{ActionList, Deserialize, DeserializeError, Outcome, Serialize};
+pub use crate::two_player::Player;
+use crate::two_player::{TwoPlayerGame, VsOpponentState, play};
 use std::fmt::Display;
+use std::str::FromStr;
+
+#[cfg(feature = "simd")]
+use crate::simd;
 
 pub struct MankallaGame;
 
+// In every pit at the start of `MankallaGame`
+pub const STONES_PER_PIT: u8 = 6;
+
+// All stones can end up in one field
+const MAX_STONES: usize = 12 * STONES_PER_PIT as usize;
+
+// One key per (field, stone count), generated at compile time from a fixed seed so hashes stay the
+// same across runs and builds
+const ZOBRIST_KEYS: [[u64; MAX_STONES + 1]; 14] = {
+    let mut keys = [[0; MAX_STONES + 1]; 14];
+    let mut state = 0x6d61_6e6b_616c_6c61u64;
+    let mut field = 0;
+    while field < 14 {
+        let mut stones = 0;
+        while stones <= MAX_STONES {
+            keys[field][stones] = zobrist_key(&mut state);
+            stones += 1;
+        }
+        field += 1;
+    }
+    keys
+};
+const ZOBRIST_PLAYER2: u64 = zobrist_key(&mut 0x0070_6c61_7965_7232_u64);
+
+// SplitMix64, usable in constants
+const fn zobrist_key(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Enough to sow a move backwards
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveUndo {
+    // The field the stones were taken from and how many there were
+    start: u8,
+    stones: u8,
+    // Stones a steal took from the opposite pit
+    stolen: Option<u8>,
+    // The fields before the last stones were swept into the stores
+    before_sweep: Option<[u8; 14]>,
+    player_to_move: Player,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MankallaGameState {
     // 13 12 11 10  9  8  7
@@ -11,23 +64,28 @@ pub struct MankallaGameState {
     player_to_move: Player,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Player {
-    Player1,
-    Player2,
-}
-
-impl Environment for MankallaGame {
-    type State = MankallaGameState;
-    type ActionRelevantState = [u8; 12];
-    type Action = u8;
+impl TwoPlayerGame for MankallaGame {
+    type Position = MankallaGameState;
+    type View = [u8; 12];
+    type Move = u8;
+    type Undo = MoveUndo;
 
-    fn new() -> MankallaGameState {
+    fn start() -> MankallaGameState {
         Default::default()
     }
 
-    fn actions(state: &Self::ActionRelevantState) -> Vec<Self::Action> {
-        state[..6]
+    fn from_view(view: &[u8; 12]) -> MankallaGameState {
+        from_relevant_state(view)
+    }
+
+    fn current_player(position: &MankallaGameState) -> Player {
+        position.player_to_move
+    }
+
+    const MAX_MOVES: usize = 6;
+
+    fn legal_moves(view: &[u8; 12]) -> ActionList<u8> {
+        view[..6]
             .iter()
             .enumerate()
             .filter(|&(_, num_marbles)| *num_marbles > 0)
@@ -35,188 +93,647 @@ impl Environment for MankallaGame {
             .collect()
     }
 
-    fn step(state: &Self::State, action: &Self::Action) -> (Self::State, f32, bool) {
-        let mut state = state.clone();
+    fn move_index(pit: &u8) -> usize {
+        *pit as usize
+    }
+
+    fn make_move(position: &mut MankallaGameState, pit: &u8) -> MoveUndo {
+        position.apply_move(*pit)
+    }
+
+    fn unmake_move(position: &mut MankallaGameState, undo: MoveUndo) {
+        position.undo(undo);
+    }
+
+    fn outcome(position: &MankallaGameState) -> Option<Outcome> {
+        position.outcome(&Player::Player1)
+    }
 
-        let p1_points = state.get_points(&Player::Player1);
-        let p2_points = state.get_points(&Player::Player2);
+    fn zobrist(position: &MankallaGameState) -> Option<u64> {
+        Some(position.zobrist())
+    }
 
-        let mut i: usize = match state.player_to_move {
-            Player::Player1 => {
-                assert!(*action < 6);
-                *action as usize
+    fn zobrist_after(before: &MankallaGameState, key: u64, after: &MankallaGameState) -> u64 {
+        before.zobrist_after(key, after)
+    }
+
+    fn score(position: &MankallaGameState, player: Player) -> f32 {
+        position.get_points(&player) as f32
+    }
+
+    // The stores already tell who won, every stone counts along the way
+    fn outcome_reward(_outcome: Outcome) -> f32 {
+        0f32
+    }
+}
+
+#[cfg(feature = "simd")]
+impl From<MankallaGameState> for [u8; 12] {
+    fn from(value: MankallaGameState) -> Self {
+        simd::relevant_pits(&value.fields, value.player_to_move == Player::Player1)
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+impl From<MankallaGameState> for [u8; 12] {
+    fn from(value: MankallaGameState) -> Self {
+        relevant_pits(&value.fields, value.player_to_move)
+    }
+}
+
+// The byte loops `simd` replaces, compiled with it only for the tests comparing the two
+#[cfg(any(not(feature = "simd"), test))]
+fn relevant_pits(fields: &[u8; 14], player_to_move: Player) -> [u8; 12] {
+    let temp = match player_to_move {
+        Player::Player1 => [&fields[..6], &fields[7..13]],
+        Player::Player2 => [&fields[7..13], &fields[..6]],
+    };
+    temp.concat()
+        .try_into()
+        .expect("This should always be of length 12 by design")
+}
+
+#[cfg(any(not(feature = "simd"), test))]
+fn sow(fields: &mut [u8; 14], mut i: usize) -> usize {
+    let mut marbles_to_move = fields[i];
+    fields[i] = 0;
+    while marbles_to_move > 0 {
+        i = (i + 1) % 14;
+        fields[i] += 1;
+        marbles_to_move -= 1;
+    }
+    i
+}
+
+#[cfg(any(not(feature = "simd"), test))]
+fn row_sums(fields: &[u8; 14]) -> (u8, u8) {
+    (
+        fields[0..6].iter().sum::<u8>(),
+        fields[7..13].iter().sum::<u8>(),
+    )
+}
+
+impl From<VsOpponentState<MankallaGameState>> for [u8; 12] {
+    fn from(value: VsOpponentState<MankallaGameState>) -> Self {
+        value.position.into()
+    }
+}
+
+// `{:#}` gives a single line in field order for logs, e.g. "6 6 6 6 6 6 (0) 6 6 6 6 6 6 (0) P1"
+impl Display for MankallaGameState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            for (i, field) in self.fields.iter().enumerate() {
+                match i {
+                    6 | 13 => write!(f, "({}) ", field)?,
+                    _ => write!(f, "{} ", field)?,
+                }
             }
-            Player::Player2 => {
-                assert!(*action < 6);
-                (*action + 7) as usize
+            return match self.player_to_move {
+                Player::Player1 => write!(f, "P1"),
+                Player::Player2 => write!(f, "P2"),
+            };
+        }
+
+        for field in self.fields[7..14].iter().rev() {
+            write!(f, "{:>2}", field)?;
+        }
+        write!(f, "\n  ")?;
+        for field in self.fields[..7].iter() {
+            write!(f, "{:>2}", field)?;
+        }
+        Ok(())
+    }
+}
+
+// The pits of each player from their first pit on, stores apart, and the `{:#}` text besides
+impl ToJson for MankallaGameState {
+    fn to_json(&self) -> Json {
+        let pits = |pits: &[u8]| pits.to_vec().into();
+        Json::object([
+            ("text", format!("{self:#}").into()),
+            ("player1_pits", pits(&self.fields[..6])),
+            ("player1_store", self.fields[6].into()),
+            ("player2_pits", pits(&self.fields[7..13])),
+            ("player2_store", self.fields[13].into()),
+            (
+                "player_to_move",
+                match self.player_to_move {
+                    Player::Player1 => "P1",
+                    Player::Player2 => "P2",
+                }
+                .into(),
+            ),
+        ])
+    }
+}
+
+// Reads what `{:#}` writes, the parentheses around the stores are optional
+impl FromStr for MankallaGameState {
+    type Err = DeserializeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens: Vec<&str> = s.split_whitespace().collect();
+        let player_to_move = match tokens.pop() {
+            Some("P1") => Player::Player1,
+            Some("P2") => Player::Player2,
+            _ => {
+                return Err(DeserializeError::because(
+                    "a position ends with the player to move, P1 or P2",
+                ));
             }
         };
-
-        let mut marbles_to_move = state.fields[i];
-        state.fields[i] = 0;
-        while marbles_to_move > 0 {
-            i = (i + 1) % 14;
-            state.fields[i] += 1;
-            marbles_to_move -= 1;
+        let fields: Vec<u8> = tokens
+            .iter()
+            .map(|token| token.trim_start_matches('(').trim_end_matches(')').parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| DeserializeError::because(format!("bad stone count in \"{s}\"")))?;
+        let fields: [u8; 14] = fields
+            .try_into()
+            .map_err(|_| DeserializeError::because("a position has 14 fields"))?;
+        if fields.iter().map(|&stones| stones as usize).sum::<usize>() > MAX_STONES {
+            return Err(DeserializeError::because(format!(
+                "a position holds at most {MAX_STONES} stones"
+            )));
         }
+        Ok(MankallaGameState {
+            fields,
+            player_to_move,
+        })
+    }
+}
+
+impl Default for MankallaGameState {
+    fn default() -> Self {
+        MankallaGameState::with_stones(STONES_PER_PIT)
+    }
+}
+
+// The position as seen by the player to move, with empty stores
+fn from_relevant_state(state: &[u8; 12]) -> MankallaGameState {
+    let mut fields = [0; 14];
+    fields[..6].copy_from_slice(&state[..6]);
+    fields[7..13].copy_from_slice(&state[6..]);
+    MankallaGameState {
+        fields,
+        player_to_move: Player::Player1,
+    }
+}
 
-        state.handle_steal(i);
+// All a view tells about the stores is how many stones they hold together, `None` if the pits
+// alone hold more than the game has
+pub fn stones_in_stores(view: &[u8; 12]) -> Option<u8> {
+    let in_pits: usize = view.iter().map(|&stones| stones as usize).sum();
+    MAX_STONES.checked_sub(in_pits).map(|stones| stones as u8)
+}
 
-        let finished = state.handle_if_game_finished();
+// The pits as `[u8; 12]` sees them plus how far the player to move leads in stones, in buckets of
+// `BUCKET` stones that round toward zero. `[u8; 12]` alone puts a position won by ten stones and one
+// lost by ten into the same row of the table. Written as the 12 pits and the bucket, e.g.
+// "6 6 6 6 6 6 6 6 6 6 6 6 0".
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LeadView<const BUCKET: u8> {
+    pub pits: [u8; 12],
+    pub lead: i8,
+}
 
-        let mut reward = (state.get_points(&Player::Player1) - p1_points) as f32
-            - (state.get_points(&Player::Player2) - p2_points) as f32;
-        if state.player_to_move == Player::Player2 {
-            reward *= -1f32;
+impl<const BUCKET: u8> From<MankallaGameState> for LeadView<BUCKET> {
+    fn from(value: MankallaGameState) -> Self {
+        const { assert!(BUCKET > 0, "a bucket holds at least one stone") };
+        let mover = value.player_to_move;
+        let lead = value.get_points(&mover) as i16 - value.get_points(&mover.opponent()) as i16;
+        LeadView {
+            pits: value.into(),
+            lead: (lead / BUCKET as i16) as i8,
         }
+    }
+}
 
-        state.handle_switch_player(i);
+impl<const BUCKET: u8> Serialize for LeadView<BUCKET> {
+    fn serialize(&self) -> String {
+        format!("{} {}", self.pits.serialize(), self.lead)
+    }
+}
 
-        return (state, reward, finished);
+impl<const BUCKET: u8> Deserialize for LeadView<BUCKET> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let (pits, lead) = input
+            .rsplit_once(' ')
+            .ok_or_else(|| DeserializeError::because("a lead view has 12 pits and a lead"))?;
+        Ok(LeadView {
+            pits: <[u8; 12]>::deserialize(pits)?,
+            lead: i8::deserialize(lead)?,
+        })
     }
 }
 
-impl Serialize for [u8; 12] {
-    fn serialize(&self) -> String {
-        self.iter()
-            .map(u8::to_string)
-            .reduce(|a, b| format!("{} {}", a, b))
-            .expect("I do not see how this slice could ever be empty")
-    }
-}
-
-impl Deserialize for [u8; 12] {
-    fn deserialize(input: &str) -> Result<Self, DeserializeError>
-    where
-        Self: Sized,
-    {
-        let mut count = 0;
-        let mut values: [u8; 12] = Default::default();
-        let elems = input
-            .split(' ')
-            .inspect(|_| count += 1)
-            .map(|a| match a.parse::<u8>() {
-                Ok(v) => Ok(v),
-                Err(_) => Err(DeserializeError),
-            });
+// The parts of `TwoPlayerGame` that are `MankallaGame`'s rules whatever the view, for the games
+// that only differ from it in what they start from or key on
+macro_rules! mankalla_rules {
+    () => {
+        fn current_player(position: &MankallaGameState) -> Player {
+            MankallaGame::current_player(position)
+        }
 
-        for (i, elem) in elems.enumerate() {
-            if i >= 12 {
-                return Err(DeserializeError);
-            }
-            match elem {
-                Ok(v) => values[i] = v,
-                Err(e) => return Err(e),
-            }
+        fn move_index(pit: &u8) -> usize {
+            MankallaGame::move_index(pit)
+        }
+
+        fn make_move(position: &mut MankallaGameState, pit: &u8) -> MoveUndo {
+            MankallaGame::make_move(position, pit)
+        }
+
+        fn unmake_move(position: &mut MankallaGameState, undo: MoveUndo) {
+            MankallaGame::unmake_move(position, undo);
         }
 
-        if count != 12 {
-            return Err(DeserializeError);
+        fn outcome(position: &MankallaGameState) -> Option<Outcome> {
+            MankallaGame::outcome(position)
         }
 
-        Ok(values)
+        fn zobrist(position: &MankallaGameState) -> Option<u64> {
+            MankallaGame::zobrist(position)
+        }
+
+        fn zobrist_after(before: &MankallaGameState, key: u64, after: &MankallaGameState) -> u64 {
+            MankallaGame::zobrist_after(before, key, after)
+        }
+
+        fn score(position: &MankallaGameState, player: Player) -> f32 {
+            MankallaGame::score(position, player)
+        }
+
+        fn outcome_reward(outcome: Outcome) -> f32 {
+            MankallaGame::outcome_reward(outcome)
+        }
+    };
+}
+
+// Mankalla learned with `LeadView` as the state. The rules are `MankallaGame`'s, only what the
+// table keys on differs, so its policies play through this game rather than through
+// `MankallaGame`, whose views have no stores.
+pub struct LeadAwareMankalla<const BUCKET: u8>;
+
+impl<const BUCKET: u8> TwoPlayerGame for LeadAwareMankalla<BUCKET> {
+    type Position = MankallaGameState;
+    type View = LeadView<BUCKET>;
+    type Move = u8;
+    type Undo = MoveUndo;
+
+    fn start() -> MankallaGameState {
+        MankallaGame::start()
+    }
+
+    // Exact for buckets of one stone, wider ones give the lead at the bucket's end nearest zero
+    fn from_view(view: &LeadView<BUCKET>) -> MankallaGameState {
+        let in_stores = stones_in_stores(&view.pits).unwrap_or(0) as i16;
+        let own_store = (in_stores + view.lead as i16 * BUCKET as i16).clamp(0, 2 * in_stores) / 2;
+        MankallaGameState::reconstruct(&view.pits, Player::Player1, own_store as u8)
+            .unwrap_or_else(|_| from_relevant_state(&view.pits))
+    }
+
+    const MAX_MOVES: usize = MankallaGame::MAX_MOVES;
+
+    fn legal_moves(view: &LeadView<BUCKET>) -> ActionList<u8> {
+        MankallaGame::legal_moves(&view.pits)
     }
+
+    mankalla_rules!();
 }
 
-impl Serialize for u8 {
-    fn serialize(&self) -> String {
-        self.to_string()
+// The same board and rules starting with `STONES` stones per pit instead of `STONES_PER_PIT`, e.g.
+// `MankallaWithStones<4>` for Kalah(6,4). Smaller variants train faster and their tables can give
+// `MankallaGame` starting values through `scale_view`. The views are `MankallaGame`'s, which also
+// means `stones_in_stores` and `reconstruct` do not apply to them.
+pub struct MankallaWithStones<const STONES: u8>;
+
+impl<const STONES: u8> TwoPlayerGame for MankallaWithStones<STONES> {
+    type Position = MankallaGameState;
+    type View = [u8; 12];
+    type Move = u8;
+    type Undo = MoveUndo;
+
+    fn start() -> MankallaGameState {
+        MankallaGameState::with_stones(STONES)
+    }
+
+    fn from_view(view: &[u8; 12]) -> MankallaGameState {
+        MankallaGame::from_view(view)
+    }
+
+    const MAX_MOVES: usize = MankallaGame::MAX_MOVES;
+
+    fn legal_moves(view: &[u8; 12]) -> ActionList<u8> {
+        MankallaGame::legal_moves(view)
     }
+
+    mankalla_rules!();
 }
 
-impl Deserialize for u8 {
-    fn deserialize(input: &str) -> Result<Self, DeserializeError>
-    where
-        Self: Sized,
-    {
-        match input.parse::<u8>() {
-            Ok(v) => Ok(v),
-            Err(_) => Err(DeserializeError),
+// A view of a game with `from` stones per pit as it would look with `to`, every pit scaled and
+// rounded to the nearest count. Pits that hold stones keep at least one, so the same moves stay
+// legal and a table of the other game has values for them.
+pub fn scale_view(view: &[u8; 12], from: u8, to: u8) -> [u8; 12] {
+    view.map(|stones| match stones {
+        0 => 0,
+        stones => {
+            let scaled = (stones as u16 * to as u16 + from as u16 / 2) / from as u16;
+            scaled.clamp(1, u8::MAX as u16) as u8
         }
-    }
+    })
 }
 
-impl From<MankallaGameState> for [u8; 12] {
-    fn from(value: MankallaGameState) -> Self {
-        let temp = match value.player_to_move {
-            Player::Player1 => [&value.fields[..6], &value.fields[7..13]],
-            Player::Player2 => [&value.fields[7..13], &value.fields[..6]],
+// Evens out a match of unequal players. Positive values favor the first player: `komi` stones
+// count for them when the game is decided and the second player starts with `pit_stones` fewer
+// stones in each pit. Negative values favor the second player the same way. The komi never
+// enters the board, so the views, the rewards and what a table learns are those of the even game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Handicap {
+    pub komi: i8,
+    pub pit_stones: i8,
+}
+
+impl Handicap {
+    // Every pit keeps at least one stone
+    pub const MAX_PIT_STONES: i8 = STONES_PER_PIT as i8 - 1;
+
+    pub fn is_even(&self) -> bool {
+        *self == Handicap::default()
+    }
+
+    pub fn start(&self) -> MankallaGameState {
+        assert!(
+            self.pit_stones.abs() <= Handicap::MAX_PIT_STONES,
+            "A pit handicap leaves at least one stone per pit"
+        );
+        let mut state = MankallaGame::start();
+        let pits = match self.pit_stones > 0 {
+            true => 7..13,
+            false => 0..6,
         };
-        temp.concat()
-            .try_into()
-            .expect("This should always be of length 12 by design")
+        for pit in &mut state.fields[pits] {
+            *pit -= self.pit_stones.unsigned_abs();
+        }
+        state
+    }
+
+    // The stones in the player's store plus the komi they get
+    pub fn score(&self, state: &MankallaGameState, player: Player) -> u16 {
+        let komi = match player {
+            Player::Player1 => self.komi.max(0) as u16,
+            Player::Player2 => self.komi.min(0).unsigned_abs() as u16,
+        };
+        state.get_points(&player) as u16 + komi
+    }
+
+    // `MankallaGameState::outcome` with the komi counted
+    pub fn outcome(&self, state: &MankallaGameState, player: Player) -> Option<Outcome> {
+        state.outcome(&player)?;
+        Some(
+            match self
+                .score(state, player)
+                .cmp(&self.score(state, player.opponent()))
+            {
+                std::cmp::Ordering::Greater => Outcome::Win,
+                std::cmp::Ordering::Less => Outcome::Loss,
+                std::cmp::Ordering::Equal => Outcome::Draw,
+            },
+        )
     }
 }
 
-impl Display for MankallaGameState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut result: String = "".to_owned();
-        result.push_str(
-            self.fields[7..14]
-                .iter()
-                .rev()
-                .map(|field| format!("{:>2}", field))
-                .collect::<String>()
-                .as_str(),
-        );
-        result.push_str("\n  ");
-        result.push_str(
-            self.fields[..7]
-                .iter()
-                .map(|field| format!("{:>2}", field))
-                .collect::<String>()
-                .as_str(),
-        );
-        write!(f, "{}", result)
+// What the move scores right away, captures included, seen from the player to move. A cheap
+// guess for pairs the table has not seen yet.
+pub fn capture_heuristic(state: &[u8; 12], action: &u8) -> f32 {
+    play::<MankallaGame>(&from_relevant_state(state), action)
+        .1
+        .value()
+}
+
+// The stones on the mover's side of the board after the move, store included, minus those on the
+// other side. Unlike `capture_heuristic` it counts stones sown over to the opponent against the
+// move. The view has no stores, so only what this move puts in them counts.
+pub fn material_lookahead(state: &[u8; 12], action: &u8) -> f32 {
+    let (next_state, _, _) = play::<MankallaGame>(&from_relevant_state(state), action);
+    let side = |fields: &[u8]| fields.iter().map(|&stones| stones as i32).sum::<i32>();
+    (side(&next_state.fields[..7]) - side(&next_state.fields[7..])) as f32
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveInfo {
+    // Stones taken by a steal, the one that landed in the empty pit included
+    pub captured: u8,
+    // The last stone landed in the own store and the game goes on
+    pub extra_turn: bool,
+}
+
+// The pits after the move, seen by whoever moves next
+pub fn next_relevant_state(state: &[u8; 12], action: &u8) -> [u8; 12] {
+    MankallaGame::apply_move(&from_relevant_state(state), action).into()
+}
+
+pub fn move_info(state: &[u8; 12], action: &u8) -> MoveInfo {
+    let state = from_relevant_state(state);
+    let mut sown = state;
+    let i = sown.sow(*action as usize);
+    let store = sown.fields[6];
+    sown.handle_steal(i);
+    let (next_state, _, outcome) = play::<MankallaGame>(&state, action);
+    MoveInfo {
+        captured: sown.fields[6] - store,
+        extra_turn: outcome.is_none() && next_state.player_to_move == Player::Player1,
     }
 }
 
-impl Default for MankallaGameState {
-    fn default() -> Self {
+impl MankallaGameState {
+    // The start of a game with `stones` in each pit
+    pub fn with_stones(stones: u8) -> Self {
+        let mut fields = [stones; 14];
+        fields[6] = 0;
+        fields[13] = 0;
         MankallaGameState {
-            fields: [6, 6, 6, 6, 6, 6, 0, 6, 6, 6, 6, 6, 6, 0],
+            fields,
             player_to_move: Player::Player1,
         }
     }
-}
 
-impl MankallaGameState {
+    // Pits are counted from the mover's side
+    pub fn apply_move(&mut self, pit: u8) -> MoveUndo {
+        assert!(pit < 6);
+        let start = match self.player_to_move {
+            Player::Player1 => pit,
+            Player::Player2 => pit + 7,
+        };
+        let stones = self.fields[start as usize];
+        let player_to_move = self.player_to_move;
+
+        let i = self.sow(start as usize);
+        let stolen = self.handle_steal(i);
+        let fields = self.fields;
+        let before_sweep = self.handle_if_game_finished().then_some(fields);
+        self.handle_switch_player(i);
+        MoveUndo {
+            start,
+            stones,
+            stolen,
+            before_sweep,
+            player_to_move,
+        }
+    }
+
+    // Takes back the move that returned the token, which has to be the last one made
+    pub fn undo(&mut self, undo: MoveUndo) {
+        self.player_to_move = undo.player_to_move;
+        if let Some(fields) = undo.before_sweep {
+            self.fields = fields;
+        }
+        let start = undo.start as usize;
+        let last = (start + undo.stones as usize) % 14;
+        if let Some(stolen) = undo.stolen {
+            let store = match self.player_to_move {
+                Player::Player1 => 6,
+                Player::Player2 => 13,
+            };
+            self.fields[store] -= stolen + 1;
+            self.fields[last] = 1;
+            self.fields[12 - last] = stolen;
+        }
+        let mut i = start;
+        for _ in 0..undo.stones {
+            i = (i + 1) % 14;
+            self.fields[i] -= 1;
+        }
+        self.fields[start] = undo.stones;
+    }
+
+    // Equal positions always get the same key, different ones almost never do
+    pub fn zobrist(&self) -> u64 {
+        let fields = self.fields.iter().enumerate().fold(0, |key, (i, &stones)| {
+            key ^ ZOBRIST_KEYS[i][stones as usize]
+        });
+        match self.player_to_move {
+            Player::Player1 => fields,
+            Player::Player2 => fields ^ ZOBRIST_PLAYER2,
+        }
+    }
+
+    // The key of `next` from this position's key. A sowing touches a few pits and a store, only
+    // those are hashed again.
+    pub fn zobrist_after(&self, key: u64, next: &MankallaGameState) -> u64 {
+        let key = self
+            .fields
+            .iter()
+            .zip(&next.fields)
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .fold(key, |key, (i, (&before, &after))| {
+                key ^ ZOBRIST_KEYS[i][before as usize] ^ ZOBRIST_KEYS[i][after as usize]
+            });
+        match self.player_to_move == next.player_to_move {
+            true => key,
+            false => key ^ ZOBRIST_PLAYER2,
+        }
+    }
+
+    // The inverse of the view a Q-table is keyed by. A view keeps the pits as the player to move
+    // sees them and nothing else, so who that was and what their store holds have to come from
+    // the caller, the opponent's store gets the rest of the stones. For every position
+    // `reconstruct(&state.into(), mover, state.get_points(&mover))` gives `state` back.
+    pub fn reconstruct(
+        view: &[u8; 12],
+        player_to_move: Player,
+        own_store: u8,
+    ) -> Result<Self, DeserializeError> {
+        let in_stores = stones_in_stores(view).ok_or_else(|| {
+            DeserializeError::because(format!(
+                "the pits hold more than the {MAX_STONES} stones of the game"
+            ))
+        })?;
+        let other_store = in_stores.checked_sub(own_store).ok_or_else(|| {
+            DeserializeError::because(format!(
+                "only {in_stores} stones are left for the stores, not {own_store}"
+            ))
+        })?;
+        let mut state = from_relevant_state(view);
+        state.fields[6] = own_store;
+        state.fields[13] = other_store;
+        if player_to_move == Player::Player2 {
+            state.fields.rotate_left(7);
+        }
+        state.player_to_move = player_to_move;
+        Ok(state)
+    }
+
     pub fn get_player_to_move(&self) -> Player {
         self.player_to_move
     }
 
-    fn get_points(&self, player: &Player) -> u8 {
+    pub fn get_points(&self, player: &Player) -> u8 {
         match player {
             Player::Player1 => self.fields[6],
             Player::Player2 => self.fields[13],
         }
     }
 
-    fn handle_steal(&mut self, i: usize) {
-        if self.fields[i] == 1
-            && self.player_to_move == Player::Player1
-            && i < 6
-            && self.fields[12 - i] > 0
-        {
-            self.fields[6] += self.fields[i] + self.fields[12 - i];
-            self.fields[i] = 0;
-            self.fields[12 - i] = 0;
-        }
-        if self.fields[i] == 1
-            && self.player_to_move == Player::Player2
-            && 6 < i
-            && i < 13
-            && self.fields[12 - i] > 0
+    // Only available once the game is over, i.e. all pits are empty
+    pub fn outcome(&self, player: &Player) -> Option<Outcome> {
+        if self.fields[0..6]
+            .iter()
+            .chain(&self.fields[7..13])
+            .any(|&f| f > 0)
         {
-            self.fields[13] += self.fields[i] + self.fields[12 - i];
-            self.fields[i] = 0;
-            self.fields[12 - i] = 0;
+            return None;
+        }
+        Some(
+            match self
+                .get_points(player)
+                .cmp(&self.get_points(&player.opponent()))
+            {
+                std::cmp::Ordering::Greater => Outcome::Win,
+                std::cmp::Ordering::Less => Outcome::Loss,
+                std::cmp::Ordering::Equal => Outcome::Draw,
+            },
+        )
+    }
+
+    #[cfg(feature = "simd")]
+    fn sow(&mut self, i: usize) -> usize {
+        simd::sow(&mut self.fields, i)
+    }
+
+    #[cfg(not(feature = "simd"))]
+    fn sow(&mut self, i: usize) -> usize {
+        sow(&mut self.fields, i)
+    }
+
+    #[cfg(feature = "simd")]
+    fn row_sums(&self) -> (u8, u8) {
+        simd::row_sums(&self.fields)
+    }
+
+    #[cfg(not(feature = "simd"))]
+    fn row_sums(&self) -> (u8, u8) {
+        row_sums(&self.fields)
+    }
+
+    // Returns how many stones were taken from the opposite pit
+    fn handle_steal(&mut self, i: usize) -> Option<u8> {
+        let (own_pit, store) = match self.player_to_move {
+            Player::Player1 => (i < 6, 6),
+            Player::Player2 => (6 < i && i < 13, 13),
+        };
+        if !(self.fields[i] == 1 && own_pit && self.fields[12 - i] > 0) {
+            return None;
         }
+        let stolen = self.fields[12 - i];
+        self.fields[store] += self.fields[i] + stolen;
+        self.fields[i] = 0;
+        self.fields[12 - i] = 0;
+        Some(stolen)
     }
 
     fn handle_if_game_finished(&mut self) -> bool {
-        let mut p1_sum = self.fields[0..6].iter().sum::<u8>();
-        let mut p2_sum = self.fields[7..13].iter().sum::<u8>();
+        let (mut p1_sum, mut p2_sum) = self.row_sums();
 
         if p1_sum != 0 && p2_sum != 0 {
             return false;
@@ -231,16 +748,59 @@ impl MankallaGameState {
         self.fields[6] = p1_sum;
         self.fields[13] = p2_sum;
 
-        return true;
+        true
     }
 
     fn handle_switch_player(&mut self, i: usize) {
         if self.player_to_move == Player::Player1 && i != 6
             || self.player_to_move == Player::Player2 && i != 13
         {
-            self.player_to_move = match self.player_to_move {
-                Player::Player1 => Player::Player2,
-                Player::Player2 => Player::Player1,
+            self.player_to_move = self.player_to_move.opponent();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod simd_tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rand::seq::IndexedRandom;
+
+    use super::*;
+
+    const GAMES: u64 = 200;
+
+    // Every ply of seeded random games: the same board and stores after sowing from each field,
+    // the same row sums and the same relevant pits for either player to move
+    #[test]
+    fn simd_matches_the_byte_loops() {
+        for seed in 0..GAMES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut state = MankallaGame::start();
+            while MankallaGame::outcome(&state).is_none() {
+                let fields = state.fields;
+                assert_eq!(simd::row_sums(&fields), row_sums(&fields), "{state:#}");
+                for player in [Player::Player1, Player::Player2] {
+                    assert_eq!(
+                        simd::relevant_pits(&fields, player == Player::Player1),
+                        relevant_pits(&fields, player),
+                        "{state:#}"
+                    );
+                }
+                // Also with a pile big enough to go more than a full lap
+                for (start, extra) in (0..14).flat_map(|start| [(start, 0), (start, 14)]) {
+                    let (mut swar, mut scalar) = (fields, fields);
+                    swar[start] += extra;
+                    scalar[start] += extra;
+                    let landed = simd::sow(&mut swar, start);
+                    assert_eq!(landed, sow(&mut scalar, start), "{state:#} from {start}");
+                    assert_eq!(swar, scalar, "{state:#} from {start}");
+                }
+                let moves = MankallaGame::legal_moves(&state.into());
+                let pit = *moves
+                    .choose(&mut rng)
+                    .expect("A running game has legal moves");
+                state = MankallaGame::apply_move(&state, &pit);
             }
         }
     }
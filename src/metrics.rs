@@ -0,0 +1,330 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+
+use crate::json::{Json, ToJson};
+use crate::q_learning::{Environment, EpisodeStats, Policy, TrainingObserver, Transition};
+
+// Tracks the first `depth` moves of the last `window` episodes, a falling entropy means
+// self-play keeps repeating the same few openings
+pub struct OpeningDiversity<E: Environment> {
+    depth: usize,
+    window: usize,
+    current: Vec<Vec<E::Action>>,
+    recent: VecDeque<Vec<E::Action>>,
+}
+
+impl<E: Environment> OpeningDiversity<E> {
+    pub fn new(depth: usize, window: usize) -> Self {
+        assert!(window > 0, "The window needs to hold at least one episode");
+        OpeningDiversity {
+            depth,
+            window,
+            current: Vec::new(),
+            recent: VecDeque::with_capacity(window),
+        }
+    }
+
+    pub fn episodes(&self) -> usize {
+        self.recent.len()
+    }
+
+    pub fn distinct_lines(&self) -> usize {
+        self.line_counts().len()
+    }
+
+    // Shannon entropy of the opening lines in bits, 0 if every episode opened the same way
+    pub fn entropy(&self) -> f32 {
+        let total = self.recent.len() as f32;
+        self.line_counts()
+            .values()
+            .map(|&count| {
+                let p = count as f32 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    // The `n` lines played most often with their counts, ties in the order they were first seen
+    pub fn most_common(&self, n: usize) -> Vec<(Vec<E::Action>, usize)> {
+        let counts = self.line_counts();
+        let mut seen = HashSet::new();
+        let mut lines: Vec<_> = self
+            .recent
+            .iter()
+            .filter(|line| seen.insert(line.as_slice()))
+            .map(|line| (line.clone(), counts[line.as_slice()]))
+            .collect();
+        lines.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        lines.truncate(n);
+        lines
+    }
+
+    fn line_counts(&self) -> HashMap<&[E::Action], usize> {
+        let mut counts = HashMap::new();
+        for line in &self.recent {
+            *counts.entry(line.as_slice()).or_default() += 1;
+        }
+        counts
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized> TrainingObserver<E, P> for OpeningDiversity<E> {
+    fn on_step(&mut self, env: usize, transition: &Transition<E>) {
+        if self.current.len() <= env {
+            self.current.resize_with(env + 1, Vec::new);
+        }
+        let line = &mut self.current[env];
+        if line.len() < self.depth {
+            line.push(transition.action);
+        }
+        if transition.outcome.is_some() || transition.truncated {
+            if self.recent.len() == self.window {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(std::mem::take(line));
+        }
+    }
+
+    fn on_episode_end(&mut self, _policy: &P, _stats: &EpisodeStats) {}
+}
+
+impl<E: Environment> Display for OpeningDiversity<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Opening entropy {:.2} bits, {} distinct lines of {} moves over the last {} episodes",
+            self.entropy(),
+            self.distinct_lines(),
+            self.depth,
+            self.episodes()
+        )
+    }
+}
+
+// Keeps a histogram of episode lengths and the transcripts of episodes longer than the given
+// percentile of all episodes before them, so rule interactions that make games drag on
+// (starvation loops and the like) show up without anyone looking for them
+pub struct EpisodeLengths<E: Environment> {
+    percentile: f32,
+    min_history: usize,
+    max_kept: usize,
+    // counts[n] is the number of episodes with n steps
+    counts: Vec<usize>,
+    episodes: usize,
+    current: Vec<Vec<Transition<E>>>,
+    anomalies: Vec<(usize, Vec<Transition<E>>)>,
+    anomaly_count: usize,
+}
+
+impl<E: Environment> EpisodeLengths<E> {
+    // Nothing is flagged before `min_history` episodes are in, and only the first `max_kept`
+    // flagged transcripts are kept
+    pub fn new(percentile: f32, min_history: usize, max_kept: usize) -> Self {
+        assert!(
+            (0f32..=1f32).contains(&percentile),
+            "The percentile has to lie in [0, 1]"
+        );
+        EpisodeLengths {
+            percentile,
+            min_history,
+            max_kept,
+            counts: Vec::new(),
+            episodes: 0,
+            current: Vec::new(),
+            anomalies: Vec::new(),
+            anomaly_count: 0,
+        }
+    }
+
+    pub fn episodes(&self) -> usize {
+        self.episodes
+    }
+
+    // The smallest length that at least the given share of episodes did not exceed
+    pub fn length_at(&self, percentile: f32) -> usize {
+        let needed = (percentile * self.episodes as f32).ceil() as usize;
+        let mut seen = 0;
+        for (length, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= needed.max(1) {
+                return length;
+            }
+        }
+        self.max()
+    }
+
+    pub fn max(&self) -> usize {
+        self.counts.len().saturating_sub(1)
+    }
+
+    // How many episodes were flagged, including those whose transcripts were dropped
+    pub fn anomaly_count(&self) -> usize {
+        self.anomaly_count
+    }
+
+    // (index of the episode in finishing order, its transitions)
+    pub fn anomalies(&self) -> &[(usize, Vec<Transition<E>>)] {
+        &self.anomalies
+    }
+
+    fn finish(&mut self, transitions: Vec<Transition<E>>) {
+        let length = transitions.len();
+        if self.episodes >= self.min_history && length > self.length_at(self.percentile) {
+            self.anomaly_count += 1;
+            if self.anomalies.len() < self.max_kept {
+                self.anomalies.push((self.episodes, transitions));
+            }
+        }
+        if self.counts.len() <= length {
+            self.counts.resize(length + 1, 0);
+        }
+        self.counts[length] += 1;
+        self.episodes += 1;
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized> TrainingObserver<E, P> for EpisodeLengths<E> {
+    fn on_step(&mut self, env: usize, transition: &Transition<E>) {
+        if self.current.len() <= env {
+            self.current.resize_with(env + 1, Vec::new);
+        }
+        self.current[env].push(*transition);
+        if transition.outcome.is_some() || transition.truncated {
+            let transitions = std::mem::take(&mut self.current[env]);
+            self.finish(transitions);
+        }
+    }
+
+    fn on_episode_end(&mut self, _policy: &P, _stats: &EpisodeStats) {}
+}
+
+impl<E: Environment> Display for EpisodeLengths<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Episode length p50 {}, p90 {}, p99 {}, max {}; {} episodes longer than the p{} of those before them",
+            self.length_at(0.5),
+            self.length_at(0.9),
+            self.length_at(0.99),
+            self.max(),
+            self.anomaly_count,
+            self.percentile * 100f32
+        )
+    }
+}
+
+// Per-episode statistics averaged over blocks of `every` episodes, small enough to keep for
+// every run. The learner's own results say nothing in self-play, where one side of every game
+// wins, so the win rate is only there when `ProbedCurve` measures it.
+pub struct TrainingCurve {
+    every: usize,
+    block: (usize, f32, usize),
+    points: Vec<CurvePoint>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CurvePoint {
+    // The last episode of the block
+    pub episode: usize,
+    pub mean_reward: f32,
+    pub mean_steps: f32,
+    pub win_rate: Option<f32>,
+}
+
+impl ToJson for CurvePoint {
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("episode", self.episode.into()),
+            ("mean_reward", self.mean_reward.into()),
+            ("mean_steps", self.mean_steps.into()),
+            ("win_rate", self.win_rate.into()),
+        ])
+    }
+}
+
+// The win rate is left empty in blocks nobody probed
+pub const CURVE_CSV_HEADER: &str = "episode,mean_reward,mean_steps,win_rate";
+
+impl TrainingCurve {
+    pub fn new(every: usize) -> Self {
+        assert!(every > 0, "A block needs at least one episode");
+        TrainingCurve {
+            every,
+            block: (0, 0f32, 0),
+            points: Vec::new(),
+        }
+    }
+
+    pub fn points(&self) -> &[CurvePoint] {
+        &self.points
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{CURVE_CSV_HEADER}\n");
+        for p in &self.points {
+            csv += &format!(
+                "{},{},{},{}\n",
+                p.episode,
+                p.mean_reward,
+                p.mean_steps,
+                p.win_rate.map_or_else(String::new, |w| w.to_string())
+            );
+        }
+        csv
+    }
+
+    // Whether the episode closed a block
+    fn record(&mut self, stats: &EpisodeStats) -> bool {
+        let (episodes, reward, steps) = &mut self.block;
+        *episodes += 1;
+        *reward += stats.total_reward;
+        *steps += stats.steps;
+        if *episodes < self.every {
+            return false;
+        }
+        let n = *episodes as f32;
+        self.points.push(CurvePoint {
+            episode: stats.episode,
+            mean_reward: *reward / n,
+            mean_steps: *steps as f32 / n,
+            win_rate: None,
+        });
+        self.block = (0, 0f32, 0);
+        true
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized> TrainingObserver<E, P> for TrainingCurve {
+    fn on_episode_end(&mut self, _policy: &P, stats: &EpisodeStats) {
+        self.record(stats);
+    }
+}
+
+// A curve whose blocks end with a win rate from `probe`, e.g. the greedy policy against a fixed
+// opponent
+pub struct ProbedCurve<F> {
+    curve: TrainingCurve,
+    probe: F,
+}
+
+impl<F> ProbedCurve<F> {
+    pub fn new(curve: TrainingCurve, probe: F) -> Self {
+        ProbedCurve { curve, probe }
+    }
+
+    pub fn into_curve(self) -> TrainingCurve {
+        self.curve
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized, F: FnMut(&P) -> f32> TrainingObserver<E, P>
+    for ProbedCurve<F>
+{
+    fn on_episode_end(&mut self, policy: &P, stats: &EpisodeStats) {
+        if self.curve.record(stats)
+            && let Some(point) = self.curve.points.last_mut()
+        {
+            point.win_rate = Some((self.probe)(policy));
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+
+use crate::hyperparameters::{HyperparameterError, Hyperparameters};
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, EpsilonGreedyPolicy, Policy, QLearning,
+    Serialize, TrainingObserver, TrainingOptions, Transition,
+};
+
+// First-visit Monte Carlo control: nothing is learned while a game runs. Once it is over the
+// returns are summed backward from the last move, and every pair moves toward the return that
+// followed its first visit in the episode, later visits of the same pair are left out. No value is
+// bootstrapped from another, so a capture late in the game reaches the opening within one episode
+// at the price of noisier targets. An episode cut off by the step limit starts its returns from the
+// greedy value of where it stopped. Trained by `QLearning` the steps are kept until the episode
+// ends, one game at a time since interleaved games would mix, `MonteCarloControl` hands over whole
+// episodes instead. Table, exploration and file format are `EpsilonGreedyPolicy`'s.
+pub struct MonteCarloPolicy<E: Environment> {
+    policy: EpsilonGreedyPolicy<E>,
+    // The episode so far when trained step by step, with where the last step ended
+    pending: Vec<(E::ActionRelevantState, E::Action, f32)>,
+    pending_end: Option<E::ActionRelevantState>,
+}
+
+impl<E: Environment> MonteCarloPolicy<E> {
+    pub fn from_hyperparameters(
+        hyperparameters: Hyperparameters,
+    ) -> Result<Self, HyperparameterError> {
+        Ok(MonteCarloPolicy::from(
+            EpsilonGreedyPolicy::from_hyperparameters(hyperparameters)?,
+        ))
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.policy.reseed(seed);
+    }
+
+    pub fn epsilon_greedy_policy(&self) -> &EpsilonGreedyPolicy<E> {
+        &self.policy
+    }
+
+    pub fn epsilon_greedy_policy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        &mut self.policy
+    }
+
+    // The transitions of one whole episode in the order they were played
+    pub fn learn_episode(&mut self, transitions: &[Transition<E>]) {
+        let tail = match transitions.last() {
+            Some(last) if last.truncated => self.max_value(last.next_state.into()),
+            _ => 0f32,
+        };
+        let steps: Vec<_> = transitions
+            .iter()
+            .map(|t| (t.state.into(), t.action, t.reward))
+            .collect();
+        self.learn(&steps, tail);
+    }
+
+    fn learn(&mut self, steps: &[(E::ActionRelevantState, E::Action, f32)], tail: f32) {
+        let gamma = self.policy.gamma();
+        let mut returns = vec![0f32; steps.len()];
+        let mut following = tail;
+        for (i, &(_, _, reward)) in steps.iter().enumerate().rev() {
+            following = reward + gamma * following;
+            returns[i] = following;
+        }
+
+        let learning_rate = self.policy.hyperparameters().learning_rate;
+        let mut visited = HashSet::new();
+        for (&(state, action, _), &following) in steps.iter().zip(&returns) {
+            if !visited.insert((state, action)) {
+                continue;
+            }
+            self.policy.record_visit(state);
+            let greedy_policy = self.policy.greedy_policy_mut();
+            let former_value = greedy_policy.value(state, action);
+            greedy_policy.set_value(
+                state,
+                action,
+                former_value + learning_rate * (following - former_value),
+            );
+        }
+    }
+
+    fn max_value(&self, state: E::ActionRelevantState) -> f32 {
+        let greedy_policy = self.policy.greedy_policy();
+        greedy_policy.value(state, greedy_policy.choose_action(state, None))
+    }
+}
+
+// Goes on from what the policy learned so far, with its exploration
+impl<E: Environment> From<EpsilonGreedyPolicy<E>> for MonteCarloPolicy<E> {
+    fn from(policy: EpsilonGreedyPolicy<E>) -> Self {
+        MonteCarloPolicy {
+            policy,
+            pending: Vec::new(),
+            pending_end: None,
+        }
+    }
+}
+
+impl<E: Environment> Policy<E> for MonteCarloPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.policy.choose_action(state, mask)
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.pending.push((state, action, reward));
+        self.pending_end = Some(next_state.into());
+        if finished {
+            let steps = std::mem::take(&mut self.pending);
+            self.pending_end = None;
+            self.learn(&steps, 0f32);
+        }
+    }
+
+    // Steps still pending belong to an episode the step limit cut off
+    fn on_episode_increment(&mut self) {
+        let steps = std::mem::take(&mut self.pending);
+        if let Some(end) = self.pending_end.take() {
+            let tail = self.max_value(end);
+            self.learn(&steps, tail);
+        }
+        self.policy.on_episode_increment();
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.policy.action_distribution(state)
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.policy.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.policy.knows_state(state)
+    }
+}
+
+impl<E: Environment> Serialize for MonteCarloPolicy<E> {
+    fn serialize(&self) -> String {
+        self.policy.serialize()
+    }
+}
+
+impl<E: Environment> Deserialize for MonteCarloPolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        Ok(MonteCarloPolicy::from(EpsilonGreedyPolicy::deserialize(
+            input,
+        )?))
+    }
+}
+
+// Plays every episode to its end with `QLearning::collect_episode` before the policy learns from
+// it, the observer sees the steps once they were learned from. One game at a time.
+pub struct MonteCarloControl;
+
+impl MonteCarloControl {
+    pub fn train<E: Environment>(
+        policy: &mut MonteCarloPolicy<E>,
+        num_training_episodes: usize,
+        max_steps: Option<usize>,
+    ) {
+        let options = TrainingOptions {
+            max_steps,
+            ..Default::default()
+        };
+        MonteCarloControl::train_observed(policy, num_training_episodes, &options, &mut ());
+    }
+
+    pub fn train_observed<E: Environment>(
+        policy: &mut MonteCarloPolicy<E>,
+        num_training_episodes: usize,
+        options: &TrainingOptions,
+        observer: &mut impl TrainingObserver<E, MonteCarloPolicy<E>>,
+    ) {
+        for episode in 0..num_training_episodes {
+            let trajectory = QLearning::collect_episode(&*policy, episode, options);
+            policy.learn_episode(&trajectory.transitions);
+            policy.on_episode_increment();
+            for transition in &trajectory.transitions {
+                observer.on_step(0, transition);
+            }
+            observer.on_episode_end(policy, &trajectory.stats);
+            observer.adjust_policy(policy);
+            if observer.should_stop() {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::q_learning::{ActionList, Environment, Outcome, Policy};
+
+// Three heaps, whoever takes the last object wins. The xor of the heaps is 6, so the first
+// player wins with perfect play.
+pub const START: [u8; 3] = [3, 4, 5];
+
+// Single agent Nim: the opponent is folded into `step` and plays the optimal xor strategy. From a
+// winning position every move that is not optimal loses for sure, so a learner that converged
+// plays exactly the optimal moves. That makes it a canary for the learning algorithms.
+pub struct Nim;
+
+// (heap, number of objects taken)
+pub type NimAction = [u8; 2];
+
+fn take(heaps: &[u8; 3], action: &NimAction) -> [u8; 3] {
+    let [heap, count] = *action;
+    assert!(
+        count > 0 && count <= heaps[heap as usize],
+        "Heap {heap} holds {}, can not take {count}",
+        heaps[heap as usize]
+    );
+    let mut heaps = *heaps;
+    heaps[heap as usize] -= count;
+    heaps
+}
+
+fn nim_sum(heaps: &[u8; 3]) -> u8 {
+    heaps.iter().fold(0, |a, b| a ^ b)
+}
+
+fn empty(heaps: &[u8; 3]) -> bool {
+    heaps.iter().all(|&h| h == 0)
+}
+
+// A move that leaves a xor of zero, `None` if the position is already lost. There can be several.
+pub fn optimal_move(heaps: &[u8; 3]) -> Option<NimAction> {
+    let sum = nim_sum(heaps);
+    if sum == 0 {
+        return None;
+    }
+    heaps
+        .iter()
+        .enumerate()
+        .find(|&(_, &h)| h ^ sum < h)
+        .map(|(i, &h)| [i as u8, h - (h ^ sum)])
+}
+
+// Stalls as long as possible when there is nothing better to do
+fn opponent_move(heaps: &[u8; 3]) -> NimAction {
+    optimal_move(heaps).unwrap_or_else(|| {
+        let largest = (0..3)
+            .max_by_key(|&i| heaps[i])
+            .expect("There are three heaps");
+        [largest as u8, 1]
+    })
+}
+
+impl Environment for Nim {
+    type State = [u8; 3];
+    type ActionRelevantState = [u8; 3];
+    type Action = NimAction;
+
+    fn new() -> [u8; 3] {
+        START
+    }
+
+    // Three heaps of eight slots, see `action_index`
+    const MAX_ACTIONS: usize = 24;
+
+    fn actions(state: &[u8; 3]) -> ActionList<NimAction> {
+        state
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &h)| (1..=h).map(move |count| [i as u8, count]))
+            .collect()
+    }
+
+    // Eight slots per heap, no heap ever holds more than it starts with
+    fn action_index(action: &NimAction) -> usize {
+        let [heap, count] = *action;
+        heap as usize * 8 + count as usize - 1
+    }
+
+    // The heaps the opponent gets to see, taking the last stone already wins
+    type Afterstate = [u8; 3];
+
+    fn afterstate(state: &[u8; 3], action: &NimAction) -> ([u8; 3], f32) {
+        let heaps = take(state, action);
+        match empty(&heaps) {
+            true => (heaps, 1f32),
+            false => (heaps, 0f32),
+        }
+    }
+
+    fn step(state: &[u8; 3], action: &NimAction) -> ([u8; 3], f32, Option<Outcome>) {
+        let heaps = take(state, action);
+        if empty(&heaps) {
+            return (heaps, 1f32, Some(Outcome::Win));
+        }
+        let heaps = take(&heaps, &opponent_move(&heaps));
+        match empty(&heaps) {
+            true => (heaps, -1f32, Some(Outcome::Loss)),
+            false => (heaps, 0f32, None),
+        }
+    }
+}
+
+// Plays greedily from the start against the optimal opponent and counts the moves that matched
+// the optimal strategy, (optimal moves, all moves). Winning needs every single move to be optimal.
+pub fn optimal_moves(policy: &(impl Policy<Nim> + ?Sized)) -> (usize, usize) {
+    let mut heaps = Nim::new();
+    let mut moves = (0, 0);
+    loop {
+        let action = policy.choose_action(heaps, None);
+        moves.0 += (nim_sum(&heaps) != 0 && nim_sum(&take(&heaps, &action)) == 0) as usize;
+        moves.1 += 1;
+        let (next_heaps, _, outcome) = Nim::step(&heaps, &action);
+        if outcome.is_some() {
+            return moves;
+        }
+        heaps = next_heaps;
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::q_learning::{Deserialize, DeserializeError, Environment, Policy, Serialize};
+
+// One decision of the logged player, the reward covers everything that happened until its next decision
+pub struct LoggedStep<E: Environment> {
+    pub state: E::ActionRelevantState,
+    pub action: E::Action,
+    pub reward: f32,
+    // Unknown for human players, in that case it is estimated from the logged action frequencies
+    pub behavior_probability: Option<f32>,
+}
+
+pub struct Transcript<E: Environment> {
+    pub steps: Vec<LoggedStep<E>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct OffPolicyEstimate {
+    pub episodes: usize,
+    pub ordinary: f32,
+    pub weighted: f32,
+    pub effective_sample_size: f32,
+}
+
+impl<E: Environment> Default for Transcript<E> {
+    fn default() -> Self {
+        Transcript { steps: Vec::new() }
+    }
+}
+
+impl<E: Environment> Transcript<E> {
+    pub fn push(&mut self, step: LoggedStep<E>) {
+        self.steps.push(step);
+    }
+
+    // Rewards received before the first decision are dropped, they do not depend on the policy
+    pub fn add_reward(&mut self, reward: f32) {
+        if let Some(step) = self.steps.last_mut() {
+            step.reward += reward;
+        }
+    }
+
+    pub fn discounted_return(&self, gamma: f32) -> f32 {
+        self.steps
+            .iter()
+            .rev()
+            .fold(0f32, |g, step| step.reward + gamma * g)
+    }
+}
+
+impl<E: Environment> Serialize for Transcript<E> {
+    fn serialize(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| {
+                format!(
+                    "{};{};{};{}\n",
+                    step.state.serialize(),
+                    step.action.serialize(),
+                    step.reward,
+                    step.behavior_probability
+                        .map(|p| p.to_string())
+                        .unwrap_or_default()
+                )
+            })
+            .collect()
+    }
+}
+
+impl<E: Environment> Deserialize for Transcript<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let mut transcript = Transcript::default();
+        for line in input.lines() {
+            let mut parts = line.split(';');
+            let state = match parts.next() {
+                Some(s) => E::ActionRelevantState::deserialize(s)?,
+                _ => return Err(DeserializeError::new()),
+            };
+            let action = match parts.next() {
+                Some(a) => E::Action::deserialize(a)?,
+                _ => return Err(DeserializeError::new()),
+            };
+            let reward = match parts.next().map(str::parse::<f32>) {
+                Some(Ok(r)) => r,
+                _ => return Err(DeserializeError::new()),
+            };
+            let behavior_probability = match parts.next() {
+                Some("") => None,
+                Some(p) => match p.parse::<f32>() {
+                    Ok(p) => Some(p),
+                    Err(_) => return Err(DeserializeError::new()),
+                },
+                None => return Err(DeserializeError::new()),
+            };
+            if parts.next().is_some() {
+                return Err(DeserializeError::new());
+            }
+
+            transcript.push(LoggedStep {
+                state,
+                action,
+                reward,
+                behavior_probability,
+            });
+        }
+        Ok(transcript)
+    }
+}
+
+// Transcripts are stored one after another, separated by an empty line
+pub fn parse_transcripts<E: Environment>(
+    input: &str,
+) -> Result<Vec<Transcript<E>>, DeserializeError> {
+    input
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(Transcript::deserialize)
+        .collect()
+}
+
+// Ordinary and weighted importance sampling with one ratio per episode
+pub fn estimate<E: Environment>(
+    target: &(impl Policy<E> + ?Sized),
+    transcripts: &[Transcript<E>],
+    gamma: f32,
+) -> OffPolicyEstimate {
+    let mut action_counts = HashMap::<(E::ActionRelevantState, E::Action), usize>::new();
+    let mut state_counts = HashMap::<E::ActionRelevantState, usize>::new();
+    for step in transcripts.iter().flat_map(|t| t.steps.iter()) {
+        *action_counts.entry((step.state, step.action)).or_default() += 1;
+        *state_counts.entry(step.state).or_default() += 1;
+    }
+
+    let weighted_returns: Vec<(f32, f32)> = transcripts
+        .iter()
+        .map(|transcript| {
+            let ratio = transcript
+                .steps
+                .iter()
+                .map(|step| {
+                    let behavior = step.behavior_probability.unwrap_or_else(|| {
+                        action_counts[&(step.state, step.action)] as f32
+                            / state_counts[&step.state] as f32
+                    });
+                    let target_probability = target
+                        .action_distribution(step.state)
+                        .into_iter()
+                        .find(|&(a, _)| a == step.action)
+                        .map_or(0f32, |(_, p)| p);
+                    target_probability / behavior
+                })
+                .product::<f32>();
+            (ratio, transcript.discounted_return(gamma))
+        })
+        .collect();
+
+    let episodes = weighted_returns.len();
+    let ratio_sum: f32 = weighted_returns.iter().map(|&(w, _)| w).sum();
+    let ratio_square_sum: f32 = weighted_returns.iter().map(|&(w, _)| w * w).sum();
+    let weighted_sum: f32 = weighted_returns.iter().map(|&(w, g)| w * g).sum();
+
+    OffPolicyEstimate {
+        episodes,
+        ordinary: match episodes {
+            0 => 0f32,
+            n => weighted_sum / n as f32,
+        },
+        weighted: match ratio_sum > 0f32 {
+            true => weighted_sum / ratio_sum,
+            false => 0f32,
+        },
+        effective_sample_size: match ratio_square_sum > 0f32 {
+            true => ratio_sum * ratio_sum / ratio_square_sum,
+            false => 0f32,
+        },
+    }
+}
+
+impl Display for OffPolicyEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Episodes:              {}", self.episodes)?;
+        writeln!(f, "Ordinary IS estimate:  {:.4}", self.ordinary)?;
+        writeln!(f, "Weighted IS estimate:  {:.4}", self.weighted)?;
+        write!(
+            f,
+            "Effective sample size: {:.1}",
+            self.effective_sample_size
+        )
+    }
+}
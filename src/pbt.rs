@@ -0,0 +1,232 @@
+use std::fmt::Display;
+
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+
+use crate::evaluation;
+use crate::hyperparameters::Hyperparameters;
+use crate::mankalla::MankallaGame;
+use crate::q_learning::{EpsilonGreedyPolicy, QLearning};
+use crate::seeding::SeedStreams;
+
+// Greedy play is deterministic, so one game per seat is all a pairing can tell
+const GAMES_PER_PAIRING: usize = 2;
+const PERTURB_FACTORS: [f32; 2] = [0.8, 1.2];
+
+#[derive(Clone, Copy, Debug)]
+pub struct PbtOptions {
+    pub population: usize,
+    pub episodes_per_round: usize,
+    // Share of the population that is replaced after every round, taken from the bottom
+    pub truncation: f32,
+}
+
+pub struct Member {
+    pub id: usize,
+    pub policy: EpsilonGreedyPolicy<MankallaGame>,
+    pub score: f32,
+    // The member this one last copied its table from
+    pub parent: Option<usize>,
+}
+
+pub struct PopulationTrainer {
+    members: Vec<Member>,
+    options: PbtOptions,
+    round: usize,
+    rng: StdRng,
+}
+
+pub struct RoundReport {
+    pub round: usize,
+    // Scores and hyperparameters after evaluation, best first
+    pub ranking: Vec<(usize, f32, Hyperparameters)>,
+    // (loser, winner) pairs, the loser copied the winner's table
+    pub replacements: Vec<(usize, usize)>,
+}
+
+impl Default for PbtOptions {
+    fn default() -> Self {
+        PbtOptions {
+            population: 8,
+            episodes_per_round: 500,
+            truncation: 0.25,
+        }
+    }
+}
+
+fn clamp(hyperparameters: Hyperparameters) -> Hyperparameters {
+    let max_epsilon = hyperparameters.max_epsilon.clamp(0f32, 1f32);
+    Hyperparameters {
+        learning_rate: hyperparameters.learning_rate.clamp(f32::EPSILON, 1f32),
+        gamma: hyperparameters.gamma.clamp(0f32, 1f32),
+        max_epsilon,
+        min_epsilon: hyperparameters.min_epsilon.clamp(0f32, max_epsilon),
+        decay_rate: hyperparameters.decay_rate,
+    }
+}
+
+fn perturb(hyperparameters: Hyperparameters, rng: &mut impl Rng) -> Hyperparameters {
+    let mut factor = || {
+        *PERTURB_FACTORS
+            .choose(rng)
+            .expect("There are perturbation factors")
+    };
+    clamp(Hyperparameters {
+        learning_rate: hyperparameters.learning_rate * factor(),
+        gamma: hyperparameters.gamma * factor(),
+        max_epsilon: hyperparameters.max_epsilon * factor(),
+        min_epsilon: hyperparameters.min_epsilon * factor(),
+        decay_rate: hyperparameters.decay_rate * factor(),
+    })
+}
+
+fn new_member(id: usize, hyperparameters: Hyperparameters, seed: u64) -> Member {
+    let mut policy = EpsilonGreedyPolicy::from_hyperparameters(hyperparameters)
+        .expect("Perturbed hyperparameters are clamped into their valid ranges");
+    policy.reseed(seed);
+    Member {
+        id,
+        policy,
+        score: 0f32,
+        parent: None,
+    }
+}
+
+impl PopulationTrainer {
+    // The first member starts from `base` unchanged, all others from a perturbed copy of it.
+    // Every member explores with its own stream, the trainer perturbs and picks with another one.
+    pub fn new(base: Hyperparameters, options: PbtOptions, seeds: &mut SeedStreams) -> Self {
+        assert!(
+            options.population > 1,
+            "A population needs at least two members"
+        );
+        let mut rng = seeds.rng("pbt");
+        let members = (0..options.population)
+            .map(|id| {
+                let hyperparameters = match id {
+                    0 => clamp(base),
+                    _ => perturb(base, &mut rng),
+                };
+                new_member(id, hyperparameters, seeds.worker_seed("pbt/member", id))
+            })
+            .collect();
+        PopulationTrainer {
+            members,
+            options,
+            round: 0,
+            rng,
+        }
+    }
+
+    pub fn members(&self) -> &[Member] {
+        &self.members
+    }
+
+    pub fn best(&self) -> &Member {
+        self.members
+            .iter()
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+            .expect("A population is never empty")
+    }
+
+    // Train, play every pairing head-to-head, then let the bottom copy the top
+    pub fn round(&mut self) -> RoundReport {
+        for member in &mut self.members {
+            QLearning::train(&mut member.policy, self.options.episodes_per_round, None);
+        }
+        self.evaluate();
+
+        let mut order: Vec<usize> = (0..self.members.len()).collect();
+        order.sort_by(|&a, &b| self.members[b].score.total_cmp(&self.members[a].score));
+        let cutoff = ((self.members.len() as f32 * self.options.truncation) as usize)
+            .min(self.members.len() / 2);
+        let (top, bottom) = (&order[..cutoff], &order[order.len() - cutoff..]);
+
+        let ranking = order
+            .iter()
+            .map(|&i| {
+                let member = &self.members[i];
+                (member.id, member.score, member.policy.hyperparameters())
+            })
+            .collect();
+        let mut replacements = Vec::new();
+        for &loser in bottom {
+            let winner = *top
+                .choose(&mut self.rng)
+                .expect("The top is never empty here");
+            self.exploit(loser, winner);
+            replacements.push((self.members[loser].id, self.members[winner].id));
+        }
+
+        self.round += 1;
+        RoundReport {
+            round: self.round,
+            ranking,
+            replacements,
+        }
+    }
+
+    fn evaluate(&mut self) {
+        let mut points = vec![0f32; self.members.len()];
+        for i in 0..self.members.len() {
+            for j in i + 1..self.members.len() {
+                let report = evaluation::evaluate(
+                    self.members[i].policy.greedy_policy(),
+                    self.members[j].policy.greedy_policy(),
+                    GAMES_PER_PAIRING,
+                );
+                points[i] += report.wins as f32 + 0.5 * report.draws as f32;
+                points[j] += report.losses as f32 + 0.5 * report.draws as f32;
+            }
+        }
+        let games = ((self.members.len() - 1) * GAMES_PER_PAIRING) as f32;
+        for (member, points) in self.members.iter_mut().zip(points) {
+            member.score = points / games;
+        }
+    }
+
+    // The loser takes over the winner's table and progress, but explores new hyperparameters
+    fn exploit(&mut self, loser: usize, winner: usize) {
+        let hyperparameters = perturb(self.members[winner].policy.hyperparameters(), &mut self.rng);
+        let seed = self.rng.random();
+        let mut member = new_member(self.members[loser].id, hyperparameters, seed);
+        member
+            .policy
+            .greedy_policy_mut()
+            .qtable_mut()
+            .clone_from(self.members[winner].policy.greedy_policy().qtable());
+        member
+            .policy
+            .set_episode(self.members[winner].policy.episode());
+        member.parent = Some(self.members[winner].id);
+        self.members[loser] = member;
+    }
+}
+
+impl Display for RoundReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Round {}", self.round)?;
+        for (id, score, h) in &self.ranking {
+            writeln!(
+                f,
+                "  #{id:<3} score {:>5.1}%  lr {:.3}  gamma {:.3}  epsilon {:.2}..{:.2}  decay {:.4}",
+                score * 100f32,
+                h.learning_rate,
+                h.gamma,
+                h.max_epsilon,
+                h.min_epsilon,
+                h.decay_rate
+            )?;
+        }
+        let replacements: Vec<String> = self
+            .replacements
+            .iter()
+            .map(|(loser, winner)| format!("#{loser} <- #{winner}"))
+            .collect();
+        match replacements.is_empty() {
+            true => write!(f, "  Replaced: none"),
+            false => write!(f, "  Replaced: {}", replacements.join(", ")),
+        }
+    }
+}
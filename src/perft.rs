@@ -0,0 +1,52 @@
+use crate::two_player::TwoPlayerGame;
+
+// Continuations of exactly `depth` plies, a game that ends sooner counts once. An extra turn is a
+// ply of its own. Moves are made in place, so the counts also check that every move is taken back.
+pub fn perft<G: TwoPlayerGame>(position: &mut G::Position, depth: usize) -> u64 {
+    if depth == 0 || G::outcome(position).is_some() {
+        return 1;
+    }
+    G::legal_moves(&G::View::from(*position))
+        .iter()
+        .map(|game_move| {
+            let undo = G::make_move(position, game_move);
+            let count = perft::<G>(position, depth - 1);
+            G::unmake_move(position, undo);
+            count
+        })
+        .sum()
+}
+
+// `perft` split by the first move, to find the branch where two counts part ways
+pub fn divide<G: TwoPlayerGame>(position: &G::Position, depth: usize) -> Vec<(G::Move, u64)> {
+    assert!(depth > 0, "Dividing needs at least one ply");
+    G::legal_moves(&G::View::from(*position))
+        .into_iter()
+        .map(|game_move| {
+            let mut next_position = G::apply_move(position, &game_move);
+            (game_move, perft::<G>(&mut next_position, depth - 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mankalla::MankallaGame;
+
+    // What the move generator gives today, a change means make/unmake or the rules changed
+    const START_COUNTS: [u64; 4] = [6, 35, 190, 1056];
+
+    #[test]
+    fn start_position_counts_stay_the_same() {
+        let start = MankallaGame::start();
+        for (depth, &expected) in (1..).zip(&START_COUNTS) {
+            let mut position = start;
+            assert_eq!(perft::<MankallaGame>(&mut position, depth), expected);
+            assert!(
+                position == start,
+                "Perft {depth} did not take every move back"
+            );
+        }
+    }
+}
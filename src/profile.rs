@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::json::{Json, ToJson};
+use crate::q_learning::{Deserialize, DeserializeError, Outcome, Serialize};
+
+pub const BIG_CAPTURE: u8 = 8;
+pub const EXTRA_TURN_CHAIN: usize = 3;
+pub const WIN_STREAK: i32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Achievement {
+    FirstWin,
+    BigCapture,
+    ExtraTurnChain,
+    BeatStrongestBot,
+    WinStreak,
+}
+
+impl Achievement {
+    const ALL: [Achievement; 5] = [
+        Achievement::FirstWin,
+        Achievement::BigCapture,
+        Achievement::ExtraTurnChain,
+        Achievement::BeatStrongestBot,
+        Achievement::WinStreak,
+    ];
+
+    fn id(&self) -> &'static str {
+        match self {
+            Achievement::FirstWin => "first-win",
+            Achievement::BigCapture => "big-capture",
+            Achievement::ExtraTurnChain => "extra-turn-chain",
+            Achievement::BeatStrongestBot => "beat-strongest-bot",
+            Achievement::WinStreak => "win-streak",
+        }
+    }
+}
+
+impl Display for Achievement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+impl FromStr for Achievement {
+    type Err = DeserializeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Achievement::ALL
+            .into_iter()
+            .find(|a| a.id() == s)
+            .ok_or(DeserializeError::new())
+    }
+}
+
+// What happened during one game, beyond its outcome
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GameHighlights {
+    pub biggest_capture: u8,
+    // Extra turns earned in a row
+    pub longest_extra_turn_chain: usize,
+    pub against_strongest_bot: bool,
+}
+
+// Results of one human against the bots, kept across sessions
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerProfile {
+    pub games: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+    // Positive while winning in a row, negative while losing in a row, draws reset it
+    pub streak: i32,
+    pub longest_win_streak: u32,
+    // First move of every game and how often it was played
+    pub openings: HashMap<u8, usize>,
+    pub achievements: BTreeSet<Achievement>,
+}
+
+impl PlayerProfile {
+    pub fn record(&mut self, outcome: Outcome, opening: u8) {
+        self.games += 1;
+        *self.openings.entry(opening).or_default() += 1;
+        match outcome {
+            Outcome::Win => {
+                self.wins += 1;
+                self.streak = self.streak.max(0) + 1;
+                self.longest_win_streak = self.longest_win_streak.max(self.streak as u32);
+            }
+            Outcome::Loss => {
+                self.losses += 1;
+                self.streak = self.streak.min(0) - 1;
+            }
+            Outcome::Draw => {
+                self.draws += 1;
+                self.streak = 0;
+            }
+        }
+    }
+
+    // Adds the achievements the game earned and returns those that are new. Expects the game to
+    // be recorded already so the streak includes it.
+    pub fn unlock(&mut self, outcome: Outcome, highlights: &GameHighlights) -> Vec<Achievement> {
+        let won = outcome == Outcome::Win;
+        let earned = [
+            (Achievement::FirstWin, won),
+            (
+                Achievement::BigCapture,
+                highlights.biggest_capture >= BIG_CAPTURE,
+            ),
+            (
+                Achievement::ExtraTurnChain,
+                highlights.longest_extra_turn_chain >= EXTRA_TURN_CHAIN,
+            ),
+            (
+                Achievement::BeatStrongestBot,
+                won && highlights.against_strongest_bot,
+            ),
+            (Achievement::WinStreak, self.streak >= WIN_STREAK),
+        ];
+        earned
+            .into_iter()
+            .filter(|&(achievement, earned)| earned && self.achievements.insert(achievement))
+            .map(|(achievement, _)| achievement)
+            .collect()
+    }
+
+    pub fn win_rate(&self) -> f32 {
+        match self.games {
+            0 => 0f32,
+            games => self.wins as f32 / games as f32,
+        }
+    }
+
+    // The most played first moves, ties broken by the lower pit
+    pub fn favorite_openings(&self, count: usize) -> Vec<(u8, usize)> {
+        let mut openings: Vec<(u8, usize)> = self.openings.iter().map(|(&a, &n)| (a, n)).collect();
+        openings.sort_by(|(a, n), (b, m)| m.cmp(n).then(a.cmp(b)));
+        openings.truncate(count);
+        openings
+    }
+}
+
+// Openings are pits counted from the player's side, most played first
+impl ToJson for PlayerProfile {
+    fn to_json(&self) -> Json {
+        let openings = self
+            .favorite_openings(self.openings.len())
+            .into_iter()
+            .map(|(pit, games)| Json::object([("pit", pit.into()), ("games", games.into())]))
+            .collect();
+        Json::object([
+            ("games", self.games.into()),
+            ("wins", self.wins.into()),
+            ("losses", self.losses.into()),
+            ("draws", self.draws.into()),
+            ("win_rate", self.win_rate().into()),
+            ("streak", self.streak.into()),
+            ("longest_win_streak", self.longest_win_streak.into()),
+            ("openings", Json::Array(openings)),
+            (
+                "achievements",
+                self.achievements
+                    .iter()
+                    .map(Achievement::to_string)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profiles {
+    profiles: BTreeMap<String, PlayerProfile>,
+}
+
+impl Profiles {
+    pub fn get(&self, name: &str) -> Option<&PlayerProfile> {
+        self.profiles.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PlayerProfile)> {
+        self.profiles.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+
+    // Returns the achievements unlocked by this game. Names end up in a ';' separated file, one
+    // profile per line.
+    pub fn record(
+        &mut self,
+        name: &str,
+        outcome: Outcome,
+        opening: u8,
+        highlights: &GameHighlights,
+    ) -> Vec<Achievement> {
+        assert!(
+            valid_name(name),
+            "A player name must not be empty or contain ';' or line breaks"
+        );
+        let profile = self.profiles.entry(name.to_owned()).or_default();
+        profile.record(outcome, opening);
+        profile.unlock(outcome, highlights)
+    }
+}
+
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains([';', '\n', '\r'])
+}
+
+// name;games;wins;losses;draws;streak;longest win streak;opening:count ...;achievement ...
+// Files from before achievements existed have no last field.
+impl Serialize for Profiles {
+    fn serialize(&self) -> String {
+        self.profiles
+            .iter()
+            .map(|(name, p)| {
+                let mut openings: Vec<_> = p.openings.iter().collect();
+                openings.sort();
+                format!(
+                    "{};{};{};{};{};{};{};{};{}\n",
+                    name,
+                    p.games,
+                    p.wins,
+                    p.losses,
+                    p.draws,
+                    p.streak,
+                    p.longest_win_streak,
+                    openings
+                        .iter()
+                        .map(|(a, n)| format!("{a}:{n}"))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    p.achievements
+                        .iter()
+                        .map(Achievement::to_string)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )
+            })
+            .collect()
+    }
+}
+
+fn parse<T: FromStr>(part: Option<&str>) -> Result<T, DeserializeError> {
+    match part.map(str::parse::<T>) {
+        Some(Ok(v)) => Ok(v),
+        _ => Err(DeserializeError::new()),
+    }
+}
+
+impl Deserialize for Profiles {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let mut profiles = BTreeMap::new();
+        for line in input.lines().filter(|l| !l.is_empty()) {
+            let mut parts = line.split(';');
+            let name = match parts.next() {
+                Some(name) if valid_name(name) => name.to_owned(),
+                _ => return Err(DeserializeError::new()),
+            };
+            let mut profile = PlayerProfile {
+                games: parse(parts.next())?,
+                wins: parse(parts.next())?,
+                losses: parse(parts.next())?,
+                draws: parse(parts.next())?,
+                streak: parse(parts.next())?,
+                longest_win_streak: parse(parts.next())?,
+                openings: HashMap::new(),
+                achievements: BTreeSet::new(),
+            };
+            let openings = parts.next().ok_or(DeserializeError::new())?;
+            for opening in openings.split(' ').filter(|o| !o.is_empty()) {
+                let (action, count) = opening.split_once(':').ok_or(DeserializeError::new())?;
+                profile
+                    .openings
+                    .insert(parse(Some(action))?, parse(Some(count))?);
+            }
+            if let Some(achievements) = parts.next() {
+                for achievement in achievements.split(' ').filter(|a| !a.is_empty()) {
+                    profile.achievements.insert(achievement.parse()?);
+                }
+            }
+            if parts.next().is_some() {
+                return Err(DeserializeError::new());
+            }
+            profiles.insert(name, profile);
+        }
+        Ok(Profiles { profiles })
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+// Upper bounds of the move latency buckets in seconds, a table lookup takes microseconds
+const LATENCY_BUCKETS: [f64; 8] = [0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.1];
+
+// What traind counts for a scrape, shared by the connection threads and the trainer. Connections
+// hold no games, so they are counted instead: one opens for every client and closes when it hangs
+// up. Coverage is the share of served moves in positions the serving table had learned about.
+#[derive(Default)]
+pub struct ServingMetrics {
+    connections_opened: AtomicU64,
+    connections_closed: AtomicU64,
+    requests: AtomicU64,
+    request_errors: AtomicU64,
+    moves_served: AtomicU64,
+    moves_known: AtomicU64,
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_nanos: AtomicU64,
+    episodes_trained: AtomicU64,
+    serving_episode: AtomicU64,
+}
+
+impl ServingMetrics {
+    pub fn connection_opened(&self) {
+        self.connections_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections_closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Every answer starting with "error" counts as a failed request
+    pub fn request(&self, answer: &str) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if answer.starts_with("error") {
+            self.request_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // `known` is `None` for policies that can not tell, those moves stay out of the coverage
+    pub fn move_served(&self, latency: Duration, known: Option<bool>) {
+        self.moves_served.fetch_add(1, Ordering::Relaxed);
+        if known == Some(true) {
+            self.moves_known.fetch_add(1, Ordering::Relaxed);
+        }
+        let seconds = latency.as_secs_f64();
+        for (bucket, &bound) in self.latency_buckets.iter().zip(&LATENCY_BUCKETS) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.latency_nanos
+            .fetch_add(latency.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn episode_trained(&self) {
+        self.episodes_trained.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn serving(&self, episode: usize) {
+        self.serving_episode
+            .store(episode as u64, Ordering::Relaxed);
+    }
+
+    // The text exposition format, version 0.0.4. The histogram buckets are cumulative already.
+    pub fn render(&self) -> String {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}"
+            );
+        };
+        metric(
+            "mankalla_connections_opened_total",
+            "counter",
+            "Connections accepted on the control socket.",
+            load(&self.connections_opened).to_string(),
+        );
+        metric(
+            "mankalla_connections_closed_total",
+            "counter",
+            "Connections that ended.",
+            load(&self.connections_closed).to_string(),
+        );
+        metric(
+            "mankalla_requests_total",
+            "counter",
+            "Requests answered.",
+            load(&self.requests).to_string(),
+        );
+        metric(
+            "mankalla_request_errors_total",
+            "counter",
+            "Requests answered with an error.",
+            load(&self.request_errors).to_string(),
+        );
+        metric(
+            "mankalla_moves_served_total",
+            "counter",
+            "Moves chosen by the serving policy.",
+            load(&self.moves_served).to_string(),
+        );
+        metric(
+            "mankalla_moves_known_total",
+            "counter",
+            "Moves served in positions the serving table knew.",
+            load(&self.moves_known).to_string(),
+        );
+        let served = load(&self.moves_served);
+        metric(
+            "mankalla_policy_coverage_ratio",
+            "gauge",
+            "Share of the served moves in known positions.",
+            match served {
+                0 => "0".to_owned(),
+                served => (load(&self.moves_known) as f64 / served as f64).to_string(),
+            },
+        );
+        metric(
+            "mankalla_training_episodes_total",
+            "counter",
+            "Episodes trained since the start.",
+            load(&self.episodes_trained).to_string(),
+        );
+        metric(
+            "mankalla_serving_episode",
+            "gauge",
+            "Episode of the policy that is served.",
+            load(&self.serving_episode).to_string(),
+        );
+
+        let name = "mankalla_move_latency_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Time to choose a served move.\n# TYPE {name} histogram"
+        );
+        for (bucket, bound) in self.latency_buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {}", load(bucket));
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {served}");
+        let _ = writeln!(out, "{name}_sum {}", load(&self.latency_nanos) as f64 / 1e9);
+        let _ = writeln!(out, "{name}_count {served}");
+        out
+    }
+}
+
+// Answers `GET /metrics` over plain HTTP until the process ends, and `GET /healthz` and
+// `GET /readyz` for load balancers and reverse proxies, which can not speak the line protocol.
+// Ready is whatever `readiness` answers with "ok" in front, anything else is a 503. Other paths
+// get a 404. One request per connection, which is all a scraper or a probe sends.
+pub fn serve_metrics(
+    listener: TcpListener,
+    metrics: Arc<ServingMetrics>,
+    readiness: impl Fn() -> String + Send + 'static,
+) {
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A scrape that breaks only loses itself
+            let _ = answer_scrape(stream, &metrics, &readiness);
+        }
+    });
+}
+
+fn answer_scrape(
+    mut stream: TcpStream,
+    metrics: &ServingMetrics,
+    readiness: &impl Fn() -> String,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are of no interest but have to be read before answering
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+    let (status, content_type, body) = match request_line.split_whitespace().collect::<Vec<_>>()[..]
+    {
+        ["GET", "/metrics", ..] => ("200 OK", "text/plain; version=0.0.4", metrics.render()),
+        ["GET", "/healthz", ..] => ("200 OK", "text/plain", "ok\n".to_owned()),
+        ["GET", "/readyz", ..] => match readiness() {
+            answer if answer.starts_with("ok") => ("200 OK", "text/plain", answer + "\n"),
+            answer => ("503 Service Unavailable", "text/plain", answer + "\n"),
+        },
+        _ => (
+            "404 Not Found",
+            "text/plain",
+            "Only /metrics, /healthz and /readyz are served\n".to_owned(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: \
+         close\r\n\r\n{body}",
+        body.len()
+    )
+}
@@ -0,0 +1,168 @@
+use crate::hyperparameters::{HyperparameterError, Hyperparameters};
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, EpsilonGreedyPolicy, Policy, Serialize,
+};
+
+const HEADER: &str = "q-lambda";
+
+// Traces that decayed below this are dropped, they would barely move their values
+const TRACE_CUTOFF: f32 = 0.01;
+
+// Watkins's Q(λ): every update also reaches the pairs played before in the episode, weighted by
+// eligibility traces that decay by gamma * lambda per step, so a capture or the end of the game
+// pays the moves that led there in one go instead of one step per episode. Traces are replaced
+// rather than accumulated when a pair comes up again, and cut whenever an exploring move is
+// played, since the moves before it did not lead there greedily. An episode boundary clears them
+// too. The updates assume one game at a time, the traces of interleaved games would mix.
+// Exploration and the table are `EpsilonGreedyPolicy`'s, the file puts lambda in front of its
+// format, the traces are not saved:
+//
+//   q-lambda;<lambda>
+//   <EpsilonGreedyPolicy>
+pub struct QLambdaPolicy<E: Environment> {
+    policy: EpsilonGreedyPolicy<E>,
+    lambda: f32,
+    traces: Vec<(E::ActionRelevantState, E::Action, f32)>,
+}
+
+impl<E: Environment> QLambdaPolicy<E> {
+    // Goes on from what the policy learned so far. Lambda 0 learns like one-step Q-learning.
+    pub fn new(policy: EpsilonGreedyPolicy<E>, lambda: f32) -> Self {
+        QLambdaPolicy {
+            policy,
+            lambda,
+            traces: Vec::new(),
+        }
+    }
+
+    pub fn from_hyperparameters(
+        hyperparameters: Hyperparameters,
+        lambda: f32,
+    ) -> Result<Self, HyperparameterError> {
+        Ok(QLambdaPolicy::new(
+            EpsilonGreedyPolicy::from_hyperparameters(hyperparameters)?,
+            lambda,
+        ))
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.policy.reseed(seed);
+    }
+
+    pub fn lambda(&self) -> f32 {
+        self.lambda
+    }
+
+    // Pairs with a trace right now
+    pub fn traces(&self) -> usize {
+        self.traces.len()
+    }
+
+    pub fn epsilon_greedy_policy(&self) -> &EpsilonGreedyPolicy<E> {
+        &self.policy
+    }
+
+    pub fn epsilon_greedy_policy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        &mut self.policy
+    }
+
+    pub fn into_epsilon_greedy_policy(self) -> EpsilonGreedyPolicy<E> {
+        self.policy
+    }
+
+    fn max_value(&self, state: E::ActionRelevantState) -> f32 {
+        let greedy_policy = self.policy.greedy_policy();
+        greedy_policy.value(state, greedy_policy.choose_action(state, None))
+    }
+}
+
+impl<E: Environment> Policy<E> for QLambdaPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.policy.choose_action(state, mask)
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.policy.record_visit(state);
+        if self.policy.greedy_policy().choose_action(state, None) != action {
+            self.traces.clear();
+        }
+        self.traces.retain(|&(s, a, _)| s != state || a != action);
+        self.traces.push((state, action, 1f32));
+
+        let gamma = self.policy.gamma();
+        let next_value = match finished {
+            false => self.max_value(next_state.into()),
+            true => 0f32,
+        };
+        let error = reward + gamma * next_value - self.policy.greedy_policy().value(state, action);
+        let learning_rate = self.policy.hyperparameters().learning_rate;
+        let greedy_policy = self.policy.greedy_policy_mut();
+        for &(s, a, trace) in &self.traces {
+            let former_value = greedy_policy.value(s, a);
+            greedy_policy.set_value(s, a, former_value + learning_rate * error * trace);
+        }
+
+        match finished {
+            true => self.traces.clear(),
+            false => {
+                let decay = gamma * self.lambda;
+                self.traces.retain_mut(|(_, _, trace)| {
+                    *trace *= decay;
+                    *trace >= TRACE_CUTOFF
+                });
+            }
+        }
+    }
+
+    fn on_episode_increment(&mut self) {
+        self.traces.clear();
+        self.policy.on_episode_increment();
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.policy.action_distribution(state)
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.policy.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.policy.knows_state(state)
+    }
+}
+
+impl<E: Environment> Serialize for QLambdaPolicy<E> {
+    fn serialize(&self) -> String {
+        format!("{HEADER};{}\n", self.lambda) + &self.policy.serialize()
+    }
+}
+
+impl<E: Environment> Deserialize for QLambdaPolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let (first, rest) = input
+            .split_once('\n')
+            .ok_or_else(|| DeserializeError::because("a Q(λ) policy has a first line"))?;
+        let lambda = first
+            .strip_prefix(HEADER)
+            .and_then(|lambda| lambda.strip_prefix(';'))
+            .and_then(|lambda| lambda.parse::<f32>().ok())
+            .filter(|lambda| (0f32..=1f32).contains(lambda));
+        let Some(lambda) = lambda else {
+            return Err(DeserializeError::because(format!(
+                "a Q(λ) policy starts with \"{HEADER};<lambda in [0, 1]>\""
+            )));
+        };
+        Ok(QLambdaPolicy::new(
+            EpsilonGreedyPolicy::deserialize(rest)?,
+            lambda,
+        ))
+    }
+}
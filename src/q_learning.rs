@@ -2,167 +2,1312 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Display;
 use std::hash::Hash;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
 
+use rand::distr::Distribution;
+use rand::distr::weighted::WeightedIndex;
+use rand::rngs::StdRng;
 use rand::seq::IndexedRandom;
+use rand::{Rng, RngCore, SeedableRng};
+
+use crate::hyperparameters::{HyperparameterError, HyperparameterWarning, Hyperparameters};
+use crate::replay::ReplayBuffer;
+use crate::schedule::{ExponentialDecay, LinearRamp, Reheat, Schedule};
+use crate::search::SearchStats;
+use crate::thompson::sample_dirichlet;
+use crate::vec_env::VecEnv;
 
 pub trait Environment {
     type State: Copy;
     type ActionRelevantState: From<Self::State> + Copy + Eq + Hash + Serialize + Deserialize;
-    type Action: Copy + Eq + Hash + Serialize + Deserialize;
-    fn actions(state: &Self::ActionRelevantState) -> Vec<Self::Action>;
-    fn step(state: &Self::State, action: &Self::Action) -> (Self::State, f32, bool);
+    type Action: Copy + Default + Eq + Hash + Serialize + Deserialize;
+    // Every action index is below this, and it is at most `ActionMask::CAPACITY`
+    const MAX_ACTIONS: usize;
+    fn actions(state: &Self::ActionRelevantState) -> ActionList<Self::Action>;
+    // Where the action sits in an `ActionMask`, distinct per action and below `MAX_ACTIONS`
+    fn action_index(action: &Self::Action) -> usize;
+    // The reward and outcome belong to whoever chose the action, which is all a learner needs.
+    // Code about a two-player game that keeps track of one of the players goes through
+    // `two_player::play` instead, which says whose they are.
+    fn step(state: &Self::State, action: &Self::Action) -> (Self::State, f32, Option<Outcome>);
+    // Where the action leads before anything the learner does not control happens, an opponent's
+    // reply or a card, with the reward paid that far. Actions with the same afterstate are worth
+    // the same from there on, `AfterstatePolicy` learns one value for all of them.
+    type Afterstate: Copy + Eq + Hash + Serialize + Deserialize;
+    fn afterstate(
+        state: &Self::ActionRelevantState,
+        action: &Self::Action,
+    ) -> (Self::Afterstate, f32);
     fn new() -> Self::State;
+    // Environments with chance events carry their random stream in the state, started from the
+    // seed here, deterministic ones start the same way every time
+    fn new_seeded(_seed: u64) -> Self::State {
+        Self::new()
+    }
+}
+
+// Seen from the player who made the final move, `None` while the episode is running
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Outcome {
+    // The same result seen from the other player
+    pub fn opposite(self) -> Outcome {
+        match self {
+            Outcome::Win => Outcome::Loss,
+            Outcome::Loss => Outcome::Win,
+            Outcome::Draw => Outcome::Draw,
+        }
+    }
+}
+
+// The actions allowed right now, one bit per `Environment::action_index`. Policies that score a
+// fixed set of actions can drop the illegal ones from it directly, table policies narrow
+// `E::actions` down with `masked_actions`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ActionMask(u64);
+
+impl ActionMask {
+    pub const CAPACITY: usize = 64;
+
+    pub fn new(bits: u64) -> Self {
+        ActionMask(bits)
+    }
+
+    pub fn from_actions<E: Environment>(actions: &[E::Action]) -> Self {
+        ActionMask(
+            actions
+                .iter()
+                .fold(0, |bits, action| bits | 1 << checked_index::<E>(action)),
+        )
+    }
+
+    // Everything the rules allow in this state
+    pub fn legal<E: Environment>(state: &E::ActionRelevantState) -> Self {
+        ActionMask::from_actions::<E>(&E::actions(state))
+    }
+
+    pub fn allows<E: Environment>(&self, action: &E::Action) -> bool {
+        self.0 & 1 << checked_index::<E>(action) != 0
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+// Environments that break the `MAX_ACTIONS` contract fail to compile, actions that break it panic
+fn checked_index<E: Environment>(action: &E::Action) -> usize {
+    const {
+        assert!(
+            E::MAX_ACTIONS <= ActionMask::CAPACITY,
+            "MAX_ACTIONS is larger than an ActionMask holds"
+        )
+    };
+    let index = E::action_index(action);
+    assert!(
+        index < E::MAX_ACTIONS,
+        "Action index {index} is not below MAX_ACTIONS = {}",
+        E::MAX_ACTIONS
+    );
+    index
+}
+
+// The legal actions of the state, only those the mask allows if there is one
+pub fn masked_actions<E: Environment>(
+    state: &E::ActionRelevantState,
+    mask: Option<ActionMask>,
+) -> ActionList<E::Action> {
+    let actions = E::actions(state);
+    match mask {
+        Some(mask) => actions
+            .into_iter()
+            .filter(|a| mask.allows::<E>(a))
+            .collect(),
+        None => actions,
+    }
+}
+
+// Whether policies check their choices against the legal actions. Debug builds always do, release
+// builds only with the `validate-actions` feature since it costs a listing of the actions per move.
+pub const VALIDATE_ACTIONS: bool = cfg!(any(debug_assertions, feature = "validate-actions"));
+
+// Hands the choice back if it is legal in the state and allowed by the mask. Policies that choose
+// from their own records, a table or a search done earlier, run what they picked through this
+// before anyone plays it, so a corrupted record stops the game with the state it happened in
+// instead of an illegal move.
+pub fn checked_choice<E: Environment>(
+    state: &E::ActionRelevantState,
+    mask: Option<ActionMask>,
+    action: E::Action,
+) -> E::Action {
+    if VALIDATE_ACTIONS && !masked_actions::<E>(state, mask).contains(&action) {
+        let legal = masked_actions::<E>(state, mask)
+            .iter()
+            .map(Serialize::serialize)
+            .collect::<Vec<_>>()
+            .join(", ");
+        panic!(
+            "The policy chose {} in state {}, legal there are only [{legal}]",
+            action.serialize(),
+            state.serialize()
+        );
+    }
+    action
+}
+
+// The actions of one state, kept inline so listing them never allocates. There is room for
+// `ActionMask::CAPACITY` of them, which no environment exceeds.
+#[derive(Clone, Copy)]
+pub struct ActionList<A> {
+    actions: [A; ActionMask::CAPACITY],
+    len: usize,
+}
+
+impl<A: Copy + Default> ActionList<A> {
+    pub fn new() -> Self {
+        ActionList {
+            actions: [A::default(); ActionMask::CAPACITY],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, action: A) {
+        assert!(
+            self.len < ActionMask::CAPACITY,
+            "More than {} actions in one state",
+            ActionMask::CAPACITY
+        );
+        self.actions[self.len] = action;
+        self.len += 1;
+    }
+}
+
+impl<A: Copy + Default> Default for ActionList<A> {
+    fn default() -> Self {
+        ActionList::new()
+    }
+}
+
+impl<A> Deref for ActionList<A> {
+    type Target = [A];
+
+    fn deref(&self) -> &[A] {
+        &self.actions[..self.len]
+    }
+}
+
+impl<A> DerefMut for ActionList<A> {
+    fn deref_mut(&mut self) -> &mut [A] {
+        &mut self.actions[..self.len]
+    }
+}
+
+impl<A: std::fmt::Debug> std::fmt::Debug for ActionList<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<A: PartialEq> PartialEq for ActionList<A> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<A: Copy + Default> FromIterator<A> for ActionList<A> {
+    fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+        let mut list = ActionList::new();
+        for action in iter {
+            list.push(action);
+        }
+        list
+    }
+}
+
+impl<A: Copy> IntoIterator for ActionList<A> {
+    type Item = A;
+    type IntoIter = std::iter::Take<std::array::IntoIter<A, { ActionMask::CAPACITY }>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.actions.into_iter().take(self.len)
+    }
+}
+
+impl<'a, A> IntoIterator for &'a ActionList<A> {
+    type Item = &'a A;
+    type IntoIter = std::slice::Iter<'a, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub trait Policy<E: Environment> {
+    // Picks among the legal actions, restricted to the mask if one is given
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action;
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    );
+    // `improve` with the action already chosen for `next_state`, `None` once the episode is over.
+    // On-policy learners bootstrap from it, the others learn as from `improve`.
+    fn improve_on_policy(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        next_action: Option<E::Action>,
+    ) {
+        self.improve(state, action, reward, next_state, next_action.is_none());
+    }
+    fn on_episode_increment(&mut self) {}
+    // Probabilities with which `choose_action` picks each legal action
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        vec![(self.choose_action(state, None), 1f32)]
+    }
+    // How much the policy thinks of each legal action, for policies that keep values
+    fn action_values(&self, _state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        None
+    }
+    // Whether the policy learned anything about `state` or falls back to its defaults there, for
+    // policies that keep a table
+    fn knows_state(&self, _state: E::ActionRelevantState) -> Option<bool> {
+        None
+    }
+    // What the last `choose_action` cost, for policies that search
+    fn last_search(&self) -> Option<SearchStats<E::Action>> {
+        None
+    }
+    // A hint that `state` probably comes up next, policies that search may start on it in the
+    // background. The next `choose_action` ends that, whatever state it gets.
+    fn ponder(&self, _state: E::ActionRelevantState) {}
+}
+
+// Lets runtime-selected policies (`Box<dyn Policy<E>>`) go wherever a concrete policy is expected
+impl<E: Environment, P: Policy<E> + ?Sized> Policy<E> for Box<P> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        (**self).choose_action(state, mask)
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        (**self).improve(state, action, reward, next_state, finished)
+    }
+
+    fn improve_on_policy(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        next_action: Option<E::Action>,
+    ) {
+        (**self).improve_on_policy(state, action, reward, next_state, next_action)
+    }
+
+    fn on_episode_increment(&mut self) {
+        (**self).on_episode_increment()
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        (**self).action_distribution(state)
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        (**self).action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        (**self).knows_state(state)
+    }
+
+    fn last_search(&self) -> Option<SearchStats<E::Action>> {
+        (**self).last_search()
+    }
+
+    fn ponder(&self, state: E::ActionRelevantState) {
+        (**self).ponder(state)
+    }
+}
+
+// A policy that can also be written to disk, object safe so it can be boxed as well
+pub trait PersistentPolicy<E: Environment>: Policy<E> + Serialize {}
+
+impl<E: Environment, P: Policy<E> + Serialize + ?Sized> PersistentPolicy<E> for P {}
+
+pub trait Serialize {
+    fn serialize(&self) -> String;
+}
+
+impl<T: Serialize + ?Sized> Serialize for Box<T> {
+    fn serialize(&self) -> String {
+        (**self).serialize()
+    }
+}
+
+pub trait Deserialize {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError>
+    where
+        Self: Sized;
+}
+
+// Most inputs are just malformed, a reason is given where it helps to find the problem
+#[derive(Debug, Default)]
+pub struct DeserializeError {
+    reason: Option<String>,
+}
+
+impl DeserializeError {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn because(reason: impl Into<String>) -> Self {
+        DeserializeError {
+            reason: Some(reason.into()),
+        }
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
 }
 
-pub trait Policy<E: Environment> {
-    fn choose_action(&self, state: E::ActionRelevantState) -> E::Action;
-    fn improve(
+// Implements `Serialize` and `Deserialize` through `Display` and `FromStr`, for custom states and
+// actions of third-party games. The text must not contain ';', ' ' or line breaks since those
+// separate the fields of a saved policy.
+//
+//     impl_serialization_via_str!(MyAction, MyState);
+#[macro_export]
+macro_rules! impl_serialization_via_str {
+    ($($t:ty),+ $(,)?) => {
+        $(
+            impl $crate::q_learning::Serialize for $t {
+                fn serialize(&self) -> String {
+                    self.to_string()
+                }
+            }
+
+            impl $crate::q_learning::Deserialize for $t {
+                fn deserialize(
+                    input: &str,
+                ) -> Result<Self, $crate::q_learning::DeserializeError> {
+                    input
+                        .parse::<$t>()
+                        .map_err(|_| $crate::q_learning::DeserializeError::new())
+                }
+            }
+        )+
+    };
+}
+
+impl_serialization_via_str!(u8, u16, u32, u64, usize, i8, i16, i32, i64, bool, char);
+
+// Space separated, so the elements themselves must not serialize to anything containing a space
+impl<T: Serialize, const N: usize> Serialize for [T; N] {
+    fn serialize(&self) -> String {
+        self.iter()
+            .map(Serialize::serialize)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl<T: Deserialize + Copy + Default, const N: usize> Deserialize for [T; N] {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let mut values = [T::default(); N];
+        let mut elems = input.split(' ');
+        for value in values.iter_mut() {
+            match elems.next() {
+                Some(elem) => *value = T::deserialize(elem)?,
+                None => return Err(DeserializeError::new()),
+            }
+        }
+        match elems.next() {
+            Some(_) => Err(DeserializeError::new()),
+            None => Ok(values),
+        }
+    }
+}
+
+impl Error for DeserializeError {}
+
+impl Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "Error deserializing input: {reason}"),
+            None => write!(f, "Error deserializing input"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EpisodeStats {
+    pub episode: usize,
+    pub steps: usize,
+    pub total_reward: f32,
+    pub outcome: Option<Outcome>,
+    pub clipped_steps: usize,
+}
+
+// Added on top of the environment reward once an episode ends
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RewardOptions {
+    pub win: f32,
+    pub loss: f32,
+    pub draw: f32,
+    // Caps every environment reward to [-clip, clip] so single huge captures can not dominate the
+    // table, the terminal rewards above are added after and kept as they are
+    pub clip: Option<f32>,
+}
+
+// The first `plies` moves of every training game are drawn from a softmax over the learner's
+// action values, after that it chooses as usual. Games start out varied without exploring any
+// more in the endgame, where a wasted move costs the most. Each episode draws from its own stream
+// of `seed`, games played in lockstep share one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OpeningTemperature {
+    pub plies: usize,
+    pub temperature: f32,
+    pub seed: u64,
+}
+
+impl OpeningTemperature {
+    pub fn new(plies: usize, temperature: f32, seed: u64) -> Self {
+        assert!(temperature > 0f32, "The temperature has to be positive");
+        OpeningTemperature {
+            plies,
+            temperature,
+            seed,
+        }
+    }
+
+    fn rng(&self, episode: usize) -> StdRng {
+        StdRng::seed_from_u64(self.seed ^ (episode as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TrainingOptions {
+    pub max_steps: Option<usize>,
+    pub rewards: RewardOptions,
+    pub opening_temperature: Option<OpeningTemperature>,
+    // Every episode's environment gets its own stream from this, `Environment::new` without it
+    pub environment_seed: Option<u64>,
+}
+
+// The first state of the `episode`th game
+pub fn episode_start<E: Environment>(environment_seed: Option<u64>, episode: usize) -> E::State {
+    match environment_seed {
+        Some(seed) => E::new_seeded(seed ^ (episode as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)),
+        None => E::new(),
+    }
+}
+
+impl TrainingOptions {
+    // Only drawn from while the opening is tempered
+    pub fn opening_rng(&self, episode: usize) -> Option<StdRng> {
+        self.opening_temperature.map(|t| t.rng(episode))
+    }
+
+    // The move for the `ply`th step of a game, counted from 0
+    pub fn choose_action<E: Environment>(
+        &self,
+        policy: &(impl Policy<E> + ?Sized),
+        state: E::ActionRelevantState,
+        ply: usize,
+        rng: &mut Option<StdRng>,
+    ) -> E::Action {
+        match (self.opening_temperature, rng) {
+            (Some(t), Some(rng)) if ply < t.plies => {
+                softmax_action::<E>(policy, state, t.temperature, rng)
+            }
+            _ => policy.choose_action(state, None),
+        }
+    }
+}
+
+// Draws with probability proportional to exp(value / temperature). Policies without values pick
+// uniformly among the legal moves.
+pub fn softmax_action<E: Environment>(
+    policy: &(impl Policy<E> + ?Sized),
+    state: E::ActionRelevantState,
+    temperature: f32,
+    rng: &mut dyn RngCore,
+) -> E::Action {
+    let Some(values) = policy.action_values(state) else {
+        return *E::actions(&state)
+            .choose(rng)
+            .expect("A running game always has a legal move");
+    };
+    let (actions, values): (Vec<_>, Vec<_>) = values.into_iter().unzip();
+    let chosen = WeightedIndex::new(softmax(&values, temperature))
+        .expect("A running game always has a legal move")
+        .sample(rng);
+    actions[chosen]
+}
+
+// The probability of each value, exp(value / temperature) normalized. The largest value is
+// subtracted first so that big values at a low temperature do not overflow.
+pub fn softmax(values: &[f32], temperature: f32) -> Vec<f32> {
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let weights: Vec<f32> = values
+        .iter()
+        .map(|value| ((value - max) / temperature).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.iter().map(|weight| weight / total).collect()
+}
+
+impl RewardOptions {
+    pub fn terminal_reward(&self, outcome: Option<Outcome>) -> f32 {
+        match outcome {
+            Some(Outcome::Win) => self.win,
+            Some(Outcome::Loss) => self.loss,
+            Some(Outcome::Draw) => self.draw,
+            None => 0f32,
+        }
+    }
+
+    // The reward the policy learns from, together with whether clipping changed it
+    pub fn shape(&self, reward: f32, outcome: Option<Outcome>) -> (f32, bool) {
+        let (reward, clipped) = match self.clip {
+            Some(c) if reward.abs() > c => (reward.clamp(-c, c), true),
+            _ => (reward, false),
+        };
+        (reward + self.terminal_reward(outcome), clipped)
+    }
+}
+
+pub trait TrainingObserver<E: Environment, P: Policy<E> + ?Sized> {
+    // `env` tells interleaved games apart when training vectorized, it is always 0 otherwise
+    fn on_step(&mut self, _env: usize, _transition: &Transition<E>) {}
+
+    fn on_episode_end(&mut self, policy: &P, stats: &EpisodeStats);
+
+    // Called right after `on_episode_end` for observers that steer training, e.g. the exploration rate
+    fn adjust_policy(&mut self, _policy: &mut P) {}
+
+    // Ends training early, checked after every episode
+    fn should_stop(&self) -> bool {
+        false
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized> TrainingObserver<E, P> for () {
+    fn on_episode_end(&mut self, _policy: &P, _stats: &EpisodeStats) {}
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized> TrainingObserver<E, P> for Option<EpisodeStats> {
+    fn on_episode_end(&mut self, _policy: &P, stats: &EpisodeStats) {
+        *self = Some(*stats);
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized, T> TrainingObserver<E, P> for &mut T
+where
+    T: TrainingObserver<E, P> + ?Sized,
+{
+    fn on_step(&mut self, env: usize, transition: &Transition<E>) {
+        (**self).on_step(env, transition);
+    }
+
+    fn on_episode_end(&mut self, policy: &P, stats: &EpisodeStats) {
+        (**self).on_episode_end(policy, stats);
+    }
+
+    fn adjust_policy(&mut self, policy: &mut P) {
+        (**self).adjust_policy(policy);
+    }
+
+    fn should_stop(&self) -> bool {
+        (**self).should_stop()
+    }
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized, A, B> TrainingObserver<E, P> for (A, B)
+where
+    A: TrainingObserver<E, P>,
+    B: TrainingObserver<E, P>,
+{
+    fn on_step(&mut self, env: usize, transition: &Transition<E>) {
+        self.0.on_step(env, transition);
+        self.1.on_step(env, transition);
+    }
+
+    fn on_episode_end(&mut self, policy: &P, stats: &EpisodeStats) {
+        self.0.on_episode_end(policy, stats);
+        self.1.on_episode_end(policy, stats);
+    }
+
+    fn adjust_policy(&mut self, policy: &mut P) {
+        self.0.adjust_policy(policy);
+        self.1.adjust_policy(policy);
+    }
+
+    fn should_stop(&self) -> bool {
+        self.0.should_stop() || self.1.should_stop()
+    }
+}
+
+pub struct QLearning;
+
+impl QLearning {
+    pub fn train<E: Environment>(
+        policy: &mut (impl Policy<E> + ?Sized),
+        num_training_episodes: usize,
+        max_steps: Option<usize>,
+    ) {
+        let options = TrainingOptions {
+            max_steps,
+            ..Default::default()
+        };
+        QLearning::train_observed(policy, num_training_episodes, &options, &mut ());
+    }
+
+    pub fn train_observed<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+        num_training_episodes: usize,
+        options: &TrainingOptions,
+        observer: &mut impl TrainingObserver<E, P>,
+    ) {
+        for episode in 0..num_training_episodes {
+            let stats = QLearning::one_episode(policy, episode, options, observer);
+            observer.on_episode_end(policy, &stats);
+            observer.adjust_policy(policy);
+            if observer.should_stop() {
+                break;
+            }
+        }
+    }
+
+    // `train_observed` that also keeps every transition in `buffer` and learns from a batch of it
+    // after each episode. The replayed updates see the rewards as shaped when they were stored and
+    // are not shown to the observer. Only for learners whose update does not depend on the order of
+    // the moves, eligibility traces or on-policy targets would be fed the wrong history.
+    pub fn train_with_replay<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+        num_training_episodes: usize,
+        options: &TrainingOptions,
+        buffer: &mut ReplayBuffer<E>,
+        observer: &mut impl TrainingObserver<E, P>,
+    ) {
+        let mut storing = (&mut *buffer, &mut *observer);
+        for episode in 0..num_training_episodes {
+            let stats = QLearning::one_episode(policy, episode, options, &mut storing);
+            for transition in storing.0.sample() {
+                policy.improve(
+                    transition.state.into(),
+                    transition.action,
+                    transition.reward,
+                    transition.next_state,
+                    transition.outcome.is_some(),
+                );
+            }
+            storing.1.on_episode_end(policy, &stats);
+            storing.1.adjust_policy(policy);
+            if storing.1.should_stop() {
+                break;
+            }
+        }
+    }
+
+    // Plays `num_envs` games in lockstep so each iteration yields one transition per game
+    pub fn train_vectorized<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+        num_envs: usize,
+        num_training_episodes: usize,
+        options: &TrainingOptions,
+        observer: &mut impl TrainingObserver<E, P>,
+    ) {
+        let mut envs = VecEnv::<E>::new(num_envs, options.max_steps, options.environment_seed);
+        let mut returns = vec![0f32; num_envs];
+        let mut clipped_steps = vec![0; num_envs];
+        let mut rng = options.opening_rng(0);
+        let mut episode = 0;
+        while episode < num_training_episodes {
+            let actions: Vec<E::Action> = envs
+                .states()
+                .iter()
+                .zip(envs.steps())
+                .map(|(&state, &ply)| options.choose_action(&*policy, state.into(), ply, &mut rng))
+                .collect();
+            let (transitions, finished) = envs.step(&actions);
+            for (i, transition) in transitions.into_iter().enumerate() {
+                let (reward, clipped) =
+                    options.rewards.shape(transition.reward, transition.outcome);
+                returns[i] += reward;
+                clipped_steps[i] += clipped as usize;
+                policy.improve(
+                    transition.state.into(),
+                    transition.action,
+                    reward,
+                    transition.next_state,
+                    transition.outcome.is_some(),
+                );
+                observer.on_step(
+                    i,
+                    &Transition {
+                        reward,
+                        clipped,
+                        ..transition
+                    },
+                );
+            }
+            for finished_episode in finished {
+                let i = finished_episode.index;
+                let stats = EpisodeStats {
+                    episode,
+                    steps: finished_episode.steps,
+                    total_reward: std::mem::take(&mut returns[i]),
+                    outcome: finished_episode.outcome,
+                    clipped_steps: std::mem::take(&mut clipped_steps[i]),
+                };
+                if episode == num_training_episodes {
+                    break;
+                }
+                policy.on_episode_increment();
+                observer.on_episode_end(policy, &stats);
+                observer.adjust_policy(policy);
+                episode += 1;
+                if observer.should_stop() {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Runs one training episode lazily, the policy improves on every transition it yields
+    pub fn episode_iter<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+    ) -> EpisodeIter<'_, E, P> {
+        QLearning::episode_iter_with_options(policy, TrainingOptions::default())
+    }
+
+    // A tempered opening is drawn like the first episode's
+    pub fn episode_iter_with_options<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+        options: TrainingOptions,
+    ) -> EpisodeIter<'_, E, P> {
+        QLearning::numbered_episode_iter(policy, options, 0)
+    }
+
+    fn numbered_episode_iter<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+        options: TrainingOptions,
+        episode: usize,
+    ) -> EpisodeIter<'_, E, P> {
+        EpisodeIter {
+            policy,
+            state: Some(episode_start::<E>(options.environment_seed, episode)),
+            steps: 0,
+            options,
+            rng: options.opening_rng(episode),
+        }
+    }
+
+    fn one_episode<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+        episode: usize,
+        options: &TrainingOptions,
+        observer: &mut impl TrainingObserver<E, P>,
+    ) -> EpisodeStats {
+        let mut stats = EpisodeStats {
+            episode,
+            steps: 0,
+            total_reward: 0f32,
+            outcome: None,
+            clipped_steps: 0,
+        };
+
+        for transition in QLearning::numbered_episode_iter(policy, *options, episode) {
+            stats.steps += 1;
+            stats.total_reward += transition.reward;
+            stats.outcome = transition.outcome;
+            stats.clipped_steps += transition.clipped as usize;
+            observer.on_step(0, &transition);
+        }
+
+        stats
+    }
+
+    // Plays one episode with the policy's choices and the options' rewards without learning from
+    // any of it, for learners that wait for the end of the episode and for datasets that record
+    // the policy as it is. The policy is not told the episode ended either, that is up to whoever
+    // learns from the trajectory.
+    pub fn collect_episode<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &P,
+        episode: usize,
+        options: &TrainingOptions,
+    ) -> Trajectory<E> {
+        let mut trajectory = Trajectory {
+            transitions: Vec::new(),
+            stats: EpisodeStats {
+                episode,
+                steps: 0,
+                total_reward: 0f32,
+                outcome: None,
+                clipped_steps: 0,
+            },
+        };
+        let mut rng = options.opening_rng(episode);
+        let mut state = episode_start::<E>(options.environment_seed, episode);
+        loop {
+            let stats = &mut trajectory.stats;
+            let action = options.choose_action(policy, state.into(), stats.steps, &mut rng);
+            let (next_state, reward, outcome) = E::step(&state, &action);
+            let (reward, clipped) = options.rewards.shape(reward, outcome);
+            stats.steps += 1;
+            stats.total_reward += reward;
+            stats.outcome = outcome;
+            stats.clipped_steps += clipped as usize;
+
+            let truncated =
+                outcome.is_none() && options.max_steps.is_some_and(|m| stats.steps >= m);
+            trajectory.transitions.push(Transition {
+                state,
+                action,
+                reward,
+                next_state,
+                outcome,
+                truncated,
+                clipped,
+            });
+            if outcome.is_some() || truncated {
+                return trajectory;
+            }
+            state = next_state;
+        }
+    }
+}
+
+// One episode as `QLearning::collect_episode` played it, in the order of the moves
+pub struct Trajectory<E: Environment> {
+    pub transitions: Vec<Transition<E>>,
+    pub stats: EpisodeStats,
+}
+
+pub struct Transition<E: Environment> {
+    pub state: E::State,
+    pub action: E::Action,
+    pub reward: f32,
+    pub next_state: E::State,
+    pub outcome: Option<Outcome>,
+    // Set when the episode was cut off by the step limit instead of reaching an outcome
+    pub truncated: bool,
+    // Set when reward clipping changed the reward
+    pub clipped: bool,
+}
+
+// Derived impls would require `E` itself to be `Copy`
+impl<E: Environment> Clone for Transition<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E: Environment> Copy for Transition<E> {}
+
+pub struct EpisodeIter<'a, E: Environment, P: Policy<E> + ?Sized> {
+    policy: &'a mut P,
+    state: Option<E::State>,
+    steps: usize,
+    options: TrainingOptions,
+    rng: Option<StdRng>,
+}
+
+impl<E: Environment, P: Policy<E> + ?Sized> Iterator for EpisodeIter<'_, E, P> {
+    type Item = Transition<E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let state = self.state.take()?;
+        let action =
+            self.options
+                .choose_action(&*self.policy, state.into(), self.steps, &mut self.rng);
+
+        let (next_state, reward, outcome) = E::step(&state, &action);
+        let (reward, clipped) = self.options.rewards.shape(reward, outcome);
+        self.policy
+            .improve(state.into(), action, reward, next_state, outcome.is_some());
+        self.steps += 1;
+
+        let truncated =
+            outcome.is_none() && self.options.max_steps.is_some_and(|m| self.steps >= m);
+        match outcome.is_some() || truncated {
+            true => self.policy.on_episode_increment(),
+            false => self.state = Some(next_state),
+        }
+
+        Some(Transition {
+            state,
+            action,
+            reward,
+            next_state,
+            outcome,
+            truncated,
+            clipped,
+        })
+    }
+}
+
+// The values of one state, a slot for every action index. Slots that were never set hold NaN,
+// which `GreedyPolicy::set_value` keeps out of the real values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QRow<'a>(&'a [f32]);
+
+impl QRow<'_> {
+    pub fn get(&self, index: usize) -> Option<f32> {
+        match self.0[index].is_nan() {
+            true => None,
+            false => Some(self.0[index]),
+        }
+    }
+
+    // By action index, `None` for slots that were never set
+    pub fn values(&self) -> impl Iterator<Item = Option<f32>> {
+        (0..self.0.len()).map(|index| self.get(index))
+    }
+}
+
+// Q-values by state, so one lookup gives the values of all actions of a state. The rows sit back
+// to back in one buffer, a new state costs no allocation of its own.
+pub struct QTable<E: Environment> {
+    rows: HashMap<E::ActionRelevantState, usize>,
+    // `E::MAX_ACTIONS` slots per row
+    values: Vec<f32>,
+    // Values set, over all rows
+    len: usize,
+}
+
+impl<E: Environment> QTable<E> {
+    pub fn new() -> Self {
+        QTable {
+            rows: HashMap::new(),
+            values: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn row(&self, state: &E::ActionRelevantState) -> Option<QRow<'_>> {
+        self.rows.get(state).map(|&row| self.row_at(row))
+    }
+
+    fn row_at(&self, row: usize) -> QRow<'_> {
+        QRow(&self.values[row * E::MAX_ACTIONS..(row + 1) * E::MAX_ACTIONS])
+    }
+
+    pub fn get(&self, state: &E::ActionRelevantState, action: &E::Action) -> Option<f32> {
+        self.row(state)
+            .and_then(|row| row.get(checked_index::<E>(action)))
+    }
+
+    pub fn insert(&mut self, state: E::ActionRelevantState, action: E::Action, value: f32) {
+        assert!(!value.is_nan(), "NaN marks values that were never set");
+        let rows = self.rows.len();
+        let row = *self.rows.entry(state).or_insert(rows);
+        if row == rows {
+            self.values.resize((rows + 1) * E::MAX_ACTIONS, f32::NAN);
+        }
+        let slot = &mut self.values[row * E::MAX_ACTIONS + checked_index::<E>(&action)];
+        if slot.is_nan() {
+            self.len += 1;
+        }
+        *slot = value;
+    }
+
+    // For values read from a file, which may be broken or belong to other rules
+    pub fn try_insert(
         &mut self,
         state: E::ActionRelevantState,
         action: E::Action,
-        reward: f32,
-        next_state: E::State,
-        finished: bool,
-    );
-    fn on_episode_increment(&mut self) {}
-}
+        value: f32,
+    ) -> Result<(), DeserializeError> {
+        if !value.is_finite() {
+            return Err(DeserializeError::because("non-finite Q-value"));
+        }
+        if !ActionMask::legal::<E>(&state).allows::<E>(&action) {
+            return Err(DeserializeError::because("Q-value for an illegal action"));
+        }
+        self.insert(state, action, value);
+        Ok(())
+    }
 
-pub trait Serialize {
-    fn serialize(&self) -> String;
-}
+    // Every value that was set. Only legal actions can be set, so they are all found by listing
+    // the actions of each state.
+    pub fn iter(&self) -> impl Iterator<Item = (E::ActionRelevantState, E::Action, f32)> + '_ {
+        self.rows.iter().flat_map(|(&state, &row)| {
+            let row = self.row_at(row);
+            E::actions(&state).into_iter().filter_map(move |action| {
+                row.get(E::action_index(&action))
+                    .map(|value| (state, action, value))
+            })
+        })
+    }
 
-pub trait Deserialize {
-    fn deserialize(input: &str) -> Result<Self, DeserializeError>
-    where
-        Self: Sized;
-}
+    // Each state once with its row, in no particular order
+    pub fn rows(&self) -> impl Iterator<Item = (E::ActionRelevantState, QRow<'_>)> + '_ {
+        self.rows
+            .iter()
+            .map(|(&state, &row)| (state, self.row_at(row)))
+    }
 
-#[derive(Debug)]
-pub struct DeserializeError;
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-impl Error for DeserializeError {}
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 
-impl Display for DeserializeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error deserializing input")
+    pub fn states(&self) -> usize {
+        self.rows.len()
     }
 }
 
-pub struct QLearning;
+impl<E: Environment> Default for QTable<E> {
+    fn default() -> Self {
+        QTable::new()
+    }
+}
 
-impl QLearning {
-    pub fn train<E: Environment>(
-        policy: &mut impl Policy<E>,
-        num_training_episodes: usize,
-        max_steps: Option<usize>,
-    ) {
-        for _ in 0..num_training_episodes {
-            QLearning::one_episode(policy, max_steps);
-            policy.on_episode_increment();
+impl<E: Environment> Clone for QTable<E> {
+    fn clone(&self) -> Self {
+        QTable {
+            rows: self.rows.clone(),
+            values: self.values.clone(),
+            len: self.len,
         }
     }
+}
 
-    fn one_episode<E: Environment>(policy: &mut impl Policy<E>, max_steps: Option<usize>) {
-        let mut state = E::new();
-
-        if let Some(m) = max_steps {
-            for _ in 0..m {
-                let (next_state, finished) = QLearning::choose_and_improve(policy, state);
-                if !finished {
-                    state = next_state;
-                } else {
-                    break;
-                }
-            }
-        } else {
-            loop {
-                let (next_state, finished) = QLearning::choose_and_improve(policy, state);
-                if !finished {
-                    state = next_state;
-                } else {
-                    break;
-                }
-            }
+impl<E: Environment> Extend<((E::ActionRelevantState, E::Action), f32)> for QTable<E> {
+    fn extend<I: IntoIterator<Item = ((E::ActionRelevantState, E::Action), f32)>>(
+        &mut self,
+        iter: I,
+    ) {
+        for ((state, action), value) in iter {
+            self.insert(state, action, value);
         }
     }
+}
 
-    fn choose_and_improve<E: Environment>(
-        policy: &mut impl Policy<E>,
-        state: E::State,
-    ) -> (E::State, bool) {
-        let action = policy.choose_action(state.into());
+// Value of a (state, action) pair that was never updated. Code can not be saved, so a policy read
+// back from a file starts out with zeros again.
+pub type InitialValue<E> = Arc<
+    dyn Fn(&<E as Environment>::ActionRelevantState, &<E as Environment>::Action) -> f32
+        + Send
+        + Sync,
+>;
 
-        let (next_state, reward, finished) = E::step(&state, &action);
-        policy.improve(state.into(), action, reward, next_state, finished);
-        (next_state, finished)
-    }
+pub fn constant_initial_value<E: Environment>(value: f32) -> InitialValue<E> {
+    Arc::new(move |_, _| value)
 }
 
 pub struct GreedyPolicy<E: Environment> {
-    qtable: HashMap<(E::ActionRelevantState, E::Action), f32>,
+    qtable: QTable<E>,
     learning_rate: f32,
     gamma: f32,
+    initial_value: Option<InitialValue<E>>,
+    // NaN or infinite values that `set_value` refused, not saved with the table
+    rejected_updates: usize,
 }
 
 impl<E: Environment> GreedyPolicy<E> {
     pub fn new(learning_rate: f32, gamma: f32) -> Self {
         GreedyPolicy {
-            qtable: HashMap::new(),
+            qtable: QTable::new(),
             learning_rate,
             gamma,
+            initial_value: None,
+            rejected_updates: 0,
         }
     }
-}
 
-impl<E: Environment> Policy<E> for GreedyPolicy<E> {
-    fn choose_action(&self, state: E::ActionRelevantState) -> E::Action {
-        let actions = E::actions(&state);
-        *actions.iter()
-            .max_by(|&a, &b|
-                self.qtable.get(&(state, *a))
-                    .unwrap_or(&0f32)
-                    .total_cmp(self.qtable.get(&(state, *b))
-                    .unwrap_or(&0f32))
-            )
-            .expect(
-            "The way it is implemented now, there should always be possible actions (might be bad)",
-        )
+    // A single NaN would win every `total_cmp` comparison and spread through all targets that
+    // read it, so non-finite values are dropped and counted instead. Returns whether it was stored.
+    pub fn set_value(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        value: f32,
+    ) -> bool {
+        match value.is_finite() {
+            true => {
+                self.qtable.insert(state, action, value);
+                true
+            }
+            false => {
+                self.rejected_updates += 1;
+                false
+            }
+        }
     }
-    fn improve(
+
+    pub fn rejected_updates(&self) -> usize {
+        self.rejected_updates
+    }
+
+    pub fn set_initial_value(&mut self, initial_value: InitialValue<E>) {
+        self.initial_value = Some(initial_value);
+    }
+
+    pub fn value(&self, state: E::ActionRelevantState, action: E::Action) -> f32 {
+        self.row_value(self.qtable.row(&state), state, action)
+    }
+
+    // `value` with the row already looked up
+    fn row_value(
+        &self,
+        row: Option<QRow>,
+        state: E::ActionRelevantState,
+        action: E::Action,
+    ) -> f32 {
+        match (
+            row.and_then(|row| row.get(E::action_index(&action))),
+            &self.initial_value,
+        ) {
+            (Some(value), _) => value,
+            (None, Some(initial_value)) => initial_value(&state, &action),
+            (None, None) => 0f32,
+        }
+    }
+
+    pub fn qtable_size(&self) -> usize {
+        self.qtable.len()
+    }
+
+    pub fn qtable(&self) -> &QTable<E> {
+        &self.qtable
+    }
+
+    pub fn qtable_mut(&mut self) -> &mut QTable<E> {
+        &mut self.qtable
+    }
+
+    pub fn gamma(&self) -> f32 {
+        self.gamma
+    }
+
+    // `improve` with a discount other than the policy's own, for schedules that change it during
+    // training
+    pub fn improve_discounted(
         &mut self,
         state: E::ActionRelevantState,
         action: E::Action,
         reward: f32,
         next_state: E::State,
         finished: bool,
+        gamma: f32,
     ) {
-        let former_value = *self.qtable.get(&(state, action)).unwrap_or(&0f32);
+        let former_value = self.value(state, action);
         let target = reward
             + match finished {
                 false => {
-                    self.gamma
-                        * self
-                            .qtable
-                            .get(&(next_state.into(), self.choose_action(next_state.into())))
-                            .unwrap_or(&0f32)
+                    let next_state = next_state.into();
+                    gamma * self.value(next_state, self.choose_action(next_state, None))
                 }
                 true => 0f32,
             };
-        self.qtable.insert(
-            (state, action),
+        self.set_value(
+            state,
+            action,
             former_value + self.learning_rate * (target - former_value),
         );
     }
 }
 
+// Snapshots for readers on other threads, the initial values are shared
+impl<E: Environment> Clone for GreedyPolicy<E> {
+    fn clone(&self) -> Self {
+        GreedyPolicy {
+            qtable: self.qtable.clone(),
+            learning_rate: self.learning_rate,
+            gamma: self.gamma,
+            initial_value: self.initial_value.clone(),
+            rejected_updates: self.rejected_updates,
+        }
+    }
+}
+
+impl<E: Environment> Policy<E> for GreedyPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        let actions = masked_actions::<E>(&state, mask);
+        let row = self.qtable.row(&state);
+        let action = *actions.iter()
+            .max_by(|&a, &b| {
+                self.row_value(row, state, *a)
+                    .total_cmp(&self.row_value(row, state, *b))
+            })
+            .expect(
+            "The way it is implemented now, there should always be possible actions (might be bad)",
+        );
+        checked_choice::<E>(&state, mask, action)
+    }
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.improve_discounted(state, action, reward, next_state, finished, self.gamma);
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        let row = self.qtable.row(&state);
+        Some(
+            E::actions(&state)
+                .into_iter()
+                .map(|a| (a, self.row_value(row, state, a)))
+                .collect(),
+        )
+    }
+
+    // A row only exists once a value was set in it
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        Some(self.qtable.row(&state).is_some())
+    }
+}
+
+// One line per state with the values in action index order, "-" for slots that were never set
 impl<E: Environment> Serialize for GreedyPolicy<E> {
     fn serialize(&self) -> String {
         format!("{};{}\n", self.gamma, self.learning_rate)
             + self
                 .qtable
+                .rows
                 .iter()
-                .map(|((state, action), value)| {
-                    format!("{};{};{}\n", state.serialize(), action.serialize(), value)
+                .map(|(state, &row)| {
+                    let values = self
+                        .qtable
+                        .row_at(row)
+                        .values()
+                        .map(|value| value.map_or("-".to_owned(), |v| v.to_string()))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    format!("{};{}\n", state.serialize(), values)
                 })
                 .reduce(|a, b| a + b.as_str())
                 .unwrap_or(String::new())
@@ -170,66 +1315,212 @@ impl<E: Environment> Serialize for GreedyPolicy<E> {
     }
 }
 
+fn deserialize_row<E: Environment>(
+    qtable: &mut QTable<E>,
+    state: &str,
+    values: &str,
+) -> Result<(), DeserializeError> {
+    let state = E::ActionRelevantState::deserialize(state)?;
+    let values: Vec<&str> = values.split(' ').collect();
+    if values.len() != E::MAX_ACTIONS {
+        return Err(DeserializeError::because(format!(
+            "{} values for {} actions",
+            values.len(),
+            E::MAX_ACTIONS
+        )));
+    }
+    let legal = E::actions(&state);
+    for (index, value) in values.into_iter().enumerate() {
+        let value = match value {
+            "-" => continue,
+            value => value.parse::<f32>().map_err(|_| DeserializeError::new())?,
+        };
+        match legal.iter().find(|a| E::action_index(a) == index) {
+            Some(&action) => qtable.try_insert(state, action, value)?,
+            None => {
+                return Err(DeserializeError::because("Q-value for an illegal action"));
+            }
+        }
+    }
+    Ok(())
+}
+
 impl<E: Environment> Deserialize for GreedyPolicy<E> {
     fn deserialize(input: &str) -> Result<Self, DeserializeError> {
         let mut lines = input.lines();
 
         let mut parameters = match lines.next() {
             Some(s) => s.split(';').map(|a| a.parse::<f32>()),
-            _ => return Err(DeserializeError),
+            _ => return Err(DeserializeError::new()),
         };
         let gamma = match parameters.next() {
             Some(Ok(f)) => f,
-            _ => return Err(DeserializeError),
+            _ => return Err(DeserializeError::new()),
         };
         let learning_rate = match parameters.next() {
             Some(Ok(f)) => f,
-            _ => return Err(DeserializeError),
+            _ => return Err(DeserializeError::new()),
         };
-        if parameters.next() != None {
-            return Err(DeserializeError);
+        if parameters.next().is_some() {
+            return Err(DeserializeError::new());
+        }
+        if !gamma.is_finite() || !learning_rate.is_finite() {
+            return Err(DeserializeError::because(
+                "non-finite gamma or learning rate",
+            ));
         }
 
-        let mut qtable = HashMap::<(E::ActionRelevantState, E::Action), f32>::new();
+        let mut qtable = QTable::<E>::new();
         for line in lines {
-            let mut parts = line.split(';');
-            let state = match parts.next() {
-                Some(s) => E::ActionRelevantState::deserialize(s)?,
-                _ => return Err(DeserializeError),
-            };
-            let action = match parts.next() {
-                Some(a) => E::Action::deserialize(a)?,
-                _ => return Err(DeserializeError),
+            let parts: Vec<&str> = line.split(';').collect();
+            let result = match parts.as_slice() {
+                [state, values] => deserialize_row(&mut qtable, state, values),
+                // Files from before the rows, one line per (state, action) pair
+                [state, action, value] => match value.parse::<f32>() {
+                    Ok(value) => qtable.try_insert(
+                        E::ActionRelevantState::deserialize(state)?,
+                        E::Action::deserialize(action)?,
+                        value,
+                    ),
+                    Err(_) => Err(DeserializeError::new()),
+                },
+                _ => Err(DeserializeError::new()),
             };
-            let value_result = match parts.next() {
-                Some(v) => v.parse::<f32>(),
-                _ => return Err(DeserializeError),
-            };
-            let value = match value_result {
-                Ok(v) => v,
-                _ => return Err(DeserializeError),
-            };
-            if parts.next() != None {
-                return Err(DeserializeError);
-            }
-
-            qtable.insert((state, action), value);
+            result.map_err(|e| match e.reason() {
+                Some(reason) => DeserializeError::because(format!("{reason} in line \"{line}\"")),
+                None => e,
+            })?;
         }
 
         Ok(GreedyPolicy::<E> {
             qtable,
             gamma,
             learning_rate,
+            initial_value: None,
+            rejected_updates: 0,
         })
     }
 }
 
+pub struct RandomPolicy;
+
+impl<E: Environment> Policy<E> for RandomPolicy {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        *masked_actions::<E>(&state, mask).choose(&mut rand::rng()).expect(
+            "The way it is implemented now, there should always be possible actions (might be bad)",
+        )
+    }
+
+    fn improve(
+        &mut self,
+        _state: E::ActionRelevantState,
+        _action: E::Action,
+        _reward: f32,
+        _next_state: E::State,
+        _finished: bool,
+    ) {
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        let actions = E::actions(&state);
+        let probability = 1f32 / actions.len() as f32;
+        actions.into_iter().map(|a| (a, probability)).collect()
+    }
+}
+
+// Uniform like `RandomPolicy`, but draws from its own seeded rng so evaluations can be repeated
+pub struct SeededRandomPolicy {
+    rng: Mutex<StdRng>,
+}
+
+impl SeededRandomPolicy {
+    pub fn new(seed: u64) -> Self {
+        SeededRandomPolicy {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl<E: Environment> Policy<E> for SeededRandomPolicy {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        let mut rng = self
+            .rng
+            .lock()
+            .expect("The rng lock is never held across a panic");
+        *masked_actions::<E>(&state, mask).choose(&mut *rng).expect(
+            "The way it is implemented now, there should always be possible actions (might be bad)",
+        )
+    }
+
+    fn improve(
+        &mut self,
+        _state: E::ActionRelevantState,
+        _action: E::Action,
+        _reward: f32,
+        _next_state: E::State,
+        _finished: bool,
+    ) {
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        Policy::<E>::action_distribution(&RandomPolicy, state)
+    }
+}
+
+// AlphaZero's exploration for self-play: at every move the greedy choice only gets `1 - fraction`
+// of the probability, the rest is spread by a fresh draw from a symmetric Dirichlet with
+// concentration `alpha` over the legal moves. Unlike epsilon it does not spread evenly, each draw
+// favours a few moves, which keeps games from settling into one line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RootNoise {
+    pub alpha: f32,
+    pub fraction: f32,
+}
+
+impl Default for RootNoise {
+    fn default() -> Self {
+        RootNoise {
+            alpha: 0.3,
+            fraction: 0.25,
+        }
+    }
+}
+
+impl RootNoise {
+    fn choose<A: Copy + Eq>(&self, actions: &[A], greedy: A, rng: &mut impl Rng) -> A {
+        let noise = sample_dirichlet(self.alpha, actions.len(), rng);
+        let mut left = rng.random_range(0f32..1f32);
+        for (&action, share) in actions.iter().zip(noise) {
+            let probability = match action == greedy {
+                true => 1f32 - self.fraction + self.fraction * share,
+                false => self.fraction * share,
+            };
+            if left < probability {
+                return action;
+            }
+            left -= probability;
+        }
+        greedy
+    }
+}
+
 pub struct EpsilonGreedyPolicy<E: Environment> {
     greedy_policy: GreedyPolicy<E>,
     min_epsilon: f32,
     max_epsilon: f32,
     decay_rate: f32,
     episode: usize,
+    // Like the rng, a running reheat only lives as long as the training run
+    reheat: Option<Reheat>,
+    // Same for annealing, the saved gamma is always the one it leads to
+    gamma_ramp: Option<LinearRamp>,
+    // And for the noise, only training runs add it
+    root_noise: Option<RootNoise>,
+    // Per-state exploration, see `explore_by_visits`. Counts are not saved either, a resumed run
+    // starts exploring every state anew.
+    visit_scale: Option<f32>,
+    visits: HashMap<E::ActionRelevantState, u32>,
+    rng: Mutex<StdRng>,
 }
 
 impl<E: Environment> EpsilonGreedyPolicy<E> {
@@ -246,27 +1537,220 @@ impl<E: Environment> EpsilonGreedyPolicy<E> {
             max_epsilon,
             decay_rate,
             episode: 0,
+            reheat: None,
+            gamma_ramp: None,
+            root_noise: None,
+            visit_scale: None,
+            visits: HashMap::new(),
+            rng: Mutex::new(StdRng::from_os_rng()),
         }
     }
 
-    fn epsilon(&self) -> f32 {
-        self.min_epsilon
-            + (self.max_epsilon - self.min_epsilon) * (-self.decay_rate * self.episode as f32).exp()
+    pub fn from_hyperparameters(
+        hyperparameters: Hyperparameters,
+    ) -> Result<Self, HyperparameterError> {
+        hyperparameters.validate()?;
+        Ok(EpsilonGreedyPolicy::new(
+            hyperparameters.learning_rate,
+            hyperparameters.gamma,
+            hyperparameters.max_epsilon,
+            hyperparameters.min_epsilon,
+            hyperparameters.decay_rate,
+        ))
     }
-}
 
-impl<E: Environment> Policy<E> for EpsilonGreedyPolicy<E> {
-    fn choose_action(&self, state: E::ActionRelevantState) -> E::Action {
-        let action: E::Action;
-        if rand::random_range(0f32..1f32) < self.epsilon() {
-            action = *E::actions(&state).choose(&mut rand::rng()).expect(
+    pub fn hyperparameters(&self) -> Hyperparameters {
+        Hyperparameters {
+            learning_rate: self.greedy_policy.learning_rate,
+            gamma: self.greedy_policy.gamma,
+            max_epsilon: self.max_epsilon,
+            min_epsilon: self.min_epsilon,
+            decay_rate: self.decay_rate,
+        }
+    }
+
+    // Keeps the table and the episode count, the new values apply from the next update and the
+    // next choice on
+    pub fn set_hyperparameters(
+        &mut self,
+        hyperparameters: Hyperparameters,
+    ) -> Result<Vec<HyperparameterWarning>, HyperparameterError> {
+        let warnings = hyperparameters.validate()?;
+        self.greedy_policy.learning_rate = hyperparameters.learning_rate;
+        self.greedy_policy.gamma = hyperparameters.gamma;
+        if let Some(ramp) = &mut self.gamma_ramp {
+            ramp.end = hyperparameters.gamma;
+        }
+        self.max_epsilon = hyperparameters.max_epsilon;
+        self.min_epsilon = hyperparameters.min_epsilon;
+        self.decay_rate = hyperparameters.decay_rate;
+        Ok(warnings)
+    }
+
+    // Makes exploration reproducible, the seed itself is not part of the serialized policy
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Mutex::new(StdRng::seed_from_u64(seed));
+    }
+
+    pub fn episode(&self) -> usize {
+        self.episode
+    }
+
+    // For policies that take over another policy's table together with its progress
+    pub fn set_episode(&mut self, episode: usize) {
+        self.episode = episode;
+    }
+
+    pub fn greedy_policy(&self) -> &GreedyPolicy<E> {
+        &self.greedy_policy
+    }
+
+    pub fn greedy_policy_mut(&mut self) -> &mut GreedyPolicy<E> {
+        &mut self.greedy_policy
+    }
+
+    pub fn decay_schedule(&self) -> ExponentialDecay {
+        ExponentialDecay {
+            start: self.max_epsilon,
+            end: self.min_epsilon,
+            rate: self.decay_rate,
+        }
+    }
+
+    // Raises epsilon by `boost` right away, the extra exploration fades out over `duration` episodes
+    pub fn reheat(&mut self, boost: f32, duration: usize) {
+        self.reheat = Some(Reheat {
+            start_episode: self.episode,
+            boost,
+            duration,
+        });
+    }
+
+    // Discounts with a gamma that rises linearly from `start` to the policy's own over `episodes`
+    // from now on. Short-sighted targets settle faster early on, while the rewards at the end of a
+    // game have not yet spread back through the table.
+    pub fn anneal_gamma(&mut self, start: f32, episodes: usize) {
+        self.gamma_ramp = Some(LinearRamp {
+            start_episode: self.episode,
+            start,
+            end: self.greedy_policy.gamma,
+            duration: episodes,
+        });
+    }
+
+    // Mixes Dirichlet noise into every move this policy chooses greedily from now on
+    pub fn add_root_noise(&mut self, noise: RootNoise) {
+        assert!(
+            noise.alpha > 0f32,
+            "The noise concentration has to be positive"
+        );
+        assert!(
+            (0f32..=1f32).contains(&noise.fraction),
+            "The noise fraction is a share of the probability"
+        );
+        self.root_noise = Some(noise);
+    }
+
+    pub fn root_noise(&self) -> Option<RootNoise> {
+        self.root_noise
+    }
+
+    // How much of the probability goes elsewhere than to the greedy choice, spread evenly on
+    // average: epsilon and then the noise's share of the rest
+    pub fn exploration_for(&self, state: E::ActionRelevantState) -> f32 {
+        let epsilon = self.epsilon_for(state).clamp(0f32, 1f32);
+        epsilon + (1f32 - epsilon) * self.root_noise.map_or(0f32, |noise| noise.fraction)
+    }
+
+    // The discount updates use right now
+    pub fn gamma(&self) -> f32 {
+        self.gamma_ramp
+            .map_or(self.greedy_policy.gamma, |r| r.value(self.episode))
+    }
+
+    pub fn epsilon(&self) -> f32 {
+        self.decay_schedule().value(self.episode)
+            + self.reheat.map_or(0f32, |r| r.value(self.episode))
+    }
+
+    // Replaces the per-episode decay with one per state: a state seen n times is explored with
+    // min + (max - min) * scale / (scale + n), so new positions get tried out while well-known
+    // ones are trusted. Reheating still adds on top.
+    pub fn explore_by_visits(&mut self, scale: f32) {
+        assert!(scale > 0f32, "The visit scale has to be positive");
+        self.visit_scale = Some(scale);
+    }
+
+    // Called by every update, only counts while exploring by visits
+    pub fn record_visit(&mut self, state: E::ActionRelevantState) {
+        if self.visit_scale.is_some() {
+            *self.visits.entry(state).or_default() += 1;
+        }
+    }
+
+    pub fn visits(&self, state: E::ActionRelevantState) -> u32 {
+        *self.visits.get(&state).unwrap_or(&0)
+    }
+
+    pub fn epsilon_for(&self, state: E::ActionRelevantState) -> f32 {
+        match self.visit_scale {
+            Some(scale) => {
+                let share = scale / (scale + self.visits(state) as f32);
+                self.min_epsilon
+                    + (self.max_epsilon - self.min_epsilon) * share
+                    + self.reheat.map_or(0f32, |r| r.value(self.episode))
+            }
+            None => self.epsilon(),
+        }
+    }
+
+    // `choose_action` exploring as this policy does around another greedy choice, for policies
+    // that decide on more than this table
+    pub fn choose_action_around(
+        &self,
+        state: E::ActionRelevantState,
+        mask: Option<ActionMask>,
+        greedy: impl FnOnce() -> E::Action,
+    ) -> E::Action {
+        let mut rng = self
+            .rng
+            .lock()
+            .expect("The rng lock is never held across a panic");
+        if rng.random_range(0f32..1f32) < self.epsilon_for(state) {
+            *masked_actions::<E>(&state, mask).choose(&mut *rng).expect(
                 "The way it is implemented now, there should always be possible actions (might be bad)",
-            );
+            )
+        } else if let Some(noise) = self.root_noise {
+            noise.choose(&masked_actions::<E>(&state, mask), greedy(), &mut *rng)
         } else {
-            action = self.greedy_policy.choose_action(state);
+            greedy()
         }
+    }
+
+    // What `choose_action_around` picks with, for the same greedy action
+    pub fn action_distribution_around(
+        &self,
+        state: E::ActionRelevantState,
+        greedy_action: E::Action,
+    ) -> Vec<(E::Action, f32)> {
+        let actions = E::actions(&state);
+        let epsilon = self.exploration_for(state);
+        let exploration_share = epsilon / actions.len() as f32;
+        actions
+            .into_iter()
+            .map(|a| match a == greedy_action {
+                true => (a, exploration_share + 1f32 - epsilon),
+                false => (a, exploration_share),
+            })
+            .collect()
+    }
+}
 
-        action
+impl<E: Environment> Policy<E> for EpsilonGreedyPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.choose_action_around(state, mask, || {
+            self.greedy_policy.choose_action(state, mask)
+        })
     }
 
     fn improve(
@@ -277,8 +1761,22 @@ impl<E: Environment> Policy<E> for EpsilonGreedyPolicy<E> {
         next_state: E::State,
         finished: bool,
     ) {
+        self.record_visit(state);
+        let gamma = self.gamma();
         self.greedy_policy
-            .improve(state, action, reward, next_state, finished);
+            .improve_discounted(state, action, reward, next_state, finished, gamma);
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.action_distribution_around(state, self.greedy_policy.choose_action(state, None))
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.greedy_policy.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.greedy_policy.knows_state(state)
     }
 
     fn on_episode_increment(&mut self) {
@@ -302,27 +1800,27 @@ impl<E: Environment> Deserialize for EpsilonGreedyPolicy<E> {
     {
         let (parts, rest) = match input.split_once('\n') {
             Some(s) => s,
-            _ => return Err(DeserializeError),
+            _ => return Err(DeserializeError::new()),
         };
         let mut parts = parts.split(';').map(|a| a.parse::<f32>());
         let min_epsilon = match parts.next() {
             Some(Ok(m)) => m,
-            _ => return Err(DeserializeError),
+            _ => return Err(DeserializeError::new()),
         };
         let max_epsilon = match parts.next() {
             Some(Ok(m)) => m,
-            _ => return Err(DeserializeError),
+            _ => return Err(DeserializeError::new()),
         };
         let decay_rate = match parts.next() {
             Some(Ok(d)) => d,
-            _ => return Err(DeserializeError),
+            _ => return Err(DeserializeError::new()),
         };
         let episode = match parts.next() {
             Some(Ok(e)) => e,
-            _ => return Err(DeserializeError),
+            _ => return Err(DeserializeError::new()),
         };
-        if parts.next() != None {
-            return Err(DeserializeError);
+        if parts.next().is_some() {
+            return Err(DeserializeError::new());
         }
 
         Ok(EpsilonGreedyPolicy::<E> {
@@ -331,6 +1829,70 @@ impl<E: Environment> Deserialize for EpsilonGreedyPolicy<E> {
             max_epsilon,
             decay_rate,
             episode: episode as usize,
+            reheat: None,
+            gamma_ramp: None,
+            root_noise: None,
+            visit_scale: None,
+            visits: HashMap::new(),
+            rng: Mutex::new(StdRng::from_os_rng()),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{LEFT, RIGHT, TwoStateGame};
+
+    #[test]
+    fn set_value_rejects_non_finite_values() {
+        let mut policy = GreedyPolicy::<TwoStateGame>::new(0.5, 0.5);
+        assert!(policy.set_value(1, RIGHT, 0.75));
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert!(!policy.set_value(1, RIGHT, value));
+        }
+        assert_eq!(policy.value(1, RIGHT), 0.75);
+        assert_eq!(policy.rejected_updates(), 3);
+    }
+
+    #[test]
+    fn clipping_leaves_the_terminal_reward_alone() {
+        let rewards = RewardOptions {
+            win: 10f32,
+            clip: Some(1f32),
+            ..Default::default()
+        };
+        assert_eq!(rewards.shape(5f32, Some(Outcome::Win)), (11f32, true));
+        assert_eq!(rewards.shape(0.5, Some(Outcome::Win)), (10.5, false));
+        assert_eq!(rewards.shape(-5f32, None), (-1f32, true));
+    }
+
+    #[test]
+    fn try_insert_rejects_non_finite_values() {
+        let mut qtable = QTable::<TwoStateGame>::new();
+        assert!(qtable.try_insert(1, RIGHT, f32::NAN).is_err());
+        assert!(qtable.try_insert(1, RIGHT, f32::INFINITY).is_err());
+        assert!(qtable.is_empty());
+        assert!(qtable.try_insert(1, LEFT, 0.5).is_ok());
+        assert_eq!(qtable.len(), 1);
+    }
+
+    #[test]
+    fn deserialize_names_the_offending_line() {
+        for line in [
+            format!("1;{RIGHT};NaN"),
+            format!("1;{RIGHT};inf"),
+            "1;- NaN".to_owned(),
+        ] {
+            let error =
+                GreedyPolicy::<TwoStateGame>::deserialize(&format!("0.5;0.5\n0;0 0\n{line}\n"))
+                    .err()
+                    .expect("A table with a non-finite value does not load");
+            let reason = error.reason().expect("The error says what is wrong");
+            assert!(
+                reason.contains("non-finite") && reason.contains(&line),
+                "\"{reason}\" does not point at \"{line}\""
+            );
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Tells whether a file was written since it was last read, by its modification time. Polled
+// between moves rather than watched, a game never waits for the file system.
+pub struct FileWatcher {
+    path: PathBuf,
+    seen: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    // The file as it is now counts as read
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let mut watcher = FileWatcher {
+            path: path.into(),
+            seen: None,
+        };
+        watcher.mark_read();
+        watcher
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    // A file that is gone has nothing new to read
+    pub fn changed(&self) -> bool {
+        match self.modified() {
+            Some(modified) => Some(modified) != self.seen,
+            None => false,
+        }
+    }
+
+    pub fn mark_read(&mut self) {
+        self.seen = self.modified();
+    }
+}
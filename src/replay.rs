@@ -0,0 +1,79 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::q_learning::{Environment, EpisodeStats, Policy, TrainingObserver, Transition};
+
+// The last `capacity` transitions of training, older ones are overwritten in the order they came
+// in. `QLearning::train_with_replay` learns from `batch_size` of them drawn at random after every
+// episode, so a rare capture keeps being learned from after the game it happened in.
+pub struct ReplayBuffer<E: Environment> {
+    transitions: Vec<Transition<E>>,
+    capacity: usize,
+    batch_size: usize,
+    // Where the next transition goes once the buffer is full
+    next: usize,
+    rng: StdRng,
+}
+
+impl<E: Environment> ReplayBuffer<E> {
+    pub fn new(capacity: usize, batch_size: usize) -> Self {
+        assert!(capacity > 0, "A replay buffer needs room for a transition");
+        ReplayBuffer {
+            transitions: Vec::with_capacity(capacity),
+            capacity,
+            batch_size,
+            next: 0,
+            rng: StdRng::from_os_rng(),
+        }
+    }
+
+    // Makes the drawn batches reproducible, the seed is not part of anything saved
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn push(&mut self, transition: Transition<E>) {
+        match self.transitions.len() < self.capacity {
+            true => self.transitions.push(transition),
+            false => {
+                self.transitions[self.next] = transition;
+                self.next = (self.next + 1) % self.capacity;
+            }
+        }
+    }
+
+    // `batch_size` transitions drawn with replacement, none while the buffer is empty
+    pub fn sample(&mut self) -> Vec<Transition<E>> {
+        if self.transitions.is_empty() {
+            return Vec::new();
+        }
+        (0..self.batch_size)
+            .map(|_| self.transitions[self.rng.random_range(0..self.transitions.len())])
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+}
+
+// Fills the buffer from any training loop, every step it sees is stored
+impl<E: Environment, P: Policy<E> + ?Sized> TrainingObserver<E, P> for ReplayBuffer<E> {
+    fn on_step(&mut self, _env: usize, transition: &Transition<E>) {
+        self.push(*transition);
+    }
+
+    fn on_episode_end(&mut self, _policy: &P, _stats: &EpisodeStats) {}
+}
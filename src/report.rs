@@ -0,0 +1,243 @@
+use crate::evaluation::EvaluationReport;
+use crate::experiments::Manifest;
+use crate::json::{Json, ToJson};
+use crate::metrics::{CurvePoint, TrainingCurve};
+use crate::q_learning::{Environment, GreedyPolicy, Policy, QTable, Serialize};
+
+pub const REPORT_FILE: &str = "report.md";
+pub const WIN_RATE_CHART: &str = "win-rate.svg";
+pub const REWARD_CHART: &str = "reward.svg";
+
+const CHART_WIDTH: f32 = 640f32;
+const CHART_HEIGHT: f32 = 240f32;
+// Room for the axis labels
+const CHART_MARGIN: f32 = 48f32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QTableStats {
+    pub states: usize,
+    pub values: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl QTableStats {
+    pub fn new<E: Environment>(table: &QTable<E>) -> Self {
+        let mut stats = QTableStats {
+            states: table.states(),
+            values: table.len(),
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            mean: 0f32,
+        };
+        let mut sum = 0f64;
+        for (_, _, value) in table.iter() {
+            stats.min = stats.min.min(value);
+            stats.max = stats.max.max(value);
+            sum += value as f64;
+        }
+        if stats.values > 0 {
+            stats.mean = (sum / stats.values as f64) as f32;
+        }
+        stats
+    }
+}
+
+impl ToJson for QTableStats {
+    fn to_json(&self) -> Json {
+        let (min, max) = match self.values {
+            0 => (Json::Null, Json::Null),
+            _ => (self.min.into(), self.max.into()),
+        };
+        Json::object([
+            ("states", self.states.into()),
+            ("values", self.values.into()),
+            ("min", min),
+            ("max", max),
+            ("mean", self.mean.into()),
+        ])
+    }
+}
+
+// The first `plies` moves the policy plays against itself, fewer if the game ends before
+pub fn greedy_line<E: Environment>(policy: &GreedyPolicy<E>, plies: usize) -> Vec<String> {
+    let mut state = E::new();
+    let mut line = Vec::new();
+    for _ in 0..plies {
+        let action = policy.choose_action(state.into(), None);
+        line.push(action.serialize());
+        let (next_state, _, outcome) = E::step(&state, &action);
+        if outcome.is_some() {
+            break;
+        }
+        state = next_state;
+    }
+    line
+}
+
+// Everything worth keeping from a training run in one Markdown page, the charts are separate
+// SVG files next to it
+pub struct TrainingReport<'a> {
+    pub run: &'a str,
+    pub config: &'a Manifest,
+    pub curve: &'a TrainingCurve,
+    // The learner's results against each opponent
+    pub evaluations: &'a [(String, EvaluationReport)],
+    pub table: QTableStats,
+    // What the greedy policy plays against itself from the start
+    pub greedy_line: Vec<String>,
+    // The most played openings of the last episodes with how often they came up
+    pub openings: Vec<(Vec<String>, usize)>,
+    pub opening_window: usize,
+}
+
+impl TrainingReport<'_> {
+    // Over the probed blocks only
+    pub fn win_rate_chart(&self) -> Option<String> {
+        let probed: Vec<CurvePoint> = self
+            .curve
+            .points()
+            .iter()
+            .filter(|p| p.win_rate.is_some())
+            .copied()
+            .collect();
+        line_chart("Win rate", &probed, |p| p.win_rate.unwrap_or(0f32))
+    }
+
+    pub fn reward_chart(&self) -> Option<String> {
+        line_chart("Mean reward", self.curve.points(), |p| p.mean_reward)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("# Training run {}\n\n## Configuration\n\n", self.run);
+        md += "| Setting | Value |\n|---|---|\n";
+        for (key, value) in self.config.entries() {
+            md += &format!("| {key} | {value} |\n");
+        }
+
+        md += "\n## Learning curve\n\n";
+        match self.curve.points().len() {
+            0 | 1 => md += "Too few episodes for a curve.\n",
+            _ => {
+                if self.win_rate_chart().is_some() {
+                    md += &format!("![Win rate]({WIN_RATE_CHART})\n\n");
+                }
+                md += &format!("![Mean reward]({REWARD_CHART})\n");
+            }
+        }
+
+        if !self.evaluations.is_empty() {
+            md += "\n## Evaluation\n\n";
+            md += "| Opponent | Games | Won | Drawn | Lost | Score |\n|---|---|---|---|---|---|\n";
+            for (opponent, report) in self.evaluations {
+                md += &format!(
+                    "| {opponent} | {} | {} | {} | {} | {:.1}% |\n",
+                    report.games(),
+                    report.wins,
+                    report.draws,
+                    report.losses,
+                    report.score() * 100f32
+                );
+            }
+        }
+
+        let table = &self.table;
+        md += "\n## Q-table\n\n";
+        md += &format!("- {} states, {} values\n", table.states, table.values);
+        if table.values > 0 {
+            md += &format!(
+                "- Values from {:.3} to {:.3}, {:.3} on average\n",
+                table.min, table.max, table.mean
+            );
+        }
+
+        md += "\n## Openings\n\n";
+        md += &format!("Greedy self-play: `{}`\n", self.greedy_line.join(" "));
+        if !self.openings.is_empty() {
+            md += &format!(
+                "\nMost played in the last {} episodes:\n\n| Opening | Episodes |\n|---|---|\n",
+                self.opening_window
+            );
+            for (line, count) in &self.openings {
+                md += &format!("| `{}` | {count} |\n", line.join(" "));
+            }
+        }
+        md
+    }
+}
+
+// A plain polyline over the episodes with the value range on the left, `None` below two points
+fn line_chart(
+    title: &str,
+    points: &[CurvePoint],
+    value: impl Fn(&CurvePoint) -> f32,
+) -> Option<String> {
+    let (first, last) = (points.first()?, points.last()?);
+    if points.len() < 2 {
+        return None;
+    }
+    let (low, high) = points
+        .iter()
+        .map(&value)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), v| {
+            (low.min(v), high.max(v))
+        });
+    // A flat curve still needs a range to be drawn in
+    let range = match high > low {
+        true => high - low,
+        false => 1f32,
+    };
+    let episodes = (last.episode - first.episode) as f32;
+    let (plot_width, plot_height) = (
+        CHART_WIDTH - 2f32 * CHART_MARGIN,
+        CHART_HEIGHT - 2f32 * CHART_MARGIN,
+    );
+    let polyline = points
+        .iter()
+        .map(|p| {
+            let x = CHART_MARGIN + (p.episode - first.episode) as f32 / episodes * plot_width;
+            let y = CHART_MARGIN + (high - value(p)) / range * plot_height;
+            format!("{x:.1},{y:.1}")
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let (left, right) = (CHART_MARGIN, CHART_WIDTH - CHART_MARGIN);
+    let (top, bottom) = (CHART_MARGIN, CHART_HEIGHT - CHART_MARGIN);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{CHART_WIDTH}\" \
+         height=\"{CHART_HEIGHT}\" font-family=\"sans-serif\" font-size=\"12\">\n"
+    );
+    svg += &format!(
+        "<text x=\"{}\" y=\"24\" text-anchor=\"middle\" font-size=\"14\">{title}</text>\n",
+        CHART_WIDTH / 2f32
+    );
+    svg += &format!(
+        "<polyline points=\"{left},{top} {left},{bottom} {right},{bottom}\" fill=\"none\" \
+         stroke=\"black\"/>\n"
+    );
+    svg += &format!(
+        "<polyline points=\"{polyline}\" fill=\"none\" stroke=\"steelblue\" stroke-width=\"2\"/>\n"
+    );
+    for (y, label) in [(top, high), (bottom, low)] {
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{label:.3}</text>\n",
+            left - 4f32,
+            y + 4f32
+        );
+    }
+    for (x, episode) in [(left, first.episode), (right, last.episode)] {
+        svg += &format!(
+            "<text x=\"{x}\" y=\"{}\" text-anchor=\"middle\">{episode}</text>\n",
+            bottom + 16f32
+        );
+    }
+    svg += &format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">episode</text>\n",
+        CHART_WIDTH / 2f32,
+        bottom + 32f32
+    );
+    svg += "</svg>\n";
+    Some(svg)
+}
@@ -0,0 +1,152 @@
+use crate::hyperparameters::{HyperparameterError, Hyperparameters};
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, EpsilonGreedyPolicy, Policy, Serialize,
+};
+
+const HEADER: &str = "average-reward";
+
+// R-learning: no discount, the table holds differential values, rewards measured against an
+// estimate of the average reward per step. The estimate only moves on greedy steps, where it
+// follows the same kind of error as the values, scaled by its own `rate`. Exploration and the
+// table are `EpsilonGreedyPolicy`'s, the file puts the estimate in front of its format:
+//
+//   average-reward;<average reward>;<rate>
+//   <EpsilonGreedyPolicy>
+pub struct RLearningPolicy<E: Environment> {
+    policy: EpsilonGreedyPolicy<E>,
+    average_reward: f32,
+    rate: f32,
+}
+
+impl<E: Environment> RLearningPolicy<E> {
+    // Starts from what the policy learned so far, with no idea yet of the average reward
+    pub fn new(policy: EpsilonGreedyPolicy<E>, rate: f32) -> Self {
+        RLearningPolicy {
+            policy,
+            average_reward: 0f32,
+            rate,
+        }
+    }
+
+    pub fn from_hyperparameters(
+        hyperparameters: Hyperparameters,
+        rate: f32,
+    ) -> Result<Self, HyperparameterError> {
+        Ok(RLearningPolicy::new(
+            EpsilonGreedyPolicy::from_hyperparameters(hyperparameters)?,
+            rate,
+        ))
+    }
+
+    pub fn average_reward(&self) -> f32 {
+        self.average_reward
+    }
+
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    pub fn epsilon_greedy_policy(&self) -> &EpsilonGreedyPolicy<E> {
+        &self.policy
+    }
+
+    pub fn epsilon_greedy_policy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        &mut self.policy
+    }
+
+    // The differential values choose moves like any other table
+    pub fn into_epsilon_greedy_policy(self) -> EpsilonGreedyPolicy<E> {
+        self.policy
+    }
+
+    fn max_value(&self, state: E::ActionRelevantState) -> f32 {
+        let greedy_policy = self.policy.greedy_policy();
+        greedy_policy.value(state, greedy_policy.choose_action(state, None))
+    }
+}
+
+impl<E: Environment> Policy<E> for RLearningPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.policy.choose_action(state, mask)
+    }
+
+    // An episode that ends is worth nothing more, the next one starts from scratch
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.policy.record_visit(state);
+        let greedy = self.policy.greedy_policy().choose_action(state, None) == action;
+        let next_value = match finished {
+            false => self.max_value(next_state.into()),
+            true => 0f32,
+        };
+        let learning_rate = self.policy.hyperparameters().learning_rate;
+        let former_value = self.policy.greedy_policy().value(state, action);
+        let target = reward - self.average_reward + next_value;
+        self.policy.greedy_policy_mut().set_value(
+            state,
+            action,
+            former_value + learning_rate * (target - former_value),
+        );
+        if greedy {
+            self.average_reward +=
+                self.rate * (reward - self.average_reward + next_value - self.max_value(state));
+        }
+    }
+
+    fn on_episode_increment(&mut self) {
+        self.policy.on_episode_increment();
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.policy.action_distribution(state)
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.policy.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.policy.knows_state(state)
+    }
+}
+
+impl<E: Environment> Serialize for RLearningPolicy<E> {
+    fn serialize(&self) -> String {
+        format!("{HEADER};{};{}\n", self.average_reward, self.rate) + &self.policy.serialize()
+    }
+}
+
+impl<E: Environment> Deserialize for RLearningPolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let (first, rest) = input
+            .split_once('\n')
+            .ok_or_else(|| DeserializeError::because("an R-learning policy has a first line"))?;
+        let parameters = first
+            .strip_prefix(HEADER)
+            .and_then(|parameters| parameters.strip_prefix(';'))
+            .and_then(|parameters| parameters.split_once(';'))
+            .and_then(|(average_reward, rate)| {
+                Some((
+                    average_reward.parse::<f32>().ok()?,
+                    rate.parse::<f32>().ok()?,
+                ))
+            })
+            .filter(|(average_reward, rate)| average_reward.is_finite() && rate.is_finite());
+        let Some((average_reward, rate)) = parameters else {
+            return Err(DeserializeError::because(format!(
+                "an R-learning policy starts with \"{HEADER};<average reward>;<rate>\""
+            )));
+        };
+        Ok(RLearningPolicy {
+            policy: EpsilonGreedyPolicy::deserialize(rest)?,
+            average_reward,
+            rate,
+        })
+    }
+}
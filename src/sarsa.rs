@@ -0,0 +1,286 @@
+use std::marker::PhantomData;
+
+use crate::hyperparameters::{HyperparameterError, Hyperparameters};
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, EpisodeStats, EpsilonGreedyPolicy,
+    Policy, Serialize, TrainingObserver, TrainingOptions, Transition, episode_start,
+};
+
+// Plain SARSA, learning from the action it goes on to play. That action only reaches it through
+// `improve_on_policy`, so it has to be trained by `Sarsa` or another loop that picks the next
+// action ahead; `improve` only takes the last step of an episode and panics on any other, a loop
+// that does not know the next action would quietly make it a different algorithm.
+pub type SarsaPolicy<E> = SarsaVariant<E, OnPolicy>;
+
+// Expected SARSA under any trainer: the target is the expectation over the epsilon greedy choice
+// at the next state instead of Q-learning's max, so exploring moves do not make the updates jump
+// around as much as plain SARSA's. The action `Sarsa` picks ahead is ignored.
+pub type ExpectedSarsaPolicy<E> = SarsaVariant<E, Expected>;
+
+// What the variants differ in, the discounted value of going on from the next state
+pub trait SarsaTarget {
+    // `next_action` is the action played there if the loop picked it ahead
+    fn following<E: Environment>(
+        policy: &EpsilonGreedyPolicy<E>,
+        next_state: E::State,
+        next_action: Option<E::Action>,
+        finished: bool,
+    ) -> f32;
+}
+
+pub struct OnPolicy;
+
+impl SarsaTarget for OnPolicy {
+    fn following<E: Environment>(
+        policy: &EpsilonGreedyPolicy<E>,
+        next_state: E::State,
+        next_action: Option<E::Action>,
+        finished: bool,
+    ) -> f32 {
+        if finished {
+            return 0f32;
+        }
+        let next_action = next_action
+            .expect("SarsaPolicy learns from the action played next, train it with Sarsa");
+        policy.gamma() * policy.greedy_policy().value(next_state.into(), next_action)
+    }
+}
+
+pub struct Expected;
+
+impl SarsaTarget for Expected {
+    // Averages the next state's values over the actions `policy` would pick there
+    fn following<E: Environment>(
+        policy: &EpsilonGreedyPolicy<E>,
+        next_state: E::State,
+        _next_action: Option<E::Action>,
+        finished: bool,
+    ) -> f32 {
+        if finished {
+            return 0f32;
+        }
+        let next_state = next_state.into();
+        policy.gamma()
+            * policy
+                .action_distribution(next_state)
+                .into_iter()
+                .map(|(a, p)| p * policy.greedy_policy().value(next_state, a))
+                .sum::<f32>()
+    }
+}
+
+// Exploration and the stored table are shared with `EpsilonGreedyPolicy`, which also keeps the
+// file format the same
+pub struct SarsaVariant<E: Environment, T: SarsaTarget> {
+    policy: EpsilonGreedyPolicy<E>,
+    target: PhantomData<T>,
+}
+
+impl<E: Environment, T: SarsaTarget> SarsaVariant<E, T> {
+    pub fn new(
+        learning_rate: f32,
+        gamma: f32,
+        max_epsilon: f32,
+        min_epsilon: f32,
+        decay_rate: f32,
+    ) -> Self {
+        SarsaVariant::from(EpsilonGreedyPolicy::new(
+            learning_rate,
+            gamma,
+            max_epsilon,
+            min_epsilon,
+            decay_rate,
+        ))
+    }
+
+    pub fn from_hyperparameters(
+        hyperparameters: Hyperparameters,
+    ) -> Result<Self, HyperparameterError> {
+        EpsilonGreedyPolicy::from_hyperparameters(hyperparameters).map(SarsaVariant::from)
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.policy.reseed(seed);
+    }
+
+    pub fn epsilon_greedy_policy(&self) -> &EpsilonGreedyPolicy<E> {
+        &self.policy
+    }
+
+    pub fn epsilon_greedy_policy_mut(&mut self) -> &mut EpsilonGreedyPolicy<E> {
+        &mut self.policy
+    }
+
+    fn update(&mut self, state: E::ActionRelevantState, action: E::Action, target: f32) {
+        self.policy.record_visit(state);
+        let former_value = self.policy.greedy_policy().value(state, action);
+        let learning_rate = self.policy.hyperparameters().learning_rate;
+        self.policy.greedy_policy_mut().set_value(
+            state,
+            action,
+            former_value + learning_rate * (target - former_value),
+        );
+    }
+}
+
+// Goes on from what the policy learned so far, with its exploration
+impl<E: Environment, T: SarsaTarget> From<EpsilonGreedyPolicy<E>> for SarsaVariant<E, T> {
+    fn from(policy: EpsilonGreedyPolicy<E>) -> Self {
+        SarsaVariant {
+            policy,
+            target: PhantomData,
+        }
+    }
+}
+
+impl<E: Environment, T: SarsaTarget> Policy<E> for SarsaVariant<E, T> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.policy.choose_action(state, mask)
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        let following = T::following(&self.policy, next_state, None, finished);
+        self.update(state, action, reward + following);
+    }
+
+    fn improve_on_policy(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        next_action: Option<E::Action>,
+    ) {
+        let following = T::following(&self.policy, next_state, next_action, next_action.is_none());
+        self.update(state, action, reward + following);
+    }
+
+    fn on_episode_increment(&mut self) {
+        self.policy.on_episode_increment();
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.policy.action_distribution(state)
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.policy.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.policy.knows_state(state)
+    }
+}
+
+impl<E: Environment, T: SarsaTarget> Serialize for SarsaVariant<E, T> {
+    fn serialize(&self) -> String {
+        self.policy.serialize()
+    }
+}
+
+impl<E: Environment, T: SarsaTarget> Deserialize for SarsaVariant<E, T> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        EpsilonGreedyPolicy::deserialize(input).map(SarsaVariant::from)
+    }
+}
+
+// The on-policy counterpart of `QLearning`: the action picked for the next state is the one that
+// gets played there, and the policy learns from it through `improve_on_policy`. One game at a
+// time, an action chosen ahead does not fit `VecEnv`'s lockstep.
+pub struct Sarsa;
+
+impl Sarsa {
+    pub fn train<E: Environment>(
+        policy: &mut (impl Policy<E> + ?Sized),
+        num_training_episodes: usize,
+        max_steps: Option<usize>,
+    ) {
+        let options = TrainingOptions {
+            max_steps,
+            ..Default::default()
+        };
+        Sarsa::train_observed(policy, num_training_episodes, &options, &mut ());
+    }
+
+    pub fn train_observed<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+        num_training_episodes: usize,
+        options: &TrainingOptions,
+        observer: &mut impl TrainingObserver<E, P>,
+    ) {
+        for episode in 0..num_training_episodes {
+            let stats = Sarsa::one_episode(policy, episode, options, observer);
+            observer.on_episode_end(policy, &stats);
+            observer.adjust_policy(policy);
+            if observer.should_stop() {
+                break;
+            }
+        }
+    }
+
+    // A cut off episode still picks the next action, its value stands in for the rest of the game
+    fn one_episode<E: Environment, P: Policy<E> + ?Sized>(
+        policy: &mut P,
+        episode: usize,
+        options: &TrainingOptions,
+        observer: &mut impl TrainingObserver<E, P>,
+    ) -> EpisodeStats {
+        let mut stats = EpisodeStats {
+            episode,
+            steps: 0,
+            total_reward: 0f32,
+            outcome: None,
+            clipped_steps: 0,
+        };
+        let mut rng = options.opening_rng(episode);
+        let mut state = episode_start::<E>(options.environment_seed, episode);
+        let mut action = options.choose_action(&*policy, state.into(), 0, &mut rng);
+        loop {
+            let (next_state, reward, outcome) = E::step(&state, &action);
+            let (reward, clipped) = options.rewards.shape(reward, outcome);
+            let next_action = match outcome {
+                None => Some(options.choose_action(
+                    &*policy,
+                    next_state.into(),
+                    stats.steps + 1,
+                    &mut rng,
+                )),
+                Some(_) => None,
+            };
+            policy.improve_on_policy(state.into(), action, reward, next_state, next_action);
+            stats.steps += 1;
+            stats.total_reward += reward;
+            stats.outcome = outcome;
+            stats.clipped_steps += clipped as usize;
+
+            let truncated =
+                outcome.is_none() && options.max_steps.is_some_and(|m| stats.steps >= m);
+            observer.on_step(
+                0,
+                &Transition {
+                    state,
+                    action,
+                    reward,
+                    next_state,
+                    outcome,
+                    truncated,
+                    clipped,
+                },
+            );
+            match (next_action, truncated) {
+                (Some(next_action), false) => (state, action) = (next_state, next_action),
+                _ => {
+                    policy.on_episode_increment();
+                    return stats;
+                }
+            }
+        }
+    }
+}
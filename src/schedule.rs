@@ -0,0 +1,132 @@
+// A value that changes with the number of finished episodes, e.g. the exploration rate
+pub trait Schedule {
+    fn value(&self, episode: usize) -> f32;
+}
+
+// Starts at `start` and approaches `end` exponentially
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExponentialDecay {
+    pub start: f32,
+    pub end: f32,
+    pub rate: f32,
+}
+
+// A temporary boost that fades out linearly over `duration` episodes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reheat {
+    pub start_episode: usize,
+    pub boost: f32,
+    pub duration: usize,
+}
+
+// Goes from `start` to `end` in a straight line over `duration` episodes from `start_episode` on,
+// then stays at `end`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearRamp {
+    pub start_episode: usize,
+    pub start: f32,
+    pub end: f32,
+    pub duration: usize,
+}
+
+// Discounting starts out short-sighted at gamma `start` and reaches the policy's own gamma after
+// `episodes`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GammaAnnealing {
+    pub start: f32,
+    pub episodes: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReheatOptions {
+    pub patience: usize,
+    pub min_improvement: f32,
+    pub boost: f32,
+    pub duration: usize,
+}
+
+// Reports a plateau once the score has not improved by `min_improvement` for `patience` checks
+pub struct PlateauDetector {
+    patience: usize,
+    min_improvement: f32,
+    best: Option<f32>,
+    stalled: usize,
+}
+
+impl Schedule for ExponentialDecay {
+    fn value(&self, episode: usize) -> f32 {
+        self.end + (self.start - self.end) * (-self.rate * episode as f32).exp()
+    }
+}
+
+impl Schedule for Reheat {
+    fn value(&self, episode: usize) -> f32 {
+        match episode.checked_sub(self.start_episode) {
+            Some(elapsed) if elapsed < self.duration => {
+                self.boost * (1f32 - elapsed as f32 / self.duration as f32)
+            }
+            _ => 0f32,
+        }
+    }
+}
+
+impl Schedule for LinearRamp {
+    fn value(&self, episode: usize) -> f32 {
+        match episode.checked_sub(self.start_episode) {
+            Some(elapsed) if elapsed < self.duration => {
+                self.start + (self.end - self.start) * elapsed as f32 / self.duration as f32
+            }
+            Some(_) => self.end,
+            None => self.start,
+        }
+    }
+}
+
+impl Default for GammaAnnealing {
+    fn default() -> Self {
+        GammaAnnealing {
+            start: 0.5,
+            episodes: 1000,
+        }
+    }
+}
+
+impl Default for ReheatOptions {
+    fn default() -> Self {
+        ReheatOptions {
+            patience: 3,
+            min_improvement: 0.01,
+            boost: 0.3,
+            duration: 500,
+        }
+    }
+}
+
+impl PlateauDetector {
+    pub fn new(patience: usize, min_improvement: f32) -> Self {
+        PlateauDetector {
+            patience,
+            min_improvement,
+            best: None,
+            stalled: 0,
+        }
+    }
+
+    // Starts counting from scratch after reporting, so a plateau is reported once per `patience` checks
+    pub fn observe(&mut self, score: f32) -> bool {
+        match self.best {
+            Some(best) if score < best + self.min_improvement => self.stalled += 1,
+            _ => {
+                self.best = Some(score);
+                self.stalled = 0;
+            }
+        }
+        match self.stalled >= self.patience {
+            true => {
+                self.stalled = 0;
+                true
+            }
+            false => false,
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::q_learning::{ActionMask, Environment, Policy, checked_choice, masked_actions};
+use crate::two_player::{TwoPlayerGame, play_in_place};
+
+// How often the clock is read, in nodes
+const CLOCK_INTERVAL: usize = 1024;
+// Entries of the transposition table, once full it only updates positions it already holds
+const TABLE_CAPACITY: usize = 1 << 20;
+
+// Shared by all nodes of one search, the table carries over from one depth to the next
+struct Run<'a, M> {
+    deadline: Option<Instant>,
+    stop: Option<&'a AtomicBool>,
+    nodes: usize,
+    table: HashMap<u64, Entry<M>>,
+    table_hits: usize,
+}
+
+// What the value of a searched position says, alpha-beta only pins it down inside its window
+#[derive(Clone, Copy)]
+enum Bound {
+    Exact,
+    // At least the value
+    Lower,
+    // At most the value
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct Entry<M> {
+    depth: usize,
+    value: f32,
+    bound: Bound,
+    best: M,
+}
+
+impl<M> Run<'_, M> {
+    fn out_of_time(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+            || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+}
+
+// What one decision of a search-based policy cost and what it expects
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchStats<A> {
+    pub nodes: usize,
+    // Deepest fully searched iteration, in plies
+    pub depth: usize,
+    pub time: Duration,
+    // Best play for both sides as far as the search looked, starting with the chosen move
+    pub principal_variation: Vec<A>,
+    // How far the mover expects to extend their lead along the principal variation
+    pub score: f32,
+    // Positions the transposition table answered without searching them again
+    pub table_hits: usize,
+    // Found while the opponent was thinking, `time` is what that took
+    pub pondered: bool,
+}
+
+impl<A> Display for SearchStats<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} table hits, depth {}, {:.1} ms{}, score {:+}",
+            self.nodes,
+            self.table_hits,
+            self.depth,
+            self.time.as_secs_f64() * 1000f64,
+            match self.pondered {
+                true => " pondered",
+                false => "",
+            },
+            self.score
+        )
+    }
+}
+
+// Alpha-beta over the game rules with iterative deepening, scoring lines by the rewards of
+// `TwoPlayerGame`. Extra turns keep the perspective, only a change of mover flips it. Does not
+// learn, `improve` is a no-op.
+pub struct MinimaxPolicy<G: TwoPlayerGame> {
+    max_depth: usize,
+    // Deepening stops once it runs out, an unfinished iteration is thrown away
+    time_limit: Option<Duration>,
+    last_search: Mutex<Option<SearchStats<G::Move>>>,
+    pondering: Mutex<Option<Pondering<G>>>,
+}
+
+// A search running on its own thread while the opponent thinks
+struct Pondering<G: TwoPlayerGame> {
+    view: G::View,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<SearchStats<G::Move>>,
+}
+
+impl<G: TwoPlayerGame> Pondering<G> {
+    // Waits for the thread, which gives up at the next clock check
+    fn stop(self) -> (G::View, SearchStats<G::Move>) {
+        self.stop.store(true, Ordering::Relaxed);
+        let stats = self
+            .thread
+            .join()
+            .expect("A pondering search does not panic on a legal position");
+        (self.view, stats)
+    }
+}
+
+impl<G: TwoPlayerGame> MinimaxPolicy<G> {
+    pub fn new(max_depth: usize, time_limit: Option<Duration>) -> Self {
+        assert!(
+            max_depth > 0,
+            "The search has to look at least one ply ahead"
+        );
+        MinimaxPolicy {
+            max_depth,
+            time_limit,
+            last_search: Mutex::new(None),
+            pondering: Mutex::new(None),
+        }
+    }
+
+    // From the next search on, e.g. to follow a game clock
+    pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+        self.time_limit = time_limit;
+    }
+
+    // The best move and what finding it took. Always finishes depth 1, whatever the time limit.
+    pub fn search(&self, view: G::View, mask: Option<ActionMask>) -> (G::Move, SearchStats<G::Move>)
+    where
+        G: Environment<ActionRelevantState = G::View, Action = G::Move>,
+    {
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        let stats = self.search_until(view, mask, deadline, None);
+        (stats.principal_variation[0], stats)
+    }
+
+    // Deepens until the last depth, the deadline or the stop flag. The deadline spares depth 1,
+    // the stop flag does not, `depth` stays 0 if it hits that early.
+    fn search_until(
+        &self,
+        view: G::View,
+        mask: Option<ActionMask>,
+        deadline: Option<Instant>,
+        stop: Option<&AtomicBool>,
+    ) -> SearchStats<G::Move>
+    where
+        G: Environment<ActionRelevantState = G::View, Action = G::Move>,
+    {
+        let started = Instant::now();
+        let mut root = G::from_view(&view);
+        let key = G::zobrist(&root);
+        let mut moves = masked_actions::<G>(&view, mask);
+        assert!(!moves.is_empty(), "There is no legal move to search");
+
+        let mut stats = SearchStats {
+            nodes: 0,
+            depth: 0,
+            time: Duration::ZERO,
+            principal_variation: vec![moves[0]],
+            score: 0f32,
+            table_hits: 0,
+            pondered: false,
+        };
+        let mut run = Run {
+            deadline: None,
+            stop,
+            nodes: 0,
+            table: HashMap::new(),
+            table_hits: 0,
+        };
+        for depth in 1..=self.max_depth {
+            run.deadline = match depth {
+                1 => None,
+                _ => deadline,
+            };
+            run.nodes += 1;
+            let result = self.best_of(
+                &mut root,
+                key,
+                &moves,
+                depth,
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+                &mut run,
+            );
+            match result {
+                Some((score, line)) => {
+                    // The best move so far goes first, the next iteration cuts more with it
+                    let best = line[0];
+                    if let Some(i) = moves.iter().position(|m| *m == best) {
+                        moves[..=i].rotate_right(1);
+                    }
+                    stats.depth = depth;
+                    stats.score = score;
+                    stats.principal_variation = line;
+                }
+                None => break,
+            }
+            if run.out_of_time() {
+                break;
+            }
+        }
+        stats.nodes = run.nodes;
+        stats.table_hits = run.table_hits;
+        stats.time = started.elapsed();
+        stats
+    }
+
+    // Negamax value of the position for the player to move, with the line that gets it.
+    // `None` once the deadline passed.
+    fn negamax(
+        &self,
+        position: &mut G::Position,
+        key: Option<u64>,
+        depth: usize,
+        alpha: f32,
+        beta: f32,
+        run: &mut Run<G::Move>,
+    ) -> Option<(f32, Vec<G::Move>)> {
+        run.nodes += 1;
+        if run.nodes.is_multiple_of(CLOCK_INTERVAL) && run.out_of_time() {
+            return None;
+        }
+        if depth == 0 {
+            return Some((0f32, Vec::new()));
+        }
+        let mut moves = G::legal_moves(&G::View::from(*position));
+        let entry = key.and_then(|key| run.table.get(&key).copied());
+        if let Some(entry) = entry {
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.value >= beta,
+                Bound::Upper => entry.value <= alpha,
+            };
+            if entry.depth >= depth && usable {
+                run.table_hits += 1;
+                return Some((entry.value, vec![entry.best]));
+            }
+            // Not enough for a cutoff, but its best move is the likeliest one to cut
+            if let Some(i) = moves.iter().position(|m| *m == entry.best) {
+                moves[..=i].rotate_right(1);
+            }
+        }
+
+        let (value, line) = self.best_of(position, key, &moves, depth, alpha, beta, run)?;
+        if let Some(key) = key
+            && (run.table.len() < TABLE_CAPACITY || run.table.contains_key(&key))
+        {
+            let bound = match (value <= alpha, value >= beta) {
+                (true, _) => Bound::Upper,
+                (false, true) => Bound::Lower,
+                (false, false) => Bound::Exact,
+            };
+            run.table.insert(
+                key,
+                Entry {
+                    depth,
+                    value,
+                    bound,
+                    best: line[0],
+                },
+            );
+        }
+        Some((value, line))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    // Moves are made on `position` and taken back before it returns
+    fn best_of(
+        &self,
+        position: &mut G::Position,
+        key: Option<u64>,
+        moves: &[G::Move],
+        depth: usize,
+        mut alpha: f32,
+        beta: f32,
+        run: &mut Run<G::Move>,
+    ) -> Option<(f32, Vec<G::Move>)> {
+        let mover = G::current_player(position);
+        let mut best = (f32::NEG_INFINITY, Vec::new());
+        for game_move in moves {
+            // The key update compares both positions, only games with keys keep the one before
+            let before = key.map(|key| (key, *position));
+            let (undo, reward, outcome) = play_in_place::<G>(position, game_move);
+            let reward = reward.value();
+            let next_key = before.map(|(key, before)| G::zobrist_after(&before, key, position));
+            let result = match (outcome, G::current_player(position) == mover) {
+                (Some(_), _) => Some((0f32, Vec::new())),
+                (None, true) => self.negamax(
+                    position,
+                    next_key,
+                    depth - 1,
+                    alpha - reward,
+                    beta - reward,
+                    run,
+                ),
+                (None, false) => self
+                    .negamax(
+                        position,
+                        next_key,
+                        depth - 1,
+                        reward - beta,
+                        reward - alpha,
+                        run,
+                    )
+                    .map(|(value, line)| (-value, line)),
+            };
+            G::unmake_move(position, undo);
+            let (rest, line) = result?;
+            let value = reward + rest;
+            if value > best.0 {
+                best = (value, [vec![*game_move], line].concat());
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+}
+
+impl<G: TwoPlayerGame> MinimaxPolicy<G> {
+    fn stop_pondering(&self) -> Option<(G::View, SearchStats<G::Move>)> {
+        self.pondering
+            .lock()
+            .expect("The pondering lock is never held across a panic")
+            .take()
+            .map(Pondering::stop)
+    }
+}
+
+impl<G: TwoPlayerGame> Drop for MinimaxPolicy<G> {
+    fn drop(&mut self) {
+        self.stop_pondering();
+    }
+}
+
+impl<G> Policy<G> for MinimaxPolicy<G>
+where
+    G: TwoPlayerGame + Environment<ActionRelevantState = G::View, Action = G::Move> + 'static,
+    G::View: Send,
+    G::Move: Send,
+{
+    // A fully searched guess is as good as searching now, anything less is thrown away
+    fn choose_action(&self, state: G::View, mask: Option<ActionMask>) -> G::Move {
+        let (action, stats) = match self.stop_pondering() {
+            Some((view, stats))
+                if view == state && mask.is_none() && stats.depth == self.max_depth =>
+            {
+                (stats.principal_variation[0], stats)
+            }
+            _ => self.search(state, mask),
+        };
+        *self
+            .last_search
+            .lock()
+            .expect("The stats lock is never held across a panic") = Some(stats);
+        checked_choice::<G>(&state, mask, action)
+    }
+
+    fn improve(
+        &mut self,
+        _state: G::View,
+        _action: G::Move,
+        _reward: f32,
+        _next_state: G::State,
+        _finished: bool,
+    ) {
+    }
+
+    fn ponder(&self, state: G::View) {
+        self.stop_pondering();
+        if G::legal_moves(&state).is_empty() {
+            return;
+        }
+        let max_depth = self.max_depth;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let stats = MinimaxPolicy::<G>::new(max_depth, None).search_until(
+                    state,
+                    None,
+                    None,
+                    Some(&stop),
+                );
+                SearchStats {
+                    pondered: true,
+                    ..stats
+                }
+            })
+        };
+        *self
+            .pondering
+            .lock()
+            .expect("The pondering lock is never held across a panic") = Some(Pondering {
+            view: state,
+            stop,
+            thread,
+        });
+    }
+
+    fn last_search(&self) -> Option<SearchStats<G::Move>> {
+        self.last_search
+            .lock()
+            .expect("The stats lock is never held across a panic")
+            .clone()
+    }
+}
+
+// The position the principal variation expects to come back to its first mover after the other
+// side replied, what to ponder on while they think. `None` if the line ends the game or never
+// gets there.
+pub fn predicted_return<G: TwoPlayerGame>(
+    position: &G::Position,
+    line: &[G::Move],
+) -> Option<G::View> {
+    let mover = G::current_player(position);
+    let mut position = *position;
+    let mut replied = false;
+    for game_move in line {
+        match (G::current_player(&position) == mover, replied) {
+            (true, true) => break,
+            (false, _) => replied = true,
+            (true, false) => {}
+        }
+        G::make_move(&mut position, game_move);
+        if G::outcome(&position).is_some() {
+            return None;
+        }
+    }
+    match replied && G::current_player(&position) == mover {
+        true => Some(position.into()),
+        false => None,
+    }
+}
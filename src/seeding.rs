@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// Every component draws from its own stream derived from the master seed and its name, so adding
+// a consumer or changing how much one of them draws never shifts the numbers another one sees
+pub struct SeedStreams {
+    master: u64,
+    issued: Vec<(String, u64)>,
+}
+
+// Finalizer of SplitMix64, spreads similar inputs over the whole range
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+// One step of SplitMix64, small enough to carry a random stream inside a `Copy` state
+pub(crate) fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    mix(*state)
+}
+
+// Stable across platforms and compiler versions, unlike the std hashers
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(FNV_OFFSET, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+impl SeedStreams {
+    pub fn new(master: u64) -> Self {
+        SeedStreams {
+            master,
+            issued: Vec::new(),
+        }
+    }
+
+    pub fn master(&self) -> u64 {
+        self.master
+    }
+
+    pub fn seed(&mut self, name: &str) -> u64 {
+        let seed = mix(self.master ^ mix(fnv1a(name)));
+        if !self.issued.iter().any(|(n, _)| n == name) {
+            self.issued.push((name.to_owned(), seed));
+        }
+        seed
+    }
+
+    pub fn rng(&mut self, name: &str) -> StdRng {
+        StdRng::seed_from_u64(self.seed(name))
+    }
+
+    pub fn worker_seed(&mut self, name: &str, index: usize) -> u64 {
+        self.seed(&format!("{name}/{index}"))
+    }
+
+    // Every stream handed out so far, in the order they were first requested
+    pub fn issued(&self) -> &[(String, u64)] {
+        &self.issued
+    }
+}
+
+// Run metadata, one `name;seed` line per stream after the master seed
+impl Display for SeedStreams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "master;{}", self.master)?;
+        for (name, seed) in &self.issued {
+            write!(f, "\n{name};{seed}")?;
+        }
+        Ok(())
+    }
+}
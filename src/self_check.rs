@@ -0,0 +1,501 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::afterstate::AfterstatePolicy;
+use crate::blackjack::{self, Blackjack};
+use crate::connect4::ConnectFour;
+use crate::double_q::DoubleQLearningPolicy;
+use crate::dyna_q::DynaQPolicy;
+use crate::evaluation::{all_openings, parse_opening_book};
+use crate::hyperparameters::Hyperparameters;
+use crate::mankalla::{LeadAwareMankalla, LeadView, MankallaGame, MankallaGameState};
+use crate::monte_carlo::{MonteCarloControl, MonteCarloPolicy};
+use crate::nim::{self, Nim};
+use crate::perft::perft;
+use crate::q_lambda::QLambdaPolicy;
+use crate::q_learning::{
+    ActionMask, Deserialize, Environment, EpsilonGreedyPolicy, GreedyPolicy, Policy, QLearning,
+    QTable, Serialize, TrainingOptions,
+};
+use crate::sarsa::{ExpectedSarsaPolicy, Sarsa, SarsaPolicy};
+use crate::schedule::ExponentialDecay;
+use crate::similarity::similar_states;
+use crate::softmax::SoftmaxPolicy;
+use crate::state_index::StateIndex;
+use crate::testing::{LEFT, RIGHT, TwoStateGame};
+use crate::thompson::{NormalGamma, ThompsonPolicy};
+use crate::two_player::{TwoPlayerGame, play};
+use crate::ucb::UcbPolicy;
+
+// Learning rate and discount are powers of two and so are all rewards, which keeps every value
+// in the fixture exactly representable. A correct build hits them bit for bit.
+const LEARNING_RATE: f32 = 0.5;
+const GAMMA: f32 = 0.5;
+const TOLERANCE: f32 = 1e-6;
+const CLOSED_FORM_EPISODES: i32 = 20;
+// The learners need a few hundred episodes, the rest is margin
+const NIM_EPISODES: usize = 2000;
+const NIM_SEED: u64 = 1;
+// Without bootstrapping the win has to be found by exploring all the way from the start, which
+// takes Monte Carlo control tens of thousands of episodes
+const MONTE_CARLO_NIM_EPISODES: usize = 150_000;
+// Planning goes over every known move again after each real one, a fraction of the episodes does
+const DYNA_Q_NIM_EPISODES: usize = 200;
+const DYNA_Q_PLANNING_STEPS: usize = 20;
+// Some states are so close that even the exact values barely separate the actions, so
+// only most of the policy has to match the known optimum
+const BLACKJACK_EPISODES: usize = 500_000;
+const BLACKJACK_SEED: u64 = 1;
+const BLACKJACK_AGREEMENT: f32 = 0.9;
+// A bit over a million positions, far more than one search visits
+const ZOBRIST_PLIES: usize = 8;
+// Counted when the rules were last changed on purpose, a different count means the move
+// generation did too
+const MANKALLA_PERFT: (usize, u64) = (6, 32248);
+// 7^7 minus the seven games that try to put a seventh stone into one column, no game can be won
+// before that
+const CONNECT_FOUR_PERFT: (usize, u64) = (7, 823_536);
+const BOOK_PLIES: usize = 3;
+
+// (state, action, reward, next_state, finished, Q(state, action) after the update), worked out by
+// hand from Q <- Q + lr * (reward + gamma * max Q(next_state) - Q)
+const FIXTURE: [(u8, u8, f32, u8, bool, f32); 8] = [
+    (0, RIGHT, 0., 1, false, 0.),
+    (1, RIGHT, 1., 1, true, 0.5),
+    (0, RIGHT, 0., 1, false, 0.125),
+    (1, RIGHT, 1., 1, true, 0.75),
+    (0, RIGHT, 0., 1, false, 0.25),
+    (1, LEFT, 0., 1, true, 0.),
+    (0, RIGHT, 0., 1, false, 0.3125),
+    (1, RIGHT, 1., 1, true, 0.875),
+];
+
+pub struct Check {
+    pub name: String,
+    pub expected: f32,
+    pub actual: f32,
+    pub tolerance: f32,
+}
+
+pub struct SelfCheckReport {
+    pub checks: Vec<Check>,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        (self.expected - self.actual).abs() <= self.tolerance
+    }
+}
+
+impl SelfCheckReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(Check::passed)
+    }
+
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|c| !c.passed()).count()
+    }
+}
+
+fn q_value(policy: &GreedyPolicy<TwoStateGame>, state: u8, action: u8) -> f32 {
+    policy.value(state, action)
+}
+
+// Replays the hand computed fixture through the real update, then checks the closed form
+// Q(1, right) = 1 - (1 - lr)^n after n more wins from the right state. Last, every learner has
+// to find the optimal Nim strategy and most of the optimal Blackjack policy.
+pub fn run() -> SelfCheckReport {
+    let mut checks = Vec::new();
+    let mut policy = GreedyPolicy::<TwoStateGame>::new(LEARNING_RATE, GAMMA);
+    for (i, (state, action, reward, next_state, finished, expected)) in FIXTURE.iter().enumerate() {
+        policy.improve(*state, *action, *reward, *next_state, *finished);
+        checks.push(Check {
+            name: format!("update {}: Q({}, {})", i + 1, state, action),
+            expected: *expected,
+            actual: q_value(&policy, *state, *action),
+            tolerance: TOLERANCE,
+        });
+    }
+
+    let mut policy = GreedyPolicy::<TwoStateGame>::new(LEARNING_RATE, GAMMA);
+    for _ in 0..CLOSED_FORM_EPISODES {
+        policy.improve(1, RIGHT, 1f32, 1, true);
+    }
+    checks.push(Check {
+        name: format!("Q(1, {RIGHT}) after {CLOSED_FORM_EPISODES} wins"),
+        expected: 1f32 - (1f32 - LEARNING_RATE).powi(CLOSED_FORM_EPISODES),
+        actual: q_value(&policy, 1, RIGHT),
+        tolerance: TOLERANCE,
+    });
+    checks.push(Check {
+        name: "greedy action in state 0".to_owned(),
+        expected: RIGHT as f32,
+        actual: {
+            policy.improve(0, RIGHT, 0f32, 1, false);
+            policy.choose_action(0, None) as f32
+        },
+        tolerance: TOLERANCE,
+    });
+    checks.push(Check {
+        name: "greedy action in state 0 with right masked out".to_owned(),
+        expected: LEFT as f32,
+        actual: policy.choose_action(0, Some(ActionMask::from_actions::<TwoStateGame>(&[LEFT])))
+            as f32,
+        tolerance: TOLERANCE,
+    });
+
+    // A non-finite reward must neither reach the table nor go unnoticed, and a saved table holding
+    // one must not load
+    let before = q_value(&policy, 1, RIGHT);
+    policy.improve(1, RIGHT, f32::INFINITY, 1, true);
+    checks.push(Check {
+        name: "Q(1, right) after an infinite reward".to_owned(),
+        expected: before,
+        actual: q_value(&policy, 1, RIGHT),
+        tolerance: 0f32,
+    });
+    checks.push(Check {
+        name: "rejected updates".to_owned(),
+        expected: 1f32,
+        actual: policy.rejected_updates() as f32,
+        tolerance: 0f32,
+    });
+    checks.push(Check {
+        name: "tables with NaN rejected on load".to_owned(),
+        expected: 1f32,
+        actual: GreedyPolicy::<TwoStateGame>::deserialize(&format!(
+            "{GAMMA};{LEARNING_RATE}\n1;{RIGHT};NaN\n"
+        ))
+        .is_err() as u8 as f32,
+        tolerance: 0f32,
+    });
+
+    // Tables are saved a row per state now, files with a line per (state, action) still load
+    let reloaded = GreedyPolicy::<TwoStateGame>::deserialize(&policy.serialize())
+        .expect("A saved table loads again");
+    checks.push(Check {
+        name: "Q(1, right) after saving and loading".to_owned(),
+        expected: q_value(&policy, 1, RIGHT),
+        actual: q_value(&reloaded, 1, RIGHT),
+        tolerance: 0f32,
+    });
+    let old_format = GreedyPolicy::<TwoStateGame>::deserialize(&format!(
+        "{GAMMA};{LEARNING_RATE}\n1;{RIGHT};0.875\n"
+    ))
+    .expect("Tables with a line per (state, action) load");
+    checks.push(Check {
+        name: "Q(1, right) from a table in the old format".to_owned(),
+        expected: 0.875,
+        actual: q_value(&old_format, 1, RIGHT),
+        tolerance: 0f32,
+    });
+
+    let mut q_learning =
+        EpsilonGreedyPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+            .expect("The default hyperparameters are valid");
+    q_learning.reseed(NIM_SEED);
+    QLearning::train(&mut q_learning, NIM_EPISODES, None);
+    checks.push(nim_check("Q-learning", q_learning.greedy_policy()));
+    checks.push(Check {
+        name: "Nim states a prefix query missed".to_owned(),
+        expected: 0f32,
+        actual: missed_by_prefix(q_learning.greedy_policy().qtable()) as f32,
+        tolerance: 0f32,
+    });
+
+    let qtable = q_learning.greedy_policy().qtable();
+    checks.push(Check {
+        name: "Nim states not their own nearest".to_owned(),
+        expected: 0f32,
+        actual: qtable
+            .rows()
+            .filter(|(state, _)| similar_states(qtable, state, 1) != [(*state, 0)])
+            .count() as f32,
+        tolerance: 0f32,
+    });
+
+    let mut expected_sarsa =
+        ExpectedSarsaPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+            .expect("The default hyperparameters are valid");
+    expected_sarsa.reseed(NIM_SEED);
+    QLearning::train(&mut expected_sarsa, NIM_EPISODES, None);
+    checks.push(nim_check(
+        "expected SARSA",
+        expected_sarsa.epsilon_greedy_policy().greedy_policy(),
+    ));
+
+    let mut sarsa = SarsaPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    sarsa.reseed(NIM_SEED);
+    Sarsa::train(&mut sarsa, NIM_EPISODES, None);
+    checks.push(nim_check(
+        "SARSA",
+        sarsa.epsilon_greedy_policy().greedy_policy(),
+    ));
+
+    let mut q_lambda = QLambdaPolicy::<Nim>::from_hyperparameters(Hyperparameters::default(), 0.8)
+        .expect("The default hyperparameters are valid");
+    q_lambda.reseed(NIM_SEED);
+    QLearning::train(&mut q_lambda, NIM_EPISODES, None);
+    checks.push(nim_check(
+        "Q(λ)",
+        q_lambda.epsilon_greedy_policy().greedy_policy(),
+    ));
+
+    let mut monte_carlo = MonteCarloPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    monte_carlo.reseed(NIM_SEED);
+    MonteCarloControl::train(&mut monte_carlo, MONTE_CARLO_NIM_EPISODES, None);
+    checks.push(nim_check(
+        "Monte Carlo control",
+        monte_carlo.epsilon_greedy_policy().greedy_policy(),
+    ));
+
+    let mut dyna_q =
+        DynaQPolicy::<Nim>::from_hyperparameters(Hyperparameters::default(), DYNA_Q_PLANNING_STEPS)
+            .expect("The default hyperparameters are valid");
+    dyna_q.reseed(NIM_SEED);
+    QLearning::train(&mut dyna_q, DYNA_Q_NIM_EPISODES, None);
+    checks.push(nim_check(
+        "Dyna-Q",
+        dyna_q.epsilon_greedy_policy().greedy_policy(),
+    ));
+
+    // Through a saved file, which fills the table in from the afterstate values
+    let mut afterstate = AfterstatePolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    afterstate.reseed(NIM_SEED);
+    QLearning::train(&mut afterstate, NIM_EPISODES, None);
+    let afterstate = AfterstatePolicy::<Nim>::deserialize(&afterstate.serialize())
+        .expect("A saved afterstate policy loads again")
+        .into_epsilon_greedy_policy();
+    checks.push(nim_check("afterstate values", afterstate.greedy_policy()));
+
+    // Through a saved file, the way the merged table is played
+    let mut double_q =
+        DoubleQLearningPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+            .expect("The default hyperparameters are valid");
+    double_q.reseed(NIM_SEED);
+    QLearning::train(&mut double_q, NIM_EPISODES, None);
+    let double_q = DoubleQLearningPolicy::<Nim>::deserialize(&double_q.serialize())
+        .expect("A saved Double Q-learning policy loads again")
+        .into_epsilon_greedy_policy();
+    checks.push(nim_check("Double Q-learning", double_q.greedy_policy()));
+
+    let mut thompson = ThompsonPolicy::<Nim>::new(1f32, NormalGamma::default(), 100f32);
+    thompson.reseed(NIM_SEED);
+    QLearning::train(&mut thompson, NIM_EPISODES, None);
+    checks.push(nim_check("Thompson sampling", &thompson.to_greedy_policy()));
+
+    let hyperparameters = Hyperparameters::default();
+    let mut softmax = SoftmaxPolicy::<Nim>::new(
+        hyperparameters.learning_rate,
+        hyperparameters.gamma,
+        ExponentialDecay {
+            start: 1f32,
+            end: 0.05,
+            rate: hyperparameters.decay_rate,
+        },
+    );
+    softmax.reseed(NIM_SEED);
+    QLearning::train(&mut softmax, NIM_EPISODES, None);
+    let softmax = SoftmaxPolicy::<Nim>::deserialize(&softmax.serialize())
+        .expect("A saved softmax policy loads again");
+    checks.push(nim_check("softmax exploration", softmax.greedy_policy()));
+
+    // Through a saved file, the counts have to come back for it to keep exploring the same way
+    let mut ucb = UcbPolicy::<Nim>::new(hyperparameters.learning_rate, hyperparameters.gamma, 1f32);
+    QLearning::train(&mut ucb, NIM_EPISODES / 2, None);
+    let mut ucb =
+        UcbPolicy::<Nim>::deserialize(&ucb.serialize()).expect("A saved UCB policy loads again");
+    QLearning::train(&mut ucb, NIM_EPISODES / 2, None);
+    checks.push(nim_check("UCB1", ucb.greedy_policy()));
+
+    let (collisions, drifted, unrestored, unreconstructed) = walk_positions();
+    checks.push(Check {
+        name: format!("Zobrist collisions within {ZOBRIST_PLIES} plies"),
+        expected: 0f32,
+        actual: collisions as f32,
+        tolerance: 0f32,
+    });
+    checks.push(Check {
+        name: "incremental Zobrist keys that drifted".to_owned(),
+        expected: 0f32,
+        actual: drifted as f32,
+        tolerance: 0f32,
+    });
+    checks.push(Check {
+        name: "moves undo did not take back".to_owned(),
+        expected: 0f32,
+        actual: unrestored as f32,
+        tolerance: 0f32,
+    });
+    checks.push(Check {
+        name: "views reconstructed wrong".to_owned(),
+        expected: 0f32,
+        actual: unreconstructed as f32,
+        tolerance: 0f32,
+    });
+
+    let (depth, expected) = MANKALLA_PERFT;
+    checks.push(Check {
+        name: format!("Mankalla continuations of {depth} plies"),
+        expected: expected as f32,
+        actual: perft::<MankallaGame>(&mut MankallaGame::start(), depth) as f32,
+        tolerance: 0f32,
+    });
+    let (depth, expected) = CONNECT_FOUR_PERFT;
+    checks.push(Check {
+        name: format!("Connect Four continuations of {depth} plies"),
+        expected: expected as f32,
+        actual: perft::<ConnectFour>(&mut ConnectFour::start(), depth) as f32,
+        tolerance: 0f32,
+    });
+
+    let book = all_openings(BOOK_PLIES);
+    let written: String = book
+        .iter()
+        .map(|opening| format!("{opening:#}\n"))
+        .collect();
+    let read = parse_opening_book(&written).expect("A written book reads again");
+    checks.push(Check {
+        name: format!("{BOOK_PLIES}-ply openings read back wrong"),
+        expected: 0f32,
+        actual: (book.len() - book.iter().zip(&read).filter(|(a, b)| a == b).count()) as f32,
+        tolerance: 0f32,
+    });
+
+    let mut blackjack = EpsilonGreedyPolicy::<Blackjack>::from_hyperparameters(Hyperparameters {
+        learning_rate: 0.02,
+        decay_rate: 0.0001,
+        ..Default::default()
+    })
+    .expect("The blackjack hyperparameters are valid");
+    blackjack.reseed(BLACKJACK_SEED);
+    let options = TrainingOptions {
+        environment_seed: Some(BLACKJACK_SEED),
+        ..Default::default()
+    };
+    QLearning::train_observed(&mut blackjack, BLACKJACK_EPISODES, &options, &mut ());
+    let (agreeing, states) = blackjack::optimal_agreement(blackjack.greedy_policy());
+    checks.push(Check {
+        name: "Blackjack states played optimally".to_owned(),
+        expected: 1f32,
+        actual: agreeing as f32 / states as f32,
+        tolerance: 1f32 - BLACKJACK_AGREEMENT,
+    });
+
+    SelfCheckReport { checks }
+}
+
+// Rows of a saved index with the first one or two heaps given, against a scan of the table
+fn missed_by_prefix(qtable: &QTable<Nim>) -> usize {
+    let index = StateIndex::<Nim>::deserialize(&StateIndex::new(qtable).serialize())
+        .expect("A saved state index loads again");
+    let heaps = |state: &[u8; 3]| state.map(|h| h.to_string());
+    let mut missed = 0;
+    for (state, _) in qtable.rows() {
+        let [first, second, _] = heaps(&state);
+        for prefix in [vec![first.as_str()], vec![first.as_str(), second.as_str()]] {
+            let found = index.with_prefix(&prefix).len();
+            let scanned = qtable
+                .rows()
+                .filter(|(other, _)| heaps(other)[..prefix.len()] == prefix[..])
+                .count();
+            missed += scanned.abs_diff(found);
+        }
+    }
+    missed
+}
+
+// A learner that converged plays every move of the game optimally
+fn nim_check(learner: &str, policy: &GreedyPolicy<Nim>) -> Check {
+    let (optimal, moves) = nim::optimal_moves(policy);
+    Check {
+        name: format!("Nim optimal moves, {learner}"),
+        expected: moves as f32,
+        actual: optimal as f32,
+        tolerance: TOLERANCE,
+    }
+}
+
+// Every position a few plies deep from the start, returns (keys shared by different positions,
+// moves whose updated key differs from hashing the new position from scratch, moves that undo left
+// a different position behind, positions that `reconstruct` or `LeadAwareMankalla::from_view` did
+// not get back from their view)
+fn walk_positions() -> (usize, usize, usize, usize) {
+    let mut keys = HashMap::from([(MankallaGame::new().zobrist(), MankallaGame::new())]);
+    let mut seen = HashSet::from([MankallaGame::new()]);
+    let mut frontier = vec![MankallaGame::new()];
+    let (mut collisions, mut drifted, mut unrestored, mut unreconstructed) = (0, 0, 0, 0);
+    for _ in 0..ZOBRIST_PLIES {
+        let mut next_frontier = Vec::new();
+        for state in frontier {
+            let key = state.zobrist();
+            for action in MankallaGame::actions(&state.into()) {
+                let (next_state, _, outcome) = play::<MankallaGame>(&state, &action);
+                let next_key = state.zobrist_after(key, &next_state);
+                let mut undone = state;
+                let undo = undone.apply_move(action);
+                undone.undo(undo);
+                if undone != state {
+                    unrestored += 1;
+                }
+                if next_key != next_state.zobrist() {
+                    drifted += 1;
+                }
+                if !seen.insert(next_state) {
+                    continue;
+                }
+                let mover = next_state.get_player_to_move();
+                let reconstructed = MankallaGameState::reconstruct(
+                    &next_state.into(),
+                    mover,
+                    next_state.get_points(&mover),
+                );
+                if reconstructed.ok() != Some(next_state) {
+                    unreconstructed += 1;
+                }
+                let lead_view = LeadView::<1>::from(next_state);
+                if LeadView::from(LeadAwareMankalla::<1>::from_view(&lead_view)) != lead_view {
+                    unreconstructed += 1;
+                }
+                if keys.insert(next_key, next_state).is_some() {
+                    collisions += 1;
+                }
+                if outcome.is_none() {
+                    next_frontier.push(next_state);
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+    (collisions, drifted, unrestored, unreconstructed)
+}
+
+impl Display for SelfCheckReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            writeln!(
+                f,
+                "{:<6} {:<36} expected {:>10.7}  got {:>10.7}",
+                match check.passed() {
+                    true => "ok",
+                    false => "FAILED",
+                },
+                check.name,
+                check.expected,
+                check.actual
+            )?;
+        }
+        match self.passed() {
+            true => write!(f, "All {} checks passed", self.checks.len()),
+            false => write!(
+                f,
+                "{} of {} checks failed",
+                self.failures(),
+                self.checks.len()
+            ),
+        }
+    }
+}
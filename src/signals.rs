@@ -0,0 +1,37 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set once SIGINT or SIGTERM arrived. A signal handler can only safely touch something static, so
+// this is the one flag the handler and whoever polls it share.
+static TERMINATION: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_termination(signal: libc::c_int) {
+    TERMINATION.store(true, Ordering::Relaxed);
+    // A second one kills the process, in case stopping hangs
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+// From here on SIGINT and SIGTERM only set the flag, the first one of them that is
+#[cfg(unix)]
+pub fn catch_termination() -> io::Result<()> {
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        let handler = on_termination as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// Elsewhere the process ends on them as before
+#[cfg(not(unix))]
+pub fn catch_termination() -> io::Result<()> {
+    Ok(())
+}
+
+pub fn termination_requested() -> bool {
+    TERMINATION.load(Ordering::Relaxed)
+}
@@ -0,0 +1,62 @@
+// SIMD within a register: the 14 fields are packed into one u128 so that sowing, the row sums
+// and the feature extraction become a handful of integer operations instead of byte loops.
+// A board never holds more than 255 marbles in a field, so byte lanes can not carry into each other.
+
+const NUM_FIELDS: usize = 14;
+const FIELDS_MASK: u128 = (1 << (8 * NUM_FIELDS)) - 1;
+const ONES: u128 = 0x0101_0101_0101_0101_0101_0101_0101_0101 & FIELDS_MASK;
+const ROW_MASK: u128 = 0xFFFF_FFFF_FFFF;
+const ROW_ONES: u128 = 0x0101_0101_0101;
+
+fn pack(fields: &[u8; NUM_FIELDS]) -> u128 {
+    let mut bytes = [0u8; 16];
+    bytes[..NUM_FIELDS].copy_from_slice(fields);
+    u128::from_le_bytes(bytes)
+}
+
+fn unpack(packed: u128, fields: &mut [u8; NUM_FIELDS]) {
+    fields.copy_from_slice(&packed.to_le_bytes()[..NUM_FIELDS]);
+}
+
+fn rotate_fields(packed: u128, by: usize) -> u128 {
+    let by = by % NUM_FIELDS;
+    ((packed << (8 * by)) | (packed >> (8 * (NUM_FIELDS - by)))) & FIELDS_MASK
+}
+
+fn row_sum(packed: u128) -> u8 {
+    (((packed & ROW_MASK) * ROW_ONES) >> 40) as u8
+}
+
+// Same semantics as sowing one marble at a time, returns the field the last marble landed in
+pub fn sow(fields: &mut [u8; NUM_FIELDS], start: usize) -> usize {
+    let marbles = fields[start] as usize;
+    fields[start] = 0;
+    if marbles == 0 {
+        return start;
+    }
+
+    let full_laps = (marbles / NUM_FIELDS) as u128;
+    let rest = marbles % NUM_FIELDS;
+    let partial_lap = rotate_fields(ONES & ((1 << (8 * rest)) - 1), start + 1);
+
+    unpack(pack(fields) + full_laps * ONES + partial_lap, fields);
+    (start + marbles) % NUM_FIELDS
+}
+
+// Marbles left in the pits of player 1 and player 2, stores excluded
+pub fn row_sums(fields: &[u8; NUM_FIELDS]) -> (u8, u8) {
+    let packed = pack(fields);
+    (row_sum(packed), row_sum(packed >> 56))
+}
+
+// Pits of the player to move first, then the opponent's pits
+pub fn relevant_pits(fields: &[u8; NUM_FIELDS], player1_to_move: bool) -> [u8; 12] {
+    let packed = pack(fields);
+    let (own, other) = match player1_to_move {
+        true => (packed & ROW_MASK, (packed >> 56) & ROW_MASK),
+        false => ((packed >> 56) & ROW_MASK, packed & ROW_MASK),
+    };
+    (own | (other << 48)).to_le_bytes()[..12]
+        .try_into()
+        .expect("Twelve bytes are taken from a sixteen byte array")
+}
@@ -0,0 +1,114 @@
+use crate::q_learning::{
+    ActionMask, Environment, GreedyPolicy, Policy, QTable, checked_choice, masked_actions,
+};
+
+// Stones that differ pit by pit, summed
+pub fn l1_distance<const N: usize>(a: &[u8; N], b: &[u8; N]) -> u32 {
+    a.iter().zip(b).map(|(&a, &b)| a.abs_diff(b) as u32).sum()
+}
+
+// The `k` known states closest to `state` by `l1_distance`, nearest first. Ties go in state order
+// so the answer does not depend on how the table happens to be laid out, and `state` itself comes
+// first if the table knows it. Scans the whole table.
+pub fn similar_states<E, const N: usize>(
+    qtable: &QTable<E>,
+    state: &[u8; N],
+    k: usize,
+) -> Vec<([u8; N], u32)>
+where
+    E: Environment<ActionRelevantState = [u8; N]>,
+{
+    let by_distance =
+        |a: &([u8; N], u32), b: &([u8; N], u32)| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0));
+    let mut states: Vec<([u8; N], u32)> = qtable
+        .rows()
+        .map(|(other, _)| (other, l1_distance(state, &other)))
+        .collect();
+    if states.len() > k {
+        states.select_nth_unstable_by(k, by_distance);
+        states.truncate(k);
+    }
+    states.sort_by(by_distance);
+    states
+}
+
+// Plays the table where it knows the state. Anywhere else a move is worth what the `k` most
+// similar known states think of it, averaged over those that have a value for it, or the table's
+// default if none has. With `k` 0 it plays like the table alone. Only for playing, `improve` does
+// nothing.
+pub struct SimilarStatesFallback<'a, E: Environment> {
+    table: &'a GreedyPolicy<E>,
+    k: usize,
+}
+
+impl<'a, E: Environment> SimilarStatesFallback<'a, E> {
+    pub fn new(table: &'a GreedyPolicy<E>, k: usize) -> Self {
+        SimilarStatesFallback { table, k }
+    }
+}
+
+impl<E, const N: usize> SimilarStatesFallback<'_, E>
+where
+    E: Environment<ActionRelevantState = [u8; N]>,
+{
+    fn value(&self, state: [u8; N], action: E::Action, neighbors: &[([u8; N], u32)]) -> f32 {
+        let values: Vec<f32> = neighbors
+            .iter()
+            .filter_map(|(neighbor, _)| self.table.qtable().get(neighbor, &action))
+            .collect();
+        match values.len() {
+            0 => self.table.value(state, action),
+            n => values.iter().sum::<f32>() / n as f32,
+        }
+    }
+
+    fn neighbors(&self, state: [u8; N]) -> Vec<([u8; N], u32)> {
+        match self.table.knows_state(state) {
+            Some(true) => Vec::new(),
+            _ => similar_states(self.table.qtable(), &state, self.k),
+        }
+    }
+}
+
+impl<E, const N: usize> Policy<E> for SimilarStatesFallback<'_, E>
+where
+    E: Environment<ActionRelevantState = [u8; N]>,
+{
+    fn choose_action(&self, state: [u8; N], mask: Option<ActionMask>) -> E::Action {
+        let neighbors = self.neighbors(state);
+        let action = *masked_actions::<E>(&state, mask)
+            .iter()
+            .max_by(|&&a, &&b| {
+                self.value(state, a, &neighbors)
+                    .total_cmp(&self.value(state, b, &neighbors))
+            })
+            .expect(
+                "The way it is implemented now, there should always be possible actions (might be bad)",
+            );
+        checked_choice::<E>(&state, mask, action)
+    }
+
+    fn improve(
+        &mut self,
+        _state: [u8; N],
+        _action: E::Action,
+        _reward: f32,
+        _next_state: E::State,
+        _finished: bool,
+    ) {
+    }
+
+    fn action_values(&self, state: [u8; N]) -> Option<Vec<(E::Action, f32)>> {
+        let neighbors = self.neighbors(state);
+        Some(
+            E::actions(&state)
+                .into_iter()
+                .map(|a| (a, self.value(state, a, &neighbors)))
+                .collect(),
+        )
+    }
+
+    fn knows_state(&self, state: [u8; N]) -> Option<bool> {
+        self.table.knows_state(state)
+    }
+}
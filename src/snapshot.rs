@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+
+// A finished copy of something the training thread owns, tagged with the episode it was taken at
+pub struct Snapshot<T> {
+    pub episode: usize,
+    pub value: T,
+}
+
+// Double buffering for readers on other threads: the trainer builds the next snapshot on its
+// own and only swaps a pointer to publish it, readers keep the snapshot they got for as long as
+// they like. Neither side ever waits for more than that swap, and nobody sees a half-updated copy.
+pub struct SnapshotPublisher<T> {
+    latest: Arc<Mutex<Option<Arc<Snapshot<T>>>>>,
+}
+
+pub struct SnapshotReader<T> {
+    latest: Arc<Mutex<Option<Arc<Snapshot<T>>>>>,
+}
+
+impl<T> SnapshotPublisher<T> {
+    pub fn new() -> Self {
+        SnapshotPublisher {
+            latest: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn publish(&self, episode: usize, value: T) {
+        let snapshot = Arc::new(Snapshot { episode, value });
+        *self
+            .latest
+            .lock()
+            .expect("The snapshot lock is never held across a panic") = Some(snapshot);
+    }
+
+    pub fn reader(&self) -> SnapshotReader<T> {
+        SnapshotReader {
+            latest: Arc::clone(&self.latest),
+        }
+    }
+}
+
+impl<T> Default for SnapshotPublisher<T> {
+    fn default() -> Self {
+        SnapshotPublisher::new()
+    }
+}
+
+impl<T> SnapshotReader<T> {
+    // `None` until the first snapshot is published
+    pub fn latest(&self) -> Option<Arc<Snapshot<T>>> {
+        self.latest
+            .lock()
+            .expect("The snapshot lock is never held across a panic")
+            .clone()
+    }
+}
+
+impl<T> Clone for SnapshotReader<T> {
+    fn clone(&self) -> Self {
+        SnapshotReader {
+            latest: Arc::clone(&self.latest),
+        }
+    }
+}
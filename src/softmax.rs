@@ -0,0 +1,184 @@
+use std::sync::Mutex;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, GreedyPolicy, Policy, Serialize,
+    masked_actions, softmax,
+};
+use crate::schedule::{ExponentialDecay, Schedule};
+
+const HEADER: &str = "softmax";
+
+// Boltzmann exploration: actions are sampled with probability proportional to exp(Q / τ), so a
+// move that gives away half the board is tried far less often than one that is nearly as good as
+// the best. The temperature τ cools from `start` to `end` with the episodes like epsilon decays,
+// hot it plays almost uniformly, cold almost greedily. Learns with Q-learning's target on a plain
+// table. The file puts the schedule and the episode in front of the table's format:
+//
+//   softmax;<start>;<end>;<rate>;<episode>
+//   <GreedyPolicy>
+pub struct SoftmaxPolicy<E: Environment> {
+    greedy_policy: GreedyPolicy<E>,
+    temperature: ExponentialDecay,
+    episode: usize,
+    rng: Mutex<StdRng>,
+}
+
+impl<E: Environment> SoftmaxPolicy<E> {
+    pub fn new(learning_rate: f32, gamma: f32, temperature: ExponentialDecay) -> Self {
+        SoftmaxPolicy::with_table(GreedyPolicy::new(learning_rate, gamma), temperature)
+    }
+
+    // Goes on from an existing table, e.g. one learned with epsilon greedy exploration
+    pub fn with_table(greedy_policy: GreedyPolicy<E>, temperature: ExponentialDecay) -> Self {
+        assert!(
+            temperature.start > 0f32 && temperature.end > 0f32,
+            "The temperature has to stay positive"
+        );
+        SoftmaxPolicy {
+            greedy_policy,
+            temperature,
+            episode: 0,
+            rng: Mutex::new(StdRng::from_os_rng()),
+        }
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Mutex::new(StdRng::seed_from_u64(seed));
+    }
+
+    pub fn greedy_policy(&self) -> &GreedyPolicy<E> {
+        &self.greedy_policy
+    }
+
+    pub fn greedy_policy_mut(&mut self) -> &mut GreedyPolicy<E> {
+        &mut self.greedy_policy
+    }
+
+    pub fn into_greedy_policy(self) -> GreedyPolicy<E> {
+        self.greedy_policy
+    }
+
+    pub fn episode(&self) -> usize {
+        self.episode
+    }
+
+    pub fn temperature_schedule(&self) -> ExponentialDecay {
+        self.temperature
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.temperature.value(self.episode)
+    }
+
+    // The probability of each action
+    fn probabilities(&self, state: E::ActionRelevantState, actions: &[E::Action]) -> Vec<f32> {
+        let values: Vec<f32> = actions
+            .iter()
+            .map(|&a| self.greedy_policy.value(state, a))
+            .collect();
+        softmax(&values, self.temperature())
+    }
+}
+
+impl<E: Environment> Policy<E> for SoftmaxPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        let actions = masked_actions::<E>(&state, mask);
+        let probabilities = self.probabilities(state, &actions);
+        let mut left = self
+            .rng
+            .lock()
+            .expect("The rng lock is never held across a panic")
+            .random_range(0f32..1f32);
+        for (&action, probability) in actions.iter().zip(probabilities) {
+            if left < probability {
+                return action;
+            }
+            left -= probability;
+        }
+        // Rounding can leave a sliver of probability at the end
+        *actions.last().expect(
+            "The way it is implemented now, there should always be possible actions (might be bad)",
+        )
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.greedy_policy
+            .improve(state, action, reward, next_state, finished);
+    }
+
+    fn on_episode_increment(&mut self) {
+        self.episode += 1;
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        let actions = E::actions(&state);
+        let probabilities = self.probabilities(state, &actions);
+        actions.into_iter().zip(probabilities).collect()
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.greedy_policy.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.greedy_policy.knows_state(state)
+    }
+}
+
+impl<E: Environment> Serialize for SoftmaxPolicy<E> {
+    fn serialize(&self) -> String {
+        format!(
+            "{HEADER};{};{};{};{}\n",
+            self.temperature.start, self.temperature.end, self.temperature.rate, self.episode
+        ) + self.greedy_policy.serialize().as_str()
+    }
+}
+
+impl<E: Environment> Deserialize for SoftmaxPolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let (first, rest) = input
+            .split_once('\n')
+            .ok_or_else(|| DeserializeError::because("a softmax policy has a first line"))?;
+        let expected = || {
+            DeserializeError::because(format!(
+                "a softmax policy starts with \"{HEADER};<start>;<end>;<rate>;<episode>\""
+            ))
+        };
+        let mut parts = first
+            .strip_prefix(HEADER)
+            .and_then(|parts| parts.strip_prefix(';'))
+            .ok_or_else(expected)?
+            .split(';');
+        let mut value = || match parts.next().map(str::parse::<f32>) {
+            Some(Ok(v)) if v.is_finite() => Ok(v),
+            _ => Err(expected()),
+        };
+        let temperature = ExponentialDecay {
+            start: value()?,
+            end: value()?,
+            rate: value()?,
+        };
+        let episode = value()?;
+        if parts.next().is_some() {
+            return Err(expected());
+        }
+        if temperature.start <= 0f32 || temperature.end <= 0f32 {
+            return Err(DeserializeError::because(
+                "a softmax policy needs a positive temperature",
+            ));
+        }
+        let mut policy = SoftmaxPolicy::with_table(GreedyPolicy::deserialize(rest)?, temperature);
+        policy.episode = episode as usize;
+        Ok(policy)
+    }
+}
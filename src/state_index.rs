@@ -0,0 +1,162 @@
+use std::cmp::Ordering;
+
+use crate::q_learning::{Deserialize, DeserializeError, Environment, QTable, Serialize};
+
+const HEADER: &str = "mankalla-rl state index 1";
+
+// A Q-table turned around for reading: one row per state with the values of all its actions,
+// sorted by state. States compare field by field, the words of their serialized form with numbers
+// in numeric order, so every state that starts with the same fields sits in one run and
+// `with_prefix` finds it with two binary searches instead of a scan of the table. Rows are written
+// like a policy's, in their order:
+//
+//   mankalla-rl state index 1
+//   0 0 1 7 7 7 7 6 6 6 6 6;- - 0.1 0.2 0.05 -
+//   0 0 1 7 7 7 7 6 6 6 6 7;- - 0.3 - 0.12 0.4
+pub struct StateIndex<E: Environment> {
+    rows: Vec<IndexRow<E>>,
+}
+
+pub struct IndexRow<E: Environment> {
+    pub state: E::ActionRelevantState,
+    fields: Vec<String>,
+    // By action index, `None` for slots that were never set
+    values: Vec<Option<f32>>,
+}
+
+impl<E: Environment> IndexRow<E> {
+    fn new(state: E::ActionRelevantState, values: Vec<Option<f32>>) -> Self {
+        IndexRow {
+            state,
+            fields: fields(&state.serialize()),
+            values,
+        }
+    }
+
+    // The actions that have a value
+    pub fn values(&self) -> impl Iterator<Item = (E::Action, f32)> + '_ {
+        E::actions(&self.state)
+            .into_iter()
+            .filter_map(|action| self.values[E::action_index(&action)].map(|value| (action, value)))
+    }
+
+    pub fn best(&self) -> Option<(E::Action, f32)> {
+        self.values().max_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    fn starts_with(&self, prefix: &[String]) -> Ordering {
+        let shared = prefix.len().min(self.fields.len());
+        compare_fields(&self.fields[..shared], prefix)
+    }
+}
+
+impl<E: Environment> StateIndex<E> {
+    pub fn new(qtable: &QTable<E>) -> Self {
+        let mut rows: Vec<IndexRow<E>> = qtable
+            .rows()
+            .map(|(state, row)| IndexRow::new(state, row.values().collect()))
+            .collect();
+        rows.sort_by(|a, b| compare_fields(&a.fields, &b.fields));
+        StateIndex { rows }
+    }
+
+    // All rows whose state starts with these fields, e.g. ["0"] for Mankalla positions where the
+    // first pit of the player to move is empty. No fields give every row.
+    pub fn with_prefix(&self, prefix: &[&str]) -> &[IndexRow<E>] {
+        let prefix: Vec<String> = prefix.iter().map(|field| field.to_string()).collect();
+        let start = self
+            .rows
+            .partition_point(|row| row.starts_with(&prefix) == Ordering::Less);
+        let end = self
+            .rows
+            .partition_point(|row| row.starts_with(&prefix) != Ordering::Greater);
+        &self.rows[start..end]
+    }
+
+    pub fn rows(&self) -> &[IndexRow<E>] {
+        &self.rows
+    }
+
+    pub fn states(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+fn fields(serialized: &str) -> Vec<String> {
+    serialized.split_whitespace().map(str::to_owned).collect()
+}
+
+// Numbers before anything else and by value, the rest as text
+fn compare_fields(a: &[String], b: &[String]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| compare_field(a, b))
+        .find(|&order| order != Ordering::Equal)
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+fn compare_field(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.total_cmp(&y).then_with(|| a.cmp(b)),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+impl<E: Environment> Serialize for StateIndex<E> {
+    fn serialize(&self) -> String {
+        let mut s = format!("{HEADER}\n");
+        for row in &self.rows {
+            let values = row
+                .values
+                .iter()
+                .map(|value| value.map_or("-".to_owned(), |v| v.to_string()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            s += &format!("{};{values}\n", row.state.serialize());
+        }
+        s
+    }
+}
+
+// Rows out of order would hide from `with_prefix`, so they are refused rather than sorted
+impl<E: Environment> Deserialize for StateIndex<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let mut lines = input.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(DeserializeError::because(format!(
+                "a state index starts with \"{HEADER}\""
+            )));
+        }
+        let mut rows: Vec<IndexRow<E>> = Vec::new();
+        for line in lines {
+            let reason =
+                |reason: &str| DeserializeError::because(format!("{reason} in \"{line}\""));
+            let (state, values) = line.split_once(';').ok_or_else(|| reason("no values"))?;
+            let values = values
+                .split(' ')
+                .map(|value| match value {
+                    "-" => Ok(None),
+                    value => value.parse::<f32>().map(Some),
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| reason("bad Q-value"))?;
+            if values.len() != E::MAX_ACTIONS {
+                return Err(reason(&format!(
+                    "{} values for {} actions",
+                    values.len(),
+                    E::MAX_ACTIONS
+                )));
+            }
+            let row = IndexRow::new(E::ActionRelevantState::deserialize(state)?, values);
+            if let Some(previous) = rows.last()
+                && compare_fields(&previous.fields, &row.fields) != Ordering::Less
+            {
+                return Err(reason("state out of order"));
+            }
+            rows.push(row);
+        }
+        Ok(StateIndex { rows })
+    }
+}
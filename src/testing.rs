@@ -0,0 +1,201 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::q_learning::{ActionList, ActionMask, Environment, Outcome, Policy, masked_actions};
+
+// Plays the given moves in order, whatever the state. Panics once the script runs out or when a
+// scripted move is not legal, so a test notices as soon as a game goes differently than planned.
+pub struct ScriptedPolicy<E: Environment> {
+    moves: Vec<E::Action>,
+    next: AtomicUsize,
+}
+
+impl<E: Environment> ScriptedPolicy<E> {
+    pub fn new(moves: Vec<E::Action>) -> Self {
+        ScriptedPolicy {
+            moves,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn played(&self) -> usize {
+        self.next.load(Ordering::Relaxed)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.played() >= self.moves.len()
+    }
+
+    // Start over from the first move, e.g. for the next episode
+    pub fn rewind(&self) {
+        self.next.store(0, Ordering::Relaxed);
+    }
+}
+
+impl<E: Environment> Policy<E> for ScriptedPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        let action = *self
+            .moves
+            .get(i)
+            .unwrap_or_else(|| panic!("The script only has {} moves", self.moves.len()));
+        assert!(
+            masked_actions::<E>(&state, mask).contains(&action),
+            "Scripted move {} is not legal here",
+            i
+        );
+        action
+    }
+
+    fn improve(
+        &mut self,
+        _state: E::ActionRelevantState,
+        _action: E::Action,
+        _reward: f32,
+        _next_state: E::State,
+        _finished: bool,
+    ) {
+    }
+}
+
+// Passes everything through to the wrapped policy and counts what a trainer asked of it
+pub struct CountingPolicy<P> {
+    inner: P,
+    choices: AtomicUsize,
+    updates: usize,
+    finished_updates: usize,
+    episodes: usize,
+    total_reward: f32,
+}
+
+impl<P> CountingPolicy<P> {
+    pub fn new(inner: P) -> Self {
+        CountingPolicy {
+            inner,
+            choices: AtomicUsize::new(0),
+            updates: 0,
+            finished_updates: 0,
+            episodes: 0,
+            total_reward: 0f32,
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    pub fn choices(&self) -> usize {
+        self.choices.load(Ordering::Relaxed)
+    }
+
+    pub fn updates(&self) -> usize {
+        self.updates
+    }
+
+    // Updates for the last step of an episode
+    pub fn finished_updates(&self) -> usize {
+        self.finished_updates
+    }
+
+    pub fn episodes(&self) -> usize {
+        self.episodes
+    }
+
+    // Sum of the rewards passed to `improve`
+    pub fn total_reward(&self) -> f32 {
+        self.total_reward
+    }
+}
+
+impl<E: Environment, P: Policy<E>> Policy<E> for CountingPolicy<P> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        self.choices.fetch_add(1, Ordering::Relaxed);
+        self.inner.choose_action(state, mask)
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        self.updates += 1;
+        self.finished_updates += finished as usize;
+        self.total_reward += reward;
+        self.inner
+            .improve(state, action, reward, next_state, finished);
+    }
+
+    fn on_episode_increment(&mut self) {
+        self.episodes += 1;
+        self.inner.on_episode_increment();
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        self.inner.action_distribution(state)
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.inner.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.inner.knows_state(state)
+    }
+}
+
+// The smallest game that still needs credit assignment: going right twice wins, going left
+// anywhere loses, so every episode ends after at most two steps.
+//
+//   state 0 --right--> state 1 --right--> win, reward 1
+//      |                  |
+//     left               left
+//      v                  v
+//    loss, reward 0     loss, reward 0
+//
+// The optimal values are known in closed form: Q(1, right) = 1 and Q(0, right) = gamma.
+pub struct TwoStateGame;
+
+pub const LEFT: u8 = 0;
+pub const RIGHT: u8 = 1;
+
+impl Environment for TwoStateGame {
+    type State = u8;
+    type ActionRelevantState = u8;
+    type Action = u8;
+
+    fn new() -> u8 {
+        0
+    }
+
+    const MAX_ACTIONS: usize = 2;
+
+    fn actions(_state: &u8) -> ActionList<u8> {
+        [LEFT, RIGHT].into_iter().collect()
+    }
+
+    fn action_index(action: &u8) -> usize {
+        *action as usize
+    }
+
+    // Every pair on its own, like a Q-table
+    type Afterstate = [u8; 2];
+
+    fn afterstate(state: &u8, action: &u8) -> ([u8; 2], f32) {
+        ([*state, *action], 0f32)
+    }
+
+    fn step(state: &u8, action: &u8) -> (u8, f32, Option<Outcome>) {
+        match (*state, *action) {
+            (_, LEFT) => (*state, 0f32, Some(Outcome::Loss)),
+            (0, RIGHT) => (1, 0f32, None),
+            (1, RIGHT) => (1, 1f32, Some(Outcome::Win)),
+            _ => panic!("The two state game only knows states 0, 1 and actions left, right"),
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::bandit::standard_normal;
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, GreedyPolicy, Policy, Serialize,
+    masked_actions,
+};
+
+// How often `action_distribution` samples the posteriors, enough for about a percent of accuracy.
+// Its stream is fixed, asking twice gives the same answer and leaves `choose_action`'s alone.
+const DISTRIBUTION_SAMPLES: usize = 10_000;
+const DISTRIBUTION_SEED: u64 = 0;
+
+// Normal-Gamma posterior over the value of one (state, action) pair and the noise of its targets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NormalGamma {
+    pub mean: f32,
+    pub precision: f32,
+    pub shape: f32,
+    pub rate: f32,
+}
+
+impl Default for NormalGamma {
+    fn default() -> Self {
+        NormalGamma {
+            mean: 0f32,
+            precision: 1f32,
+            shape: 1f32,
+            rate: 1f32,
+        }
+    }
+}
+
+// Marsaglia and Tsang, shapes below 1 are boosted by one and scaled back down
+pub fn sample_gamma(shape: f32, rate: f32, rng: &mut impl Rng) -> f32 {
+    if shape < 1f32 {
+        let u: f32 = rng.random_range(f32::EPSILON..1f32);
+        return sample_gamma(shape + 1f32, rate, rng) * u.powf(1f32 / shape);
+    }
+    let d = shape - 1f32 / 3f32;
+    let c = 1f32 / (9f32 * d).sqrt();
+    loop {
+        let x = standard_normal(rng);
+        let v = (1f32 + c * x).powi(3);
+        if v <= 0f32 {
+            continue;
+        }
+        let u: f32 = rng.random_range(f32::EPSILON..1f32);
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v / rate;
+        }
+    }
+}
+
+// `n` shares that sum to 1, all drawn with the same concentration `alpha`. Below 1 most of the
+// mass tends to go to a few of them.
+pub fn sample_dirichlet(alpha: f32, n: usize, rng: &mut impl Rng) -> Vec<f32> {
+    let draws: Vec<f32> = (0..n).map(|_| sample_gamma(alpha, 1f32, rng)).collect();
+    let total: f32 = draws.iter().sum();
+    match total > 0f32 {
+        true => draws.iter().map(|draw| draw / total).collect(),
+        false => vec![1f32 / n as f32; n],
+    }
+}
+
+impl NormalGamma {
+    // Conjugate update with one observed target. Past `max_observations` the old evidence is
+    // scaled down before each update, precision, shape and rate alike, so the posterior keeps
+    // following targets that move while learning and its estimate of their noise stays as wide as
+    // the observations it is really based on.
+    fn observe(&mut self, target: f32, max_observations: f32) {
+        let forget = (max_observations / self.precision).min(1f32);
+        let (precision, shape, rate) = (
+            self.precision * forget,
+            self.shape * forget,
+            self.rate * forget,
+        );
+        let error = target - self.mean;
+        self.rate = rate + precision * error * error / (2f32 * (precision + 1f32));
+        self.shape = shape + 0.5;
+        self.mean += error / (precision + 1f32);
+        self.precision = precision + 1f32;
+    }
+
+    // A plausible value: the noise precision from its Gamma, then the mean given that precision
+    fn sample(&self, rng: &mut impl Rng) -> f32 {
+        let tau = sample_gamma(self.shape, self.rate, rng).max(f32::MIN_POSITIVE);
+        self.mean + standard_normal(rng) / (self.precision * tau).sqrt()
+    }
+}
+
+// Explores by acting greedily on values sampled from each posterior, uncertain actions get
+// picked now and then without any epsilon or decay schedule to tune
+pub struct ThompsonPolicy<E: Environment> {
+    posteriors: HashMap<(E::ActionRelevantState, E::Action), NormalGamma>,
+    prior: NormalGamma,
+    gamma: f32,
+    max_observations: f32,
+    rng: Mutex<StdRng>,
+}
+
+impl<E: Environment> ThompsonPolicy<E> {
+    pub fn new(gamma: f32, prior: NormalGamma, max_observations: f32) -> Self {
+        ThompsonPolicy {
+            posteriors: HashMap::new(),
+            prior,
+            gamma,
+            max_observations,
+            rng: Mutex::new(StdRng::from_os_rng()),
+        }
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Mutex::new(StdRng::seed_from_u64(seed));
+    }
+
+    pub fn posterior(&self, state: E::ActionRelevantState, action: E::Action) -> NormalGamma {
+        *self.posteriors.get(&(state, action)).unwrap_or(&self.prior)
+    }
+
+    pub fn posteriors_size(&self) -> usize {
+        self.posteriors.len()
+    }
+
+    pub fn greedy_action(&self, state: E::ActionRelevantState) -> E::Action {
+        *E::actions(&state)
+            .iter()
+            .max_by(|&&a, &&b| {
+                self.posterior(state, a)
+                    .mean
+                    .total_cmp(&self.posterior(state, b).mean)
+            })
+            .expect(
+                "The way it is implemented now, there should always be possible actions (might be bad)",
+            )
+    }
+
+    // The posterior means as a plain Q-table, for evaluation and every tool that reads one.
+    // A learning rate of 1 means further updates just replace values.
+    pub fn to_greedy_policy(&self) -> GreedyPolicy<E> {
+        let mut policy = GreedyPolicy::new(1f32, self.gamma);
+        policy.qtable_mut().extend(
+            self.posteriors
+                .iter()
+                .map(|(&key, posterior)| (key, posterior.mean)),
+        );
+        policy
+    }
+}
+
+impl<E: Environment> ThompsonPolicy<E> {
+    fn sampled_action(
+        &self,
+        state: E::ActionRelevantState,
+        mask: Option<ActionMask>,
+        rng: &mut impl Rng,
+    ) -> E::Action {
+        masked_actions::<E>(&state, mask)
+            .into_iter()
+            .map(|a| (a, self.posterior(state, a).sample(rng)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(a, _)| a)
+            .expect(
+                "The way it is implemented now, there should always be possible actions (might be bad)",
+            )
+    }
+}
+
+impl<E: Environment> Policy<E> for ThompsonPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        let mut rng = self
+            .rng
+            .lock()
+            .expect("The rng lock is never held across a panic");
+        self.sampled_action(state, mask, &mut *rng)
+    }
+
+    // How often each action has the best sample, there is no closed form for more than two
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        let mut rng = StdRng::seed_from_u64(DISTRIBUTION_SEED);
+        let actions = E::actions(&state);
+        let mut wins = vec![0usize; actions.len()];
+        for _ in 0..DISTRIBUTION_SAMPLES {
+            let action = self.sampled_action(state, None, &mut rng);
+            if let Some(i) = actions.iter().position(|&a| a == action) {
+                wins[i] += 1;
+            }
+        }
+        actions
+            .into_iter()
+            .zip(wins)
+            .map(|(a, wins)| (a, wins as f32 / DISTRIBUTION_SAMPLES as f32))
+            .collect()
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        let target = reward
+            + match finished {
+                false => {
+                    let next_state = next_state.into();
+                    self.gamma
+                        * self
+                            .posterior(next_state, self.greedy_action(next_state))
+                            .mean
+                }
+                true => 0f32,
+            };
+        // Skipped like in GreedyPolicy, one NaN observation would poison the posterior for good
+        if !target.is_finite() {
+            return;
+        }
+        let mut posterior = self.posterior(state, action);
+        posterior.observe(target, self.max_observations);
+        self.posteriors.insert((state, action), posterior);
+    }
+}
+
+fn serialize_posterior(posterior: &NormalGamma) -> String {
+    format!(
+        "{};{};{};{}",
+        posterior.mean, posterior.precision, posterior.shape, posterior.rate
+    )
+}
+
+fn deserialize_posterior<'a>(
+    parts: &mut impl Iterator<Item = &'a str>,
+) -> Result<NormalGamma, DeserializeError> {
+    let mut value = || match parts.next().map(str::parse::<f32>) {
+        Some(Ok(v)) if v.is_finite() => Ok(v),
+        Some(Ok(_)) => Err(DeserializeError::because("non-finite posterior parameter")),
+        _ => Err(DeserializeError::new()),
+    };
+    Ok(NormalGamma {
+        mean: value()?,
+        precision: value()?,
+        shape: value()?,
+        rate: value()?,
+    })
+}
+
+// gamma;max observations, then the prior, then one posterior per line
+impl<E: Environment> Serialize for ThompsonPolicy<E> {
+    fn serialize(&self) -> String {
+        format!(
+            "{};{}\n{}\n",
+            self.gamma,
+            self.max_observations,
+            serialize_posterior(&self.prior)
+        ) + self
+            .posteriors
+            .iter()
+            .map(|((state, action), posterior)| {
+                format!(
+                    "{};{};{}\n",
+                    state.serialize(),
+                    action.serialize(),
+                    serialize_posterior(posterior)
+                )
+            })
+            .collect::<String>()
+            .as_str()
+    }
+}
+
+impl<E: Environment> Deserialize for ThompsonPolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let mut lines = input.lines();
+
+        let mut parameters = match lines.next() {
+            Some(s) => s.split(';').map(|a| a.parse::<f32>()),
+            _ => return Err(DeserializeError::new()),
+        };
+        let (gamma, max_observations) = match (parameters.next(), parameters.next()) {
+            (Some(Ok(g)), Some(Ok(m))) => (g, m),
+            _ => return Err(DeserializeError::new()),
+        };
+        if parameters.next().is_some() {
+            return Err(DeserializeError::new());
+        }
+        let prior = match lines.next() {
+            Some(s) => deserialize_posterior(&mut s.split(';'))?,
+            _ => return Err(DeserializeError::new()),
+        };
+
+        let mut policy = ThompsonPolicy::new(gamma, prior, max_observations);
+        for line in lines {
+            let mut parts = line.split(';');
+            let state = match parts.next() {
+                Some(s) => E::ActionRelevantState::deserialize(s)?,
+                _ => return Err(DeserializeError::new()),
+            };
+            let action = match parts.next() {
+                Some(a) => E::Action::deserialize(a)?,
+                _ => return Err(DeserializeError::new()),
+            };
+            let posterior = deserialize_posterior(&mut parts).map_err(|e| match e.reason() {
+                Some(reason) => DeserializeError::because(format!("{reason} in line \"{line}\"")),
+                None => e,
+            })?;
+            if parts.next().is_some() {
+                return Err(DeserializeError::new());
+            }
+            policy.posteriors.insert((state, action), posterior);
+        }
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{RIGHT, TwoStateGame};
+
+    #[test]
+    fn capped_posteriors_stay_bounded() {
+        let mut posterior = NormalGamma::default();
+        for i in 0..10_000 {
+            posterior.observe((i % 2) as f32, 10f32);
+        }
+        assert!(posterior.precision <= 11f32);
+        assert!(posterior.shape <= 6f32);
+        // The targets spread by 0.5 around their mean, the expected variance has to stay near that
+        let variance = posterior.rate / posterior.shape;
+        assert!((0.1..1f32).contains(&variance), "{variance}");
+    }
+
+    #[test]
+    fn action_distribution_favours_the_better_posterior() {
+        let mut policy = ThompsonPolicy::<TwoStateGame>::new(1f32, NormalGamma::default(), 100f32);
+        for _ in 0..50 {
+            policy.improve(1, RIGHT, 1f32, 1, true);
+        }
+        let distribution = policy.action_distribution(1);
+        let total: f32 = distribution.iter().map(|(_, p)| p).sum();
+        assert!((total - 1f32).abs() < 1e-3);
+        let (most_likely, _) = distribution
+            .iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("There are actions");
+        assert_eq!(*most_likely, RIGHT);
+        assert_eq!(distribution, policy.action_distribution(1));
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::evaluation::{
+    EvaluationReport, PairedEvaluationReport, SearchSummary, elo_difference, random_opening,
+};
+use crate::mankalla::{MankallaGame, MankallaGameState, Player};
+use crate::q_learning::{Outcome, Serialize};
+use crate::search::SearchStats;
+use crate::two_player::TwoPlayerGame;
+
+// Long enough for a slow engine to start up
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum EngineError {
+    Io(io::Error),
+    // No answer within the time allowed
+    Timeout,
+    // The engine closed its output
+    Exited,
+    Protocol(String),
+}
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Io(e) => write!(f, "{e}"),
+            EngineError::Timeout => write!(f, "no answer in time"),
+            EngineError::Exited => write!(f, "the engine exited"),
+            EngineError::Protocol(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<io::Error> for EngineError {
+    fn from(value: io::Error) -> Self {
+        EngineError::Io(value)
+    }
+}
+
+// What an engine gets for every move
+#[derive(Clone, Copy, Debug)]
+pub struct GoLimits {
+    pub depth: Option<usize>,
+    pub movetime: Option<Duration>,
+    // An engine that has not answered by then forfeits the game
+    pub timeout: Duration,
+}
+
+impl GoLimits {
+    fn command(&self) -> String {
+        let mut command = "go".to_owned();
+        if let Some(depth) = self.depth {
+            command += &format!(" depth {depth}");
+        }
+        if let Some(movetime) = self.movetime {
+            command += &format!(" movetime {}", movetime.as_millis());
+        }
+        command
+    }
+}
+
+// A program that speaks the protocol of the `engine` subcommand. Its output is read on a thread of
+// its own so a hanging engine runs into the timeout instead of blocking the referee.
+pub struct EngineProcess {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<io::Result<String>>,
+}
+
+impl EngineProcess {
+    // The command line is split at whitespace, the first word is the program
+    pub fn spawn(command_line: &str) -> Result<Self, EngineError> {
+        let mut words = command_line.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| EngineError::Protocol("empty engine command".to_owned()))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+
+        let mut engine = EngineProcess {
+            name: command_line.to_owned(),
+            child,
+            stdin,
+            lines,
+        };
+        engine.send("mei")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = engine.receive(deadline)?;
+            match line.strip_prefix("id name ") {
+                Some(name) => engine.name = name.to_owned(),
+                None if line == "meiok" => return Ok(engine),
+                None => {}
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), EngineError> {
+        writeln!(self.stdin, "{line}")?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn receive(&self, deadline: Instant) -> Result<String, EngineError> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line?),
+            Err(RecvTimeoutError::Timeout) => Err(EngineError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(EngineError::Exited),
+        }
+    }
+
+    // The engine's move with the search it reported, if it reported one. The move is not checked
+    // against the rules here, that is the referee's job.
+    pub fn best_move(
+        &mut self,
+        position: &MankallaGameState,
+        limits: &GoLimits,
+    ) -> Result<(u8, Option<SearchStats<u8>>), EngineError> {
+        // Whatever a search that ran out of time still says arrives before the "readyok"
+        self.send("isready")?;
+        let deadline = Instant::now() + limits.timeout;
+        while self.receive(deadline)? != "readyok" {}
+
+        self.send(&format!("position fields {position:#}"))?;
+        self.send(&limits.command())?;
+        let deadline = Instant::now() + limits.timeout;
+        let mut stats = None;
+        loop {
+            let line = self.receive(deadline)?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["bestmove", pit] => {
+                    let pit = pit
+                        .parse()
+                        .map_err(|_| EngineError::Protocol(format!("bad move in \"{line}\"")))?;
+                    return Ok((pit, stats));
+                }
+                ["info", rest @ ..] => stats = parse_info(rest).or(stats),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for EngineProcess {
+    // Asks nicely first, engines that do not listen are killed
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// "depth 6 score 1 nodes 2855 time 0 pv 5 1 0", fields the engine leaves out stay empty
+fn parse_info(words: &[&str]) -> Option<SearchStats<u8>> {
+    let mut stats = SearchStats {
+        nodes: 0,
+        depth: 0,
+        time: Duration::ZERO,
+        principal_variation: Vec::new(),
+        score: 0f32,
+        table_hits: 0,
+        pondered: false,
+    };
+    let mut words = words.iter();
+    while let Some(&key) = words.next() {
+        match key {
+            "depth" => stats.depth = words.next()?.parse().ok()?,
+            "nodes" => stats.nodes = words.next()?.parse().ok()?,
+            "score" => stats.score = words.next()?.parse().ok()?,
+            "time" => stats.time = Duration::from_millis(words.next()?.parse().ok()?),
+            "pv" => {
+                stats.principal_variation = words.by_ref().filter_map(|w| w.parse().ok()).collect()
+            }
+            // "info string ..." and whatever else engines like to say
+            _ => return None,
+        }
+    }
+    Some(stats)
+}
+
+// A game between two engines, `forfeit` names the seat that broke the rules or did not answer
+pub struct RefereedGame {
+    pub moves: Vec<u8>,
+    pub final_state: MankallaGameState,
+    pub forfeit: Option<(Player, EngineError)>,
+}
+
+impl RefereedGame {
+    pub fn outcome(&self, player: &Player) -> Outcome {
+        match &self.forfeit {
+            Some((loser, _)) if loser == player => Outcome::Loss,
+            Some(_) => Outcome::Win,
+            None => self
+                .final_state
+                .outcome(player)
+                .expect("A game without a forfeit is played to the end"),
+        }
+    }
+}
+
+// Every move is checked against the rules of this crate, an illegal one loses the game
+pub fn referee_game(
+    player1: &mut EngineProcess,
+    player2: &mut EngineProcess,
+    opening: MankallaGameState,
+    limits: &GoLimits,
+    searches: &mut [SearchSummary; 2],
+) -> RefereedGame {
+    let mut state = opening;
+    let mut moves = Vec::new();
+    while MankallaGame::outcome(&state).is_none() {
+        let seat = state.get_player_to_move();
+        let (engine, summary) = match seat {
+            Player::Player1 => (&mut *player1, &mut searches[0]),
+            Player::Player2 => (&mut *player2, &mut searches[1]),
+        };
+        let pit = match engine.best_move(&state, limits) {
+            Ok((pit, stats)) => {
+                if let Some(stats) = stats {
+                    summary.record(&stats);
+                }
+                pit
+            }
+            Err(e) => {
+                return RefereedGame {
+                    moves,
+                    final_state: state,
+                    forfeit: Some((seat, e)),
+                };
+            }
+        };
+        if !MankallaGame::legal_moves(&state.into()).contains(&pit) {
+            return RefereedGame {
+                moves,
+                final_state: state,
+                forfeit: Some((
+                    seat,
+                    EngineError::Protocol(format!("illegal move {} in {state:#}", pit.serialize())),
+                )),
+            };
+        }
+        moves.push(pit);
+        state = MankallaGame::apply_move(&state, &pit);
+    }
+    RefereedGame {
+        moves,
+        final_state: state,
+        forfeit: None,
+    }
+}
+
+// Results seen from the first engine
+pub struct TournamentReport {
+    pub engines: [String; 2],
+    pub paired: PairedEvaluationReport,
+    // Games each engine lost by breaking the rules or not answering, with the first reason
+    pub forfeits: [usize; 2],
+    pub first_forfeit: Option<String>,
+}
+
+// Mirror matches from random openings like `evaluate_paired`, each opening is played once from
+// either seat
+pub fn run_tournament(
+    first: &mut EngineProcess,
+    second: &mut EngineProcess,
+    num_pairs: usize,
+    opening_plies: usize,
+    limits: &GoLimits,
+    rng: &mut impl Rng,
+) -> TournamentReport {
+    let mut report = TournamentReport {
+        engines: [first.name().to_owned(), second.name().to_owned()],
+        paired: PairedEvaluationReport::default(),
+        forfeits: [0; 2],
+        first_forfeit: None,
+    };
+    let mut games = HashSet::new();
+    for _ in 0..num_pairs {
+        let opening = random_opening(opening_plies, rng);
+        let mut pair = EvaluationReport::default();
+        for first_seat in [Player::Player1, Player::Player2] {
+            let mut searches = Default::default();
+            let game = match first_seat {
+                Player::Player1 => referee_game(first, second, opening, limits, &mut searches),
+                Player::Player2 => referee_game(second, first, opening, limits, &mut searches),
+            };
+            let [first_search, second_search] = match first_seat {
+                Player::Player1 => searches,
+                Player::Player2 => [searches[1], searches[0]],
+            };
+            report.paired.policy_search.add(&first_search);
+            report.paired.opponent_search.add(&second_search);
+            if let Some((seat, e)) = &game.forfeit {
+                let engine = match seat == &first_seat {
+                    true => 0,
+                    false => 1,
+                };
+                report.forfeits[engine] += 1;
+                report
+                    .first_forfeit
+                    .get_or_insert_with(|| format!("{}: {e}", report.engines[engine]));
+            }
+            pair.record(Some(game.outcome(&first_seat)));
+            games.insert((opening, game.moves));
+        }
+        report.paired.report.wins += pair.wins;
+        report.paired.report.losses += pair.losses;
+        report.paired.report.draws += pair.draws;
+        report.paired.pair_scores.push(pair.score());
+    }
+    report.paired.distinct_games = games.len();
+    report
+}
+
+impl Display for TournamentReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} vs {}", self.engines[0], self.engines[1])?;
+        writeln!(f, "{}", self.paired)?;
+        writeln!(
+            f,
+            "About {:+.0} Elo for {}",
+            elo_difference(self.paired.mean_score(), self.paired.report.games()),
+            self.engines[0]
+        )?;
+        write!(
+            f,
+            "Forfeits: {} by {}, {} by {}",
+            self.forfeits[0], self.engines[0], self.forfeits[1], self.engines[1]
+        )?;
+        if let Some(reason) = &self.first_forfeit {
+            write!(f, " (first: {reason})")?;
+        }
+        Ok(())
+    }
+}
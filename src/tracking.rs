@@ -0,0 +1,273 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::experiments::{self, Experiment, Manifest};
+use crate::json::Json;
+use crate::q_learning::Deserialize;
+
+// MLflow's file store, a directory `mlflow ui --backend-store-uri <root>` reads as is. All runs
+// go into one MLflow experiment, the crate's run id becomes the MLflow run name.
+const MLFLOW_EXPERIMENT_ID: &str = "1";
+const MLFLOW_EXPERIMENT_NAME: &str = "mankalla-rl";
+// RunStatus in MLflow's protos
+const MLFLOW_RUNNING: u8 = 1;
+const MLFLOW_FINISHED: u8 = 3;
+const MLFLOW_KILLED: u8 = 5;
+
+// One row of the training curve: episode and (metric, value) pairs in column order
+pub struct MetricRow {
+    pub episode: usize,
+    pub values: Vec<(String, f64)>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_manifest(experiment: &Experiment, file: &str) -> io::Result<Manifest> {
+    match fs::read_to_string(experiment.path(file)) {
+        Ok(s) => Manifest::deserialize(&s)
+            .map_err(|_| invalid(format!("Could not parse {file} of {}", experiment.id()))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Manifest::default()),
+        Err(e) => Err(e),
+    }
+}
+
+// The metrics file of a run, empty if the run never got to write one
+pub fn metric_rows(experiment: &Experiment) -> io::Result<Vec<MetricRow>> {
+    let csv = match fs::read_to_string(experiment.path(experiments::METRICS_FILE)) {
+        Ok(csv) => csv,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut lines = csv.lines();
+    let header: Vec<&str> = lines.next().unwrap_or("").split(',').collect();
+    lines
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let mut fields = line.split(',');
+            let episode = fields
+                .next()
+                .and_then(|e| e.parse().ok())
+                .ok_or_else(|| invalid(format!("Bad metrics row \"{line}\"")))?;
+            // Empty fields were not measured in that block
+            let values = header
+                .iter()
+                .skip(1)
+                .zip(fields)
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(name, value)| match value.parse::<f64>() {
+                    Ok(v) => Ok((name.to_string(), v)),
+                    Err(_) => Err(invalid(format!("Bad metrics row \"{line}\""))),
+                })
+                .collect::<io::Result<_>>()?;
+            Ok(MetricRow { episode, values })
+        })
+        .collect()
+}
+
+fn millis(manifest: &Manifest, key: &str) -> u64 {
+    manifest
+        .get(key)
+        .and_then(|s| s.parse::<u64>().ok())
+        .map_or(0, |s| s * 1000)
+}
+
+// Stable per crate run so exporting again overwrites instead of duplicating
+fn mlflow_run_id(id: &str) -> String {
+    let hash = |seed: u64| {
+        id.bytes().fold(seed, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    };
+    format!(
+        "{:016x}{:016x}",
+        hash(0xcbf2_9ce4_8422_2325),
+        hash(0x8422_2325_cbf2_9ce4)
+    )
+}
+
+fn file_uri(path: &Path) -> io::Result<String> {
+    Ok(format!("file://{}", fs::canonicalize(path)?.display()))
+}
+
+// MLflow keys may only hold alphanumerics, '_', '-', '.', ' ' and '/'
+fn mlflow_key(key: &str) -> String {
+    key.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "_-. /".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+// Returns the directory of the exported run
+pub fn export_mlflow(experiment: &Experiment, root: &Path) -> io::Result<PathBuf> {
+    let manifest = experiment.manifest()?;
+    let config = read_manifest(experiment, experiments::CONFIG_FILE)?;
+    let started = millis(&manifest, "started");
+    let finished = millis(&manifest, "finished");
+
+    let experiment_dir = root.join(MLFLOW_EXPERIMENT_ID);
+    fs::create_dir_all(&experiment_dir)?;
+    if !experiment_dir.join("meta.yaml").exists() {
+        fs::write(
+            experiment_dir.join("meta.yaml"),
+            format!(
+                "artifact_location: {}\ncreation_time: {started}\nexperiment_id: '{MLFLOW_EXPERIMENT_ID}'\nlast_update_time: {started}\nlifecycle_stage: active\nname: {MLFLOW_EXPERIMENT_NAME}\n",
+                file_uri(&experiment_dir)?
+            ),
+        )?;
+    }
+
+    let run_id = mlflow_run_id(experiment.id());
+    let run_dir = experiment_dir.join(&run_id);
+    if run_dir.exists() {
+        fs::remove_dir_all(&run_dir)?;
+    }
+    for dir in ["params", "metrics", "tags", "artifacts"] {
+        fs::create_dir_all(run_dir.join(dir))?;
+    }
+    let status = match manifest.get("status") {
+        Some("running") => MLFLOW_RUNNING,
+        Some("finished") => MLFLOW_FINISHED,
+        _ => MLFLOW_KILLED,
+    };
+    let end_time = match finished {
+        0 => "null".to_owned(),
+        finished => finished.to_string(),
+    };
+    fs::write(
+        run_dir.join("meta.yaml"),
+        format!(
+            "artifact_uri: {}\nend_time: {end_time}\nentry_point_name: ''\nexperiment_id: '{MLFLOW_EXPERIMENT_ID}'\nlifecycle_stage: active\nrun_id: {run_id}\nrun_name: {id}\nrun_uuid: {run_id}\nsource_name: ''\nsource_type: 4\nsource_version: ''\nstart_time: {started}\nstatus: {status}\ntags: []\nuser_id: ''\n",
+            file_uri(&run_dir.join("artifacts"))?,
+            id = experiment.id()
+        ),
+    )?;
+
+    for (key, value) in config.entries() {
+        fs::write(run_dir.join("params").join(mlflow_key(key)), value)?;
+    }
+    let mut tags = vec![
+        ("mlflow.runName".to_owned(), experiment.id().to_owned()),
+        (
+            "mlflow.source.name".to_owned(),
+            manifest.get("command").unwrap_or("").to_owned(),
+        ),
+    ];
+    tags.extend(
+        manifest
+            .entries()
+            .iter()
+            .map(|(key, value)| (format!("mankalla.{}", mlflow_key(key)), value.clone())),
+    );
+    for (key, value) in tags {
+        fs::write(run_dir.join("tags").join(key), value)?;
+    }
+
+    // Metric files hold "timestamp value step" lines, the step is the episode
+    let mut metrics: Vec<(String, String)> = Vec::new();
+    for row in metric_rows(experiment)? {
+        for (name, value) in &row.values {
+            let line = format!("{finished} {value} {}\n", row.episode);
+            match metrics.iter_mut().find(|(n, _)| n == name) {
+                Some((_, lines)) => lines.push_str(&line),
+                None => metrics.push((name.clone(), line)),
+            }
+        }
+    }
+    if let Some(win_rate) = manifest.get("eval_win_rate") {
+        let episode = manifest.get("policy_episode").unwrap_or("0");
+        metrics.push((
+            "eval_win_rate".to_owned(),
+            format!("{finished} {win_rate} {episode}\n"),
+        ));
+    }
+    for (name, lines) in metrics {
+        fs::write(run_dir.join("metrics").join(mlflow_key(&name)), lines)?;
+    }
+
+    for file in experiment.files()? {
+        let target = run_dir.join("artifacts").join(&file);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(experiment.path(&file), target)?;
+    }
+    Ok(run_dir)
+}
+
+fn json_string(s: &str) -> String {
+    Json::from(s).to_string()
+}
+
+// Numbers and booleans stay numbers and booleans, everything else becomes a string. So do
+// integers a double can not hold exactly, like seeds.
+fn json_value(s: &str) -> String {
+    match (s, s.parse::<f64>()) {
+        ("true" | "false", _) => s.to_owned(),
+        (_, Ok(v)) if v.is_finite() && v.abs() < (1u64 << 53) as f64 => s.to_owned(),
+        _ => json_string(s),
+    }
+}
+
+fn json_object<'a>(entries: impl Iterator<Item = (&'a str, String)>) -> String {
+    let fields: Vec<String> = entries
+        .map(|(key, value)| format!("{}: {value}", json_string(key)))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+// The three files Weights & Biases keeps per run: config.json, one history line per logged step
+// with `_step` set to the episode, and summary.json with the final values. Replaying them through
+// `wandb.init(config=...)` and `wandb.log(row, step=row["_step"])` recreates the run.
+pub fn export_wandb(experiment: &Experiment, root: &Path) -> io::Result<PathBuf> {
+    let manifest = experiment.manifest()?;
+    let config = read_manifest(experiment, experiments::CONFIG_FILE)?;
+    let dir = root.join(experiment.id());
+    fs::create_dir_all(&dir)?;
+
+    let config_json = json_object(
+        config
+            .entries()
+            .iter()
+            .map(|(k, v)| (k.as_str(), json_value(v))),
+    );
+    fs::write(dir.join("config.json"), config_json + "\n")?;
+
+    let rows = metric_rows(experiment)?;
+    let history: String = rows
+        .iter()
+        .map(|row| {
+            let step = [("_step", row.episode.to_string())];
+            json_object(
+                step.into_iter().chain(
+                    row.values
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.to_string())),
+                ),
+            ) + "\n"
+        })
+        .collect();
+    fs::write(dir.join("history.jsonl"), history)?;
+
+    let mut summary: Vec<(&str, String)> = rows.last().map_or(Vec::new(), |row| {
+        row.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.to_string()))
+            .collect()
+    });
+    summary.extend(
+        manifest
+            .entries()
+            .iter()
+            .map(|(k, v)| (k.as_str(), json_value(v))),
+    );
+    fs::write(
+        dir.join("summary.json"),
+        json_object(summary.into_iter()) + "\n",
+    )?;
+    Ok(dir)
+}
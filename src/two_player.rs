@@ -0,0 +1,370 @@
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::q_learning::{
+    ActionList, Deserialize, DeserializeError, Environment, Outcome, Policy, SeededRandomPolicy,
+    Serialize,
+};
+use crate::seeding::split_mix;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Player {
+    Player1,
+    Player2,
+}
+
+impl Player {
+    pub fn opponent(&self) -> Player {
+        match self {
+            Player::Player1 => Player::Player2,
+            Player::Player2 => Player::Player1,
+        }
+    }
+}
+
+// Only the rules of a turn-based game for two, `Environment` comes with it. Moves may give the
+// mover another turn, the adapter asks whose turn it is instead of assuming they alternate.
+pub trait TwoPlayerGame {
+    type Position: Copy;
+    // What the player to move sees, the same for both players in the same situation
+    type View: From<Self::Position> + Copy + Eq + Hash + Serialize + Deserialize;
+    type Move: Copy + Default + Eq + Hash + Serialize + Deserialize;
+    // What `unmake_move` needs to take a move back
+    type Undo: Copy;
+
+    fn start() -> Self::Position;
+    // A position that looks like the view to the player to move, for searches that start from
+    // what a policy gets to see. Whatever the view leaves out may differ, scores included.
+    fn from_view(view: &Self::View) -> Self::Position;
+    fn current_player(position: &Self::Position) -> Player;
+    // `Environment::MAX_ACTIONS`
+    const MAX_MOVES: usize;
+    fn legal_moves(view: &Self::View) -> ActionList<Self::Move>;
+    // Distinct per move and below `MAX_MOVES`
+    fn move_index(game_move: &Self::Move) -> usize;
+    // Plays the move on the position itself, searches walk the tree without a copy per node
+    fn make_move(position: &mut Self::Position, game_move: &Self::Move) -> Self::Undo;
+    fn unmake_move(position: &mut Self::Position, undo: Self::Undo);
+    fn apply_move(position: &Self::Position, game_move: &Self::Move) -> Self::Position {
+        let mut position = *position;
+        Self::make_move(&mut position, game_move);
+        position
+    }
+    // Seen from `Player1`, `None` while the game goes on
+    fn outcome(position: &Self::Position) -> Option<Outcome>;
+    // Points collected so far for games that count them along the way, every move is rewarded
+    // with how much it widened the mover's lead
+    fn score(_position: &Self::Position, _player: Player) -> f32 {
+        0f32
+    }
+    // A hash of the position for transposition tables, `None` leaves them out
+    fn zobrist(_position: &Self::Position) -> Option<u64> {
+        None
+    }
+    // The hash after a move from the one before, games that can update it piece by piece do that
+    fn zobrist_after(_before: &Self::Position, _key: u64, after: &Self::Position) -> u64 {
+        Self::zobrist(after).unwrap_or(0)
+    }
+    // Paid to the mover on top once the game is over
+    fn outcome_reward(outcome: Outcome) -> f32 {
+        match outcome {
+            Outcome::Win => 1f32,
+            Outcome::Loss => -1f32,
+            Outcome::Draw => 0f32,
+        }
+    }
+}
+
+// A reward as the player who moved sees it. Learners train on `value`, everyone who keeps track of
+// a fixed player goes through `for_player`, so a number about one side never gets booked to the
+// other by a forgotten sign flip.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RelativeReward {
+    mover: Player,
+    value: f32,
+}
+
+impl RelativeReward {
+    pub fn new(mover: Player, value: f32) -> Self {
+        RelativeReward { mover, value }
+    }
+
+    pub fn mover(&self) -> Player {
+        self.mover
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    // Zero-sum, what the mover gained the opponent lost
+    pub fn for_player(&self, player: Player) -> f32 {
+        match player == self.mover {
+            true => self.value,
+            false => -self.value,
+        }
+    }
+}
+
+// `Environment::step` with the perspective of the reward kept. Outcome and reward belong to the
+// player who moved, whoever moves next.
+pub fn play<G: TwoPlayerGame>(
+    position: &G::Position,
+    game_move: &G::Move,
+) -> (G::Position, RelativeReward, Option<Outcome>) {
+    let mut next_position = *position;
+    let (_, reward, outcome) = play_in_place::<G>(&mut next_position, game_move);
+    (next_position, reward, outcome)
+}
+
+// `play` on the position itself, `G::unmake_move` with the token restores it
+pub fn play_in_place<G: TwoPlayerGame>(
+    position: &mut G::Position,
+    game_move: &G::Move,
+) -> (G::Undo, RelativeReward, Option<Outcome>) {
+    let mover = G::current_player(position);
+    let lead =
+        |position: &G::Position| G::score(position, mover) - G::score(position, mover.opponent());
+    let lead_before = lead(position);
+    let undo = G::make_move(position, game_move);
+    let outcome = G::outcome(position).map(|outcome| match mover {
+        Player::Player1 => outcome,
+        Player::Player2 => outcome.opposite(),
+    });
+    let reward = lead(position) - lead_before + outcome.map_or(0f32, G::outcome_reward);
+    (undo, RelativeReward::new(mover, reward), outcome)
+}
+
+// The position right after a move and before any reply, as the player to move next sees it, and
+// whether that is the mover again. A move that ends the game leaves the final position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AfterMove<V> {
+    pub view: V,
+    pub moves_again: bool,
+}
+
+// The view and then 1 or 0 for moving again
+impl<V: Serialize> Serialize for AfterMove<V> {
+    fn serialize(&self) -> String {
+        format!("{} {}", self.view.serialize(), self.moves_again as u8)
+    }
+}
+
+impl<V: Deserialize> Deserialize for AfterMove<V> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let expected = || DeserializeError::because("an afterstate is a view and 1 or 0");
+        let (view, moves_again) = input.rsplit_once(' ').ok_or_else(expected)?;
+        Ok(AfterMove {
+            view: V::deserialize(view)?,
+            moves_again: match moves_again {
+                "1" => true,
+                "0" => false,
+                _ => return Err(expected()),
+            },
+        })
+    }
+}
+
+// Played from a position that looks like the view, so the reward is only as right as the view
+// tells: scores it leaves out can not decide the outcome reward
+fn after_move<G: TwoPlayerGame>(view: &G::View, game_move: &G::Move) -> (AfterMove<G::View>, f32) {
+    let position = G::from_view(view);
+    let (next_position, reward, _) = play::<G>(&position, game_move);
+    (
+        AfterMove {
+            view: next_position.into(),
+            moves_again: G::current_player(&next_position) == G::current_player(&position),
+        },
+        reward.value(),
+    )
+}
+
+impl<G: TwoPlayerGame> Environment for G {
+    type State = G::Position;
+    type ActionRelevantState = G::View;
+    type Action = G::Move;
+
+    fn new() -> G::Position {
+        G::start()
+    }
+
+    const MAX_ACTIONS: usize = G::MAX_MOVES;
+
+    fn actions(state: &G::View) -> ActionList<G::Move> {
+        G::legal_moves(state)
+    }
+
+    fn action_index(action: &G::Move) -> usize {
+        G::move_index(action)
+    }
+
+    type Afterstate = AfterMove<G::View>;
+
+    fn afterstate(state: &G::View, action: &G::Move) -> (AfterMove<G::View>, f32) {
+        after_move::<G>(state, action)
+    }
+
+    // The learners see every position from the side of the player to move, so they get the
+    // reward of the mover
+    fn step(state: &G::Position, action: &G::Move) -> (G::Position, f32, Option<Outcome>) {
+        let (next_state, reward, outcome) = play::<G>(state, action);
+        (next_state, reward.value(), outcome)
+    }
+}
+
+// Single agent training against a fixed opponent, like `Nim` has built in: `step` plays the
+// opponent's replies with `P` until it is the learner's turn again, and pays the learner its own
+// reward minus the opponent's. The learner always takes the first seat. `step` has no `self`, so
+// the opponent is made for every reply, from a seed drawn from the stream the state carries, and
+// should be cheap to make. A seeded episode is played the same way every time. The views and moves
+// are the game's, so a policy trained here plays the game itself as well, the game only has to
+// tell how to see its position in a `VsOpponentState`.
+pub struct VsOpponentEnv<G, P> {
+    game: PhantomData<G>,
+    opponent: PhantomData<P>,
+}
+
+// A position of a `VsOpponentEnv` with the random stream of the opponent's replies
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VsOpponentState<S> {
+    pub position: S,
+    rng: u64,
+}
+
+// An opponent for `VsOpponentEnv`, made from a seed for every reply. Deterministic ones ignore it.
+pub trait SeededOpponent<G: TwoPlayerGame>: Policy<G> {
+    fn seeded(seed: u64) -> Self;
+}
+
+impl<G: TwoPlayerGame> SeededOpponent<G> for SeededRandomPolicy {
+    fn seeded(seed: u64) -> Self {
+        SeededRandomPolicy::new(seed)
+    }
+}
+
+impl<G: TwoPlayerGame, P: SeededOpponent<G>> Environment for VsOpponentEnv<G, P>
+where
+    G::View: From<VsOpponentState<G::Position>>,
+{
+    type State = VsOpponentState<G::Position>;
+    type ActionRelevantState = G::View;
+    type Action = G::Move;
+
+    fn new() -> VsOpponentState<G::Position> {
+        Self::new_seeded(rand::random())
+    }
+
+    fn new_seeded(seed: u64) -> VsOpponentState<G::Position> {
+        VsOpponentState {
+            position: G::start(),
+            rng: seed,
+        }
+    }
+
+    const MAX_ACTIONS: usize = G::MAX_MOVES;
+
+    fn actions(state: &G::View) -> ActionList<G::Move> {
+        G::legal_moves(state)
+    }
+
+    fn action_index(action: &G::Move) -> usize {
+        G::move_index(action)
+    }
+
+    // Before the opponent's replies, which are what the afterstate value learns to expect
+    type Afterstate = AfterMove<G::View>;
+
+    fn afterstate(state: &G::View, action: &G::Move) -> (AfterMove<G::View>, f32) {
+        after_move::<G>(state, action)
+    }
+
+    // The outcome is the learner's, whoever made the last move
+    fn step(
+        state: &VsOpponentState<G::Position>,
+        action: &G::Move,
+    ) -> (VsOpponentState<G::Position>, f32, Option<Outcome>) {
+        let mut rng = state.rng;
+        let learner = G::current_player(&state.position);
+        let (mut position, reward, mut outcome) = play::<G>(&state.position, action);
+        let mut total = reward.for_player(learner);
+        while outcome.is_none() && G::current_player(&position) != learner {
+            let reply = P::seeded(split_mix(&mut rng)).choose_action(position.into(), None);
+            let reward;
+            (position, reward, outcome) = play::<G>(&position, &reply);
+            total += reward.for_player(learner);
+            outcome = outcome.map(Outcome::opposite);
+        }
+        (VsOpponentState { position, rng }, total, outcome)
+    }
+}
+
+// The same game with an extra-turn chain played out as part of the move that started it, so an
+// episode has one step per turn instead of one per sub-move. Follow-ups come from `F`, made with
+// `Default` for each of them, e.g. a policy that takes what scores
+// most right away. The learner's own policy can not pick them, `make_move` does not know it.
+pub struct ChainedTurns<G, F> {
+    game: PhantomData<G>,
+    follow_ups: PhantomData<F>,
+}
+
+impl<G: TwoPlayerGame, F: Policy<G> + Default> TwoPlayerGame for ChainedTurns<G, F> {
+    type Position = G::Position;
+    type View = G::View;
+    type Move = G::Move;
+    // A chain has no fixed length, the position before it is the simplest way back
+    type Undo = G::Position;
+
+    fn start() -> G::Position {
+        G::start()
+    }
+
+    fn from_view(view: &G::View) -> G::Position {
+        G::from_view(view)
+    }
+
+    fn current_player(position: &G::Position) -> Player {
+        G::current_player(position)
+    }
+
+    const MAX_MOVES: usize = G::MAX_MOVES;
+
+    fn legal_moves(view: &G::View) -> ActionList<G::Move> {
+        G::legal_moves(view)
+    }
+
+    fn move_index(game_move: &G::Move) -> usize {
+        G::move_index(game_move)
+    }
+
+    fn make_move(position: &mut G::Position, game_move: &G::Move) -> G::Position {
+        let before = *position;
+        let mover = G::current_player(position);
+        G::make_move(position, game_move);
+        let follow_ups = F::default();
+        while G::outcome(position).is_none() && G::current_player(position) == mover {
+            let follow_up = follow_ups.choose_action((*position).into(), None);
+            G::make_move(position, &follow_up);
+        }
+        before
+    }
+
+    fn unmake_move(position: &mut G::Position, undo: G::Position) {
+        *position = undo;
+    }
+
+    fn outcome(position: &G::Position) -> Option<Outcome> {
+        G::outcome(position)
+    }
+
+    fn score(position: &G::Position, player: Player) -> f32 {
+        G::score(position, player)
+    }
+
+    fn zobrist(position: &G::Position) -> Option<u64> {
+        G::zobrist(position)
+    }
+
+    fn outcome_reward(outcome: Outcome) -> f32 {
+        G::outcome_reward(outcome)
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use crate::q_learning::{
+    ActionMask, Deserialize, DeserializeError, Environment, GreedyPolicy, Policy, Serialize,
+    checked_choice, masked_actions,
+};
+
+const HEADER: &str = "ucb";
+const COUNTS: &str = "counts";
+
+// UCB1: plays the action with the highest value plus `exploration` * sqrt(ln N / n), where n counts
+// how often the action was taken in the state and N how often the state was played at all. Actions
+// never tried there go first, and the bonus of the rest shrinks as they are tried, so exploration
+// goes to moves that are uncertain rather than to any move at random. No randomness, no schedule.
+// Learns with Q-learning's target. The file puts the counts behind the table:
+//
+//   ucb;<exploration>
+//   <GreedyPolicy>
+//   counts
+//   <state>;<action>;<count>
+pub struct UcbPolicy<E: Environment> {
+    greedy_policy: GreedyPolicy<E>,
+    exploration: f32,
+    counts: HashMap<(E::ActionRelevantState, E::Action), u32>,
+}
+
+impl<E: Environment> UcbPolicy<E> {
+    pub fn new(learning_rate: f32, gamma: f32, exploration: f32) -> Self {
+        UcbPolicy::with_table(GreedyPolicy::new(learning_rate, gamma), exploration)
+    }
+
+    // Goes on from an existing table with every action counted as never tried
+    pub fn with_table(greedy_policy: GreedyPolicy<E>, exploration: f32) -> Self {
+        assert!(
+            exploration >= 0f32,
+            "The exploration weight can not be negative"
+        );
+        UcbPolicy {
+            greedy_policy,
+            exploration,
+            counts: HashMap::new(),
+        }
+    }
+
+    pub fn greedy_policy(&self) -> &GreedyPolicy<E> {
+        &self.greedy_policy
+    }
+
+    pub fn greedy_policy_mut(&mut self) -> &mut GreedyPolicy<E> {
+        &mut self.greedy_policy
+    }
+
+    pub fn into_greedy_policy(self) -> GreedyPolicy<E> {
+        self.greedy_policy
+    }
+
+    pub fn exploration(&self) -> f32 {
+        self.exploration
+    }
+
+    pub fn count(&self, state: E::ActionRelevantState, action: E::Action) -> u32 {
+        *self.counts.get(&(state, action)).unwrap_or(&0)
+    }
+
+    // Pairs tried at least once
+    pub fn counts_size(&self) -> usize {
+        self.counts.len()
+    }
+
+    // `None` for actions never tried, they outrank every bound
+    fn upper_bound(
+        &self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        state_count: u32,
+    ) -> Option<f32> {
+        match self.count(state, action) {
+            0 => None,
+            count => Some(
+                self.greedy_policy.value(state, action)
+                    + self.exploration * ((state_count as f32).ln() / count as f32).sqrt(),
+            ),
+        }
+    }
+}
+
+impl<E: Environment> Policy<E> for UcbPolicy<E> {
+    fn choose_action(&self, state: E::ActionRelevantState, mask: Option<ActionMask>) -> E::Action {
+        let actions = masked_actions::<E>(&state, mask);
+        let state_count: u32 = actions.iter().map(|&a| self.count(state, a)).sum();
+        let action = *actions
+            .iter()
+            .max_by(|&&a, &&b| {
+                match (
+                    self.upper_bound(state, a, state_count),
+                    self.upper_bound(state, b, state_count),
+                ) {
+                    (Some(a), Some(b)) => a.total_cmp(&b),
+                    (a, b) => b.is_some().cmp(&a.is_some()),
+                }
+            })
+            .expect(
+                "The way it is implemented now, there should always be possible actions (might be bad)",
+            );
+        checked_choice::<E>(&state, mask, action)
+    }
+
+    fn improve(
+        &mut self,
+        state: E::ActionRelevantState,
+        action: E::Action,
+        reward: f32,
+        next_state: E::State,
+        finished: bool,
+    ) {
+        *self.counts.entry((state, action)).or_default() += 1;
+        self.greedy_policy
+            .improve(state, action, reward, next_state, finished);
+    }
+
+    fn action_distribution(&self, state: E::ActionRelevantState) -> Vec<(E::Action, f32)> {
+        let chosen = self.choose_action(state, None);
+        E::actions(&state)
+            .into_iter()
+            .map(|a| (a, if a == chosen { 1f32 } else { 0f32 }))
+            .collect()
+    }
+
+    fn action_values(&self, state: E::ActionRelevantState) -> Option<Vec<(E::Action, f32)>> {
+        self.greedy_policy.action_values(state)
+    }
+
+    fn knows_state(&self, state: E::ActionRelevantState) -> Option<bool> {
+        self.greedy_policy.knows_state(state)
+    }
+}
+
+impl<E: Environment> Serialize for UcbPolicy<E> {
+    fn serialize(&self) -> String {
+        format!(
+            "{HEADER};{}\n{}{COUNTS}\n",
+            self.exploration,
+            self.greedy_policy.serialize()
+        ) + self
+            .counts
+            .iter()
+            .map(|((state, action), count)| {
+                format!("{};{};{count}\n", state.serialize(), action.serialize())
+            })
+            .collect::<String>()
+            .as_str()
+    }
+}
+
+impl<E: Environment> Deserialize for UcbPolicy<E> {
+    fn deserialize(input: &str) -> Result<Self, DeserializeError> {
+        let (first, rest) = input
+            .split_once('\n')
+            .ok_or_else(|| DeserializeError::because("a UCB policy has a first line"))?;
+        let exploration = first
+            .strip_prefix(HEADER)
+            .and_then(|exploration| exploration.strip_prefix(';'))
+            .and_then(|exploration| exploration.parse::<f32>().ok())
+            .filter(|exploration| *exploration >= 0f32);
+        let Some(exploration) = exploration else {
+            return Err(DeserializeError::because(format!(
+                "a UCB policy starts with \"{HEADER};<exploration weight of at least 0>\""
+            )));
+        };
+        let (table, counts) = rest.split_once(&format!("\n{COUNTS}\n")).ok_or_else(|| {
+            DeserializeError::because(format!(
+                "a UCB policy has a line {COUNTS} before its visit counts"
+            ))
+        })?;
+
+        let mut policy = UcbPolicy::with_table(GreedyPolicy::deserialize(table)?, exploration);
+        for line in counts.lines() {
+            let reason =
+                |reason: &str| DeserializeError::because(format!("{reason} in \"{line}\""));
+            let mut parts = line.split(';');
+            let (Some(state), Some(action), Some(count), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                return Err(reason("not <state>;<action>;<count>"));
+            };
+            let count = count.parse::<u32>().map_err(|_| reason("bad count"))?;
+            policy.counts.insert(
+                (
+                    E::ActionRelevantState::deserialize(state)?,
+                    E::Action::deserialize(action)?,
+                ),
+                count,
+            );
+        }
+        Ok(policy)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::q_learning::{Environment, Outcome, Transition, episode_start};
+
+// Steps several independent games in lockstep, every field holds one entry per game
+pub struct VecEnv<E: Environment> {
+    states: Vec<E::State>,
+    steps: Vec<usize>,
+    returns: Vec<f32>,
+    max_steps: Option<usize>,
+    environment_seed: Option<u64>,
+    // Games started so far, each numbers the stream of the next one
+    started: usize,
+}
+
+pub struct FinishedEpisode {
+    pub index: usize,
+    pub steps: usize,
+    pub total_reward: f32,
+    pub outcome: Option<Outcome>,
+}
+
+impl<E: Environment> VecEnv<E> {
+    pub fn new(num_envs: usize, max_steps: Option<usize>, environment_seed: Option<u64>) -> Self {
+        assert!(num_envs > 0, "A VecEnv needs at least one environment");
+        VecEnv {
+            states: (0..num_envs)
+                .map(|i| episode_start::<E>(environment_seed, i))
+                .collect(),
+            steps: vec![0; num_envs],
+            returns: vec![0f32; num_envs],
+            max_steps,
+            environment_seed,
+            started: num_envs,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn states(&self) -> &[E::State] {
+        &self.states
+    }
+
+    // Moves played so far in each running game
+    pub fn steps(&self) -> &[usize] {
+        &self.steps
+    }
+
+    // Finished games are reset right away, their transition still carries the terminal state
+    pub fn step(&mut self, actions: &[E::Action]) -> (Vec<Transition<E>>, Vec<FinishedEpisode>) {
+        assert_eq!(actions.len(), self.len(), "Exactly one action per game");
+
+        let mut transitions = Vec::with_capacity(self.len());
+        let mut finished = Vec::new();
+        for (i, action) in actions.iter().enumerate() {
+            let state = self.states[i];
+            let (next_state, reward, outcome) = E::step(&state, action);
+            self.steps[i] += 1;
+            self.returns[i] += reward;
+
+            let truncated = outcome.is_none() && self.max_steps.is_some_and(|m| self.steps[i] >= m);
+            if outcome.is_some() || truncated {
+                finished.push(FinishedEpisode {
+                    index: i,
+                    steps: self.steps[i],
+                    total_reward: self.returns[i],
+                    outcome,
+                });
+                self.states[i] = episode_start::<E>(self.environment_seed, self.started);
+                self.started += 1;
+                self.steps[i] = 0;
+                self.returns[i] = 0f32;
+            } else {
+                self.states[i] = next_state;
+            }
+
+            transitions.push(Transition {
+                state,
+                action: *action,
+                reward,
+                next_state,
+                outcome,
+                truncated,
+                clipped: false,
+            });
+        }
+
+        (transitions, finished)
+    }
+}
@@ -0,0 +1,163 @@
+// Every learner has to find the optimal Nim strategy and Q-learning most of the optimal Blackjack
+// policy, the same canaries `self-check` runs, so a broken update shows up in `cargo test` as
+// well.
+
+use mankalla_rl::afterstate::AfterstatePolicy;
+use mankalla_rl::blackjack::{self, Blackjack};
+use mankalla_rl::double_q::DoubleQLearningPolicy;
+use mankalla_rl::dyna_q::DynaQPolicy;
+use mankalla_rl::hyperparameters::Hyperparameters;
+use mankalla_rl::monte_carlo::{MonteCarloControl, MonteCarloPolicy};
+use mankalla_rl::nim::{self, Nim};
+use mankalla_rl::q_lambda::QLambdaPolicy;
+use mankalla_rl::q_learning::{
+    Deserialize, Environment, EpsilonGreedyPolicy, GreedyPolicy, QLearning, Serialize,
+    TrainingOptions,
+};
+use mankalla_rl::sarsa::{ExpectedSarsaPolicy, Sarsa, SarsaPolicy};
+
+const NIM_EPISODES: usize = 2000;
+const NIM_SEED: u64 = 1;
+const DYNA_Q_NIM_EPISODES: usize = 200;
+const DYNA_Q_PLANNING_STEPS: usize = 20;
+// Without bootstrapping the win has to be found by exploring all the way from the start
+const MONTE_CARLO_NIM_EPISODES: usize = 150_000;
+// Some states are so close that even the exact values barely separate the actions, so only most
+// of the policy has to match the known optimum
+const BLACKJACK_EPISODES: usize = 500_000;
+const BLACKJACK_SEED: u64 = 1;
+const BLACKJACK_AGREEMENT: f32 = 0.9;
+
+fn assert_optimal(learner: &str, policy: &GreedyPolicy<Nim>) {
+    let (optimal, moves) = nim::optimal_moves(policy);
+    assert_eq!(
+        optimal, moves,
+        "{learner} plays {optimal} of {moves} winning Nim positions optimally"
+    );
+}
+
+#[test]
+fn q_learning_plays_nim_optimally() {
+    let mut policy = EpsilonGreedyPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    policy.reseed(NIM_SEED);
+    QLearning::train(&mut policy, NIM_EPISODES, None);
+    assert_optimal("Q-learning", policy.greedy_policy());
+}
+
+#[test]
+fn sarsa_plays_nim_optimally() {
+    let mut policy = SarsaPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    policy.reseed(NIM_SEED);
+    Sarsa::train(&mut policy, NIM_EPISODES, None);
+    assert_optimal("SARSA", policy.epsilon_greedy_policy().greedy_policy());
+}
+
+#[test]
+fn expected_sarsa_plays_nim_optimally() {
+    let mut policy = ExpectedSarsaPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    policy.reseed(NIM_SEED);
+    QLearning::train(&mut policy, NIM_EPISODES, None);
+    assert_optimal(
+        "Expected SARSA",
+        policy.epsilon_greedy_policy().greedy_policy(),
+    );
+}
+
+#[test]
+fn q_lambda_plays_nim_optimally() {
+    let mut policy = QLambdaPolicy::<Nim>::from_hyperparameters(Hyperparameters::default(), 0.8)
+        .expect("The default hyperparameters are valid");
+    policy.reseed(NIM_SEED);
+    QLearning::train(&mut policy, NIM_EPISODES, None);
+    assert_optimal("Q(λ)", policy.epsilon_greedy_policy().greedy_policy());
+}
+
+#[test]
+fn dyna_q_plays_nim_optimally() {
+    let mut policy =
+        DynaQPolicy::<Nim>::from_hyperparameters(Hyperparameters::default(), DYNA_Q_PLANNING_STEPS)
+            .expect("The default hyperparameters are valid");
+    policy.reseed(NIM_SEED);
+    QLearning::train(&mut policy, DYNA_Q_NIM_EPISODES, None);
+    assert_optimal("Dyna-Q", policy.epsilon_greedy_policy().greedy_policy());
+}
+
+// Through a saved file, which fills the table in from the afterstate values
+#[test]
+fn afterstate_values_play_nim_optimally() {
+    let mut policy = AfterstatePolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    policy.reseed(NIM_SEED);
+    QLearning::train(&mut policy, NIM_EPISODES, None);
+    let policy = AfterstatePolicy::<Nim>::deserialize(&policy.serialize())
+        .expect("A saved afterstate policy loads again")
+        .into_epsilon_greedy_policy();
+    assert_optimal("afterstate values", policy.greedy_policy());
+}
+
+#[test]
+fn double_q_learning_plays_nim_optimally() {
+    let mut policy = DoubleQLearningPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    policy.reseed(NIM_SEED);
+    QLearning::train(&mut policy, NIM_EPISODES, None);
+    assert_optimal(
+        "Double Q-learning",
+        policy.into_epsilon_greedy_policy().greedy_policy(),
+    );
+}
+
+#[test]
+fn monte_carlo_control_plays_nim_optimally() {
+    let mut policy = MonteCarloPolicy::<Nim>::from_hyperparameters(Hyperparameters::default())
+        .expect("The default hyperparameters are valid");
+    policy.reseed(NIM_SEED);
+    MonteCarloControl::train(&mut policy, MONTE_CARLO_NIM_EPISODES, None);
+    assert_optimal(
+        "Monte Carlo control",
+        policy.epsilon_greedy_policy().greedy_policy(),
+    );
+}
+
+// The cards travel in the state, the same seed deals the same game
+#[test]
+fn blackjack_deals_the_same_cards_for_a_seed() {
+    let play = |seed| {
+        let mut state = Blackjack::new_seeded(seed);
+        let mut seen = vec![<[u8; 3]>::from(state)];
+        loop {
+            let (next_state, reward, outcome) = Blackjack::step(&state, &blackjack::HIT);
+            seen.push(next_state.into());
+            if outcome.is_some() {
+                return (seen, reward);
+            }
+            state = next_state;
+        }
+    };
+    assert!((0..100).all(|seed| play(seed) == play(seed)));
+    assert!((0..100).any(|seed| play(seed) != play(seed + 100)));
+}
+
+#[test]
+fn q_learning_approximates_optimal_blackjack() {
+    let mut policy = EpsilonGreedyPolicy::<Blackjack>::from_hyperparameters(Hyperparameters {
+        learning_rate: 0.02,
+        decay_rate: 0.0001,
+        ..Default::default()
+    })
+    .expect("The blackjack hyperparameters are valid");
+    policy.reseed(BLACKJACK_SEED);
+    let options = TrainingOptions {
+        environment_seed: Some(BLACKJACK_SEED),
+        ..Default::default()
+    };
+    QLearning::train_observed(&mut policy, BLACKJACK_EPISODES, &options, &mut ());
+    let (agreeing, states) = blackjack::optimal_agreement(policy.greedy_policy());
+    assert!(
+        agreeing as f32 / states as f32 >= BLACKJACK_AGREEMENT,
+        "Q-learning plays {agreeing} of {states} Blackjack states optimally"
+    );
+}
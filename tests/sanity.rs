@@ -0,0 +1,57 @@
+// A short real training run that has to beat chance. Catches what the fixed-value checks of
+// `self-check` can not see, like a reward with the wrong sign for one seat, which trains without
+// any error and only shows in the results. Takes a while, so only with --features slow-tests.
+#![cfg(feature = "slow-tests")]
+
+use mankalla_rl::evaluation;
+use mankalla_rl::hyperparameters::Hyperparameters;
+use mankalla_rl::mankalla::MankallaGame;
+use mankalla_rl::metrics::{CurvePoint, TrainingCurve};
+use mankalla_rl::q_learning::{
+    EpsilonGreedyPolicy, QLearning, SeededRandomPolicy, TrainingOptions,
+};
+
+const EPISODES: usize = 20_000;
+const CURVE_EVERY: usize = 1000;
+const EVAL_GAMES: usize = 400;
+const MIN_WIN_RATE: f32 = 0.6;
+// Means over the first and the last blocks of the curve, one block alone is too noisy
+const BLOCKS: usize = 5;
+const SEED: u64 = 1;
+
+#[test]
+fn short_training_beats_random_and_improves() {
+    let mut policy =
+        EpsilonGreedyPolicy::<MankallaGame>::from_hyperparameters(Hyperparameters::default())
+            .expect("The default hyperparameters are valid");
+    policy.reseed(SEED);
+    let mut curve = TrainingCurve::new(CURVE_EVERY);
+    QLearning::train_observed(
+        &mut policy,
+        EPISODES,
+        &TrainingOptions::default(),
+        &mut curve,
+    );
+
+    let random = SeededRandomPolicy::new(SEED);
+    let report = evaluation::evaluate(policy.greedy_policy(), &random, EVAL_GAMES);
+    assert!(
+        report.win_rate() > MIN_WIN_RATE,
+        "Won {:.1}% against a random opponent after {EPISODES} episodes, expected over {:.0}%",
+        report.win_rate() * 100f32,
+        MIN_WIN_RATE * 100f32
+    );
+
+    let points = curve.points();
+    let mean = |points: &[CurvePoint]| {
+        points.iter().map(|p| p.mean_reward).sum::<f32>() / points.len() as f32
+    };
+    let (first, last) = (
+        mean(&points[..BLOCKS]),
+        mean(&points[points.len() - BLOCKS..]),
+    );
+    assert!(
+        last > first,
+        "The mean reward fell from {first:.3} to {last:.3} over {EPISODES} episodes"
+    );
+}
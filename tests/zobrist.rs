@@ -0,0 +1,64 @@
+// Zobrist keys of every position a few plies deep from the start have to be distinct, and the
+// key updated along a move has to match hashing the new position from scratch. The walk that
+// `self-check` does, a bit over a million positions, only runs with --features slow-tests.
+
+use std::collections::HashMap;
+
+use mankalla_rl::mankalla::MankallaGame;
+use mankalla_rl::q_learning::Environment;
+use mankalla_rl::two_player::play;
+
+const PLIES: usize = 5;
+#[cfg(feature = "slow-tests")]
+const SLOW_PLIES: usize = 8;
+
+// (positions, keys shared by different positions, moves whose updated key drifted)
+fn walk_positions(plies: usize) -> (usize, usize, usize) {
+    let start = MankallaGame::new();
+    let mut keys = HashMap::from([(start.zobrist(), start)]);
+    let mut frontier = vec![start];
+    let (mut collisions, mut drifted) = (0, 0);
+    for _ in 0..plies {
+        let mut next_frontier = Vec::new();
+        for state in frontier {
+            let key = state.zobrist();
+            for action in MankallaGame::actions(&state.into()) {
+                let (next_state, _, outcome) = play::<MankallaGame>(&state, &action);
+                let next_key = state.zobrist_after(key, &next_state);
+                if next_key != next_state.zobrist() {
+                    drifted += 1;
+                }
+                match keys.insert(next_key, next_state) {
+                    Some(other) if other != next_state => collisions += 1,
+                    Some(_) => continue,
+                    None => {}
+                }
+                if outcome.is_none() {
+                    next_frontier.push(next_state);
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+    (keys.len(), collisions, drifted)
+}
+
+fn assert_sound(plies: usize) {
+    let (positions, collisions, drifted) = walk_positions(plies);
+    assert_eq!(
+        collisions, 0,
+        "{collisions} keys shared among {positions} positions within {plies} plies"
+    );
+    assert_eq!(drifted, 0, "{drifted} incrementally updated keys drifted");
+}
+
+#[test]
+fn zobrist_keys_are_distinct_and_do_not_drift() {
+    assert_sound(PLIES);
+}
+
+#[cfg(feature = "slow-tests")]
+#[test]
+fn zobrist_keys_are_distinct_and_do_not_drift_deep() {
+    assert_sound(SLOW_PLIES);
+}